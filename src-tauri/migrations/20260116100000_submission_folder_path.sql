@@ -0,0 +1,2 @@
+-- The importer and every file command use folder_path, but the initial schema never declared it
+ALTER TABLE submissions ADD COLUMN folder_path TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,25 @@
+-- Grades were keyed by (assignment, student) in the initial schema, but the app
+-- reads and writes them per submission. Rebuild the table in that shape.
+CREATE TABLE grades_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    submission_id TEXT NOT NULL,
+    question_id TEXT NOT NULL,
+    score REAL,
+    comment TEXT,
+    rubric_selections_json TEXT,
+    updated_by_ta_id TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (submission_id, question_id),
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE CASCADE,
+    FOREIGN KEY (updated_by_ta_id) REFERENCES tas(id)
+);
+
+-- Carry old rows over to the student's submission
+INSERT OR IGNORE INTO grades_new (submission_id, question_id, score, comment, rubric_selections_json, updated_by_ta_id, updated_at)
+SELECT s.id, g.question_id, g.score, g.comment, g.rubric_selections_json, g.updated_by_ta_id, g.updated_at
+FROM grades g
+JOIN submissions s ON s.assignment_id = g.assignment_id
+    AND s.student_id = g.student_id;
+
+DROP TABLE grades;
+ALTER TABLE grades_new RENAME TO grades;
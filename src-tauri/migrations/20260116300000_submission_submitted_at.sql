@@ -0,0 +1,2 @@
+-- The importer records when each submission came in, but the initial schema never declared it
+ALTER TABLE submissions ADD COLUMN submitted_at DATETIME;
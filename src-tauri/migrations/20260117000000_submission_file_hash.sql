@@ -0,0 +1,7 @@
+-- Content hash of the uploaded ZIP, used to detect identical re-uploads
+ALTER TABLE submissions ADD COLUMN file_hash TEXT;
+
+-- Newer submission from the same student that replaces this one (NULL = current)
+ALTER TABLE submissions ADD COLUMN superseded_by TEXT REFERENCES submissions(id);
+
+CREATE INDEX IF NOT EXISTS idx_submissions_assignment_hash ON submissions(assignment_id, file_hash);
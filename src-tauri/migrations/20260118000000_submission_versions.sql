@@ -0,0 +1,2 @@
+-- Position in a student's resubmission chain for an assignment (1 = first upload)
+ALTER TABLE submissions ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,53 @@
+-- Allow 'fuzzy_name' as a match method. SQLite can't alter a CHECK constraint,
+-- so the submissions table is rebuilt.
+
+-- Dropping submissions cascades into submission_files/formula_analysis; keep a copy
+CREATE TABLE submission_files_backup AS SELECT * FROM submission_files;
+CREATE TABLE formula_analysis_backup AS SELECT * FROM formula_analysis;
+
+-- superseded_by points at rows that move to the new table; check FKs at commit
+PRAGMA defer_foreign_keys = ON;
+
+CREATE TABLE submissions_new (
+    id TEXT PRIMARY KEY,
+    assignment_id TEXT NOT NULL,
+    student_id TEXT, -- Nullable until matched
+    source_zip_path TEXT NOT NULL,
+    zip_hash TEXT NOT NULL,
+    received_at DATETIME NOT NULL,
+    match_confidence REAL DEFAULT 0,
+    match_method TEXT CHECK(match_method IN ('filename', 'metadata', 'fuzzy_name', 'manual', 'none')),
+    status TEXT NOT NULL DEFAULT 'unstarted' CHECK(status IN ('unstarted', 'in_progress', 'done', 'flagged', 'error')),
+    claimed_by_ta_id TEXT,
+    claimed_at DATETIME,
+    last_opened_at DATETIME,
+    notes TEXT,
+    folder_path TEXT NOT NULL DEFAULT '',
+    submitted_at DATETIME,
+    file_hash TEXT,
+    superseded_by TEXT REFERENCES submissions(id),
+    version INTEGER NOT NULL DEFAULT 1,
+    FOREIGN KEY (assignment_id) REFERENCES assignments(id) ON DELETE CASCADE,
+    FOREIGN KEY (claimed_by_ta_id) REFERENCES tas(id)
+);
+
+INSERT INTO submissions_new (
+    id, assignment_id, student_id, source_zip_path, zip_hash, received_at,
+    match_confidence, match_method, status, claimed_by_ta_id, claimed_at,
+    last_opened_at, notes, folder_path, submitted_at, file_hash, superseded_by, version
+)
+SELECT
+    id, assignment_id, student_id, source_zip_path, zip_hash, received_at,
+    match_confidence, match_method, status, claimed_by_ta_id, claimed_at,
+    last_opened_at, notes, folder_path, submitted_at, file_hash, superseded_by, version
+FROM submissions;
+
+DROP TABLE submissions;
+ALTER TABLE submissions_new RENAME TO submissions;
+
+CREATE INDEX IF NOT EXISTS idx_submissions_assignment_hash ON submissions(assignment_id, file_hash);
+
+INSERT INTO submission_files SELECT * FROM submission_files_backup;
+INSERT INTO formula_analysis SELECT * FROM formula_analysis_backup;
+DROP TABLE submission_files_backup;
+DROP TABLE formula_analysis_backup;
@@ -0,0 +1,2 @@
+-- Regex used to find student IDs in submission filenames (NULL = default 8-digit pattern)
+ALTER TABLE courses ADD COLUMN id_pattern TEXT;
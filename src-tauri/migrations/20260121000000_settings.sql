@@ -0,0 +1,6 @@
+-- App-wide key/value settings (e.g. libreoffice_path)
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
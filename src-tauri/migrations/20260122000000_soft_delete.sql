@@ -0,0 +1,4 @@
+-- Soft delete: rows with deleted_at set are hidden from lists but kept for restore
+ALTER TABLE courses ADD COLUMN deleted_at DATETIME;
+ALTER TABLE assignments ADD COLUMN deleted_at DATETIME;
+ALTER TABLE submissions ADD COLUMN deleted_at DATETIME;
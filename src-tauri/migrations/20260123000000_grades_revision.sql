@@ -0,0 +1,2 @@
+-- Revision counter for optimistic concurrency on grade saves
+ALTER TABLE grades ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,8 @@
+-- Which file in a submission answers which rubric question
+CREATE TABLE IF NOT EXISTS question_files (
+    submission_id TEXT NOT NULL,
+    question_id TEXT NOT NULL,
+    file_path TEXT NOT NULL, -- Relative to the submission folder
+    PRIMARY KEY (submission_id, question_id),
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,32 @@
+-- Where each TA left off in an assignment (previously stored as audit_log rows)
+CREATE TABLE IF NOT EXISTS session_bookmarks (
+    ta_id TEXT NOT NULL,
+    assignment_id TEXT NOT NULL,
+    submission_id TEXT,
+    question_index INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (ta_id, assignment_id),
+    FOREIGN KEY (ta_id) REFERENCES tas(id) ON DELETE CASCADE,
+    FOREIGN KEY (assignment_id) REFERENCES assignments(id) ON DELETE CASCADE,
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE SET NULL
+);
+
+-- Carry over the latest bookmark per TA/assignment from the audit log
+INSERT OR REPLACE INTO session_bookmarks (ta_id, assignment_id, submission_id, question_index, updated_at)
+SELECT
+    a.ta_id,
+    a.entity_id,
+    (SELECT s.id FROM submissions s WHERE s.id = json_extract(a.details_json, '$.submission_id')),
+    COALESCE(json_extract(a.details_json, '$.question_index'), 0),
+    a.ts
+FROM audit_log a
+WHERE a.action = 'session_bookmark'
+  AND json_valid(a.details_json)
+  AND a.ta_id IN (SELECT id FROM tas)
+  AND a.entity_id IN (SELECT id FROM assignments)
+  AND a.id = (
+    SELECT MAX(b.id) FROM audit_log b
+    WHERE b.action = 'session_bookmark' AND b.ta_id = a.ta_id AND b.entity_id = a.entity_id
+  );
+
+DELETE FROM audit_log WHERE action = 'session_bookmark';
@@ -0,0 +1,8 @@
+-- Reusable rubrics that can be applied to new assignments
+CREATE TABLE IF NOT EXISTS rubric_templates (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    rubric_json TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Per-assignment late policy. No penalty is applied while late_percent_per_day is NULL.
+ALTER TABLE assignments ADD COLUMN late_percent_per_day REAL;
+ALTER TABLE assignments ADD COLUMN late_grace_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE assignments ADD COLUMN late_max_penalty_percent REAL;
@@ -0,0 +1,19 @@
+-- Regrade requests tie a flag to one rubric question and record how it was resolved
+CREATE TABLE IF NOT EXISTS regrade_requests (
+    id TEXT PRIMARY KEY,
+    submission_id TEXT NOT NULL,
+    question_id TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'open' CHECK(status IN ('open', 'resolved')),
+    previous_status TEXT, -- Submission status before it was flagged, restored once nothing is open
+    requested_by_ta_id TEXT,
+    requested_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    resolution TEXT,
+    resolved_by_ta_id TEXT,
+    resolved_at DATETIME,
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE CASCADE,
+    FOREIGN KEY (requested_by_ta_id) REFERENCES tas(id),
+    FOREIGN KEY (resolved_by_ta_id) REFERENCES tas(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_regrade_requests_submission ON regrade_requests(submission_id, status);
@@ -0,0 +1,3 @@
+-- received_at is when the ZIP was ingested; submitted_at is when the student handed it in.
+-- Older rows have no separate submission time; the ingest time is the best available
+UPDATE submissions SET submitted_at = received_at WHERE submitted_at IS NULL;
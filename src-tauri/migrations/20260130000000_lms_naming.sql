@@ -0,0 +1,6 @@
+-- LMS whose bulk-download naming the course's submissions follow
+-- ('canvas', 'moodle', 'blackboard'; NULL = plain filenames)
+ALTER TABLE courses ADD COLUMN lms_convention TEXT;
+
+-- File name the student uploaded, with any LMS prefix stripped
+ALTER TABLE submissions ADD COLUMN original_filename TEXT;
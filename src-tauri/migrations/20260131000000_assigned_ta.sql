@@ -0,0 +1,4 @@
+-- TA a coordinator has pre-assigned to grade the submission (separate from the live claim)
+ALTER TABLE submissions ADD COLUMN assigned_ta_id TEXT REFERENCES tas(id);
+
+CREATE INDEX IF NOT EXISTS idx_submissions_assigned_ta ON submissions(assignment_id, assigned_ta_id);
@@ -0,0 +1,13 @@
+-- Unsaved grade/comment text, written as the TA types so a crash doesn't lose it.
+-- Cleared when the grade is committed through save_grade.
+CREATE TABLE IF NOT EXISTS grade_drafts (
+    submission_id TEXT NOT NULL,
+    question_id TEXT NOT NULL,
+    ta_id TEXT NOT NULL,
+    score REAL,
+    comment TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (submission_id, question_id, ta_id),
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE CASCADE,
+    FOREIGN KEY (ta_id) REFERENCES tas(id) ON DELETE CASCADE
+);
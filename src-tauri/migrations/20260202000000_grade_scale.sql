@@ -0,0 +1,3 @@
+-- Letter grade cut-offs for a course: JSON array of {min_percent, letter}, highest first
+-- (NULL = no letter grades)
+ALTER TABLE courses ADD COLUMN grade_scale_json TEXT;
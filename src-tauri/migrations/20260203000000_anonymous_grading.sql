@@ -0,0 +1,2 @@
+-- Anonymous grading: hide student identity from the grading queue and detail view
+ALTER TABLE assignments ADD COLUMN anonymous INTEGER NOT NULL DEFAULT 0;
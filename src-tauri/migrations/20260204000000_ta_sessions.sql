@@ -0,0 +1,13 @@
+-- Optional per-TA PIN as an Argon2 PHC string; NULL = no PIN, commands accept the bare ta_id
+ALTER TABLE tas ADD COLUMN pin_hash TEXT;
+
+-- Tokens handed out by start_ta_session once the PIN checks out
+CREATE TABLE IF NOT EXISTS ta_sessions (
+    token TEXT PRIMARY KEY,
+    ta_id TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (ta_id) REFERENCES tas(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_ta_sessions_ta ON ta_sessions(ta_id);
@@ -0,0 +1,9 @@
+-- Which TA last had each submission open, for "also viewing" warnings
+CREATE TABLE IF NOT EXISTS submission_viewers (
+    submission_id TEXT NOT NULL,
+    ta_id TEXT NOT NULL,
+    last_seen_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (submission_id, ta_id),
+    FOREIGN KEY (submission_id) REFERENCES submissions(id) ON DELETE CASCADE,
+    FOREIGN KEY (ta_id) REFERENCES tas(id) ON DELETE CASCADE
+);
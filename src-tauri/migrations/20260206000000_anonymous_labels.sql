@@ -0,0 +1,13 @@
+-- Per-assignment sequence number behind each submission's anonymous label
+-- ("Submission #0007"), so no two submissions in an assignment share a label
+ALTER TABLE submissions ADD COLUMN anon_seq INTEGER;
+
+-- Number existing submissions by id, which is a random UUID and gives nothing away
+UPDATE submissions SET anon_seq = (
+    SELECT numbered.n FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY assignment_id ORDER BY id) AS n FROM submissions
+    ) numbered
+    WHERE numbered.id = submissions.id
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_submissions_anon_seq ON submissions(assignment_id, anon_seq);
@@ -0,0 +1,455 @@
+use crate::db::{DbPool, DbState};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use tauri::State;
+use uuid::Uuid;
+
+/// How long a session token stays valid after start_ta_session
+const SESSION_HOURS: i64 = 12;
+const MIN_PIN_LEN: usize = 4;
+/// Wrong PINs allowed per TA within LOCKOUT_MINUTES before sign-in is refused
+const MAX_PIN_ATTEMPTS: i64 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// Error returned when a PIN-protected TA calls without a live session. The
+/// frontend matches on it to ask for the PIN and retry.
+pub(crate) const SESSION_REQUIRED: &str = "session_required";
+
+/// Hash a PIN into an Argon2 PHC string ("$argon2id$...")
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes()).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(pin.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Check a PIN against a stored Argon2 hash
+fn pin_matches(stored: &str, pin: &str) -> bool {
+    PasswordHash::new(stored)
+        .map(|hash| Argon2::default().verify_password(pin.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+/// Argon2 is deliberately slow, so hashing and checking run off the async runtime
+async fn hash_pin_blocking(pin: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || hash_pin(&pin))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn pin_matches_blocking(stored: String, pin: String) -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || pin_matches(&stored, &pin))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn stored_pin(pool: &DbPool, ta_id: &str) -> Result<Option<String>, String> {
+    let row: Option<(Option<String>,)> = sqlx::query_as("SELECT pin_hash FROM tas WHERE id = ?")
+        .bind(ta_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    row.map(|(hash,)| hash).ok_or_else(|| "Unknown TA".to_string())
+}
+
+/// Make sure a grading command really comes from `ta_id`. TAs without a PIN
+/// are taken at their word; once a PIN is set, a live session token is required.
+pub(crate) async fn verify_ta(pool: &DbPool, ta_id: &str, session_token: Option<&str>) -> Result<(), String> {
+    if stored_pin(pool, ta_id).await?.is_none() {
+        return Ok(());
+    }
+    let token = session_token.ok_or(SESSION_REQUIRED)?;
+    let owner: Option<String> = sqlx::query_scalar(
+        "SELECT ta_id FROM ta_sessions WHERE token = ? AND expires_at > datetime('now')"
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    match owner {
+        Some(owner) if owner == ta_id => Ok(()),
+        Some(_) => Err("Session belongs to a different TA".to_string()),
+        None => Err(SESSION_REQUIRED.to_string()),
+    }
+}
+
+/// `verify_ta` for commands where the acting TA is optional. Leaving the TA
+/// out only works while no TA has a PIN; after that the change has to come
+/// from a signed-in TA so the audit log can name them.
+pub(crate) async fn verify_acting_ta(pool: &DbPool, ta_id: Option<&str>, session_token: Option<&str>) -> Result<(), String> {
+    if let Some(ta_id) = ta_id {
+        return verify_ta(pool, ta_id, session_token).await;
+    }
+    let any_pin: Option<i32> = sqlx::query_scalar("SELECT 1 FROM tas WHERE pin_hash IS NOT NULL LIMIT 1")
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    match any_pin {
+        Some(_) => Err(SESSION_REQUIRED.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// A TA's first PIN has to be set by a signed-in admin of one of their
+/// courses, so nobody can claim another TA's account by picking its PIN.
+/// The one exception is a course admin securing their own account while no
+/// co-admin has a PIN yet, which is how the first PIN in a course gets set.
+async fn check_first_pin_allowed(
+    pool: &DbPool,
+    ta_id: &str,
+    admin_ta_id: Option<&str>,
+    session_token: Option<&str>,
+) -> Result<(), String> {
+    if let Some(admin_ta_id) = admin_ta_id {
+        if stored_pin(pool, admin_ta_id).await?.is_none() {
+            return Err("Set your own PIN before setting one for another TA".to_string());
+        }
+        verify_ta(pool, admin_ta_id, session_token).await?;
+        let shares_course: Option<i32> = sqlx::query_scalar(
+            r#"
+            SELECT 1 FROM course_tas admin
+            JOIN course_tas member ON member.course_id = admin.course_id
+            WHERE admin.ta_id = ? AND admin.role = 'admin' AND member.ta_id = ?
+            "#
+        )
+        .bind(admin_ta_id)
+        .bind(ta_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        return match shares_course {
+            Some(_) => Ok(()),
+            None => Err("Only an admin of one of this TA's courses can set their first PIN".to_string()),
+        };
+    }
+
+    let (is_admin, pinned_co_admins): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM course_tas WHERE ta_id = ?1 AND role = 'admin'),
+            (SELECT COUNT(*) FROM course_tas me
+             JOIN course_tas other ON other.course_id = me.course_id AND other.role = 'admin'
+             JOIN tas t ON t.id = other.ta_id
+             WHERE me.ta_id = ?1 AND t.pin_hash IS NOT NULL)
+        "#
+    )
+    .bind(ta_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if is_admin == 0 || pinned_co_admins > 0 {
+        return Err("A course admin has to set this TA's first PIN".to_string());
+    }
+    Ok(())
+}
+
+/// Set, change or (with `new_pin` None) remove a TA's PIN. Changing an
+/// existing PIN needs the current one; a first PIN needs `admin_ta_id` and
+/// that admin's session (see `check_first_pin_allowed`). Open sessions are
+/// ended either way.
+#[tauri::command]
+pub async fn set_ta_pin(
+    db: State<'_, DbState>,
+    ta_id: String,
+    current_pin: Option<String>,
+    new_pin: Option<String>,
+    admin_ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    match stored_pin(&pool, &ta_id).await? {
+        Some(stored) => {
+            let current = current_pin.ok_or("Enter the current PIN")?;
+            if !pin_matches_blocking(stored, current).await? {
+                return Err("Incorrect PIN".to_string());
+            }
+        }
+        None if new_pin.is_some() => {
+            check_first_pin_allowed(&pool, &ta_id, admin_ta_id.as_deref(), session_token.as_deref()).await?;
+        }
+        None => {}
+    }
+    let new_hash = match new_pin {
+        Some(pin) if pin.chars().count() < MIN_PIN_LEN => {
+            return Err(format!("PIN must be at least {} characters", MIN_PIN_LEN));
+        }
+        Some(pin) => Some(hash_pin_blocking(pin).await?),
+        None => None,
+    };
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE tas SET pin_hash = ? WHERE id = ?")
+        .bind(&new_hash)
+        .bind(&ta_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM ta_sessions WHERE ta_id = ?")
+        .bind(&ta_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let action = if new_hash.is_some() { "set_pin" } else { "clear_pin" };
+    sqlx::query("INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, ?, 'ta', ?, NULL)")
+        .bind(&ta_id)
+        .bind(action)
+        .bind(&ta_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Exchange a TA's PIN for a session token to pass to grading commands
+#[tauri::command]
+pub async fn start_ta_session(
+    db: State<'_, DbState>,
+    ta_id: String,
+    pin: String,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let stored = stored_pin(&pool, &ta_id).await?.ok_or("This TA has no PIN set")?;
+    let recent_failures: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM audit_log
+        WHERE ta_id = ? AND action = 'session_rejected' AND entity_type = 'ta'
+          AND ts > datetime('now', ?)
+        "#
+    )
+    .bind(&ta_id)
+    .bind(format!("-{} minutes", LOCKOUT_MINUTES))
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if recent_failures >= MAX_PIN_ATTEMPTS {
+        return Err(format!("Too many incorrect PINs; try again in {} minutes", LOCKOUT_MINUTES));
+    }
+    if !pin_matches_blocking(stored, pin).await? {
+        crate::grading::log_audit_internal(&pool, Some(&ta_id), "session_rejected", "ta", &ta_id, None).await?;
+        return Err("Incorrect PIN".to_string());
+    }
+
+    sqlx::query("DELETE FROM ta_sessions WHERE expires_at <= datetime('now')")
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let token = Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO ta_sessions (token, ta_id, expires_at) VALUES (?, ?, datetime('now', ?))")
+        .bind(&token)
+        .bind(&ta_id)
+        .bind(format!("+{} hours", SESSION_HOURS))
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    crate::grading::log_audit_internal(&pool, Some(&ta_id), "session_start", "ta", &ta_id, None).await?;
+    Ok(token)
+}
+
+#[tauri::command]
+pub async fn end_ta_session(db: State<'_, DbState>, session_token: String) -> Result<(), String> {
+    let pool = db.pool()?;
+    sqlx::query("DELETE FROM ta_sessions WHERE token = ?")
+        .bind(&session_token)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use std::path::Path;
+    use tauri::Manager;
+
+    /// Adds `ta_id` to the course with `role` ("admin" or "ta")
+    async fn join(db: &TestDb, course_id: &str, ta_id: &str, role: &str) {
+        sqlx::query("INSERT INTO course_tas (course_id, ta_id, role) VALUES (?, ?, ?)")
+            .bind(course_id)
+            .bind(ta_id)
+            .bind(role)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    /// Audit entries about the TA's own account, oldest first
+    async fn audit_actions(db: &TestDb, ta_id: &str) -> Vec<String> {
+        sqlx::query_scalar("SELECT action FROM audit_log WHERE ta_id = ? AND entity_type = 'ta' ORDER BY id")
+            .bind(ta_id)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn protected_commands_need_a_session_from_the_right_pin() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        let ada = db.ta("Ada").await;
+        let grace = db.ta("Grace").await;
+        join(&db, &course, &ada, "admin").await;
+        db.claim(&submission, &ada).await;
+        let complete = |ta: &str, token: Option<&str>| {
+            crate::grading::complete_submission(app.state(), submission.clone(), ta.to_string(), token.map(String::from))
+        };
+
+        // Without a PIN the TA is taken at their word
+        assert!(complete(&ada, None).await.is_ok());
+        assert_eq!(start_ta_session(app.state(), ada.clone(), "1234".into()).await.unwrap_err(), "This TA has no PIN set");
+
+        set_ta_pin(app.state(), ada.clone(), None, Some("1234".into()), None, None).await.unwrap();
+        assert_eq!(complete(&ada, None).await.err().as_deref(), Some(SESSION_REQUIRED));
+        assert_eq!(complete(&ada, Some("made-up")).await.err().as_deref(), Some(SESSION_REQUIRED));
+
+        assert_eq!(start_ta_session(app.state(), ada.clone(), "4321".into()).await.unwrap_err(), "Incorrect PIN");
+        let token = start_ta_session(app.state(), ada.clone(), "1234".into()).await.unwrap();
+        assert!(complete(&ada, Some(&token)).await.is_ok());
+        assert_eq!(audit_actions(&db, &ada).await, ["set_pin", "session_rejected", "session_start"]);
+
+        // A token only vouches for the TA it was issued to
+        join(&db, &course, &grace, "ta").await;
+        set_ta_pin(app.state(), grace.clone(), None, Some("5678".into()), Some(ada.clone()), Some(token.clone())).await.unwrap();
+        assert_eq!(
+            verify_ta(&db.pool, &grace, Some(&token)).await.unwrap_err(),
+            "Session belongs to a different TA"
+        );
+
+        end_ta_session(app.state(), token.clone()).await.unwrap();
+        assert_eq!(complete(&ada, Some(&token)).await.err().as_deref(), Some(SESSION_REQUIRED));
+
+        let expired = start_ta_session(app.state(), ada.clone(), "1234".into()).await.unwrap();
+        sqlx::query("UPDATE ta_sessions SET expires_at = datetime('now', '-1 minute') WHERE token = ?")
+            .bind(&expired)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(complete(&ada, Some(&expired)).await.err().as_deref(), Some(SESSION_REQUIRED));
+        assert_eq!(verify_ta(&db.pool, "nobody", None).await.unwrap_err(), "Unknown TA");
+    }
+
+    #[tokio::test]
+    async fn first_pins_come_from_a_course_admin() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let other_course = db.course().await;
+        let (admin, co_admin, ta, outsider) = (db.ta("Admin").await, db.ta("Co").await, db.ta("Ta").await, db.ta("Out").await);
+        join(&db, &course, &admin, "admin").await;
+        join(&db, &course, &co_admin, "admin").await;
+        join(&db, &course, &ta, "ta").await;
+        join(&db, &other_course, &outsider, "admin").await;
+        let set = |ta_id: &str, current: Option<&str>, new: Option<&str>, admin_ta_id: Option<&str>, token: Option<&str>| {
+            set_ta_pin(
+                app.state(),
+                ta_id.to_string(),
+                current.map(String::from),
+                new.map(String::from),
+                admin_ta_id.map(String::from),
+                token.map(String::from),
+            )
+        };
+
+        assert_eq!(set(&ta, None, Some("1234"), None, None).await.unwrap_err(), "A course admin has to set this TA's first PIN");
+        assert_eq!(
+            set(&ta, None, Some("1234"), Some(&admin), None).await.unwrap_err(),
+            "Set your own PIN before setting one for another TA"
+        );
+        assert_eq!(set(&admin, None, Some("123"), None, None).await.unwrap_err(), "PIN must be at least 4 characters");
+        // The first admin to secure their account may do so alone...
+        set(&admin, None, Some("1234"), None, None).await.unwrap();
+        // ...but after that, co-admins need them
+        assert_eq!(set(&co_admin, None, Some("9999"), None, None).await.unwrap_err(), "A course admin has to set this TA's first PIN");
+
+        assert_eq!(set(&ta, None, Some("5678"), Some(&admin), None).await.unwrap_err(), SESSION_REQUIRED);
+        let token = start_ta_session(app.state(), admin.clone(), "1234".into()).await.unwrap();
+        assert_eq!(
+            set(&outsider, None, Some("5678"), Some(&admin), Some(&token)).await.unwrap_err(),
+            "Only an admin of one of this TA's courses can set their first PIN"
+        );
+        set(&ta, None, Some("5678"), Some(&admin), Some(&token)).await.unwrap();
+
+        // Changing a PIN needs the current one, and ends open sessions
+        let session = start_ta_session(app.state(), ta.clone(), "5678".into()).await.unwrap();
+        assert_eq!(set(&ta, None, Some("0000"), None, None).await.unwrap_err(), "Enter the current PIN");
+        assert_eq!(set(&ta, Some("1111"), Some("0000"), None, None).await.unwrap_err(), "Incorrect PIN");
+        set(&ta, Some("5678"), Some("0000"), None, None).await.unwrap();
+        assert_eq!(verify_ta(&db.pool, &ta, Some(&session)).await.unwrap_err(), SESSION_REQUIRED);
+        assert_eq!(start_ta_session(app.state(), ta.clone(), "5678".into()).await.unwrap_err(), "Incorrect PIN");
+
+        set(&ta, Some("0000"), None, None, None).await.unwrap();
+        assert!(verify_ta(&db.pool, &ta, None).await.is_ok());
+        assert_eq!(audit_actions(&db, &ta).await, ["set_pin", "session_start", "set_pin", "session_rejected", "clear_pin"]);
+    }
+
+    #[tokio::test]
+    async fn changes_without_a_ta_are_refused_once_pins_are_in_use() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        let ada = db.ta("Ada").await;
+        join(&db, &course, &ada, "admin").await;
+        let set_status = |ta: Option<&str>, token: Option<&str>| {
+            crate::grading::update_submission_status(
+                app.state(),
+                submission.clone(),
+                "flagged".into(),
+                ta.map(String::from),
+                token.map(String::from),
+            )
+        };
+
+        // Nobody has a PIN yet, so anonymous changes still go through
+        set_status(None, None).await.unwrap();
+
+        set_ta_pin(app.state(), ada.clone(), None, Some("1234".into()), None, None).await.unwrap();
+        assert_eq!(set_status(None, None).await.unwrap_err(), SESSION_REQUIRED);
+        assert_eq!(
+            crate::commands::delete_assignment(app.state(), assignment.clone(), None, None).await.unwrap_err(),
+            SESSION_REQUIRED
+        );
+        assert_eq!(crate::commands::delete_course(app.state(), course.clone(), None, None).await.unwrap_err(), SESSION_REQUIRED);
+
+        let token = start_ta_session(app.state(), ada.clone(), "1234".into()).await.unwrap();
+        set_status(Some(&ada), Some(&token)).await.unwrap();
+        crate::commands::delete_assignment(app.state(), assignment.clone(), Some(ada.clone()), Some(token)).await.unwrap();
+        let actors: Vec<Option<String>> =
+            sqlx::query_scalar("SELECT ta_id FROM audit_log WHERE action = 'delete' AND entity_type = 'assignment'")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(actors, [Some(ada)]);
+    }
+
+    #[tokio::test]
+    async fn repeated_wrong_pins_lock_the_ta_out() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let ada = db.ta("Ada").await;
+        join(&db, &course, &ada, "admin").await;
+        set_ta_pin(app.state(), ada.clone(), None, Some("1234".into()), None, None).await.unwrap();
+        let sign_in = |pin: &str| start_ta_session(app.state(), ada.clone(), pin.to_string());
+
+        for _ in 0..MAX_PIN_ATTEMPTS {
+            assert_eq!(sign_in("0000").await.unwrap_err(), "Incorrect PIN");
+        }
+        let locked = format!("Too many incorrect PINs; try again in {} minutes", LOCKOUT_MINUTES);
+        assert_eq!(sign_in("1234").await.unwrap_err(), locked);
+        assert_eq!(sign_in("0000").await.unwrap_err(), locked);
+
+        // Failures older than the window no longer count
+        sqlx::query("UPDATE audit_log SET ts = datetime('now', '-1 hour') WHERE action = 'session_rejected'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(sign_in("1234").await.is_ok());
+    }
+}
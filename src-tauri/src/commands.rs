@@ -1,4 +1,4 @@
-use crate::db::DbPool;
+use crate::db::{DbPool, DbState};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use tauri::{State, AppHandle};
@@ -10,6 +10,8 @@ pub struct Course {
     name: String,
     term: String,
     created_at: String, // String for simplicity in JSON, sqlite stores likely as TEXT/DATETIME
+    id_pattern: Option<String>,
+    lms_convention: Option<String>,
 }
 
 #[derive(Serialize, FromRow)]
@@ -27,14 +29,103 @@ pub struct CreateStudent {
     section: Option<String>,
 }
 
+/// Save a roster, replacing matching students. With `remove_absent`, students
+/// already in the course but missing from `students` are deleted.
 #[tauri::command]
 pub async fn save_roster(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     course_id: String,
     students: Vec<CreateStudent>,
+    remove_absent: Option<bool>,
 ) -> Result<usize, String> {
+    let pool = db.pool()?;
+    write_roster(&pool, &course_id, students, remove_absent.unwrap_or(false)).await
+}
+
+/// Which source column feeds each roster field
+#[derive(Deserialize, Debug)]
+pub struct RosterMapping {
+    student_id: String,
+    name: String,
+    email: Option<String>,
+    section: Option<String>,
+}
+
+/// Save rows from `parse_excel_roster`/`parse_csv_roster` using `mapping` to pick
+/// the columns. Every row needs a student ID, unique within the file, and a name.
+#[tauri::command]
+pub async fn save_roster_mapped(
+    db: State<'_, DbState>,
+    course_id: String,
+    rows: Vec<std::collections::HashMap<String, String>>,
+    mapping: RosterMapping,
+    remove_absent: Option<bool>,
+) -> Result<usize, String> {
+    let pool = db.pool()?;
+    let optional = |col: &Option<String>| col.as_ref().filter(|c| !c.trim().is_empty()).cloned();
+    let (email_col, section_col) = (optional(&mapping.email), optional(&mapping.section));
+    for (field, col) in [("student_id", Some(&mapping.student_id)), ("name", Some(&mapping.name)), ("email", email_col.as_ref()), ("section", section_col.as_ref())] {
+        let Some(col) = col else { continue };
+        if col.trim().is_empty() {
+            return Err(format!("Choose a column for {}", field));
+        }
+        if !rows.iter().any(|r| r.contains_key(col)) {
+            return Err(format!("Column '{}' mapped to {} is not in the file", col, field));
+        }
+    }
+
+    let cell = |row: &std::collections::HashMap<String, String>, col: &str| {
+        row.get(col).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+    };
+    let mut problems = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut students = Vec::with_capacity(rows.len());
+    for (idx, row) in rows.iter().enumerate() {
+        // +2: header row, and spreadsheets count from 1
+        let line = idx + 2;
+        // Spreadsheets often carry trailing blank rows
+        if row.values().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        let Some(student_id) = cell(row, &mapping.student_id) else {
+            problems.push(format!("Row {}: missing student ID", line));
+            continue;
+        };
+        if !seen.insert(student_id.clone()) {
+            problems.push(format!("Row {}: duplicate student ID {}", line, student_id));
+            continue;
+        }
+        let Some(name) = cell(row, &mapping.name) else {
+            problems.push(format!("Row {}: missing name for {}", line, student_id));
+            continue;
+        };
+        students.push(CreateStudent {
+            student_id,
+            name,
+            email: email_col.as_deref().and_then(|c| cell(row, c)),
+            section: section_col.as_deref().and_then(|c| cell(row, c)),
+        });
+    }
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+
+    write_roster(&pool, &course_id, students, remove_absent.unwrap_or(false)).await
+}
+
+async fn write_roster(pool: &DbPool, course_id: &str, students: Vec<CreateStudent>, remove_absent: bool) -> Result<usize, String> {
     let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
 
+    if remove_absent {
+        let keep: Vec<&str> = students.iter().map(|s| s.student_id.as_str()).collect();
+        sqlx::query("DELETE FROM students WHERE course_id = ? AND student_id NOT IN (SELECT value FROM json_each(?))")
+            .bind(course_id)
+            .bind(serde_json::to_string(&keep).map_err(|e| e.to_string())?)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
     // Clear existing roster? "Imports a course roster once... reuses it". 
     // Requirement "Roster import...". Usually M1 is simple import.
     // Let's assume append or overwrite? "Reuse" implies persistence.
@@ -44,7 +135,7 @@ pub async fn save_roster(
     let mut count = 0;
     for s in students {
         sqlx::query("INSERT OR REPLACE INTO students (course_id, student_id, name, email, section) VALUES (?, ?, ?, ?, ?)")
-            .bind(&course_id)
+            .bind(course_id)
             .bind(&s.student_id)
             .bind(&s.name)
             .bind(&s.email)
@@ -59,6 +150,86 @@ pub async fn save_roster(
     Ok(count)
 }
 
+#[derive(Serialize, Debug)]
+pub struct RosterFieldChange {
+    field: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RosterUpdate {
+    student_id: String,
+    changes: Vec<RosterFieldChange>,
+}
+
+#[derive(Serialize, Default)]
+pub struct RosterPreview {
+    added: Vec<CreateStudent>,
+    updated: Vec<RosterUpdate>,
+    unchanged: usize,
+    /// In the course but not in the new file; removed only if saved with `remove_absent`
+    absent: Vec<Student>,
+}
+
+/// Compare a roster about to be saved with the course's current one, without writing
+#[tauri::command]
+pub async fn preview_roster_import(
+    db: State<'_, DbState>,
+    course_id: String,
+    students: Vec<CreateStudent>,
+) -> Result<RosterPreview, String> {
+    let pool = db.pool()?;
+    let existing: Vec<(String, String, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT student_id, name, email, section FROM students WHERE course_id = ? ORDER BY name ASC"
+    )
+    .bind(&course_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut current: std::collections::HashMap<&str, &(String, String, Option<String>, Option<String>)> =
+        existing.iter().map(|row| (row.0.as_str(), row)).collect();
+
+    let mut preview = RosterPreview::default();
+    // Later rows win, as they would in save_roster
+    let mut seen = std::collections::HashSet::new();
+    let mut incoming: Vec<CreateStudent> = Vec::with_capacity(students.len());
+    for s in students.into_iter().rev() {
+        if seen.insert(s.student_id.clone()) {
+            incoming.push(s);
+        }
+    }
+    incoming.reverse();
+
+    for s in incoming {
+        let Some((_, name, email, section)) = current.remove(s.student_id.as_str()) else {
+            preview.added.push(s);
+            continue;
+        };
+        let mut changes = Vec::new();
+        let mut compare = |field: &'static str, old: Option<&String>, new: Option<&String>| {
+            if old != new {
+                changes.push(RosterFieldChange { field, old: old.cloned(), new: new.cloned() });
+            }
+        };
+        compare("name", Some(name), Some(&s.name));
+        compare("email", email.as_ref(), s.email.as_ref());
+        compare("section", section.as_ref(), s.section.as_ref());
+        if changes.is_empty() {
+            preview.unchanged += 1;
+        } else {
+            preview.updated.push(RosterUpdate { student_id: s.student_id, changes });
+        }
+    }
+
+    preview.absent = existing
+        .iter()
+        .filter(|(sid, ..)| current.contains_key(sid.as_str()))
+        .map(|(sid, name, email, _)| Student { student_id: sid.clone(), name: name.clone(), email: email.clone() })
+        .collect();
+    Ok(preview)
+}
+
 #[derive(Serialize, FromRow)]
 pub struct Student {
     pub student_id: String,
@@ -68,14 +239,15 @@ pub struct Student {
 
 #[tauri::command]
 pub async fn list_students(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     course_id: String,
 ) -> Result<Vec<Student>, String> {
+    let pool = db.pool()?;
     let students = sqlx::query_as::<sqlx::Sqlite, Student>(
         "SELECT student_id, name, email FROM students WHERE course_id = ? ORDER BY name ASC"
     )
     .bind(&course_id)
-    .fetch_all(&*pool)
+    .fetch_all(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
@@ -85,51 +257,200 @@ pub async fn list_students(
 
 #[tauri::command]
 pub async fn create_course(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     name: String,
     term: String,
 ) -> Result<String, String> {
+    let pool = db.pool()?;
     let id = Uuid::new_v4().to_string();
     sqlx::query("INSERT INTO courses (id, name, term) VALUES (?, ?, ?)")
         .bind(&id)
         .bind(&name)
         .bind(&term)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     Ok(id)
 }
 
 #[tauri::command]
-pub async fn list_courses(pool: State<'_, DbPool>) -> Result<Vec<Course>, String> {
-    let courses = sqlx::query_as::<sqlx::Sqlite, Course>("SELECT id, name, term, created_at FROM courses ORDER BY created_at DESC")
-        .fetch_all(&*pool)
+pub async fn list_courses(db: State<'_, DbState>) -> Result<Vec<Course>, String> {
+    let pool = db.pool()?;
+    let courses = sqlx::query_as::<sqlx::Sqlite, Course>("SELECT id, name, term, created_at, id_pattern, lms_convention FROM courses WHERE deleted_at IS NULL ORDER BY created_at DESC")
+        .fetch_all(&pool)
         .await
         .map_err(|e| e.to_string())?;
     Ok(courses)
 }
 
+/// Set the regex used to find student IDs in submission filenames.
+/// An empty/missing pattern restores the default 8-digit pattern.
+#[tauri::command]
+pub async fn set_course_id_pattern(
+    db: State<'_, DbState>,
+    course_id: String,
+    pattern: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    let pattern = pattern.filter(|p| !p.trim().is_empty());
+    crate::submissions::compile_id_pattern(pattern.as_deref())?;
+    
+    sqlx::query("UPDATE courses SET id_pattern = ? WHERE id = ?")
+        .bind(&pattern)
+        .bind(&course_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Set which LMS's bulk-download naming ("canvas", "moodle", "blackboard") the
+/// course's submission filenames follow. Empty/missing means plain filenames.
+#[tauri::command]
+pub async fn set_course_lms_convention(
+    db: State<'_, DbState>,
+    course_id: String,
+    convention: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    let convention = convention
+        .filter(|c| !c.trim().is_empty())
+        .map(|c| c.trim().to_lowercase());
+    crate::submissions::compile_lms_naming(convention.as_deref())?;
+
+    sqlx::query("UPDATE courses SET lms_convention = ? WHERE id = ?")
+        .bind(&convention)
+        .bind(&course_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct DbInfo {
+    /// Highest successfully applied migration
+    schema_version: Option<i64>,
+    /// Newest migration this build of the app knows about
+    app_schema_version: Option<i64>,
+    applied_migrations: i64,
+    /// A migration started but didn't finish (e.g. crash during startup)
+    dirty: bool,
+    /// `PRAGMA integrity_check` output; "ok" when healthy
+    integrity: Vec<String>,
+    path: String,
+    size_bytes: u64,
+}
+
+/// Diagnostics for support: migration state, integrity check, and file location
+#[tauri::command]
+pub async fn get_db_info(app: AppHandle, db: State<'_, DbState>) -> Result<DbInfo, String> {
+    let pool = db.pool()?;
+    let path = crate::db::db_path(&app).map_err(|e| e.to_string())?;
+    db_info(&pool, &path).await
+}
+
+/// `get_db_info` for the database at `path`
+async fn db_info(pool: &DbPool, path: &std::path::Path) -> Result<DbInfo, String> {
+    let (schema_version, applied_migrations): (Option<i64>, i64) = sqlx::query_as(
+        "SELECT MAX(version), COUNT(*) FROM _sqlx_migrations WHERE success = 1"
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    let dirty: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM _sqlx_migrations WHERE success = 0)")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    let app_schema_version = crate::db::MIGRATOR.iter().map(|m| m.version).max();
+    if let (Some(db), Some(known)) = (schema_version, app_schema_version) {
+        if db > known {
+            return Err(format!(
+                "Database schema version {} is newer than this app supports ({}). It was opened by a newer version of the app; please update.",
+                db, known
+            ));
+        }
+    }
+    
+    let integrity: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    let size_bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    
+    Ok(DbInfo {
+        schema_version,
+        app_schema_version,
+        applied_migrations,
+        dirty,
+        integrity,
+        path: path.to_string_lossy().to_string(),
+        size_bytes,
+    })
+}
+
+#[tauri::command]
+pub async fn get_setting(db: State<'_, DbState>, key: String) -> Result<Option<String>, String> {
+    let pool = db.pool()?;
+    sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(&key)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Store an app setting. An empty/missing value removes it.
+#[tauri::command]
+pub async fn set_setting(
+    db: State<'_, DbState>,
+    key: String,
+    value: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    match value.filter(|v| !v.trim().is_empty()) {
+        Some(v) => sqlx::query(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, CURRENT_TIMESTAMP)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )
+        .bind(&key)
+        .bind(v)
+        .execute(&pool)
+        .await,
+        None => sqlx::query("DELETE FROM settings WHERE key = ?")
+            .bind(&key)
+            .execute(&pool)
+            .await,
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn create_ta(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     display_name: String,
     initials: String,
 ) -> Result<String, String> {
+    let pool = db.pool()?;
     let id = Uuid::new_v4().to_string();
     sqlx::query("INSERT INTO tas (id, display_name, initials) VALUES (?, ?, ?)")
         .bind(&id)
         .bind(&display_name)
         .bind(&initials)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     Ok(id)
 }
 
 #[tauri::command]
-pub async fn list_tas(pool: State<'_, DbPool>) -> Result<Vec<Ta>, String> {
+pub async fn list_tas(db: State<'_, DbState>) -> Result<Vec<Ta>, String> {
+    let pool = db.pool()?;
     let tas = sqlx::query_as::<sqlx::Sqlite, Ta>("SELECT id, display_name, initials FROM tas ORDER BY display_name ASC")
-        .fetch_all(&*pool)
+        .fetch_all(&pool)
         .await
         .map_err(|e| e.to_string())?;
     Ok(tas)
@@ -142,11 +463,36 @@ pub struct Assignment {
     pub due_date: Option<String>,
     pub rubric_json: Option<String>,
     pub created_at: String,
+    /// Graders see pseudonymous labels instead of student names and ids
+    pub anonymous: bool,
+    /// Sum of the rubric's `max_points`, derived from `rubric_json`
+    #[sqlx(skip)]
+    pub total_max_points: f64,
+    #[sqlx(skip)]
+    pub question_count: i64,
+}
+
+impl Assignment {
+    /// Fill in the rubric-derived fields. A missing or unparseable rubric counts as empty.
+    fn with_rubric_totals(mut self) -> Self {
+        let questions = self
+            .rubric_json
+            .as_deref()
+            .filter(|j| !j.trim().is_empty())
+            .and_then(|j| serde_json::from_str::<serde_json::Value>(j).ok())
+            .and_then(|r| r["questions"].as_array().cloned())
+            .unwrap_or_default();
+        self.total_max_points = questions.iter().filter_map(|q| q["max_points"].as_f64()).sum();
+        self.question_count = questions.len() as i64;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Rubric {
     questions: Vec<Question>,
+    /// Normalize the final score to this many points (e.g. 100). Unset keeps raw points.
+    scale: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -154,7 +500,12 @@ pub struct Question {
     question_id: String,
     title: String,
     max_points: f64,
+    /// Contribution to the total; the score counts as `score / max_points * weight`.
+    /// Unset means the question counts its raw points.
+    weight: Option<f64>,
     description: Option<String>,
+    /// File name (or pattern) students are expected to submit for this question
+    expected_file: Option<String>,
     comment_presets: Vec<CommentPreset>,
     excel_checks: Option<Vec<ExcelCheck>>,
 }
@@ -175,215 +526,2085 @@ pub enum ExcelCheck {
     MustUseFunctions { functions: Vec<String> },
     #[serde(rename = "must_have_pivot")]
     MustHavePivot,
+    /// At least `min_count` charts (default 1), optionally of one type such as "line"
+    #[serde(rename = "must_have_chart")]
+    MustHaveChart {
+        #[serde(default)]
+        min_count: Option<usize>,
+        #[serde(default)]
+        chart_type: Option<String>,
+    },
+    /// The cell's last-saved value must equal `expected` (numbers within `tolerance`)
+    #[serde(rename = "cell_equals")]
+    CellEquals {
+        sheet: String,
+        cell: String,
+        expected: serde_json::Value,
+        #[serde(default)]
+        tolerance: Option<f64>,
+    },
+    /// Every cell in the range must carry a data validation rule (e.g. a dropdown list)
+    #[serde(rename = "range_has_data_validation")]
+    RangeHasDataValidation { sheet: String, range: String },
+    /// At least one conditional formatting rule must apply somewhere in the range
+    #[serde(rename = "range_has_conditional_formatting")]
+    RangeHasConditionalFormatting { sheet: String, range: String },
+    /// Every value in the range must use this kind of number format, with
+    /// exactly `decimals` places when given
+    #[serde(rename = "range_number_format")]
+    RangeNumberFormat {
+        sheet: String,
+        range: String,
+        format_kind: crate::excel::NumberFormatKind,
+        #[serde(default)]
+        decimals: Option<u32>,
+    },
+    /// The sheet must have frozen rows and/or columns (e.g. a frozen header row)
+    #[serde(rename = "has_frozen_panes")]
+    HasFrozenPanes { sheet: String },
+    #[serde(rename = "gridlines_hidden")]
+    GridlinesHidden { sheet: String },
 }
 
 #[tauri::command]
 pub async fn create_assignment(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     course_id: String,
     title: String,
     due_date: Option<String>,
 ) -> Result<String, String> {
+    let pool = db.pool()?;
     let id = Uuid::new_v4().to_string();
     sqlx::query("INSERT INTO assignments (id, course_id, title, due_date) VALUES (?, ?, ?, ?)")
         .bind(&id)
         .bind(&course_id)
         .bind(&title)
         .bind(&due_date)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     Ok(id)
 }
 
 #[tauri::command]
-pub async fn list_assignments(pool: State<'_, DbPool>, course_id: String) -> Result<Vec<Assignment>, String> {
+pub async fn list_assignments(db: State<'_, DbState>, course_id: String) -> Result<Vec<Assignment>, String> {
+    let pool = db.pool()?;
     let assignments = sqlx::query_as::<sqlx::Sqlite, Assignment>(
-        "SELECT id, course_id, title, due_date, rubric_json, created_at FROM assignments WHERE course_id = ? ORDER BY created_at DESC"
+        r#"
+        SELECT a.id, a.course_id, a.title, a.due_date, a.rubric_json, a.created_at, a.anonymous
+        FROM assignments a
+        JOIN courses c ON c.id = a.course_id
+        WHERE a.course_id = ? AND a.deleted_at IS NULL AND c.deleted_at IS NULL
+        ORDER BY a.created_at DESC
+        "#
     )
     .bind(course_id)
-    .fetch_all(&*pool)
+    .fetch_all(&pool)
     .await
     .map_err(|e| e.to_string())?;
-    Ok(assignments)
+    Ok(assignments.into_iter().map(Assignment::with_rubric_totals).collect())
 }
 
+/// Soft-delete a course. Its assignments disappear from lists with it
+/// but nothing is dropped.
 #[tauri::command]
-pub async fn get_assignment(pool: State<'_, DbPool>, id: String) -> Result<Assignment, String> {
-    let assignment = sqlx::query_as::<sqlx::Sqlite, Assignment>(
-        "SELECT id, course_id, title, due_date, rubric_json, created_at FROM assignments WHERE id = ?"
-    )
-    .bind(id)
-    .fetch_optional(&*pool)
-    .await
-    .map_err(|e| e.to_string())?
-    .ok_or("Assignment not found")?;
-    Ok(assignment)
+pub async fn delete_course(
+    db: State<'_, DbState>,
+    course_id: String,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
+    let result = sqlx::query("UPDATE courses SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL")
+        .bind(&course_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err("Course not found".to_string());
+    }
+    crate::grading::log_audit_internal(&pool, ta_id.as_deref(), "delete", "course", &course_id, None).await
 }
 
+/// Soft-delete an assignment
 #[tauri::command]
-pub async fn update_rubric(
-    pool: State<'_, DbPool>,
+pub async fn delete_assignment(
+    db: State<'_, DbState>,
     assignment_id: String,
-    rubric_json: String, // Expecting valid JSON string
+    ta_id: Option<String>,
+    session_token: Option<String>,
 ) -> Result<(), String> {
-    // Validate JSON structure simply
-    let _rubric: Rubric = serde_json::from_str(&rubric_json).map_err(|e| format!("Invalid Rubric JSON: {}", e))?;
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
+    let result = sqlx::query("UPDATE assignments SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL")
+        .bind(&assignment_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err("Assignment not found".to_string());
+    }
+    crate::grading::log_audit_internal(&pool, ta_id.as_deref(), "delete", "assignment", &assignment_id, None).await
+}
 
-    sqlx::query("UPDATE assignments SET rubric_json = ? WHERE id = ?")
-        .bind(&rubric_json)
+/// Undo `delete_assignment`
+#[tauri::command]
+pub async fn restore_assignment(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
+    let result = sqlx::query("UPDATE assignments SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
         .bind(&assignment_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
-    Ok(())
+    if result.rows_affected() == 0 {
+        return Err("Assignment is not deleted".to_string());
+    }
+    crate::grading::log_audit_internal(&pool, ta_id.as_deref(), "restore", "assignment", &assignment_id, None).await
 }
-#[derive(Serialize, Debug)]
-pub struct SubmissionDetail {
-    submission_id: String,
-    student_id: Option<String>,
-    student_name: Option<String>,
-    status: String,
-    files: Vec<FileInfo>,
+
+#[tauri::command]
+pub async fn get_assignment(db: State<'_, DbState>, id: String) -> Result<Assignment, String> {
+    let pool = db.pool()?;
+    let assignment = sqlx::query_as::<sqlx::Sqlite, Assignment>(
+        "SELECT id, course_id, title, due_date, rubric_json, created_at, anonymous FROM assignments WHERE id = ?"
+    )
+    .bind(id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Assignment not found")?;
+    Ok(assignment.with_rubric_totals())
 }
 
 #[derive(Serialize, Debug)]
-pub struct FileInfo {
-    path: String, // Relative path in cache
-    name: String,
-    is_dir: bool,
+pub struct RubricValidationError {
+    /// Location of the problem, e.g. `questions[2].comment_presets[0].deduction`
+    path: String,
+    message: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, FromRow)]
-pub struct GradeRecord {
-    id: i64, // Auto increment
-    submission_id: String,
-    question_id: String,
-    score: Option<f64>,
-    comment: Option<String>,
+impl RubricValidationError {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), message: message.into() }
+    }
 }
 
-#[tauri::command]
-pub async fn get_submission_detail(
-    pool: State<'_, DbPool>,
-    submission_id: String,
-) -> Result<SubmissionDetail, String> {
-    // 1. Get stats
-    let row: (Option<String>, Option<String>, String, String) = sqlx::query_as(
-        r#"
-        SELECT s.student_id, st.name, s.status, s.folder_path
-        FROM submissions s
-        LEFT JOIN students st ON s.student_id = st.student_id AND s.course_id = (SELECT course_id FROM assignments WHERE id = s.assignment_id)
-        WHERE s.id = ?
-        "#
-    )
-    .bind(&submission_id)
-    .fetch_optional(&*pool)
-    .await
-    .map_err(|e| e.to_string())?
-    .ok_or("Submission not found")?;
+/// Check a parsed rubric for problems serde can't catch
+fn validate_rubric(rubric: &Rubric) -> Vec<RubricValidationError> {
+    let mut errors = Vec::new();
+    let mut seen_ids = std::collections::HashSet::new();
 
-    let (student_id, student_name, status, folder_path) = row;
+    for (qi, q) in rubric.questions.iter().enumerate() {
+        let base = format!("questions[{}]", qi);
 
-    // 2. Walk dir for files
-    let mut files = Vec::new();
-    let root = std::path::Path::new(&folder_path);
-    if root.exists() {
-        for entry in walkdir::WalkDir::new(root) {
-            if let Ok(e) = entry {
-                let p = e.path();
-                if p.is_file() {
-                    if let Ok(rel) = p.strip_prefix(root) {
-                         files.push(FileInfo {
-                             path: rel.to_string_lossy().to_string(),
-                             name: p.file_name().unwrap().to_string_lossy().to_string(),
-                             is_dir: false
-                         });
+        if q.question_id.trim().is_empty() {
+            errors.push(RubricValidationError::new(format!("{}.question_id", base), "Question ID is required"));
+        } else if !seen_ids.insert(q.question_id.as_str()) {
+            errors.push(RubricValidationError::new(
+                format!("{}.question_id", base),
+                format!("Duplicate question ID '{}'", q.question_id),
+            ));
+        }
+
+        if !q.max_points.is_finite() || q.max_points < 0.0 {
+            errors.push(RubricValidationError::new(
+                format!("{}.max_points", base),
+                "Max points must be a non-negative number",
+            ));
+        }
+
+        if let Some(weight) = q.weight {
+            if !weight.is_finite() || weight < 0.0 {
+                errors.push(RubricValidationError::new(format!("{}.weight", base), "Weight must be a non-negative number"));
+            } else if weight > 0.0 && q.max_points <= 0.0 {
+                errors.push(RubricValidationError::new(
+                    format!("{}.weight", base),
+                    "A weighted question needs max points above zero",
+                ));
+            }
+        }
+
+        for (pi, preset) in q.comment_presets.iter().enumerate() {
+            if let Some(deduction) = preset.deduction {
+                if deduction.abs() > q.max_points {
+                    errors.push(RubricValidationError::new(
+                        format!("{}.comment_presets[{}].deduction", base, pi),
+                        format!("Deduction {} exceeds the question's {} max points", deduction, q.max_points),
+                    ));
+                }
+            }
+        }
+
+        for (ci, check) in q.excel_checks.iter().flatten().enumerate() {
+            match check {
+                ExcelCheck::RangeMustHaveFormulas { range, .. }
+                | ExcelCheck::RangeHasDataValidation { range, .. }
+                | ExcelCheck::RangeHasConditionalFormatting { range, .. }
+                | ExcelCheck::RangeNumberFormat { range, .. } => {
+                    if let Err(e) = crate::excel::parse_range(range) {
+                        errors.push(RubricValidationError::new(format!("{}.excel_checks[{}].range", base, ci), e));
+                    }
+                }
+                ExcelCheck::CellEquals { cell, .. } => {
+                    if let Err(e) = crate::excel::parse_cell_ref(cell) {
+                        errors.push(RubricValidationError::new(format!("{}.excel_checks[{}].cell", base, ci), e));
                     }
                 }
+                _ => {}
             }
         }
     }
 
-    Ok(SubmissionDetail {
-        submission_id,
-        student_id,
-        student_name,
-        status,
-        files
-    })
+    if let Some(scale) = rubric.scale {
+        if !scale.is_finite() || scale <= 0.0 {
+            errors.push(RubricValidationError::new("scale", "Scale must be a positive number"));
+        }
+    }
+
+    errors
 }
 
+/// Save an assignment's rubric. Rejects it with a list of field-level
+/// errors if it doesn't parse or fails validation. If the change would orphan
+/// grades or leave scores above a lowered max, it is refused until the caller
+/// passes `acknowledge_impact` (see `get_rubric_change_impact`).
 #[tauri::command]
-pub async fn save_grade(
-    pool: State<'_, DbPool>,
-    submission_id: String,
-    question_id: String,
-    score: Option<f64>,
-    comment: Option<String>,
-) -> Result<(), String> {
-    let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM grades WHERE submission_id = ? AND question_id = ?")
-        .bind(&submission_id)
-        .bind(&question_id)
-        .fetch_optional(&*pool)
+pub async fn update_rubric(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    rubric_json: String, // Expecting valid JSON string
+    acknowledge_impact: Option<bool>,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), Vec<RubricValidationError>> {
+    let pool = db.pool().map_err(|e| vec![RubricValidationError::new("", e)])?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref())
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| vec![RubricValidationError::new("", e)])?;
+    replace_rubric(&pool, &assignment_id, &rubric_json, acknowledge_impact.unwrap_or(false), ta_id.as_deref(), None).await
+}
 
-    if let Some(id) = exists {
-        sqlx::query("UPDATE grades SET score = ?, comment = ? WHERE id = ?")
-            .bind(score)
-            .bind(comment)
-            .bind(id)
-            .execute(&*pool)
-            .await
-            .map_err(|e| e.to_string())?;
-    } else {
-        sqlx::query("INSERT INTO grades (submission_id, question_id, score, comment) VALUES (?, ?, ?, ?)")
-            .bind(&submission_id)
-            .bind(&question_id)
-            .bind(score)
-            .bind(comment)
-            .execute(&*pool)
+/// The one path every rubric overwrite goes through: validate, refuse a change
+/// that affects existing grades unless acknowledged, store, and audit the diff.
+/// `source` is recorded in the audit entry (e.g. the template it came from).
+async fn replace_rubric(
+    pool: &DbPool,
+    assignment_id: &str,
+    rubric_json: &str,
+    acknowledge_impact: bool,
+    ta_id: Option<&str>,
+    source: Option<serde_json::Value>,
+) -> Result<(), Vec<RubricValidationError>> {
+    let rubric = check_rubric_json(rubric_json)?;
+    let impact = rubric_change_impact(pool, assignment_id, &rubric)
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e)])?;
+    if impact.has_affected_grades() && !acknowledge_impact {
+        return Err(vec![RubricValidationError::new("", format!(
+            "This change affects existing grades: {} on removed questions, {} above the new max points. Confirm to apply it anyway.",
+            impact.grades_on_removed_questions,
+            impact.grades_over_new_max.len()
+        ))]);
+    }
+
+    store_rubric(pool, assignment_id, rubric_json).await?;
+    if !impact.diff.is_empty() {
+        let details = serde_json::json!({
+            "added": impact.diff.added,
+            "removed": impact.diff.removed,
+            "modified": impact.diff.modified,
+            "scale": impact.diff.scale,
+            "grades_on_removed_questions": impact.grades_on_removed_questions,
+            "grades_over_new_max": impact.grades_over_new_max.len(),
+            "source": source,
+        })
+        .to_string();
+        crate::grading::log_audit_internal(pool, ta_id, "rubric_change", "assignment", assignment_id, Some(&details))
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| vec![RubricValidationError::new("", e)])?;
     }
-    
     Ok(())
 }
 
-#[tauri::command]
-pub async fn get_grades(
-    pool: State<'_, DbPool>,
-    submission_id: String,
-) -> Result<Vec<GradeRecord>, String> {
-    let grades = sqlx::query_as::<sqlx::Sqlite, GradeRecord>(
-        "SELECT id, submission_id, question_id, score, comment FROM grades WHERE submission_id = ?"
-    )
-    .bind(submission_id)
-    .fetch_all(&*pool)
-    .await
-    .map_err(|e| e.to_string())?;
-    Ok(grades)
-}
+/// Parse and validate a rubric without storing it
+fn check_rubric_json(rubric_json: &str) -> Result<Rubric, Vec<RubricValidationError>> {
+    let rubric: Rubric = serde_json::from_str(rubric_json)
+        .map_err(|e| vec![RubricValidationError::new("", format!("Invalid Rubric JSON: {}", e))])?;
 
-/// Read a file's content from a submission
-#[tauri::command]
-pub async fn read_submission_file(
-    pool: State<'_, DbPool>,
-    submission_id: String,
-    file_path: String,
-) -> Result<String, String> {
-    let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
-        .bind(&submission_id)
-        .fetch_one(&*pool)
-        .await
-        .map_err(|e| e.to_string())?;
-    
-    let full_path = std::path::Path::new(&folder_path).join(&file_path);
-    
-    if !full_path.exists() {
-        return Err("File not found".to_string());
+    let errors = validate_rubric(&rubric);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(rubric)
+}
+
+/// One field that differs between two versions of a question. For comment
+/// presets and Excel checks that exist on one side only, the other side is null.
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    field: String,
+    old: serde_json::Value,
+    new: serde_json::Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuestionChange {
+    question_id: String,
+    /// The question's title in the new rubric
+    title: String,
+    changes: Vec<FieldChange>,
+}
+
+/// Differences between two rubrics, with questions matched by `question_id`
+#[derive(Serialize, Debug, Default)]
+pub struct RubricDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<QuestionChange>,
+    scale: Option<FieldChange>,
+}
+
+impl RubricDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() && self.scale.is_none()
+    }
+
+    /// (old, new) max points of a question whose max changed
+    fn max_points_change(&self, question_id: &str) -> Option<(f64, f64)> {
+        let question = self.modified.iter().find(|q| q.question_id == question_id)?;
+        let change = question.changes.iter().find(|c| c.field == "max_points")?;
+        Some((change.old.as_f64()?, change.new.as_f64()?))
+    }
+}
+
+fn push_change(changes: &mut Vec<FieldChange>, field: impl Into<String>, old: serde_json::Value, new: serde_json::Value) {
+    if old != new {
+        changes.push(FieldChange { field: field.into(), old, new });
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// Field-level changes to one question. Presets are matched by label;
+/// Excel checks have no id, so an edited check shows as one removed and one added.
+fn diff_question(old: &Question, new: &Question) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    push_change(&mut changes, "title", to_json(&old.title), to_json(&new.title));
+    push_change(&mut changes, "max_points", to_json(&old.max_points), to_json(&new.max_points));
+    push_change(&mut changes, "weight", to_json(&old.weight), to_json(&new.weight));
+    push_change(&mut changes, "description", to_json(&old.description), to_json(&new.description));
+    push_change(&mut changes, "expected_file", to_json(&old.expected_file), to_json(&new.expected_file));
+
+    for preset in &new.comment_presets {
+        let previous = old.comment_presets.iter().find(|p| p.label == preset.label);
+        push_change(
+            &mut changes,
+            format!("comment_presets[{}]", preset.label),
+            previous.map(to_json).unwrap_or_default(),
+            to_json(preset),
+        );
+    }
+    for preset in old.comment_presets.iter().filter(|p| !new.comment_presets.iter().any(|n| n.label == p.label)) {
+        push_change(&mut changes, format!("comment_presets[{}]", preset.label), to_json(preset), serde_json::Value::Null);
+    }
+
+    let old_checks: Vec<serde_json::Value> = old.excel_checks.iter().flatten().map(to_json).collect();
+    let new_checks: Vec<serde_json::Value> = new.excel_checks.iter().flatten().map(to_json).collect();
+    for check in new_checks.iter().filter(|c| !old_checks.contains(c)) {
+        push_change(&mut changes, "excel_checks", serde_json::Value::Null, check.clone());
+    }
+    for check in old_checks.iter().filter(|c| !new_checks.contains(c)) {
+        push_change(&mut changes, "excel_checks", check.clone(), serde_json::Value::Null);
+    }
+    changes
+}
+
+fn diff_rubric(old: &Rubric, new: &Rubric) -> RubricDiff {
+    let mut diff = RubricDiff::default();
+    for q in &new.questions {
+        match old.questions.iter().find(|o| o.question_id == q.question_id) {
+            None => diff.added.push(q.question_id.clone()),
+            Some(o) => {
+                let changes = diff_question(o, q);
+                if !changes.is_empty() {
+                    diff.modified.push(QuestionChange {
+                        question_id: q.question_id.clone(),
+                        title: q.title.clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+    for o in &old.questions {
+        if !new.questions.iter().any(|q| q.question_id == o.question_id) {
+            diff.removed.push(o.question_id.clone());
+        }
+    }
+    let mut scale = Vec::new();
+    push_change(&mut scale, "scale", to_json(&old.scale), to_json(&new.scale));
+    diff.scale = scale.pop();
+    diff
+}
+
+/// Compare two versions of a rubric: questions added, removed and modified,
+/// down to individual fields, presets and Excel checks
+#[tauri::command]
+pub fn diff_rubrics(old_json: String, new_json: String) -> Result<RubricDiff, String> {
+    let old: Rubric = serde_json::from_str(&old_json).map_err(|e| format!("Invalid old rubric JSON: {}", e))?;
+    let new: Rubric = serde_json::from_str(&new_json).map_err(|e| format!("Invalid new rubric JSON: {}", e))?;
+    Ok(diff_rubric(&old, &new))
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExceedingGrade {
+    submission_id: String,
+    question_id: String,
+    score: f64,
+    new_max: f64,
+}
+
+/// What saving a new rubric would do to the grades already entered
+#[derive(Serialize, Debug)]
+pub struct RubricChangeImpact {
+    diff: RubricDiff,
+    /// Grades (score or comment) on questions the new rubric drops
+    grades_on_removed_questions: i64,
+    /// Current submissions holding at least one of those grades
+    submissions_with_removed_grades: i64,
+    grades_over_new_max: Vec<ExceedingGrade>,
+}
+
+impl RubricChangeImpact {
+    fn has_affected_grades(&self) -> bool {
+        self.grades_on_removed_questions > 0 || !self.grades_over_new_max.is_empty()
+    }
+}
+
+async fn rubric_change_impact(pool: &DbPool, assignment_id: &str, new_rubric: &Rubric) -> Result<RubricChangeImpact, String> {
+    let old_json: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+        .bind(assignment_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Assignment not found")?;
+    let old_rubric = old_json
+        .and_then(|j| serde_json::from_str::<Rubric>(&j).ok())
+        .unwrap_or(Rubric { questions: Vec::new(), scale: None });
+    let diff = diff_rubric(&old_rubric, new_rubric);
+
+    let grades: Vec<(String, String, Option<f64>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT g.submission_id, g.question_id, g.score, g.comment
+        FROM grades g
+        JOIN submissions s ON s.id = g.submission_id
+        WHERE s.assignment_id = ? AND s.deleted_at IS NULL AND s.superseded_by IS NULL
+        "#
+    )
+    .bind(assignment_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut grades_on_removed_questions = 0;
+    let mut removed_submissions = std::collections::HashSet::new();
+    let mut grades_over_new_max = Vec::new();
+    for (submission_id, question_id, score, comment) in grades {
+        let has_content = score.is_some() || comment.as_deref().is_some_and(|c| !c.trim().is_empty());
+        if diff.removed.contains(&question_id) {
+            if has_content {
+                grades_on_removed_questions += 1;
+                removed_submissions.insert(submission_id);
+            }
+            continue;
+        }
+        let (Some(score), Some(question)) = (score, new_rubric.questions.iter().find(|q| q.question_id == question_id)) else {
+            continue;
+        };
+        let lowered = diff.max_points_change(&question_id).is_some_and(|(old_max, new_max)| new_max < old_max);
+        if lowered && score > question.max_points {
+            grades_over_new_max.push(ExceedingGrade {
+                submission_id,
+                question_id,
+                score,
+                new_max: question.max_points,
+            });
+        }
+    }
+
+    Ok(RubricChangeImpact {
+        diff,
+        grades_on_removed_questions,
+        submissions_with_removed_grades: removed_submissions.len() as i64,
+        grades_over_new_max,
+    })
+}
+
+/// Preview how replacing an assignment's rubric would affect existing grades:
+/// grades left on removed questions and scores above a lowered max.
+#[tauri::command]
+pub async fn get_rubric_change_impact(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    new_rubric_json: String,
+) -> Result<RubricChangeImpact, String> {
+    let pool = db.pool()?;
+    let rubric: Rubric = serde_json::from_str(&new_rubric_json)
+        .map_err(|e| format!("Invalid Rubric JSON: {}", e))?;
+    rubric_change_impact(&pool, &assignment_id, &rubric).await
+}
+
+async fn store_rubric(pool: &DbPool, assignment_id: &str, rubric_json: &str) -> Result<(), Vec<RubricValidationError>> {
+    let result = sqlx::query("UPDATE assignments SET rubric_json = ? WHERE id = ?")
+        .bind(rubric_json)
+        .bind(assignment_id)
+        .execute(pool)
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e.to_string())])?;
+    if result.rows_affected() == 0 {
+        return Err(vec![RubricValidationError::new("", "Assignment not found")]);
+    }
+    Ok(())
+}
+
+/// Copy one assignment's rubric verbatim onto another. The source rubric is
+/// re-validated first so a rubric saved before validation existed can't spread.
+/// Like `update_rubric`, replacing a rubric that already has grades against it
+/// needs `acknowledge_impact`.
+#[tauri::command]
+pub async fn clone_rubric(
+    db: State<'_, DbState>,
+    from_assignment_id: String,
+    to_assignment_id: String,
+    acknowledge_impact: Option<bool>,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), Vec<RubricValidationError>> {
+    let pool = db.pool().map_err(|e| vec![RubricValidationError::new("", e)])?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref())
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e)])?;
+    let rubric_json: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+        .bind(&from_assignment_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e.to_string())])?
+        .ok_or_else(|| vec![RubricValidationError::new("", "Source assignment not found")])?;
+    let rubric_json = rubric_json
+        .ok_or_else(|| vec![RubricValidationError::new("", "Source assignment has no rubric")])?;
+
+    let source = serde_json::json!({ "cloned_from_assignment": from_assignment_id });
+    replace_rubric(&pool, &to_assignment_id, &rubric_json, acknowledge_impact.unwrap_or(false), ta_id.as_deref(), Some(source)).await
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct RubricTemplate {
+    pub id: String,
+    pub name: String,
+    pub rubric_json: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Save a rubric as a named template. Saving under an existing name replaces it.
+#[tauri::command]
+pub async fn save_rubric_template(
+    db: State<'_, DbState>,
+    name: String,
+    rubric_json: String,
+) -> Result<String, Vec<RubricValidationError>> {
+    let pool = db.pool().map_err(|e| vec![RubricValidationError::new("", e)])?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(vec![RubricValidationError::new("name", "Template name is required")]);
+    }
+    check_rubric_json(&rubric_json)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO rubric_templates (id, name, rubric_json) VALUES (?, ?, ?)
+        ON CONFLICT(name) DO UPDATE SET rubric_json = excluded.rubric_json, updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&name)
+    .bind(&rubric_json)
+    .execute(&pool)
+    .await
+    .map_err(|e| vec![RubricValidationError::new("", e.to_string())])?;
+
+    let id: String = sqlx::query_scalar("SELECT id FROM rubric_templates WHERE name = ?")
+        .bind(&name)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e.to_string())])?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn list_rubric_templates(db: State<'_, DbState>) -> Result<Vec<RubricTemplate>, String> {
+    let pool = db.pool()?;
+    let templates = sqlx::query_as::<sqlx::Sqlite, RubricTemplate>(
+        "SELECT id, name, rubric_json, created_at, updated_at FROM rubric_templates ORDER BY name COLLATE NOCASE"
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(templates)
+}
+
+/// Replace an assignment's rubric with a saved template. Needs
+/// `acknowledge_impact` when that would affect existing grades.
+#[tauri::command]
+pub async fn apply_rubric_template(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    template_id: String,
+    acknowledge_impact: Option<bool>,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<(), Vec<RubricValidationError>> {
+    let pool = db.pool().map_err(|e| vec![RubricValidationError::new("", e)])?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref())
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e)])?;
+    let rubric_json: String = sqlx::query_scalar("SELECT rubric_json FROM rubric_templates WHERE id = ?")
+        .bind(&template_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| vec![RubricValidationError::new("", e.to_string())])?
+        .ok_or_else(|| vec![RubricValidationError::new("", "Template not found")])?;
+
+    let source = serde_json::json!({ "template_id": template_id });
+    replace_rubric(&pool, &assignment_id, &rubric_json, acknowledge_impact.unwrap_or(false), ta_id.as_deref(), Some(source)).await
+}
+
+#[derive(Serialize, Debug)]
+pub struct SubmissionDetail {
+    submission_id: String,
+    student_id: Option<String>,
+    student_name: Option<String>,
+    status: String,
+    files: Vec<FileInfo>,
+    question_files: Vec<QuestionFile>,
+}
+
+/// The file a rubric question expects, and the file actually attached to it
+#[derive(Serialize, Debug)]
+pub struct QuestionFile {
+    question_id: String,
+    expected_file: Option<String>,
+    file_path: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FileInfo {
+    path: String, // Relative path in cache
+    name: String,
+    is_dir: bool,
+    size_bytes: u64,
+    parent: Option<String>, // Relative path of the containing directory, None at the top level
+}
+
+/// Archive/OS clutter that shouldn't be shown as part of a submission
+pub(crate) fn is_extraction_junk(name: &str) -> bool {
+    name == "__MACOSX" || name == ".DS_Store" || name == "Thumbs.db" || name.starts_with("._")
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct GradeRecord {
+    id: i64, // Auto increment
+    submission_id: String,
+    question_id: String,
+    score: Option<f64>,
+    comment: Option<String>,
+    revision: i64,
+    updated_at: Option<String>,
+}
+
+#[tauri::command]
+pub async fn get_submission_detail(
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<SubmissionDetail, String> {
+    let pool = db.pool()?;
+    // 1. Get stats
+    let row: (Option<String>, Option<String>, String, String, Option<String>, bool, i64) = sqlx::query_as(
+        r#"
+        SELECT s.student_id, st.name, s.status, s.folder_path, a.rubric_json, a.anonymous, s.anon_seq
+        FROM submissions s
+        JOIN assignments a ON a.id = s.assignment_id
+        LEFT JOIN students st ON s.student_id = st.student_id AND st.course_id = a.course_id
+        WHERE s.id = ?
+        "#
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+
+    let (mut student_id, mut student_name, status, folder_path, rubric_json, anonymous, anon_seq) = row;
+    if anonymous {
+        student_id = None;
+        student_name = Some(crate::grading::pseudonym(anon_seq));
+    }
+
+    // 2. Walk dir for files and folders
+    let mut files = Vec::new();
+    let root = std::path::Path::new(&folder_path);
+    if root.exists() {
+        let walker = walkdir::WalkDir::new(root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !is_extraction_junk(&e.file_name().to_string_lossy()));
+        for e in walker.flatten() {
+            let Ok(rel) = e.path().strip_prefix(root) else { continue };
+            let is_dir = e.file_type().is_dir();
+            files.push(FileInfo {
+                path: rel.to_string_lossy().to_string(),
+                name: e.file_name().to_string_lossy().to_string(),
+                is_dir,
+                size_bytes: if is_dir { 0 } else { e.metadata().map(|m| m.len()).unwrap_or(0) },
+                parent: rel
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| p.to_string_lossy().to_string()),
+            });
+        }
+    }
+
+    // 3. Per-question file mapping
+    let attached: std::collections::HashMap<String, String> = sqlx::query_as::<sqlx::Sqlite, (String, String)>(
+        "SELECT question_id, file_path FROM question_files WHERE submission_id = ?"
+    )
+    .bind(&submission_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .collect();
+
+    let questions = rubric_json
+        .and_then(|j| serde_json::from_str::<Rubric>(&j).ok())
+        .map(|r| r.questions)
+        .unwrap_or_default();
+    let question_files = questions
+        .into_iter()
+        .map(|q| QuestionFile {
+            file_path: attached.get(&q.question_id).cloned(),
+            question_id: q.question_id,
+            expected_file: q.expected_file,
+        })
+        .collect();
+
+    Ok(SubmissionDetail {
+        submission_id,
+        student_id,
+        student_name,
+        status,
+        files,
+        question_files,
+    })
+}
+
+/// Attach a submission file to a rubric question. `None` clears the mapping.
+#[tauri::command]
+pub async fn assign_file_to_question(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    file_path: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
+        .bind(&submission_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Submission not found")?;
+
+    match file_path {
+        Some(path) => {
+            if !resolve_in_folder(&folder_path, &path)?.is_file() {
+                return Err(format!("Not a file: {}", path));
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO question_files (submission_id, question_id, file_path) VALUES (?, ?, ?)
+                ON CONFLICT(submission_id, question_id) DO UPDATE SET file_path = excluded.file_path
+                "#
+            )
+            .bind(&submission_id)
+            .bind(&question_id)
+            .bind(&path)
+            .execute(&pool)
+            .await
+        }
+        None => sqlx::query("DELETE FROM question_files WHERE submission_id = ? AND question_id = ?")
+            .bind(&submission_id)
+            .bind(&question_id)
+            .execute(&pool)
+            .await,
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Look up a question in an assignment's rubric JSON
+fn rubric_question(rubric_json: Option<&str>, question_id: &str) -> Result<Question, String> {
+    rubric_json
+        .and_then(|j| serde_json::from_str::<Rubric>(j).ok())
+        .and_then(|r| r.questions.into_iter().find(|q| q.question_id == question_id))
+        .ok_or_else(|| format!("Question '{}' is not in the assignment's rubric", question_id))
+}
+
+/// A score must be at least 0 and, without extra credit, at most the question's max points
+fn check_score(question: &Question, score: Option<f64>, allow_extra_credit: bool) -> Result<(), String> {
+    let Some(score) = score else { return Ok(()) };
+    if !score.is_finite() || score < 0.0 {
+        return Err(format!("Score {} must be a number of at least 0", score));
+    }
+    if score > question.max_points && !allow_extra_credit {
+        return Err(format!(
+            "Score {} exceeds the {} max points for '{}'",
+            score, question.max_points, question.title
+        ));
+    }
+    Ok(())
+}
+
+/// Save a score/comment for one question. The submission must be claimed
+/// by `ta_id`; a course admin can bypass that with `admin_override`.
+/// `expected_revision` is the revision from `get_grades` (None if there was
+/// no grade yet); a mismatch fails with `stale_grade`. Returns the new revision.
+/// Scores must lie within the question's 0..=max_points; `allow_extra_credit`
+/// lifts the upper bound.
+#[tauri::command]
+pub async fn save_grade(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    score: Option<f64>,
+    comment: Option<String>,
+    ta_id: String,
+    admin_override: Option<bool>,
+    expected_revision: Option<i64>,
+    allow_extra_credit: Option<bool>,
+    session_token: Option<String>,
+) -> Result<i64, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let (claimed_by, course_id, rubric_json, superseded_by): (Option<String>, String, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT s.claimed_by_ta_id, a.course_id, a.rubric_json, s.superseded_by FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ? AND s.deleted_at IS NULL"
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+    if superseded_by.is_some() {
+        return Err("This submission has been replaced by a newer upload".to_string());
+    }
+
+    let question = rubric_question(rubric_json.as_deref(), &question_id)?;
+    check_score(&question, score, allow_extra_credit.unwrap_or(false))?;
+
+    if admin_override.unwrap_or(false) {
+        let is_admin: Option<i32> = sqlx::query_scalar(
+            "SELECT 1 FROM course_tas WHERE course_id = ? AND ta_id = ? AND role = 'admin'"
+        )
+        .bind(&course_id)
+        .bind(&ta_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if is_admin.is_none() {
+            return Err("Only a course admin can override a grading claim".to_string());
+        }
+    } else {
+        match &claimed_by {
+            None => return Err("Claim this submission before grading it".to_string()),
+            Some(owner) if owner != &ta_id => {
+                return Err("Cannot save grade: submission is claimed by another TA".to_string())
+            }
+            _ => {}
+        }
+    }
+
+    // Grade, draft cleanup and audit entry land together or not at all
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let revision = write_grade(&mut *tx, &submission_id, &question_id, score, comment.as_deref(), &ta_id, expected_revision).await?;
+
+    // The committed grade supersedes whatever was typed but unsaved
+    sqlx::query("DELETE FROM grade_drafts WHERE submission_id = ? AND question_id = ? AND ta_id = ?")
+        .bind(&submission_id)
+        .bind(&question_id)
+        .bind(&ta_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let details = serde_json::json!({
+        "question_id": question_id,
+        "score": score,
+        "admin_override": admin_override.unwrap_or(false),
+    })
+    .to_string();
+    sqlx::query(
+        "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, 'save_grade', 'submission', ?, ?)"
+    )
+    .bind(&ta_id)
+    .bind(&submission_id)
+    .bind(&details)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(revision)
+}
+
+#[derive(Serialize, Debug)]
+pub struct BulkGradeOutcome {
+    submission_id: String,
+    success: bool,
+    error: Option<String>,
+    revision: Option<i64>,
+}
+
+/// Give several submissions the same score/comment for one question, in one
+/// transaction. Each submission must be claimed by `ta_id` and the score must
+/// pass the same rubric checks as `save_grade`; ones that don't are reported
+/// and left alone while the rest are saved.
+#[tauri::command]
+pub async fn bulk_save_grade(
+    db: State<'_, DbState>,
+    submission_ids: Vec<String>,
+    question_id: String,
+    score: Option<f64>,
+    comment: Option<String>,
+    ta_id: String,
+    allow_extra_credit: Option<bool>,
+    session_token: Option<String>,
+) -> Result<Vec<BulkGradeOutcome>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let mut outcomes = Vec::with_capacity(submission_ids.len());
+    let details = serde_json::json!({
+        "question_id": question_id,
+        "score": score,
+        "bulk": true,
+    })
+    .to_string();
+
+    for submission_id in submission_ids {
+        let row: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+            "SELECT s.claimed_by_ta_id, a.rubric_json FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ? AND s.deleted_at IS NULL"
+        )
+        .bind(&submission_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let checked = match row {
+            None => Err("Submission not found".to_string()),
+            Some((claimed_by, _)) if claimed_by.as_deref() != Some(ta_id.as_str()) => {
+                Err("Submission is not claimed by this TA".to_string())
+            }
+            Some((_, rubric_json)) => rubric_question(rubric_json.as_deref(), &question_id)
+                .and_then(|q| check_score(&q, score, allow_extra_credit.unwrap_or(false))),
+        };
+        if let Err(e) = checked {
+            outcomes.push(BulkGradeOutcome { submission_id, success: false, error: Some(e), revision: None });
+            continue;
+        }
+
+        // Read inside the transaction so the revision guard can't race another save
+        let expected_revision: Option<i64> = sqlx::query_scalar(
+            "SELECT revision FROM grades WHERE submission_id = ? AND question_id = ?"
+        )
+        .bind(&submission_id)
+        .bind(&question_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let revision = write_grade(&mut *tx, &submission_id, &question_id, score, comment.as_deref(), &ta_id, expected_revision).await?;
+
+        sqlx::query("DELETE FROM grade_drafts WHERE submission_id = ? AND question_id = ? AND ta_id = ?")
+            .bind(&submission_id)
+            .bind(&question_id)
+            .bind(&ta_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query(
+            "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, 'save_grade', 'submission', ?, ?)"
+        )
+        .bind(&ta_id)
+        .bind(&submission_id)
+        .bind(&details)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        outcomes.push(BulkGradeOutcome { submission_id, success: true, error: None, revision: Some(revision) });
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(outcomes)
+}
+
+#[derive(Serialize, Debug)]
+pub struct AppliedPreset {
+    score: f64,
+    comment: String,
+    revision: i64,
+}
+
+/// Apply one of a question's comment presets: append its text to the comment and
+/// take its deduction off the current score (full marks when ungraded), kept
+/// within 0..=max_points. Requires the TA to hold the claim, like `save_grade`.
+#[tauri::command]
+pub async fn apply_comment_preset(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    preset_label: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<AppliedPreset, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let (claimed_by, rubric_json): (Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT s.claimed_by_ta_id, a.rubric_json FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+    match &claimed_by {
+        None => return Err("Claim this submission before grading it".to_string()),
+        Some(owner) if owner != &ta_id => {
+            return Err("Cannot save grade: submission is claimed by another TA".to_string())
+        }
+        _ => {}
+    }
+
+    let question = rubric_question(rubric_json.as_deref(), &question_id)?;
+    let preset = question.comment_presets
+        .iter()
+        .find(|p| p.label == preset_label)
+        .ok_or_else(|| format!("No comment preset named '{}'", preset_label))?;
+
+    let current: Option<(Option<f64>, Option<String>, i64)> = sqlx::query_as(
+        "SELECT score, comment, revision FROM grades WHERE submission_id = ? AND question_id = ?"
+    )
+    .bind(&submission_id)
+    .bind(&question_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let (score_before, comment_before, expected_revision) = match current {
+        Some((score, comment, revision)) => (score, comment, Some(revision)),
+        None => (None, None, None),
+    };
+
+    let deduction = preset.deduction.unwrap_or(0.0);
+    let score = (score_before.unwrap_or(question.max_points) - deduction).clamp(0.0, question.max_points);
+    let comment = match comment_before.as_deref().map(str::trim_end) {
+        Some(existing) if !existing.is_empty() => format!("{}\n{}", existing, preset.text),
+        _ => preset.text.clone(),
+    };
+
+    let revision = write_grade(&pool, &submission_id, &question_id, Some(score), Some(&comment), &ta_id, expected_revision).await?;
+
+    let details = serde_json::json!({
+        "question_id": question_id,
+        "preset": preset.label,
+        "deduction": deduction,
+        "score_before": score_before,
+        "score": score,
+    })
+    .to_string();
+    crate::grading::log_audit_internal(&pool, Some(&ta_id), "apply_comment_preset", "submission", &submission_id, Some(&details)).await?;
+
+    Ok(AppliedPreset { score, comment, revision })
+}
+
+#[derive(Serialize, Debug, FromRow)]
+pub struct GradeDraft {
+    score: Option<f64>,
+    comment: Option<String>,
+    updated_at: Option<String>,
+}
+
+/// Store a TA's in-progress score/comment for one question. Called often while
+/// typing, so it skips the claim and revision checks that `save_grade` does.
+#[tauri::command]
+pub async fn save_grade_draft(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+    score: Option<f64>,
+    comment: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO grade_drafts (submission_id, question_id, ta_id, score, comment, updated_at)
+        VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+        ON CONFLICT(submission_id, question_id, ta_id)
+        DO UPDATE SET score = excluded.score, comment = excluded.comment, updated_at = excluded.updated_at
+        "#
+    )
+    .bind(&submission_id)
+    .bind(&question_id)
+    .bind(&ta_id)
+    .bind(score)
+    .bind(&comment)
+    .execute(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The TA's unsaved draft for one question, if there is one
+#[tauri::command]
+pub async fn get_grade_draft(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<Option<GradeDraft>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    sqlx::query_as::<sqlx::Sqlite, GradeDraft>(
+        "SELECT score, comment, updated_at FROM grade_drafts WHERE submission_id = ? AND question_id = ? AND ta_id = ?"
+    )
+    .bind(&submission_id)
+    .bind(&question_id)
+    .bind(&ta_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Upsert one grade row, guarded by `expected_revision`. Returns the new revision.
+pub(crate) async fn write_grade<'e>(
+    executor: impl sqlx::SqliteExecutor<'e>,
+    submission_id: &str,
+    question_id: &str,
+    score: Option<f64>,
+    comment: Option<&str>,
+    ta_id: &str,
+    expected_revision: Option<i64>,
+) -> Result<i64, String> {
+    // The write only lands if the row is still at the revision the caller
+    // last read (or still absent); otherwise someone else saved in between.
+    let result = match expected_revision {
+        Some(rev) => sqlx::query(
+            r#"
+            UPDATE grades
+            SET score = ?, comment = ?, updated_by_ta_id = ?, updated_at = CURRENT_TIMESTAMP, revision = revision + 1
+            WHERE submission_id = ? AND question_id = ? AND revision = ?
+            "#
+        )
+        .bind(score)
+        .bind(comment)
+        .bind(ta_id)
+        .bind(submission_id)
+        .bind(question_id)
+        .bind(rev)
+        .execute(executor)
+        .await,
+        None => sqlx::query(
+            r#"
+            INSERT INTO grades (submission_id, question_id, score, comment, updated_by_ta_id)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(submission_id, question_id) DO NOTHING
+            "#
+        )
+        .bind(submission_id)
+        .bind(question_id)
+        .bind(score)
+        .bind(comment)
+        .bind(ta_id)
+        .execute(executor)
+        .await,
+    }
+    .map_err(|e| e.to_string())?;
+
+    if result.rows_affected() == 0 {
+        return Err("stale_grade".to_string());
+    }
+    Ok(expected_revision.map_or(1, |rev| rev + 1))
+}
+
+#[tauri::command]
+pub async fn get_grades(
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<Vec<GradeRecord>, String> {
+    let pool = db.pool()?;
+    let grades = sqlx::query_as::<sqlx::Sqlite, GradeRecord>(
+        "SELECT id, submission_id, question_id, score, comment, revision, updated_at FROM grades WHERE submission_id = ?"
+    )
+    .bind(submission_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(grades)
+}
+
+/// Read a file's content from a submission
+#[derive(Serialize, Debug)]
+pub struct FilePreview {
+    /// Text content, or None when the file is binary
+    content: Option<String>,
+    is_binary: bool,
+    /// More of the file remains after this chunk
+    truncated: bool,
+    total_size: u64,
+}
+
+/// Default preview cap so a huge file can't freeze the UI
+const DEFAULT_PREVIEW_BYTES: u64 = 1024 * 1024;
+/// Hard cap on a single read, whatever the caller asks for
+const MAX_PREVIEW_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Decode a chunk cut out of a larger file: drop a partial character at
+/// either edge. Returns None if the rest isn't text.
+fn decode_text_chunk(mut chunk: &[u8], cut_start: bool, cut_end: bool) -> Option<String> {
+    if chunk.contains(&0) {
+        return None;
+    }
+    if cut_start {
+        // Skip UTF-8 continuation bytes left over from a character before the offset
+        let skip = chunk.iter().take(3).take_while(|b| (**b & 0xC0) == 0x80).count();
+        chunk = &chunk[skip..];
+    }
+    match std::str::from_utf8(chunk) {
+        Ok(text) => Some(text.to_string()),
+        // Incomplete sequence at the very end: the cut landed inside a character
+        Err(e) if cut_end && e.error_len().is_none() => {
+            Some(String::from_utf8_lossy(&chunk[..e.valid_up_to()]).into_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+/// Resolve `rel_path` inside a submission folder, refusing anything that
+/// escapes it (`..`, absolute paths, symlinks pointing outside).
+pub(crate) fn resolve_in_folder(folder_path: &str, rel_path: &str) -> Result<std::path::PathBuf, String> {
+    let root = std::path::Path::new(folder_path)
+        .canonicalize()
+        .map_err(|_| "Submission folder not found".to_string())?;
+    let full = root
+        .join(rel_path)
+        .canonicalize()
+        .map_err(|_| "File not found".to_string())?;
+    if !full.starts_with(&root) {
+        return Err("Access denied: path is outside the submission folder".to_string());
+    }
+    Ok(full)
+}
+
+/// Open a submission's extracted folder in the OS file manager.
+/// Returns the folder path; fails if the folder has been removed (e.g. cache cleared).
+#[tauri::command]
+pub async fn reveal_submission_folder(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    use tauri_plugin_opener::OpenerExt;
+
+    let folder_path = extracted_folder(&pool, &submission_id).await?;
+    app.opener()
+        .open_path(folder_path.clone(), None::<&str>)
+        .map_err(|e| format!("Could not open folder: {}", e))?;
+
+    Ok(folder_path)
+}
+
+/// A submission's extracted folder, if it is still on disk
+async fn extracted_folder(pool: &DbPool, submission_id: &str) -> Result<String, String> {
+    let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
+        .bind(submission_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Submission not found")?;
+
+    if !std::path::Path::new(&folder_path).is_dir() {
+        return Err(format!(
+            "The extracted folder no longer exists ({}). Re-import the submission to restore it.",
+            folder_path
+        ));
+    }
+    Ok(folder_path)
+}
+
+/// Read up to `max_bytes` (default 1MB, at most 4MB) of a submission file starting at `offset`
+#[tauri::command]
+pub async fn read_submission_file(
+    db: State<'_, DbState>,
+    submission_id: String,
+    file_path: String,
+    offset: Option<u64>,
+    max_bytes: Option<u64>,
+) -> Result<FilePreview, String> {
+    let pool = db.pool()?;
+    let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
+        .bind(&submission_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    let full_path = resolve_in_folder(&folder_path, &file_path)?;
+    
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(&full_path).map_err(|e| e.to_string())?;
+    let total_size = file.metadata().map_err(|e| e.to_string())?.len();
+    let offset = offset.unwrap_or(0).min(total_size);
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_PREVIEW_BYTES).min(MAX_PREVIEW_BYTES);
+
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::new();
+    file.take(max_bytes).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    let truncated = offset + (bytes.len() as u64) < total_size;
+
+    // NUL bytes or invalid UTF-8 means there's nothing sensible to show as text
+    let text = decode_text_chunk(&bytes, offset > 0, truncated);
+    Ok(FilePreview {
+        is_binary: text.is_none(),
+        content: text,
+        truncated,
+        total_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use std::path::Path;
+    use tauri::Manager;
+
+    const RUBRIC: &str = r#"{"questions": [{"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []}]}"#;
+
+    /// A submission to an assignment graded with `RUBRIC`
+    async fn graded_submission(db: &TestDb) -> String {
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        db.submission(&assignment, Some("12345678"), Path::new("/nonexistent")).await
+    }
+
+    async fn rubric_of(db: &TestDb, assignment_id: &str) -> Option<String> {
+        sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+            .bind(assignment_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    /// Error paths, in order, of a rejected rubric
+    fn error_paths(errors: &[RubricValidationError]) -> Vec<&str> {
+        errors.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn update_rubric_reports_each_problem_by_path() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+
+        let errors = update_rubric(app.state(), assignment.clone(), "{\"questions\": [".into(), None, None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(error_paths(&errors), [""]);
+        assert!(errors[0].message.starts_with("Invalid Rubric JSON"));
+
+        let invalid = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 5,
+             "comment_presets": [{"label": "Late", "text": "Late", "deduction": 8}],
+             "excel_checks": [{"type": "cell_equals", "sheet": "Data", "cell": "A0", "expected": 1}]},
+            {"question_id": "q1", "title": "Ledger", "max_points": -1, "comment_presets": []}
+        ], "scale": 0}"#;
+        let errors = update_rubric(app.state(), assignment.clone(), invalid.into(), None, None, None).await.unwrap_err();
+        assert_eq!(error_paths(&errors), [
+            "questions[0].comment_presets[0].deduction",
+            "questions[0].excel_checks[0].cell",
+            "questions[1].question_id",
+            "questions[1].max_points",
+            "scale",
+        ]);
+
+        let valid = r#"{"questions": [{"question_id": "q1", "title": "Journal", "max_points": 5, "comment_presets": []}]}"#;
+        update_rubric(app.state(), assignment.clone(), valid.into(), None, None, None).await.unwrap();
+        let stored: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+            .bind(&assignment)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(stored.as_deref(), Some(valid));
+    }
+
+    #[tokio::test]
+    async fn deleted_items_leave_lists_until_restored() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let listed = || async { list_assignments(app.state(), course.clone()).await.unwrap().len() };
+
+        delete_assignment(app.state(), assignment.clone(), None, None).await.unwrap();
+        assert_eq!(listed().await, 0);
+        assert_eq!(delete_assignment(app.state(), assignment.clone(), None, None).await.unwrap_err(), "Assignment not found");
+        restore_assignment(app.state(), assignment.clone(), None, None).await.unwrap();
+        assert_eq!(listed().await, 1);
+        assert_eq!(restore_assignment(app.state(), assignment.clone(), None, None).await.unwrap_err(), "Assignment is not deleted");
+
+        // Deleting the course hides its assignments without touching their rows
+        delete_course(app.state(), course.clone(), None, None).await.unwrap();
+        assert!(list_courses(app.state()).await.unwrap().is_empty());
+        assert_eq!(listed().await, 0);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM assignments WHERE deleted_at IS NULL")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+
+        let actions: Vec<String> = sqlx::query_scalar("SELECT action FROM audit_log ORDER BY id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(actions, ["delete", "restore", "delete"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_claiming_ta_can_save_a_grade() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = graded_submission(&db).await;
+        let ada = db.ta("Ada").await;
+        let grace = db.ta("Grace").await;
+        let save = |ta: &str| save_grade(app.state(), submission.clone(), "q1".into(), Some(8.0), None, ta.to_string(), None, None, None, None);
+
+        assert_eq!(save(&ada).await.unwrap_err(), "Claim this submission before grading it");
+        db.claim(&submission, &grace).await;
+        assert_eq!(save(&ada).await.unwrap_err(), "Cannot save grade: submission is claimed by another TA");
+        assert_eq!(save(&grace).await.unwrap(), 1);
+
+        let audited: Vec<(Option<String>, String)> = sqlx::query_as("SELECT ta_id, entity_id FROM audit_log WHERE action = 'save_grade'")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(audited, [(Some(grace), submission)]);
+    }
+
+    #[tokio::test]
+    async fn grades_are_saved_with_their_audit_entry_or_not_at_all() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = graded_submission(&db).await;
+        let ta = db.ta("Ada").await;
+        db.claim(&submission, &ta).await;
+        let save = |id: &str| save_grade(app.state(), id.to_string(), "q1".into(), Some(8.0), None, ta.clone(), None, None, None, None);
+
+        sqlx::query("CREATE TRIGGER audit_down BEFORE INSERT ON audit_log BEGIN SELECT RAISE(ABORT, 'audit down'); END")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(save(&submission).await.unwrap_err().contains("audit down"));
+        assert!(get_grades(app.state(), submission.clone()).await.unwrap().is_empty());
+        sqlx::query("DROP TRIGGER audit_down").execute(&db.pool).await.unwrap();
+        assert_eq!(save(&submission).await.unwrap(), 1);
+
+        // Old versions and deleted submissions no longer take grades
+        let newer = graded_submission(&db).await;
+        sqlx::query("UPDATE submissions SET superseded_by = ? WHERE id = ?")
+            .bind(&newer)
+            .bind(&submission)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(save(&submission).await.unwrap_err(), "This submission has been replaced by a newer upload");
+        db.claim(&newer, &ta).await;
+        sqlx::query("UPDATE submissions SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(&newer)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(save(&newer).await.unwrap_err(), "Submission not found");
+    }
+
+    #[tokio::test]
+    async fn saves_against_an_old_revision_are_stale() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = graded_submission(&db).await;
+        let ta = db.ta("Ada").await;
+        db.claim(&submission, &ta).await;
+        let save = |score: f64, expected: Option<i64>| {
+            save_grade(app.state(), submission.clone(), "q1".into(), Some(score), None, ta.clone(), None, expected, None, None)
+        };
+
+        assert_eq!(save(8.0, None).await.unwrap(), 1);
+        assert_eq!(save(7.0, None).await.unwrap_err(), "stale_grade");
+        assert_eq!(save(7.0, Some(1)).await.unwrap(), 2);
+        assert_eq!(save(6.0, Some(1)).await.unwrap_err(), "stale_grade");
+
+        let grades = get_grades(app.state(), submission.clone()).await.unwrap();
+        assert_eq!((grades[0].score, grades[0].revision), (Some(7.0), 2));
+    }
+
+    #[tokio::test]
+    async fn assignments_carry_rubric_totals() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let rubric = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []},
+            {"question_id": "q2", "title": "Ledger", "max_points": 2.5, "comment_presets": []}
+        ]}"#;
+        let graded = db.assignment(&course, Some(rubric)).await;
+        let broken = db.assignment(&course, Some("{not json")).await;
+
+        let assignment = get_assignment(app.state(), graded).await.unwrap();
+        assert_eq!((assignment.total_max_points, assignment.question_count), (12.5, 2));
+        let assignment = get_assignment(app.state(), broken).await.unwrap();
+        assert_eq!((assignment.total_max_points, assignment.question_count), (0.0, 0));
+
+        let mut totals: Vec<f64> = list_assignments(app.state(), course).await.unwrap().iter().map(|a| a.total_max_points).collect();
+        totals.sort_by(f64::total_cmp);
+        assert_eq!(totals, [0.0, 12.5]);
+    }
+
+    #[tokio::test]
+    async fn files_can_be_attached_to_questions() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("journal.xlsx"), b"x").unwrap();
+        std::fs::create_dir(dir.path().join("scans")).unwrap();
+        let course = db.course().await;
+        let rubric = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 10, "expected_file": "journal.xlsx", "comment_presets": []},
+            {"question_id": "q2", "title": "Ledger", "max_points": 10, "comment_presets": []}
+        ]}"#;
+        let assignment = db.assignment(&course, Some(rubric)).await;
+        let submission = db.submission(&assignment, Some("12345678"), dir.path()).await;
+        let attached = || async {
+            get_submission_detail(app.state(), submission.clone())
+                .await
+                .unwrap()
+                .question_files
+                .into_iter()
+                .map(|q| (q.question_id, q.expected_file, q.file_path))
+                .collect::<Vec<_>>()
+        };
+
+        let attach = |path: Option<&str>| assign_file_to_question(app.state(), submission.clone(), "q1".into(), path.map(String::from));
+        assert_eq!(attach(Some("missing.xlsx")).await.unwrap_err(), "File not found");
+        assert_eq!(attach(Some("scans")).await.unwrap_err(), "Not a file: scans");
+        attach(Some("journal.xlsx")).await.unwrap();
+        assert_eq!(attached().await, [
+            ("q1".to_string(), Some("journal.xlsx".to_string()), Some("journal.xlsx".to_string())),
+            ("q2".to_string(), None, None),
+        ]);
+        attach(None).await.unwrap();
+        assert_eq!(attached().await[0].2, None);
+    }
+
+    #[tokio::test]
+    async fn detail_lists_folders_sizes_and_parents() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("part1/__MACOSX")).unwrap();
+        std::fs::write(dir.path().join("part1/journal.xlsx"), b"12345").unwrap();
+        std::fs::write(dir.path().join("part1/__MACOSX/._journal.xlsx"), b"junk").unwrap();
+        std::fs::write(dir.path().join(".DS_Store"), b"junk").unwrap();
+        std::fs::write(dir.path().join("memo.docx"), b"123").unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("12345678"), dir.path()).await;
+
+        let detail = get_submission_detail(app.state(), submission).await.unwrap();
+        let files: Vec<_> = detail.files.iter().map(|f| (f.path.as_str(), f.is_dir, f.size_bytes, f.parent.as_deref())).collect();
+        assert_eq!(files, [
+            ("memo.docx", false, 3, None),
+            ("part1", true, 0, None),
+            (Path::new("part1").join("journal.xlsx").to_str().unwrap(), false, 5, Some("part1")),
+        ]);
+        assert_eq!(detail.files[2].name, "journal.xlsx");
+    }
+
+    #[tokio::test]
+    async fn reads_stay_inside_the_submission_folder() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), b"secret").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("submission");
+        std::fs::create_dir(&folder).unwrap();
+        std::fs::write(folder.join("notes.txt"), b"Debit cash").unwrap();
+        std::fs::write(folder.join("image.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+        std::fs::write(dir.path().join("sibling.txt"), b"sibling").unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("12345678"), &folder).await;
+        let read = |path: String| read_submission_file(app.state(), submission.clone(), path, None, None);
+
+        let notes = read("notes.txt".into()).await.unwrap();
+        assert_eq!((notes.content.as_deref(), notes.is_binary, notes.total_size), (Some("Debit cash"), false, 10));
+        let image = read("image.png".into()).await.unwrap();
+        assert_eq!((image.content, image.is_binary), (None, true));
+
+        let denied = "Access denied: path is outside the submission folder";
+        assert_eq!(read("../sibling.txt".into()).await.unwrap_err(), denied);
+        let absolute = outside.path().join("secret.txt").to_string_lossy().to_string();
+        assert_eq!(read(absolute).await.unwrap_err(), denied);
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.path().join("secret.txt"), folder.join("link.txt")).unwrap();
+            assert_eq!(read("link.txt".into()).await.unwrap_err(), denied);
+        }
+    }
+
+    #[tokio::test]
+    async fn chunks_are_cut_on_character_boundaries() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memo.txt"), "café au lait").unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("12345678"), dir.path()).await;
+        let read = |offset: u64, max_bytes: u64| {
+            read_submission_file(app.state(), submission.clone(), "memo.txt".into(), Some(offset), Some(max_bytes))
+        };
+
+        // "é" takes bytes 3 and 4
+        let head = read(0, 4).await.unwrap();
+        assert_eq!((head.content.as_deref(), head.truncated, head.total_size), (Some("caf"), true, 13));
+        let tail = read(4, 100).await.unwrap();
+        assert_eq!((tail.content.as_deref(), tail.truncated), (Some(" au lait"), false));
+        let past_end = read(50, 10).await.unwrap();
+        assert_eq!((past_end.content.as_deref(), past_end.truncated), (Some(""), false));
+
+        // Invalid UTF-8 away from the edges is still binary
+        assert_eq!(decode_text_chunk(b"ab\xffcd", false, true), None);
+    }
+
+    #[tokio::test]
+    async fn reads_are_capped_whatever_the_caller_asks_for() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("big.txt"), vec![b'a'; MAX_PREVIEW_BYTES as usize + 10]).unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("12345678"), dir.path()).await;
+
+        let preview = read_submission_file(app.state(), submission, "big.txt".into(), None, Some(u64::MAX)).await.unwrap();
+        assert_eq!(preview.content.map(|c| c.len() as u64), Some(MAX_PREVIEW_BYTES));
+        assert_eq!((preview.truncated, preview.total_size), (true, MAX_PREVIEW_BYTES + 10));
+    }
+
+    #[tokio::test]
+    async fn db_info_refuses_a_newer_schema() {
+        let db = TestDb::new().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grading.db");
+        std::fs::write(&path, b"12345").unwrap();
+
+        let info = db_info(&db.pool, &path).await.unwrap();
+        assert_eq!(info.schema_version, info.app_schema_version);
+        assert_eq!(info.applied_migrations as usize, crate::db::MIGRATOR.iter().count());
+        assert!(!info.dirty);
+        assert_eq!(info.integrity, ["ok"]);
+        assert_eq!(info.size_bytes, 5);
+
+        sqlx::query("INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (99990101000000, 'future', 1, x'', 0)")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let error = db_info(&db.pool, &path).await.unwrap_err();
+        assert!(error.starts_with("Database schema version 99990101000000 is newer than this app supports"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rubrics_copy_between_assignments_and_templates() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let ledger = r#"{"questions": [{"question_id": "q2", "title": "Ledger", "max_points": 5, "comment_presets": []}]}"#;
+        let source = db.assignment(&course, Some(ledger)).await;
+        let empty = db.assignment(&course, None).await;
+        let target = db.assignment(&course, Some(RUBRIC)).await;
+        let submission = db.submission(&target, Some("1"), Path::new("")).await;
+
+        let errors = clone_rubric(app.state(), empty.clone(), target.clone(), None, None, None).await.unwrap_err();
+        assert_eq!(errors[0].message, "Source assignment has no rubric");
+
+        // q1 has a grade on the target, so dropping it needs confirming
+        db.grade(&submission, "q1", Some(8.0), None).await;
+        let errors = clone_rubric(app.state(), source.clone(), target.clone(), None, None, None).await.unwrap_err();
+        assert!(errors[0].message.starts_with("This change affects existing grades: 1 on removed questions"), "{}", errors[0].message);
+        clone_rubric(app.state(), source.clone(), target.clone(), Some(true), None, None).await.unwrap();
+        assert_eq!(rubric_of(&db, &target).await.as_deref(), Some(ledger));
+
+        let first = save_rubric_template(app.state(), " Ledger only ".into(), RUBRIC.into()).await.unwrap();
+        let second = save_rubric_template(app.state(), "Ledger only".into(), ledger.into()).await.unwrap();
+        assert_eq!(first, second);
+        save_rubric_template(app.state(), "accruals".into(), RUBRIC.into()).await.unwrap();
+        let templates = list_rubric_templates(app.state()).await.unwrap();
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["accruals", "Ledger only"]);
+        assert_eq!(templates[1].rubric_json, ledger);
+        let errors = save_rubric_template(app.state(), "  ".into(), RUBRIC.into()).await.unwrap_err();
+        assert_eq!(error_paths(&errors), ["name"]);
+
+        apply_rubric_template(app.state(), empty.clone(), first, None, None, None).await.unwrap();
+        assert_eq!(rubric_of(&db, &empty).await.as_deref(), Some(ledger));
+    }
+
+    #[tokio::test]
+    async fn only_existing_folders_are_revealed() {
+        let db = TestDb::new().await;
+        let dir = tempfile::tempdir().unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let present = db.submission(&assignment, Some("1"), dir.path()).await;
+        let cleared = dir.path().join("cleared");
+        let gone = db.submission(&assignment, Some("2"), &cleared).await;
+
+        assert_eq!(extracted_folder(&db.pool, &present).await.unwrap(), dir.path().to_string_lossy());
+        let err = extracted_folder(&db.pool, &gone).await.unwrap_err();
+        assert_eq!(
+            err,
+            format!("The extracted folder no longer exists ({}). Re-import the submission to restore it.", cleared.display())
+        );
+        assert_eq!(extracted_folder(&db.pool, "nope").await.unwrap_err(), "Submission not found");
+    }
+
+    #[tokio::test]
+    async fn drafts_are_per_ta_and_cleared_by_the_saved_grade() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = graded_submission(&db).await;
+        let (ada, grace) = (db.ta("Ada").await, db.ta("Grace").await);
+        db.claim(&submission, &ada).await;
+        let draft = |ta: &str, comment: &str| {
+            save_grade_draft(app.state(), submission.clone(), "q1".into(), ta.to_string(), None, Some(6.0), Some(comment.to_string()))
+        };
+        let comment_of = |ta: &str| {
+            let get = get_grade_draft(app.state(), submission.clone(), "q1".into(), ta.to_string(), None);
+            async { get.await.unwrap().and_then(|d| d.comment) }
+        };
+
+        draft(&ada, "Debits and cre").await.unwrap();
+        draft(&ada, "Debits and credits swapped").await.unwrap();
+        draft(&grace, "Second opinion").await.unwrap();
+        assert_eq!(comment_of(&ada).await.as_deref(), Some("Debits and credits swapped"));
+
+        save_grade(app.state(), submission.clone(), "q1".into(), Some(6.0), None, ada.clone(), None, None, None, None).await.unwrap();
+        assert_eq!(comment_of(&ada).await, None);
+        assert_eq!(comment_of(&grace).await.as_deref(), Some("Second opinion"));
+    }
+
+    #[tokio::test]
+    async fn presets_deduct_from_the_score_without_going_negative() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let rubric = r#"{"questions": [{"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": [
+            {"label": "Rounding", "text": "Rounded too early", "deduction": 3},
+            {"label": "Missing", "text": "Closing entry missing", "deduction": 8}
+        ]}]}"#;
+        let assignment = db.assignment(&course, Some(rubric)).await;
+        let submission = db.submission(&assignment, Some("12345678"), Path::new("/nonexistent")).await;
+        let ta = db.ta("Ada").await;
+        db.claim(&submission, &ta).await;
+        let apply = |label: &str| apply_comment_preset(app.state(), submission.clone(), "q1".into(), label.to_string(), ta.clone(), None);
+
+        assert_eq!(apply("Late").await.unwrap_err(), "No comment preset named 'Late'");
+        // Ungraded starts from full marks
+        let first = apply("Rounding").await.unwrap();
+        assert_eq!((first.score, first.comment.as_str(), first.revision), (7.0, "Rounded too early", 1));
+        let second = apply("Missing").await.unwrap();
+        assert_eq!((second.score, second.comment.as_str()), (0.0, "Rounded too early\nClosing entry missing"));
+
+        let audited: Vec<String> = sqlx::query_scalar("SELECT details_json FROM audit_log WHERE action = 'apply_comment_preset' ORDER BY id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        let presets: Vec<(String, f64)> = audited
+            .iter()
+            .map(|d| serde_json::from_str::<serde_json::Value>(d).unwrap())
+            .map(|d| (d["preset"].as_str().unwrap().to_string(), d["score"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(presets, [("Rounding".to_string(), 7.0), ("Missing".to_string(), 0.0)]);
+    }
+
+    #[tokio::test]
+    async fn scores_are_checked_against_the_rubric() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = graded_submission(&db).await;
+        let ta = db.ta("Ada").await;
+        db.claim(&submission, &ta).await;
+        let save = |question: &str, score: f64, expected: Option<i64>, extra_credit: Option<bool>| {
+            save_grade(app.state(), submission.clone(), question.to_string(), Some(score), None, ta.clone(), None, expected, extra_credit, None)
+        };
+
+        assert_eq!(save("q1", 10.5, None, None).await.unwrap_err(), "Score 10.5 exceeds the 10 max points for 'Journal'");
+        assert_eq!(save("q1", -1.0, None, None).await.unwrap_err(), "Score -1 must be a number of at least 0");
+        assert_eq!(save("q9", 5.0, None, None).await.unwrap_err(), "Question 'q9' is not in the assignment's rubric");
+        assert_eq!(save("q1", 10.0, None, None).await.unwrap(), 1);
+        assert_eq!(save("q1", 10.5, Some(1), Some(true)).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn bulk_grades_claimed_submissions_and_reports_the_rest() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let ta = db.ta("Ada").await;
+        let mut submissions = Vec::new();
+        for student in ["1", "2", "3", "4"] {
+            submissions.push(db.submission(&assignment, Some(student), Path::new("/nonexistent")).await);
+        }
+        for submission in &submissions[..3] {
+            db.claim(submission, &ta).await;
+        }
+        let mut ids = submissions.clone();
+        ids.push("missing".into());
+
+        let outcomes = bulk_save_grade(app.state(), ids, "q1".into(), Some(8.0), Some("-2, see note".into()), ta.clone(), None, None)
+            .await
+            .unwrap();
+        let results: Vec<(bool, Option<&str>)> = outcomes.iter().map(|o| (o.success, o.error.as_deref())).collect();
+        assert_eq!(results, [
+            (true, None),
+            (true, None),
+            (true, None),
+            (false, Some("Submission is not claimed by this TA")),
+            (false, Some("Submission not found")),
+        ]);
+
+        let rows: Vec<(String, f64, String)> = sqlx::query_as("SELECT submission_id, score, comment FROM grades")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        for submission in &submissions[..3] {
+            assert!(rows.contains(&(submission.clone(), 8.0, "-2, see note".to_string())), "{:?}", rows);
+        }
+        let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE action = 'save_grade'").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(audited, 3);
+
+        // Over the max is rejected per submission, like save_grade
+        let over = bulk_save_grade(app.state(), submissions[..1].to_vec(), "q1".into(), Some(11.0), None, ta, None, None).await.unwrap();
+        assert_eq!(over[0].error.as_deref(), Some("Score 11 exceeds the 10 max points for 'Journal'"));
+    }
+
+    fn new_student(student_id: &str, name: &str, email: Option<&str>) -> CreateStudent {
+        CreateStudent { student_id: student_id.into(), name: name.into(), email: email.map(String::from), section: None }
+    }
+
+    #[tokio::test]
+    async fn roster_preview_sorts_students_into_added_updated_and_absent() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        for (id, name) in [("1", "Ada Lovelace"), ("2", "Grace Hopper"), ("3", "Alan Turing")] {
+            db.student(&course, id, name).await;
+        }
+        let incoming = || vec![
+            new_student("1", "Ada Lovelace", Some("ada@example.edu")),
+            new_student("2", "Grace Hopper", None),
+            new_student("4", "Katherine Johnson", None),
+        ];
+
+        let preview = preview_roster_import(app.state(), course.clone(), incoming()).await.unwrap();
+        assert_eq!(preview.added.iter().map(|s| s.student_id.as_str()).collect::<Vec<_>>(), ["4"]);
+        assert_eq!(preview.unchanged, 1);
+        assert_eq!(preview.updated.len(), 1);
+        let change = &preview.updated[0].changes[0];
+        assert_eq!((preview.updated[0].student_id.as_str(), change.field), ("1", "email"));
+        assert_eq!((change.old.as_deref(), change.new.as_deref()), (None, Some("ada@example.edu")));
+        assert_eq!(preview.absent.iter().map(|s| s.student_id.as_str()).collect::<Vec<_>>(), ["3"]);
+        // Previewing writes nothing
+        assert_eq!(list_students(app.state(), course.clone()).await.unwrap().len(), 3);
+
+        save_roster(app.state(), course.clone(), incoming(), Some(true)).await.unwrap();
+        let saved = list_students(app.state(), course).await.unwrap();
+        let ids: Vec<(&str, Option<&str>)> = saved.iter().map(|s| (s.student_id.as_str(), s.email.as_deref())).collect();
+        assert_eq!(ids, [("1", Some("ada@example.edu")), ("2", None), ("4", None)]);
+    }
+
+    fn sis_rows(rows: &[[&str; 3]]) -> Vec<std::collections::HashMap<String, String>> {
+        rows.iter()
+            .map(|[id, name, section]| {
+                [("SIS ID", id), ("Student Name", name), ("Section", section)]
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn mapped_columns_feed_the_roster_fields() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let mapping = |name: &str| RosterMapping {
+            student_id: "SIS ID".into(),
+            name: name.into(),
+            email: None,
+            section: Some("Section".into()),
+        };
+        let save = |rows, name: &str| save_roster_mapped(app.state(), course.clone(), rows, mapping(name), None);
+
+        let rows = sis_rows(&[["12345678", "Ada Lovelace", "A1"], ["87654321", "Grace Hopper", ""], ["", "", ""]]);
+        assert_eq!(save(rows.clone(), "Name").await.unwrap_err(), "Column 'Name' mapped to name is not in the file");
+        let bad = sis_rows(&[["12345678", "Ada Lovelace", "A1"], ["12345678", "Ada again", "A1"], ["", "Nobody", ""], ["55555555", " ", ""]]);
+        assert_eq!(
+            save(bad, "Student Name").await.unwrap_err(),
+            "Row 3: duplicate student ID 12345678\nRow 4: missing student ID\nRow 5: missing name for 55555555"
+        );
+
+        assert_eq!(save(rows, "Student Name").await.unwrap(), 2);
+        let sections: Vec<(String, String, Option<String>)> =
+            sqlx::query_as("SELECT student_id, name, section FROM students WHERE course_id = ? ORDER BY student_id")
+                .bind(&course)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(sections, [
+            ("12345678".to_string(), "Ada Lovelace".to_string(), Some("A1".to_string())),
+            ("87654321".to_string(), "Grace Hopper".to_string(), None),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn rubric_changes_that_hit_grades_need_confirming() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let before = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []},
+            {"question_id": "q2", "title": "Ledger", "max_points": 10, "comment_presets": []}
+        ]}"#;
+        let assignment = db.assignment(&course, Some(before)).await;
+        let ada = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        let bo = db.submission(&assignment, Some("2"), Path::new("/nonexistent")).await;
+        db.grade(&ada, "q1", Some(8.0), None).await;
+        db.grade(&ada, "q2", None, Some("See the ledger")).await;
+        db.grade(&bo, "q1", Some(4.0), None).await;
+        db.grade(&bo, "q2", Some(9.0), None).await;
+        // An empty grade row on a removed question isn't worth warning about
+        let empty = db.submission(&assignment, Some("3"), Path::new("/nonexistent")).await;
+        db.grade(&empty, "q2", None, Some("  ")).await;
+        // q2 goes and q1's max drops to 5, below Ada's 8 but not Bo's 4
+        let after = r#"{"questions": [{"question_id": "q1", "title": "Journal", "max_points": 5, "comment_presets": []}]}"#;
+
+        let impact = get_rubric_change_impact(app.state(), assignment.clone(), after.into()).await.unwrap();
+        assert_eq!((impact.grades_on_removed_questions, impact.submissions_with_removed_grades), (2, 2));
+        let over: Vec<_> = impact.grades_over_new_max.iter().map(|g| (g.submission_id.as_str(), g.question_id.as_str(), g.score, g.new_max)).collect();
+        assert_eq!(over, [(ada.as_str(), "q1", 8.0, 5.0)]);
+        assert_eq!(impact.diff.removed, ["q2"]);
+
+        let refused = update_rubric(app.state(), assignment.clone(), after.into(), None, None, None).await.unwrap_err();
+        assert_eq!(
+            refused[0].message,
+            "This change affects existing grades: 2 on removed questions, 1 above the new max points. Confirm to apply it anyway."
+        );
+        assert_eq!(rubric_of(&db, &assignment).await.as_deref(), Some(before));
+
+        update_rubric(app.state(), assignment.clone(), after.into(), Some(true), None, None).await.unwrap();
+        assert_eq!(rubric_of(&db, &assignment).await.as_deref(), Some(after));
+        let details: Vec<String> = sqlx::query_scalar("SELECT details_json FROM audit_log WHERE action = 'rubric_change' AND entity_id = ?")
+            .bind(&assignment)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        let details: serde_json::Value = serde_json::from_str(&details[0]).unwrap();
+        assert_eq!(details["removed"], serde_json::json!(["q2"]));
+        assert_eq!(details["modified"][0]["changes"], serde_json::json!([{ "field": "max_points", "old": 10.0, "new": 5.0 }]));
+        assert_eq!((&details["grades_on_removed_questions"], &details["grades_over_new_max"]), (&serde_json::json!(2), &serde_json::json!(1)));
+
+        // Raising the max and adding a question back touch no grades, so no confirmation is needed
+        update_rubric(app.state(), assignment.clone(), before.into(), None, None, None).await.unwrap();
+    }
+
+    #[test]
+    fn rubric_diff_lists_questions_and_fields_that_changed() {
+        let old = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 10,
+             "comment_presets": [{"label": "Late", "text": "Submitted late", "deduction": 2}, {"label": "Typo", "text": "Typo"}],
+             "excel_checks": [{"type": "must_have_pivot"}]},
+            {"question_id": "q2", "title": "Ledger", "max_points": 5, "comment_presets": []},
+            {"question_id": "q3", "title": "Notes", "max_points": 2, "comment_presets": []}
+        ]}"#;
+        let new = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal entries", "max_points": 8,
+             "comment_presets": [{"label": "Late", "text": "Submitted late", "deduction": 3}, {"label": "Units", "text": "Missing units"}],
+             "excel_checks": [{"type": "must_use_functions", "functions": ["SUM"]}]},
+            {"question_id": "q3", "title": "Notes", "max_points": 2, "comment_presets": []},
+            {"question_id": "q4", "title": "Summary", "max_points": 5, "comment_presets": []}
+        ], "scale": 100}"#;
+
+        let diff = serde_json::to_value(diff_rubrics(old.into(), new.into()).unwrap()).unwrap();
+        assert_eq!(diff["added"], serde_json::json!(["q4"]));
+        assert_eq!(diff["removed"], serde_json::json!(["q2"]));
+        assert_eq!(diff["scale"], serde_json::json!({ "field": "scale", "old": null, "new": 100.0 }));
+        // q3 is unchanged, so only q1 is listed
+        assert_eq!(diff["modified"], serde_json::json!([{
+            "question_id": "q1",
+            "title": "Journal entries",
+            "changes": [
+                { "field": "title", "old": "Journal", "new": "Journal entries" },
+                { "field": "max_points", "old": 10.0, "new": 8.0 },
+                { "field": "comment_presets[Late]",
+                  "old": { "label": "Late", "text": "Submitted late", "deduction": 2.0 },
+                  "new": { "label": "Late", "text": "Submitted late", "deduction": 3.0 } },
+                { "field": "comment_presets[Units]", "old": null, "new": { "label": "Units", "text": "Missing units", "deduction": null } },
+                { "field": "comment_presets[Typo]", "old": { "label": "Typo", "text": "Typo", "deduction": null }, "new": null },
+                { "field": "excel_checks", "old": null, "new": { "type": "must_use_functions", "functions": ["SUM"] } },
+                { "field": "excel_checks", "old": { "type": "must_have_pivot" }, "new": null },
+            ],
+        }]));
+
+        let same = serde_json::to_value(diff_rubrics(old.into(), old.into()).unwrap()).unwrap();
+        assert_eq!(same, serde_json::json!({ "added": [], "removed": [], "modified": [], "scale": null }));
+        assert!(diff_rubrics("{".into(), old.into()).unwrap_err().starts_with("Invalid old rubric JSON"));
+        assert!(diff_rubrics(old.into(), "[]".into()).unwrap_err().starts_with("Invalid new rubric JSON"));
     }
-    
-    std::fs::read_to_string(&full_path).map_err(|e| e.to_string())
 }
@@ -1,30 +1,408 @@
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use serde::Serialize;
 use sqlx::{Pool, Sqlite};
 use std::fs;
-use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 pub type DbPool = Pool<Sqlite>;
 
-pub async fn init_db(app: &AppHandle) -> Result<DbPool, Box<dyn std::error::Error>> {
-    let app_data_dir = app.path().app_data_dir()?;
-    if !app_data_dir.exists() {
-        fs::create_dir_all(&app_data_dir)?;
+/// Event emitted once the database has finished opening, successfully or not
+pub const STARTUP_STATUS_EVENT: &str = "startup-status";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupState {
+    Initializing,
+    Ready,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StartupStatus {
+    pub state: StartupState,
+    /// Why opening or migrating the database failed
+    pub error: Option<String>,
+}
+
+/// The database pool, or why there isn't one yet. Managed from the start, so
+/// commands called before or after a failed startup get a clear "database not
+/// ready" error instead of Tauri's generic unmanaged-state failure.
+#[derive(Default)]
+pub struct DbState(RwLock<Option<Result<DbPool, String>>>);
+
+impl DbState {
+    #[cfg(test)]
+    pub(crate) fn ready(pool: DbPool) -> Self {
+        DbState(RwLock::new(Some(Ok(pool))))
+    }
+
+    fn set(&self, outcome: Result<DbPool, String>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(outcome);
+    }
+
+    /// The open pool, or a "database not ready" error with the startup failure
+    pub fn pool(&self) -> Result<DbPool, String> {
+        match &*self.0.read().unwrap_or_else(|e| e.into_inner()) {
+            Some(Ok(pool)) => Ok(pool.clone()),
+            Some(Err(e)) => Err(format!("database not ready: {}", e)),
+            None => Err("database not ready: still initializing".to_string()),
+        }
+    }
+
+    pub fn status(&self) -> StartupStatus {
+        match &*self.0.read().unwrap_or_else(|e| e.into_inner()) {
+            Some(Ok(_)) => StartupStatus { state: StartupState::Ready, error: None },
+            Some(Err(e)) => StartupStatus { state: StartupState::Failed, error: Some(e.clone()) },
+            None => StartupStatus { state: StartupState::Initializing, error: None },
+        }
+    }
+}
+
+/// Open the database and record the outcome in the managed `DbState`
+pub async fn start(app: &AppHandle) {
+    let outcome = init_db(app).await.map_err(|e| {
+        eprintln!("Failed to initialize database: {}", e);
+        e.to_string()
+    });
+    let db = app.state::<DbState>();
+    db.set(outcome);
+    let _ = app.emit(STARTUP_STATUS_EVENT, db.status());
+}
+
+/// Whether the database is ready, still opening, or failed (with the reason).
+/// The UI polls this before calling anything that needs the database.
+#[tauri::command]
+pub fn get_startup_status(db: State<'_, DbState>) -> StartupStatus {
+    db.status()
+}
+
+/// Migrations embedded in the app binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Location of the database file in the app data directory
+pub fn db_path(app: &AppHandle) -> Result<PathBuf, tauri::Error> {
+    Ok(app.path().app_data_dir()?.join("grading_hub.db"))
+}
+
+/// First 16 bytes of every SQLite database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Why a database file can't be opened as-is, judged from its header: `None`
+/// when it looks sound. The page size lives at offset 16 (big-endian, where 1
+/// means 65536) and a real database is always at least one page long.
+fn invalid_db_reason(db_path: &Path, len: u64) -> Option<String> {
+    if len == 0 {
+        return Some("is empty".to_string());
+    }
+    let mut header = [0u8; 18];
+    if fs::File::open(db_path).and_then(|mut f| f.read_exact(&mut header)).is_err() || &header[..16] != SQLITE_HEADER {
+        return Some("is not a SQLite database".to_string());
+    }
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        1 => 65536,
+        n => u64::from(n),
+    };
+    if page_size < 512 || !page_size.is_power_of_two() {
+        return Some("has a corrupt header".to_string());
     }
+    (len < page_size).then(|| format!("is truncated ({} bytes, page size {})", len, page_size))
+}
+
+/// A crash can leave a zero-byte, truncated or overwritten database behind,
+/// which then fails in confusing ways. Such a file is renamed aside with a
+/// timestamp, together with its `-wal`/`-shm` files (a WAL may still hold
+/// committed pages worth recovering), and a new database is started.
+fn set_aside_invalid_db(db_path: &Path) -> std::io::Result<()> {
+    let metadata = match fs::metadata(db_path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let Some(reason) = invalid_db_reason(db_path, metadata.len()) else {
+        return Ok(());
+    };
 
-    let db_path = app_data_dir.join("grading_hub.db");
-    let db_url = format!("sqlite://{}", db_path.to_string_lossy());
+    let sidecars = ["-wal", "-shm"].map(|suffix| {
+        let mut name = db_path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    });
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    for path in std::iter::once(db_path.to_path_buf()).chain(sidecars) {
+        if !path.exists() {
+            continue;
+        }
+        let mut aside = path.as_os_str().to_owned();
+        aside.push(format!(".invalid-{}", stamp));
+        let aside = PathBuf::from(aside);
+        eprintln!("Database file {} {}; moving {} to {}", db_path.display(), reason, path.display(), aside.display());
+        fs::rename(&path, &aside)?;
+    }
+    Ok(())
+}
 
-    if !db_path.exists() {
-        fs::File::create(&db_path)?;
+pub async fn init_db(app: &AppHandle) -> Result<DbPool, Box<dyn std::error::Error>> {
+    let db_path = db_path(app)?;
+    if let Some(app_data_dir) = db_path.parent() {
+        if !app_data_dir.exists() {
+            fs::create_dir_all(app_data_dir)?;
+        }
     }
 
+    open_db(&db_path).await
+}
+
+/// Open (or create) the database at `db_path` and bring it up to date
+pub async fn open_db(db_path: &Path) -> Result<DbPool, Box<dyn std::error::Error>> {
+    set_aside_invalid_db(db_path)?;
+
+    // WAL lets the UI keep reading while a long import writes; the busy
+    // timeout makes a contended write wait instead of failing immediately
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(5));
+
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
-        .connect(&db_url)
+        .connect_with(options)
         .await?;
 
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    MIGRATOR.run(&pool).await?;
 
     Ok(pool)
 }
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use tauri::test::{mock_app, MockRuntime};
+
+    /// A migrated database in a temporary directory, removed when dropped
+    pub(crate) struct TestDb {
+        pub pool: DbPool,
+        _dir: tempfile::TempDir,
+    }
+
+    impl TestDb {
+        pub(crate) async fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let pool = open_db(&dir.path().join("test.db")).await.unwrap();
+            TestDb { pool, _dir: dir }
+        }
+
+        /// A mock app managing this database and a conversion limiter, so
+        /// commands can be called with `app.state()`
+        pub(crate) fn app(&self) -> tauri::App<MockRuntime> {
+            let app = mock_app();
+            app.manage(DbState::ready(self.pool.clone()));
+            app.manage(crate::soffice::SofficeLimiter::default());
+            app
+        }
+
+        /// Insert a course, returning its id
+        pub(crate) async fn course(&self) -> String {
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query("INSERT INTO courses (id, name, term) VALUES (?, 'ACC 101', 'Fall')")
+                .bind(&id)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+            id
+        }
+
+        /// Insert an assignment in `course_id` with `rubric_json`, returning its id
+        pub(crate) async fn assignment(&self, course_id: &str, rubric_json: Option<&str>) -> String {
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query("INSERT INTO assignments (id, course_id, title, rubric_json) VALUES (?, ?, 'Homework 1', ?)")
+                .bind(&id)
+                .bind(course_id)
+                .bind(rubric_json)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+            id
+        }
+
+        /// Insert a submission for `student_id` whose files live in `folder_path`,
+        /// returning its id
+        pub(crate) async fn submission(&self, assignment_id: &str, student_id: Option<&str>, folder_path: &Path) -> String {
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query(
+                r#"
+                INSERT INTO submissions (id, assignment_id, student_id, source_zip_path, zip_hash, received_at, folder_path, anon_seq)
+                VALUES (?, ?, ?, '', ?, datetime('now'), ?, (SELECT COALESCE(MAX(anon_seq), 0) + 1 FROM submissions WHERE assignment_id = ?))
+                "#
+            )
+            .bind(&id)
+            .bind(assignment_id)
+            .bind(student_id)
+            .bind(&id)
+            .bind(folder_path.to_string_lossy().to_string())
+            .bind(assignment_id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+            id
+        }
+
+        /// Insert a roster student
+        pub(crate) async fn student(&self, course_id: &str, student_id: &str, name: &str) {
+            sqlx::query("INSERT INTO students (course_id, student_id, name) VALUES (?, ?, ?)")
+                .bind(course_id)
+                .bind(student_id)
+                .bind(name)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+        }
+
+        /// Insert a TA, returning its id
+        pub(crate) async fn ta(&self, name: &str) -> String {
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query("INSERT INTO tas (id, display_name, initials) VALUES (?, ?, ?)")
+                .bind(&id)
+                .bind(name)
+                .bind(&name[..1])
+                .execute(&self.pool)
+                .await
+                .unwrap();
+            id
+        }
+
+        /// Mark a submission as claimed by `ta_id`. `claim_submission` needs a
+        /// real app handle, so tests set the claim directly.
+        pub(crate) async fn claim(&self, submission_id: &str, ta_id: &str) {
+            sqlx::query("UPDATE submissions SET claimed_by_ta_id = ?, claimed_at = datetime('now') WHERE id = ?")
+                .bind(ta_id)
+                .bind(submission_id)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+        }
+
+        /// Insert a grade for one question of a submission
+        pub(crate) async fn grade(&self, submission_id: &str, question_id: &str, score: Option<f64>, comment: Option<&str>) {
+            sqlx::query("INSERT INTO grades (submission_id, question_id, score, comment) VALUES (?, ?, ?, ?)")
+                .bind(submission_id)
+                .bind(question_id)
+                .bind(score)
+                .bind(comment)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Path of a file under `tests/fixtures`
+    pub(crate) fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::TestDb;
+    use super::*;
+
+    #[tokio::test]
+    async fn connections_use_wal_and_enforce_foreign_keys() {
+        let db = TestDb::new().await;
+        let mut conn = db.pool.acquire().await.unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&mut *conn).await.unwrap();
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys").fetch_one(&mut *conn).await.unwrap();
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout").fetch_one(&mut *conn).await.unwrap();
+        assert_eq!((journal_mode.as_str(), foreign_keys, busy_timeout), ("wal", 1, 5000));
+
+        let orphan = sqlx::query("INSERT INTO assignments (id, course_id, title) VALUES ('a1', 'missing', 'Homework 1')")
+            .execute(&mut *conn)
+            .await
+            .unwrap_err();
+        assert!(orphan.to_string().contains("FOREIGN KEY constraint failed"), "{}", orphan);
+    }
+
+    #[tokio::test]
+    async fn failed_migrations_are_reported_as_startup_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grading_hub.db");
+        let pool = open_db(&path).await.unwrap();
+        // An applied migration whose checksum no longer matches the embedded one
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = (SELECT MIN(version) FROM _sqlx_migrations)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let db = DbState::default();
+        assert_eq!(db.status().state, StartupState::Initializing);
+        assert_eq!(db.pool().unwrap_err(), "database not ready: still initializing");
+
+        db.set(open_db(&path).await.map_err(|e| e.to_string()));
+        let status = db.status();
+        assert_eq!(status.state, StartupState::Failed);
+        let error = status.error.unwrap();
+        assert!(error.contains("previously applied but has been modified"), "{}", error);
+        assert_eq!(db.pool().unwrap_err(), format!("database not ready: {}", error));
+    }
+
+    /// Files in `dir` whose names start with `prefix`, sorted
+    fn names_with_prefix(dir: &Path, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| n.starts_with(prefix))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn invalid_files_are_recognized_by_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("check.db");
+        let reason = |contents: &[u8]| {
+            fs::write(&path, contents).unwrap();
+            invalid_db_reason(&path, contents.len() as u64)
+        };
+        assert_eq!(reason(b"").as_deref(), Some("is empty"));
+        assert_eq!(reason(b"PK\x03\x04 not a database at all").as_deref(), Some("is not a SQLite database"));
+        let mut header = SQLITE_HEADER.to_vec();
+        header.extend([0x03, 0x00]);
+        assert_eq!(reason(&header).as_deref(), Some("has a corrupt header"));
+        header[16..].copy_from_slice(&[0x10, 0x00]);
+        assert_eq!(reason(&header).as_deref(), Some("is truncated (18 bytes, page size 4096)"));
+        header.resize(4096, 0);
+        assert_eq!(reason(&header), None);
+    }
+
+    #[tokio::test]
+    async fn empty_and_garbage_files_are_set_aside() {
+        for contents in [&b""[..], b"garbage left behind by a crash"] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("grading_hub.db");
+            fs::write(&path, contents).unwrap();
+            fs::write(dir.path().join("grading_hub.db-wal"), b"wal").unwrap();
+
+            let pool = open_db(&path).await.unwrap();
+            let courses: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM courses").fetch_one(&pool).await.unwrap();
+            assert_eq!(courses, 0);
+            pool.close().await;
+
+            // The old file and its WAL are kept under a timestamped name
+            let aside = names_with_prefix(dir.path(), "grading_hub.db");
+            let aside: Vec<&String> = aside.iter().filter(|n| n.contains(".invalid-")).collect();
+            assert_eq!(aside.len(), 2, "{:?}", aside);
+            assert!(aside[0].starts_with("grading_hub.db-wal.invalid-"), "{:?}", aside);
+            assert!(aside[1].starts_with("grading_hub.db.invalid-"), "{:?}", aside);
+            assert_eq!(fs::read(dir.path().join(aside[1])).unwrap(), contents);
+        }
+    }
+}
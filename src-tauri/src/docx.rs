@@ -1,55 +1,120 @@
 use tauri::State;
-use crate::db::DbPool;
+use crate::db::DbState;
+use crate::soffice::{self, SofficeLimiter};
 use serde::Serialize;
-use std::path::Path;
-use std::process::Command;
 
 #[derive(Serialize)]
 pub struct DocxConversionResult {
+    /// Absolute path of the produced (or cached) PDF
     pub pdf_path: String,
+    pub pdf_name: String,
     pub success: bool,
+    /// None when the PDF's page tree couldn't be read
+    pub page_count: Option<u32>,
+    /// True when an up-to-date PDF from an earlier run was reused
+    pub cached: bool,
 }
 
-/// Convert DOCX to PDF using LibreOffice headless
+/// Convert DOCX to PDF using LibreOffice headless.
+/// Reuses an up-to-date PDF from an earlier run unless `force` is set.
 #[tauri::command]
 pub async fn convert_docx_pdf(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
+    limiter: State<'_, SofficeLimiter>,
     submission_id: String,
     file_path: String,
-) -> Result<String, String> {
+    force: Option<bool>,
+) -> Result<DocxConversionResult, String> {
+    let pool = db.pool()?;
     // Get folder path from submission
     let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
         .bind(&submission_id)
-        .fetch_one(&*pool)
+        .fetch_one(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
-    let full_path = Path::new(&folder_path).join(&file_path);
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
+    let output_dir = full_path.parent().ok_or("File has no parent folder")?;
     
-    if !full_path.exists() {
-        return Err("File not found".to_string());
-    }
-    
-    let output_dir = full_path.parent().unwrap();
+    let cached = soffice::cached_pdf(&full_path, output_dir).filter(|_| !force.unwrap_or(false));
+    let was_cached = cached.is_some();
+    let pdf_path = match cached {
+        Some(cached) => cached,
+        None => {
+            // Use LibreOffice to convert
+            let binary = soffice::configured_binary(&pool).await?;
+            soffice::convert_to_pdf(&limiter, &binary, &full_path, output_dir).await?
+        }
+    };
     
-    // Use LibreOffice to convert
-    let output = Command::new("soffice")
-        .arg("--headless")
-        .arg("--convert-to")
-        .arg("pdf")
-        .arg(&full_path)
-        .arg("--outdir")
-        .arg(output_dir)
-        .output()
-        .map_err(|e| format!("Failed to run LibreOffice: {}", e))?;
+    let pdf_path = pdf_path.canonicalize().unwrap_or(pdf_path);
+    let pdf_name = pdf_path
+        .file_name()
+        .ok_or("Converted PDF has no file name")?
+        .to_string_lossy()
+        .to_string();
     
-    if !output.status.success() {
-        return Err(format!("LibreOffice conversion failed: {}", String::from_utf8_lossy(&output.stderr)));
+    Ok(DocxConversionResult {
+        page_count: crate::pdf::page_count(&pdf_path),
+        pdf_path: pdf_path.to_string_lossy().to_string(),
+        pdf_name,
+        success: true,
+        cached: was_cached,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{fixture, TestDb};
+    use std::os::unix::fs::PermissionsExt;
+    use tauri::Manager;
+
+    #[tokio::test]
+    async fn conversion_reports_the_pdf_and_reuses_it() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        // Stands in for soffice: "converts" by copying a two-page PDF and counts its runs
+        let binary = dir.path().join("soffice");
+        let script = format!(
+            "#!/bin/sh\necho run >> \"{runs}\"\nstem=$(basename \"$5\")\ncp \"{pdf}\" \"$7/${{stem%.*}}.pdf\"\n",
+            runs = dir.path().join("runs").display(),
+            pdf = fixture("two_pages.pdf").display(),
+        );
+        std::fs::write(&binary, script).unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        crate::commands::set_setting(app.state(), soffice::LIBREOFFICE_PATH_SETTING.into(), Some(binary.to_string_lossy().into()))
+            .await
+            .unwrap();
+
+        let folder = dir.path().join("submission");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("essay.docx"), b"not really a docx").unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), &folder).await;
+        let convert = |force| convert_docx_pdf(app.state(), app.state(), submission.clone(), "essay.docx".into(), force);
+        let runs = || std::fs::read_to_string(dir.path().join("runs")).unwrap().lines().count();
+
+        let first = convert(None).await.unwrap();
+        let expected = folder.join("essay.docx.pdf").canonicalize().unwrap();
+        assert_eq!((first.pdf_path.as_str(), first.pdf_name.as_str()), (expected.to_str().unwrap(), "essay.docx.pdf"));
+        assert_eq!((first.page_count, first.cached), (Some(2), false));
+
+        let second = convert(None).await.unwrap();
+        assert_eq!((second.page_count, second.cached, runs()), (Some(2), true, 1));
+        let forced = convert(Some(true)).await.unwrap();
+        assert_eq!((forced.cached, runs()), (false, 2));
+
+        let missing = convert_docx_pdf(app.state(), app.state(), submission.clone(), "gone.docx".into(), None).await;
+        assert_eq!(missing.err().as_deref(), Some("File not found"));
+
+        // Files outside the submission folder are refused, not converted next to where they live
+        std::fs::write(dir.path().join("outside.docx"), b"not really a docx").unwrap();
+        let escaped = convert_docx_pdf(app.state(), app.state(), submission, "../outside.docx".into(), None).await;
+        assert_eq!(escaped.err().as_deref(), Some("Access denied: path is outside the submission folder"));
+        assert!(!dir.path().join("outside.docx.pdf").exists());
+        assert_eq!(runs(), 2);
     }
-    
-    // Return the PDF filename
-    let file_stem = full_path.file_stem().unwrap().to_string_lossy();
-    let pdf_name = format!("{}.pdf", file_stem);
-    
-    Ok(pdf_name)
 }
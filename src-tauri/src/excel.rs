@@ -1,84 +1,661 @@
 use tauri::{AppHandle, Manager, State};
-use crate::db::DbPool;
-use calamine::{Reader, Xlsx, open_workbook, Data, Error as CalamineError};
+use crate::db::DbState;
+use crate::soffice::{self, SofficeLimiter};
+use calamine::{Reader, Xlsx, Xls, Sheets, Range, Data};
 use serde::Serialize;
 use std::path::Path;
-use std::process::Command;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::BufReader;
+use std::sync::LazyLock;
 use std::fs::File;
+use zip::ZipArchive;
+
+type Workbook = Sheets<BufReader<File>>;
+
+/// Open a workbook with the calamine reader matching its extension
+/// (`.xls` uses the legacy BIFF reader, everything else goes through `Xlsx`)
+fn open_workbook(path: &Path) -> Result<Workbook, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = BufReader::new(file);
+
+    match ext.as_str() {
+        "xls" => Xls::new(reader).map(Sheets::Xls).map_err(|e| e.to_string()),
+        _ => Xlsx::new(reader).map(Sheets::Xlsx).map_err(|e| e.to_string()),
+    }
+}
+
+/// A sheet calamine couldn't read, and what was being read from it
+#[derive(Serialize, Debug)]
+pub struct SheetError {
+    pub sheet_name: String,
+    /// e.g. "reading formulas"
+    pub operation: String,
+    pub message: String,
+}
+
+impl SheetError {
+    fn new(sheet_name: &str, operation: &str, error: impl std::fmt::Display) -> Self {
+        Self { sheet_name: sheet_name.to_string(), operation: operation.to_string(), message: error.to_string() }
+    }
+}
+
+impl std::fmt::Display for SheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in sheet '{}': {}", self.operation, self.sheet_name, self.message)
+    }
+}
+
+impl From<SheetError> for String {
+    fn from(e: SheetError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Sheets parsed on this thread, so tests can check each is read only once
+    static SHEET_PARSES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Read the formulas of a sheet. Legacy `.xls` files may not expose formulas,
+/// so for those an empty range is returned with `false` instead of an error.
+fn sheet_formulas(excel: &mut Workbook, sheet_name: &str) -> Result<(Range<String>, bool), SheetError> {
+    #[cfg(test)]
+    SHEET_PARSES.with(|n| n.set(n.get() + 1));
+    match excel.worksheet_formula(sheet_name) {
+        Ok(formulas) => Ok((formulas, true)),
+        Err(_) if matches!(excel, Sheets::Xls(_)) => Ok((Range::empty(), false)),
+        Err(e) => Err(SheetError::new(sheet_name, "reading formulas", e)),
+    }
+}
+
+fn sheet_values(excel: &mut Workbook, sheet_name: &str) -> Result<Range<Data>, SheetError> {
+    #[cfg(test)]
+    SHEET_PARSES.with(|n| n.set(n.get() + 1));
+    excel.worksheet_range(sheet_name).map_err(|e| SheetError::new(sheet_name, "reading values", e))
+}
+
+/// Names of the parts inside an xlsx package. Legacy `.xls` files are not
+/// ZIP packages, so they yield an empty list.
+fn package_part_names(path: &Path) -> Vec<String> {
+    File::open(path)
+        .ok()
+        .and_then(|f| ZipArchive::new(f).ok())
+        .map(|archive| archive.file_names().map(|n| n.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Text of one part inside an xlsx package, if present
+fn read_package_part(path: &Path, part: &str) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    let mut entry = archive.by_name(part).ok()?;
+    let mut xml = String::new();
+    std::io::Read::read_to_string(&mut entry, &mut xml).ok()?;
+    Some(xml)
+}
+
+/// Value of attribute `name` (e.g. `r:id`) within one XML start tag, or
+/// within just its attributes (` ref="A1" type="list"`)
+fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    // Skip "<element", then walk the key="value" pairs in order
+    let mut rest = tag
+        .strip_prefix('<')
+        .map_or(tag, |t| t.trim_start_matches(|c: char| !c.is_whitespace() && c != '/' && c != '>'));
+    loop {
+        let (key, after) = rest.split_once('=')?;
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (value, next) = after[1..].split_once(quote)?;
+        if key.trim() == name {
+            return Some(unescape_xml(value));
+        }
+        rest = next;
+    }
+}
+
+static RELATIONSHIP_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<Relationship\b[^>]*>").unwrap());
+static SHEET_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<sheet\b[^>]*>").unwrap());
+
+/// Raw worksheet XML keyed by sheet name, found through `xl/workbook.xml`
+/// and its relationships. Empty for `.xls` files and unreadable packages.
+fn worksheet_xml(path: &Path) -> HashMap<String, String> {
+    let mut sheets = HashMap::new();
+    let (Some(workbook), Some(rels)) = (
+        read_package_part(path, "xl/workbook.xml"),
+        read_package_part(path, "xl/_rels/workbook.xml.rels"),
+    ) else {
+        return sheets;
+    };
+
+    let targets: HashMap<String, String> = RELATIONSHIP_TAG
+        .find_iter(&rels)
+        .filter_map(|m| Some((xml_attr(m.as_str(), "Id")?, xml_attr(m.as_str(), "Target")?)))
+        .collect();
+
+    for tag in SHEET_TAG.find_iter(&workbook).map(|m| m.as_str()) {
+        let (Some(name), Some(rel_id)) = (xml_attr(tag, "name"), xml_attr(tag, "r:id")) else { continue };
+        let Some(target) = targets.get(&rel_id) else { continue };
+        // Targets are relative to xl/ unless written as absolute package paths
+        let part = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{}", target),
+        };
+        if let Some(xml) = read_package_part(path, &part) {
+            sheets.insert(name, xml);
+        }
+    }
+    sheets
+}
+
+/// A rectangular block of cells, as 0-based (row, col) corners
+#[derive(Debug, Clone, Copy)]
+struct CellArea {
+    start: (u32, u32),
+    end: (u32, u32),
+}
+
+impl CellArea {
+    /// Parse an A1 reference (`B2`, `$A$1:C3`) as written in sheet XML
+    fn parse(reference: &str) -> Option<Self> {
+        let (first, last) = reference.split_once(':').unwrap_or((reference, reference));
+        let (c1, r1) = parse_cell_ref(&first.replace('$', "")).ok()?;
+        let (c2, r2) = parse_cell_ref(&last.replace('$', "")).ok()?;
+        Some(CellArea { start: (r1.min(r2), c1.min(c2)), end: (r1.max(r2), c1.max(c2)) })
+    }
+
+    fn contains(&self, row: u32, col: u32) -> bool {
+        (self.start.0..=self.end.0).contains(&row) && (self.start.1..=self.end.1).contains(&col)
+    }
+
+    fn anchor_address(&self) -> String {
+        format!("{}{}", col_to_letter(self.start.1 as usize), self.start.0 + 1)
+    }
+
+    fn overlaps(&self, other: &CellArea) -> bool {
+        self.start.0 <= other.end.0 && other.start.0 <= self.end.0
+            && self.start.1 <= other.end.1 && other.start.1 <= self.end.1
+    }
+
+    fn address(&self) -> String {
+        let end = format!("{}{}", col_to_letter(self.end.1 as usize), self.end.0 + 1);
+        if self.start == self.end {
+            end
+        } else {
+            format!("{}:{}", self.anchor_address(), end)
+        }
+    }
+}
+
+static MERGE_CELL: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"<mergeCell\b[^>]*\bref="([^"]+)""#).unwrap());
+
+/// The `<mergeCell ref="A1:C1"/>` areas of one worksheet
+fn merged_areas(sheet_xml: &str) -> Vec<CellArea> {
+    MERGE_CELL.captures_iter(sheet_xml).filter_map(|caps| CellArea::parse(&caps[1])).collect()
+}
+
+/// A data validation rule (`list`, `whole`, `decimal`, ...) and the cells it covers
+struct DataValidation {
+    kind: String,
+    areas: Vec<CellArea>,
+}
+
+static DATA_VALIDATION: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<dataValidation\b[^>]*>").unwrap());
+static X14_DATA_VALIDATION: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?s)<x14:dataValidation\b([^>]*)>.*?<xm:sqref>([^<]+)</xm:sqref>").unwrap()
+});
+
+/// The data validations of one worksheet: classic `<dataValidation sqref=...>`
+/// entries plus the `x14` extension Excel writes for lists sourced from other sheets
+fn data_validations(sheet_xml: &str) -> Vec<DataValidation> {
+    let areas = |sqref: &str| sqref.split_whitespace().filter_map(CellArea::parse).collect::<Vec<_>>();
+
+    let mut validations: Vec<DataValidation> = DATA_VALIDATION
+        .find_iter(sheet_xml)
+        .filter_map(|m| {
+            let sqref = xml_attr(m.as_str(), "sqref")?;
+            Some(DataValidation {
+                kind: xml_attr(m.as_str(), "type").unwrap_or_else(|| "any".to_string()),
+                areas: areas(&sqref),
+            })
+        })
+        .collect();
+    for caps in X14_DATA_VALIDATION.captures_iter(sheet_xml) {
+        validations.push(DataValidation {
+            kind: xml_attr(&caps[1], "type").unwrap_or_else(|| "any".to_string()),
+            areas: areas(&caps[2]),
+        });
+    }
+    validations
+}
+
+/// One `<conditionalFormatting>` block: the cells it applies to and its rule types
+struct ConditionalFormat {
+    areas: Vec<CellArea>,
+    rule_types: Vec<String>,
+}
+
+static CONDITIONAL_FORMATTING: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?s)<conditionalFormatting\b([^>]*)>(.*?)</conditionalFormatting>").unwrap()
+});
+static X14_CONDITIONAL_FORMATTING: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?s)<x14:conditionalFormatting\b[^>]*>(.*?)</x14:conditionalFormatting>").unwrap()
+});
+static CF_RULE: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<cfRule\b[^>]*>").unwrap());
+static X14_CF_RULE: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<x14:cfRule\b[^>]*>").unwrap());
+static XM_SQREF: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<xm:sqref>([^<]+)</xm:sqref>").unwrap());
+static X14_ID: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<x14:id>([^<]+)</x14:id>").unwrap());
+
+/// The conditional formatting of one worksheet, including the `x14` extension
+/// blocks Excel uses for data bars and icon sets with newer options
+fn conditional_formats(sheet_xml: &str) -> Vec<ConditionalFormat> {
+    let areas = |refs: &str| refs.split_whitespace().filter_map(CellArea::parse).collect::<Vec<_>>();
+    let rule_type = |tag: &str| xml_attr(tag, "type").unwrap_or_else(|| "unknown".to_string());
+
+    let mut formats: Vec<ConditionalFormat> = CONDITIONAL_FORMATTING
+        .captures_iter(sheet_xml)
+        .filter_map(|caps| {
+            let refs = xml_attr(&caps[1], "sqref")?;
+            let rules = CF_RULE.find_iter(&caps[2]).map(|m| rule_type(m.as_str())).collect();
+            Some(ConditionalFormat { areas: areas(&refs), rule_types: rules })
+        })
+        .collect();
+
+    // A classic rule with extended options links to its x14 twin by id; count it once
+    let linked: HashSet<String> = X14_ID.captures_iter(sheet_xml).map(|c| c[1].to_string()).collect();
+    for caps in X14_CONDITIONAL_FORMATTING.captures_iter(sheet_xml) {
+        let Some(refs) = XM_SQREF.captures(&caps[1]) else { continue };
+        let rules: Vec<String> = X14_CF_RULE
+            .find_iter(&caps[1])
+            .filter(|m| xml_attr(m.as_str(), "id").is_none_or(|id| !linked.contains(&id)))
+            .map(|m| rule_type(m.as_str()))
+            .collect();
+        if !rules.is_empty() {
+            formats.push(ConditionalFormat { areas: areas(&refs[1]), rule_types: rules });
+        }
+    }
+    formats
+}
+
+/// The kinds of number format a rubric can ask a range to use
+#[derive(Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormatKind {
+    Currency,
+    Percent,
+    /// Dates and times
+    Date,
+    /// Plain numbers shown without decimals
+    Integer,
+}
+
+/// Excel's built-in number formats (en-US), by `numFmtId`
+fn builtin_number_format(id: u32) -> Option<&'static str> {
+    Some(match id {
+        0 => "General",
+        1 => "0",
+        2 => "0.00",
+        3 => "#,##0",
+        4 => "#,##0.00",
+        5 => "$#,##0_);($#,##0)",
+        6 => "$#,##0_);[Red]($#,##0)",
+        7 => "$#,##0.00_);($#,##0.00)",
+        8 => "$#,##0.00_);[Red]($#,##0.00)",
+        9 => "0%",
+        10 => "0.00%",
+        11 => "0.00E+00",
+        12 => "# ?/?",
+        13 => "# ??/??",
+        14 => "m/d/yyyy",
+        15 => "d-mmm-yy",
+        16 => "d-mmm",
+        17 => "mmm-yy",
+        18 => "h:mm AM/PM",
+        19 => "h:mm:ss AM/PM",
+        20 => "h:mm",
+        21 => "h:mm:ss",
+        22 => "m/d/yyyy h:mm",
+        37 => "#,##0 ;(#,##0)",
+        38 => "#,##0 ;[Red](#,##0)",
+        39 => "#,##0.00;(#,##0.00)",
+        40 => "#,##0.00;[Red](#,##0.00)",
+        41 => r#"_(* #,##0_);_(* \(#,##0\);_(* "-"_);_(@_)"#,
+        42 => r#"_("$"* #,##0_);_("$"* \(#,##0\);_("$"* "-"_);_(@_)"#,
+        43 => r#"_(* #,##0.00_);_(* \(#,##0.00\);_(* "-"??_);_(@_)"#,
+        44 => r#"_("$"* #,##0.00_);_("$"* \(#,##0.00\);_("$"* "-"??_);_(@_)"#,
+        45 => "mm:ss",
+        46 => "[h]:mm:ss",
+        47 => "mmss.0",
+        48 => "##0.0E+0",
+        49 => "@",
+        _ => return None,
+    })
+}
+
+const CURRENCY_SYMBOLS: [char; 6] = ['$', '€', '£', '¥', '₹', '₩'];
+
+/// Classify a format code by its first (positive number) section:
+/// the kind it displays, if any, and how many decimals it shows
+fn classify_number_format(code: &str) -> (Option<NumberFormatKind>, u32) {
+    // Split off the first section and separate literal text from placeholders
+    let mut placeholders = String::new();
+    let mut currency = false;
+    let mut chars = code.chars();
+    let mut in_quotes = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            _ if in_quotes => currency |= CURRENCY_SYMBOLS.contains(&c),
+            ';' => break,
+            '\\' => currency |= chars.next().is_some_and(|n| CURRENCY_SYMBOLS.contains(&n)),
+            // `_x` pads by the width of x and `*x` repeats x; neither is shown as text
+            '_' | '*' => {
+                chars.next();
+            }
+            '[' => {
+                let bracket: String = chars.by_ref().take_while(|&b| b != ']').collect();
+                if let Some(locale) = bracket.strip_prefix('$') {
+                    // [$€-407] carries a currency symbol; [$-409] is only a locale
+                    currency |= !locale.is_empty() && !locale.starts_with('-');
+                } else if matches!(bracket.to_lowercase().as_str(), "h" | "hh" | "m" | "mm" | "s" | "ss") {
+                    // Elapsed time, e.g. [h]:mm:ss
+                    placeholders.push('h');
+                }
+                // Anything else is a colour or condition such as [Red] or [>100]
+            }
+            _ if CURRENCY_SYMBOLS.contains(&c) => currency = true,
+            _ => placeholders.push(c),
+        }
+    }
+
+    let lower = placeholders.to_lowercase();
+    if lower.trim() == "general" || lower.trim() == "@" || lower.trim().is_empty() {
+        return (None, 0);
+    }
+    let decimals = lower
+        .split_once('.')
+        .map(|(_, frac)| frac.chars().take_while(|c| matches!(c, '0' | '#' | '?')).count() as u32)
+        .unwrap_or(0);
+    let is_numeric = lower.contains(['0', '#', '?']);
+    let kind = if lower.contains('%') {
+        Some(NumberFormatKind::Percent)
+    } else if currency {
+        Some(NumberFormatKind::Currency)
+    } else if lower.contains(['d', 'm', 'y', 'h', 's']) {
+        Some(NumberFormatKind::Date)
+    } else if is_numeric && decimals == 0 && !lower.contains(['e', '/']) {
+        Some(NumberFormatKind::Integer)
+    } else {
+        None
+    };
+    (kind, decimals)
+}
+
+static NUM_FMT_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<numFmt\b[^>]*>").unwrap());
+static CELL_XFS: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"(?s)<cellXfs\b[^>]*>(.*?)</cellXfs>").unwrap());
+static XF_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<xf\b[^>]*>").unwrap());
+static CELL_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<c\b[^>]*>").unwrap());
+
+/// Number format code for each cell style (`cellXfs` index) in `xl/styles.xml`
+fn cell_style_formats(path: &Path) -> Vec<String> {
+    let Some(styles) = read_package_part(path, "xl/styles.xml") else { return Vec::new() };
+    let custom: HashMap<u32, String> = NUM_FMT_TAG
+        .find_iter(&styles)
+        .filter_map(|m| {
+            let id = xml_attr(m.as_str(), "numFmtId")?.parse().ok()?;
+            Some((id, xml_attr(m.as_str(), "formatCode")?))
+        })
+        .collect();
+
+    let Some(block) = CELL_XFS.captures(&styles) else { return Vec::new() };
+    XF_TAG.find_iter(&block[1])
+        .map(|m| {
+            let id: u32 = xml_attr(m.as_str(), "numFmtId").and_then(|v| v.parse().ok()).unwrap_or(0);
+            custom
+                .get(&id)
+                .cloned()
+                .or_else(|| builtin_number_format(id).map(str::to_string))
+                .unwrap_or_else(|| "General".to_string())
+        })
+        .collect()
+}
+
+/// Style index of every cell that names one (`<c r="E2" s="3">`), keyed by 0-based (row, col)
+fn cell_style_indexes(sheet_xml: &str) -> HashMap<(u32, u32), usize> {
+    CELL_TAG.find_iter(sheet_xml)
+        .filter_map(|m| {
+            let (col, row) = parse_cell_ref(&xml_attr(m.as_str(), "r")?).ok()?;
+            let style = xml_attr(m.as_str(), "s")?.parse().ok()?;
+            Some(((row, col), style))
+        })
+        .collect()
+}
+
+/// How a worksheet opens: frozen panes and gridline visibility from its first `<sheetView>`
+struct SheetViewInfo {
+    /// (rows, columns) held in place, when the panes are frozen
+    frozen: Option<(u32, u32)>,
+    /// First cell of the scrolling pane
+    top_left_cell: Option<String>,
+    show_gridlines: bool,
+}
+
+static SHEET_VIEW: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?s)<sheetView\b([^>]*?)(?:/>|>(.*?)</sheetView>)").unwrap());
+static PANE_TAG: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"<pane\b[^>]*>").unwrap());
+
+/// Read the sheet view. A sheet without one opens unfrozen with gridlines shown.
+fn sheet_view(sheet_xml: &str) -> SheetViewInfo {
+    let Some(view) = SHEET_VIEW.captures(sheet_xml) else {
+        return SheetViewInfo { frozen: None, top_left_cell: None, show_gridlines: true };
+    };
+    let show_gridlines = xml_attr(&view[1], "showGridLines").is_none_or(|v| v != "0" && v != "false");
+    let pane = view.get(2).and_then(|body| PANE_TAG.find(body.as_str())).map(|m| m.as_str().to_string());
+
+    // "frozen" and "frozenSplit" both hold the panes in place; plain "split" scrolls
+    let frozen = pane.as_deref().filter(|p| xml_attr(p, "state").is_some_and(|s| s.starts_with("frozen"))).map(|p| {
+        let split = |name: &str| xml_attr(p, name).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0) as u32;
+        (split("ySplit"), split("xSplit"))
+    });
+    SheetViewInfo {
+        frozen,
+        top_left_cell: pane.as_deref().and_then(|p| xml_attr(p, "topLeftCell")),
+        show_gridlines,
+    }
+}
+
+/// Count pivot tables by looking for `xl/pivotTables/pivotTable*.xml` parts
+fn count_pivot_tables(path: &Path) -> usize {
+    package_part_names(path)
+        .iter()
+        .filter(|name| name.starts_with("xl/pivotTables/pivotTable") && name.ends_with(".xml"))
+        .count()
+}
+
+/// One chart part in the package and the plot types it draws
+/// (several for a combo chart)
+#[derive(Serialize, Debug)]
+pub struct ChartInfo {
+    pub part: String,
+    pub types: Vec<String>,
+}
+
+/// Plot type element in a chart part -> reported type. Bar charts are
+/// split by `<c:barDir>` into "bar" (horizontal) and "column".
+const CHART_TYPES: [(&str, &str); 16] = [
+    ("barChart", "bar"),
+    ("bar3DChart", "bar"),
+    ("lineChart", "line"),
+    ("line3DChart", "line"),
+    ("pieChart", "pie"),
+    ("pie3DChart", "pie"),
+    ("ofPieChart", "pie"),
+    ("doughnutChart", "doughnut"),
+    ("scatterChart", "scatter"),
+    ("bubbleChart", "bubble"),
+    ("areaChart", "area"),
+    ("area3DChart", "area"),
+    ("radarChart", "radar"),
+    ("stockChart", "stock"),
+    ("surfaceChart", "surface"),
+    ("surface3DChart", "surface"),
+];
+
+static BAR_DIRECTION: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r#"<c:barDir\b[^>]*\bval="(\w+)""#).unwrap());
+static CHART_EX_LAYOUT: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"<cx:series\b[^>]*\blayoutId="(\w+)""#).unwrap());
+
+/// Charts in an xlsx package, from its `xl/charts/chart*.xml` parts (and the
+/// `chartEx*.xml` parts newer chart kinds like waterfall use). Types that
+/// can't be read are left out, so `types` may be empty.
+fn workbook_charts(path: &Path) -> Vec<ChartInfo> {
+    let mut parts: Vec<String> = package_part_names(path)
+        .into_iter()
+        .filter(|name| name.starts_with("xl/charts/chart") && name.ends_with(".xml"))
+        .collect();
+    parts.sort();
+
+    parts
+        .into_iter()
+        .map(|part| {
+            let xml = read_package_part(path, &part).unwrap_or_default();
+            let mut types: Vec<String> = Vec::new();
+            for (element, kind) in CHART_TYPES {
+                if !xml.contains(&format!("<c:{}>", element)) && !xml.contains(&format!("<c:{} ", element)) {
+                    continue;
+                }
+                let kind = match kind {
+                    "bar" if BAR_DIRECTION.captures(&xml).is_some_and(|c| &c[1] == "col") => "column",
+                    other => other,
+                };
+                types.push(kind.to_string());
+            }
+            for caps in CHART_EX_LAYOUT.captures_iter(&xml) {
+                types.push(caps[1].to_lowercase());
+            }
+            types.dedup();
+            ChartInfo { part, types }
+        })
+        .collect()
+}
+
+/// Path of the VBA project inside a macro-enabled package
+const VBA_PROJECT_PART: &str = "xl/vbaProject.bin";
+
+static VBA_MODULE_LINE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?m)^(?:Module|Class|BaseClass|Document)=").unwrap());
+
+/// Count VBA modules by reading the `PROJECT` stream's `Module=`/`Class=`/
+/// `Document=` lines, which are stored as plain text inside vbaProject.bin.
+/// Returns None if the project can't be read or no module lines are found.
+/// Nothing in the project is ever executed.
+fn count_macro_modules(path: &Path) -> Option<usize> {
+    let file = File::open(path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    let mut part = archive.by_name(VBA_PROJECT_PART).ok()?;
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut part, &mut bytes).ok()?;
+
+    let text = String::from_utf8_lossy(&bytes);
+    let count = VBA_MODULE_LINE.find_iter(&text).count();
+    (count > 0).then_some(count)
+}
 
 #[derive(Serialize)]
 pub struct WorkbookAnalysis {
     sheets: Vec<String>,
     formulas_count: usize,
     has_pivot: bool, 
+    pivot_count: usize,
+    /// Workbook contains a VBA project (e.g. `.xlsm`)
+    has_macros: bool,
+    macro_module_count: Option<usize>,
+    chart_count: usize,
+    charts: Vec<ChartInfo>,
+    /// Sheets whose formulas couldn't be read and so aren't in `formulas_count`
+    sheet_errors: Vec<SheetError>,
 }
 
 #[tauri::command]
 pub async fn analyze_excel(
-    _app: AppHandle,
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     file_path: String, 
 ) -> Result<WorkbookAnalysis, String> {
+    let pool = db.pool()?;
     let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
         .bind(&submission_id)
-        .fetch_one(&*pool)
+        .fetch_one(&pool)
         .await
         .map_err(|e| e.to_string())?;
         
-    let full_path = Path::new(&folder_path).join(&file_path);
-    
-    if !full_path.exists() {
-        return Err("File not found".to_string());
-    }
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
 
-    let file = File::open(&full_path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let mut excel: Xlsx<BufReader<File>> = Xlsx::new(reader).map_err(|e| e.to_string())?;
+    let mut excel = open_workbook(&full_path)?;
     
     let sheet_names = excel.sheet_names().to_vec();
+    // A sheet whose formulas can't be read is reported rather than failing the analysis
+    let mut formulas_count = 0;
+    let mut sheet_errors = Vec::new();
+    for name in &sheet_names {
+        match sheet_formulas(&mut excel, name) {
+            Ok((formulas, _)) => formulas_count += formulas.used_cells().filter(|(_, _, f)| !f.is_empty()).count(),
+            Err(e) => sheet_errors.push(e),
+        }
+    }
+    let pivot_count = count_pivot_tables(&full_path);
+    let has_macros = package_part_names(&full_path).iter().any(|n| n == VBA_PROJECT_PART);
+    let charts = workbook_charts(&full_path);
     
     Ok(WorkbookAnalysis {
         sheets: sheet_names,
-        formulas_count: 0, 
-        has_pivot: false
+        formulas_count,
+        has_pivot: pivot_count > 0,
+        pivot_count,
+        has_macros,
+        macro_module_count: if has_macros { count_macro_modules(&full_path) } else { None },
+        chart_count: charts.len(),
+        charts,
+        sheet_errors,
     })
 }
 
 #[tauri::command]
 pub async fn generate_excel_pdf(
     _app: AppHandle,
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
+    limiter: State<'_, SofficeLimiter>,
     submission_id: String,
     file_path: String,
+    force: Option<bool>,
 ) -> Result<String, String> {
+    let pool = db.pool()?;
      let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
         .bind(&submission_id)
-        .fetch_one(&*pool)
+        .fetch_one(&pool)
         .await
         .map_err(|e| e.to_string())?;
         
-    let full_path = Path::new(&folder_path).join(&file_path);
-    let output_dir = full_path.parent().unwrap();
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
+    let output_dir = full_path.parent().ok_or("File has no parent folder")?;
     
-    let output = Command::new("soffice")
-        .arg("--headless")
-        .arg("--convert-to")
-        .arg("pdf")
-        .arg(&full_path)
-        .arg("--outdir")
-        .arg(output_dir)
-        .output()
-        .map_err(|e| format!("Failed to run libreoffice: {}", e))?;
-        
-    if !output.status.success() {
-        return Err(format!("LibreOffice failed: {}", String::from_utf8_lossy(&output.stderr)));
-    }
-    
-    let file_stem = full_path.file_stem().unwrap().to_string_lossy();
-    let pdf_name = format!("{}.pdf", file_stem);
+    let pdf_path = match soffice::cached_pdf(&full_path, output_dir).filter(|_| !force.unwrap_or(false)) {
+        Some(cached) => cached,
+        None => {
+            let binary = soffice::configured_binary(&pool).await?;
+            soffice::convert_to_pdf(&limiter, &binary, &full_path, output_dir).await?
+        }
+    };
+    let pdf_name = pdf_path
+        .file_name()
+        .ok_or("Converted PDF has no file name")?
+        .to_string_lossy()
+        .to_string();
     
     Ok(pdf_name)
 }
@@ -98,9 +675,7 @@ pub async fn parse_excel_roster(
         return Err("File not found".to_string());
     }
 
-    let file = File::open(&path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let mut excel: Xlsx<BufReader<File>> = Xlsx::new(reader).map_err(|e| e.to_string())?;
+    let mut excel = open_workbook(path)?;
     
     let sheet_name = excel.sheet_names().first().ok_or("No sheets found")?.clone();
     
@@ -133,6 +708,74 @@ pub async fn parse_excel_roster(
     })
 }
 
+/// Pick the delimiter that splits the header line into the most fields,
+/// ignoring anything inside quotes
+fn detect_csv_delimiter(text: &str) -> u8 {
+    let header = text.lines().next().unwrap_or("");
+    let mut counts = [(b',', 0usize), (b';', 0), (b'\t', 0)];
+    let mut quoted = false;
+    for c in header.bytes() {
+        if c == b'"' {
+            quoted = !quoted;
+        } else if !quoted {
+            if let Some(entry) = counts.iter_mut().find(|(d, _)| *d == c) {
+                entry.1 += 1;
+            }
+        }
+    }
+    // Ties (including no delimiter at all) fall back to comma
+    counts.iter().fold((b',', 0), |best, &(d, n)| if n > best.1 { (d, n) } else { best }).0
+}
+
+/// Read a CSV roster into the same shape as `parse_excel_roster`. Handles a UTF-8
+/// BOM, quoted fields and comma, semicolon or tab delimiters (auto-detected).
+#[tauri::command]
+pub async fn parse_csv_roster(
+    file_path: String,
+) -> Result<ExcelParseResult, String> {
+    let path = Path::new(&file_path);
+    if !path.exists() {
+        return Err("File not found".to_string());
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(detect_csv_delimiter(text))
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    if headers.iter().all(|h| h.is_empty()) {
+        return Err("Empty file".to_string());
+    }
+
+    let mut data = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let row_map: HashMap<String, String> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.clone(), v.trim().to_string()))
+            .collect();
+        data.push(row_map);
+    }
+
+    Ok(ExcelParseResult {
+        headers,
+        data
+    })
+}
+
 // --- Formula Inspection Commands ---
 
 #[derive(Serialize)]
@@ -140,6 +783,12 @@ pub struct CellInfo {
     pub address: String,
     pub value: String,
     pub formula: Option<String>,
+    /// The formula reads from another sheet
+    pub cross_sheet: bool,
+    /// The cell is part of a merged area
+    pub is_merged: bool,
+    /// Top-left cell of that merged area, which holds its value
+    pub merge_anchor: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -148,79 +797,138 @@ pub struct SheetFormulaMap {
     pub cells: Vec<CellInfo>,
     pub formula_count: usize,
     pub functions_used: Vec<String>,
+    /// Other sheets this sheet's formulas reference
+    pub referenced_sheets: Vec<String>,
+}
+
+/// Workbook-level summary: `from_sheet` has `formula_count` formulas reading `to_sheet`
+#[derive(Serialize)]
+pub struct SheetDependency {
+    pub from_sheet: String,
+    pub to_sheet: String,
+    pub formula_count: usize,
 }
 
 #[derive(Serialize)]
 pub struct FormulaMapResult {
     pub sheets: Vec<SheetFormulaMap>,
+    pub dependencies: Vec<SheetDependency>,
     pub total_formula_count: usize,
     pub has_pivot: bool,
+    pub pivot_count: usize,
     pub hidden_sheets: Vec<String>,
+    pub formulas_supported: bool,
+    /// Sheets left out of `sheets` because they couldn't be read
+    pub sheet_errors: Vec<SheetError>,
 }
 
 /// Get formula map for all cells in a workbook
 #[tauri::command]
 pub async fn get_formula_map(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     file_path: String,
 ) -> Result<FormulaMapResult, String> {
+    let pool = db.pool()?;
     let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
         .bind(&submission_id)
-        .fetch_one(&*pool)
+        .fetch_one(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
-    let full_path = Path::new(&folder_path).join(&file_path);
-    
-    if !full_path.exists() {
-        return Err("File not found".to_string());
-    }
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
 
-    let file = File::open(&full_path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let mut excel: Xlsx<BufReader<File>> = Xlsx::new(reader).map_err(|e| e.to_string())?;
+    let mut excel = open_workbook(&full_path)?;
     
     let sheet_names = excel.sheet_names().to_vec();
     let mut sheets = Vec::new();
+    let mut dependencies = Vec::new();
     let mut total_formula_count = 0;
+    let mut formulas_supported = true;
+    let mut sheet_errors = Vec::new();
+    let sheet_xml = worksheet_xml(&full_path);
     
     for sheet_name in &sheet_names {
-        // Get formulas for this sheet
-        let formulas = excel.worksheet_formula(sheet_name)
-            .map_err(|e| e.to_string())?;
-        
-        // Get values for this sheet
-        let range = excel.worksheet_range(sheet_name)
-            .map_err(|e| e.to_string())?;
+        // One unreadable sheet is reported, not allowed to hide the rest
+        let loaded = sheet_formulas(&mut excel, sheet_name)
+            .and_then(|formulas| Ok((formulas, sheet_values(&mut excel, sheet_name)?)));
+        let ((formulas, supported), range) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                sheet_errors.push(e);
+                continue;
+            }
+        };
+        formulas_supported &= supported;
         
         let mut cells = Vec::new();
         let mut formula_count = 0;
         let mut functions_set: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let mut refs_per_sheet: HashMap<String, usize> = HashMap::new();
+        let merges = sheet_xml.get(sheet_name).map(|xml| merged_areas(xml)).unwrap_or_default();
+        let merge_at = |row: u32, col: u32| merges.iter().find(|m| m.contains(row, col));
+        let (start_row, start_col) = range.start().unwrap_or((0, 0));
         
-        // Build cell info with formulas
+        // Build cell info with formulas, addressed absolutely (the range starts at the first used cell)
         for (row_idx, row) in range.rows().enumerate() {
             for (col_idx, cell) in row.iter().enumerate() {
-                let col_letter = col_to_letter(col_idx);
-                let address = format!("{}{}", col_letter, row_idx + 1);
+                let (abs_row, abs_col) = (start_row + row_idx as u32, start_col + col_idx as u32);
+                let address = format!("{}{}", col_to_letter(abs_col as usize), abs_row + 1);
                 
                 // Check if this cell has a formula
-                let formula = formulas.get((row_idx, col_idx))
+                let formula = formulas.get_value((abs_row, abs_col))
+                    .filter(|f| !f.is_empty())
                     .map(|f| f.to_string());
                 
+                let mut cross_sheet = false;
                 if let Some(ref f) = formula {
                     formula_count += 1;
                     // Extract function names from formula
                     extract_functions(f, &mut functions_set);
+                    
+                    for target in referenced_sheets(f) {
+                        if !target.eq_ignore_ascii_case(sheet_name) {
+                            cross_sheet = true;
+                            *refs_per_sheet.entry(target).or_insert(0) += 1;
+                        }
+                    }
                 }
                 
-                // Only include cells with content or formulas
+                // Only include cells with content or formulas, plus the empty rest of a merge
                 let value = cell.to_string();
-                if !value.is_empty() || formula.is_some() {
+                let merge = merge_at(abs_row, abs_col);
+                if !value.is_empty() || formula.is_some() || merge.is_some() {
                     cells.push(CellInfo {
                         address,
                         value,
                         formula,
+                        cross_sheet,
+                        is_merged: merge.is_some(),
+                        merge_anchor: merge.map(|m| m.anchor_address()),
+                    });
+                }
+            }
+        }
+        
+        // Merged cells past the used range never appear in it
+        let in_range = |row: u32, col: u32| {
+            range.start().zip(range.end()).is_some_and(|((r1, c1), (r2, c2))| {
+                (r1..=r2).contains(&row) && (c1..=c2).contains(&col)
+            })
+        };
+        for merge in &merges {
+            for row in merge.start.0..=merge.end.0 {
+                for col in merge.start.1..=merge.end.1 {
+                    if in_range(row, col) {
+                        continue;
+                    }
+                    cells.push(CellInfo {
+                        address: format!("{}{}", col_to_letter(col as usize), row + 1),
+                        value: String::new(),
+                        formula: None,
+                        cross_sheet: false,
+                        is_merged: true,
+                        merge_anchor: Some(merge.anchor_address()),
                     });
                 }
             }
@@ -228,64 +936,344 @@ pub async fn get_formula_map(
         
         total_formula_count += formula_count;
         
+        let mut referenced: Vec<(String, usize)> = refs_per_sheet.into_iter().collect();
+        referenced.sort();
+        for (target, count) in &referenced {
+            dependencies.push(SheetDependency {
+                from_sheet: sheet_name.clone(),
+                to_sheet: target.clone(),
+                formula_count: *count,
+            });
+        }
+        
         sheets.push(SheetFormulaMap {
             sheet_name: sheet_name.clone(),
             cells,
             formula_count,
             functions_used: functions_set.into_iter().collect(),
+            referenced_sheets: referenced.into_iter().map(|(target, _)| target).collect(),
         });
     }
     
+    let pivot_count = count_pivot_tables(&full_path);
+    
     Ok(FormulaMapResult {
         sheets,
+        dependencies,
         total_formula_count,
-        has_pivot: false, // Would need deeper inspection
+        has_pivot: pivot_count > 0,
+        pivot_count,
         hidden_sheets: vec![], // Would need workbook metadata
+        formulas_supported,
+        sheet_errors,
+    })
+}
+
+/// A cell holding the same normalized formula in both workbooks
+#[derive(Serialize, Debug)]
+pub struct MatchingFormulaCell {
+    pub sheet: String,
+    pub address: String,
+    pub formula: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FormulaComparison {
+    /// Jaccard similarity of (sheet, cell, formula) triples: same formula in the same place
+    pub similarity: f64,
+    /// Jaccard similarity of the formulas alone, ignoring where they sit
+    pub formula_set_similarity: f64,
+    pub formula_count_a: usize,
+    pub formula_count_b: usize,
+    pub matching_cells: Vec<MatchingFormulaCell>,
+}
+
+/// Canonical form of a formula for comparison: `$` anchors and whitespace are
+/// dropped and everything outside string literals is upper-cased
+fn normalize_formula(formula: &str) -> String {
+    let mut out = String::with_capacity(formula.len());
+    let mut in_string = false;
+    for c in formula.chars() {
+        if c == '"' {
+            in_string = !in_string;
+            out.push(c);
+        } else if in_string {
+            out.push(c);
+        } else if c != '$' && !c.is_whitespace() {
+            out.extend(c.to_uppercase());
+        }
+    }
+    out.trim_start_matches('=').to_string()
+}
+
+/// (lower-cased sheet name, cell address) -> (sheet name, normalized formula)
+type FormulaFingerprint = HashMap<(String, String), (String, String)>;
+
+/// Normalized formulas of every sheet in the workbook
+fn formula_fingerprint(excel: &mut Workbook) -> Result<FormulaFingerprint, String> {
+    let mut fingerprint = HashMap::new();
+    for sheet_name in excel.sheet_names().to_vec() {
+        let (formulas, _) = sheet_formulas(excel, &sheet_name)?;
+        let (start_row, start_col) = formulas.start().unwrap_or((0, 0));
+        for (row, col, formula) in formulas.used_cells() {
+            if formula.is_empty() {
+                continue;
+            }
+            let address = format!("{}{}", col_to_letter(start_col as usize + col), start_row as usize + row + 1);
+            fingerprint.insert(
+                (sheet_name.to_lowercase(), address),
+                (sheet_name.clone(), normalize_formula(formula)),
+            );
+        }
+    }
+    Ok(fingerprint)
+}
+
+fn jaccard(intersection: usize, a: usize, b: usize) -> f64 {
+    let union = a + b - intersection;
+    if union == 0 { 0.0 } else { intersection as f64 / union as f64 }
+}
+
+/// Compare the formulas of the same workbook in two submissions, ignoring `$`
+/// anchors, spacing and case, to surface copied work that was reformatted
+#[tauri::command]
+pub async fn compare_workbook_formulas(
+    db: State<'_, DbState>,
+    submission_a: String,
+    submission_b: String,
+    file_path: String,
+) -> Result<FormulaComparison, String> {
+    let pool = db.pool()?;
+    let mut fingerprints = Vec::new();
+    for submission_id in [&submission_a, &submission_b] {
+        let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
+            .bind(submission_id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Submission not found")?;
+        let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
+        let mut excel = open_workbook(&full_path)?;
+        fingerprints.push(formula_fingerprint(&mut excel)?);
+    }
+    let (a, b) = (&fingerprints[0], &fingerprints[1]);
+
+    let mut matching_cells: Vec<MatchingFormulaCell> = a
+        .iter()
+        .filter(|(key, (_, formula))| b.get(*key).is_some_and(|(_, other)| other == formula))
+        .map(|((_, address), (sheet, formula))| MatchingFormulaCell {
+            sheet: sheet.clone(),
+            address: address.clone(),
+            formula: formula.clone(),
+        })
+        .collect();
+    matching_cells.sort_by(|x, y| {
+        let (xc, xr) = parse_cell_ref(&x.address).unwrap_or((0, 0));
+        let (yc, yr) = parse_cell_ref(&y.address).unwrap_or((0, 0));
+        x.sheet.cmp(&y.sheet).then(xr.cmp(&yr)).then(xc.cmp(&yc))
+    });
+
+    // Multiset overlap of the formulas themselves
+    let mut counts_a: HashMap<&str, usize> = HashMap::new();
+    for (_, formula) in a.values() {
+        *counts_a.entry(formula.as_str()).or_insert(0) += 1;
+    }
+    let mut shared = 0;
+    for (_, formula) in b.values() {
+        if let Some(n) = counts_a.get_mut(formula.as_str()).filter(|n| **n > 0) {
+            *n -= 1;
+            shared += 1;
+        }
+    }
+
+    Ok(FormulaComparison {
+        similarity: jaccard(matching_cells.len(), a.len(), b.len()),
+        formula_set_similarity: jaccard(shared, a.len(), b.len()),
+        formula_count_a: a.len(),
+        formula_count_b: b.len(),
+        matching_cells,
     })
 }
 
+/// Convert a 0-indexed column to its letter form (0 -> A, 25 -> Z, 26 -> AA, 702 -> AAA).
+/// Column letters are bijective base-26, so work on the 1-indexed value.
 fn col_to_letter(col: usize) -> String {
-    let mut result = String::new();
-    let mut n = col;
-    loop {
-        result.insert(0, (b'A' + (n % 26) as u8) as char);
-        if n < 26 {
-            break;
-        }
-        n = n / 26 - 1;
-    }
-    result
-}
-
-fn extract_functions(formula: &str, functions: &mut std::collections::HashSet<String>) {
-    // Simple regex-like extraction of function names
-    let common_functions = [
-        "SUM", "SUMIF", "SUMIFS", "AVERAGE", "AVERAGEIF", "AVERAGEIFS",
-        "COUNT", "COUNTIF", "COUNTIFS", "COUNTA", "COUNTBLANK",
-        "IF", "IFS", "IFERROR", "IFNA",
-        "VLOOKUP", "HLOOKUP", "XLOOKUP", "INDEX", "MATCH",
-        "MAX", "MIN", "MAXIFS", "MINIFS",
-        "LEFT", "RIGHT", "MID", "LEN", "TRIM", "SUBSTITUTE", "CONCATENATE", "TEXTJOIN",
-        "DATE", "YEAR", "MONTH", "DAY", "TODAY", "NOW",
-        "ROUND", "ROUNDUP", "ROUNDDOWN", "ABS",
-        "AND", "OR", "NOT",
-        "FILTER", "SORT", "UNIQUE", "SEQUENCE",
-    ];
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Collect the names of functions called in a formula: identifiers directly
+/// followed by `(`, ignoring anything inside string literals or quoted sheet names
+fn extract_functions(formula: &str, functions: &mut HashSet<String>) {
+    let mut token = String::new();
+    let mut chars = formula.chars().peekable();
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                token.clear();
+                // Skip to the closing quote; a doubled quote is an escape
+                while let Some(q) = chars.next() {
+                    if q == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '(' => {
+                if token.starts_with(|f: char| f.is_alphabetic() || f == '_') {
+                    functions.insert(normalize_function_name(&token));
+                }
+                token.clear();
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' => token.push(c),
+            _ => token.clear(),
+        }
+    }
+}
+
+/// Names of the sheets a formula references, e.g. `Sheet2!A1` or
+/// `'Data Sheet'!B2` (quoted names may contain spaces and doubled quotes).
+/// External workbook prefixes like `[1]` are dropped; string literals are skipped.
+fn referenced_sheets(formula: &str) -> Vec<String> {
+    let mut sheets: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut chars = formula.chars().peekable();
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                token.clear();
+                while let Some(q) = chars.next() {
+                    if q == '"' {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\'' => {
+                // Quoted sheet name; '' inside is a literal quote
+                token.clear();
+                while let Some(q) = chars.next() {
+                    if q == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                            token.push('\'');
+                        } else {
+                            break;
+                        }
+                    } else {
+                        token.push(q);
+                    }
+                }
+                if chars.peek() != Some(&'!') {
+                    token.clear();
+                }
+            }
+            '!' => {
+                let name = match token.rfind(']') {
+                    Some(i) => &token[i + 1..],
+                    None => token.as_str(),
+                };
+                if !name.is_empty() && !sheets.iter().any(|s| s == name) {
+                    sheets.push(name.to_string());
+                }
+                token.clear();
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '[' || c == ']' => token.push(c),
+            _ => token.clear(),
+        }
+    }
     
-    let upper = formula.to_uppercase();
-    for func in common_functions {
-        if upper.contains(&format!("{}(", func)) {
-            functions.insert(func.to_string());
+    sheets
+}
+
+/// Upper-case a function name and drop the `_xlfn.` / `_xlws.` prefixes
+/// Excel stores for functions newer than the file format (e.g. XLOOKUP).
+/// They can be stacked, as in `_xlfn._xlws.FILTER`.
+fn normalize_function_name(name: &str) -> String {
+    let upper = name.trim().to_uppercase();
+    let mut stripped = upper.as_str();
+    while let Some(rest) = stripped.strip_prefix("_XLFN.").or_else(|| stripped.strip_prefix("_XLWS.")) {
+        stripped = rest;
+    }
+    stripped.to_string()
+}
+
+/// Every function used by any formula in the workbook, plus the sheets
+/// whose formulas couldn't be read and so weren't searched
+fn workbook_functions(excel: &mut Workbook, cache: &mut SheetCache) -> (HashSet<String>, Vec<SheetError>) {
+    let mut functions = HashSet::new();
+    let mut errors = Vec::new();
+    for sheet_name in excel.sheet_names().to_vec() {
+        if let Err(e) = cache.load_formulas(excel, &sheet_name) {
+            errors.push(e);
+            continue;
+        }
+        let (formulas, _) = &cache.formulas[&sheet_name];
+        for f in formulas.used_cells().map(|(_, _, f)| f).filter(|f| !f.is_empty()) {
+            extract_functions(f, &mut functions);
         }
     }
+    (functions, errors)
 }
 
 #[derive(Serialize, serde::Deserialize)]
 pub struct RangeCheck {
-    pub range: String,       // e.g., "D2:D25"
+    #[serde(default)]
+    pub range: String,       // e.g., "D2:D25", or a workbook-defined name; unused by sheet-wide checks
     pub sheet: Option<String>,
-    pub check_type: String,  // "must_have_formulas", "must_be_numeric", etc.
+    pub check_type: String,  // "must_have_formulas", "flag_hardcoded_numbers", "cell_equals", "range_has_data_validation", "range_has_conditional_formatting", "range_number_format", etc.
     pub description: String,
+    #[serde(default)]
+    pub functions: Vec<String>, // Required function names for "must_use_functions"
+    #[serde(default)]
+    pub expected: Option<serde_json::Value>, // Expected value for "cell_equals" (range is the cell)
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    #[serde(default)]
+    pub min_count: Option<usize>, // Charts required by "must_have_chart" (default 1)
+    #[serde(default)]
+    pub chart_type: Option<String>, // Only count charts of this type, e.g. "line"
+    #[serde(default)]
+    pub format_kind: Option<NumberFormatKind>, // Required format for "range_number_format"
+    #[serde(default)]
+    pub decimals: Option<u32>, // Decimal places "range_number_format" also requires, if set
+}
+
+/// Compare a cell's cached value to the expected one: numbers within
+/// `tolerance`, text exactly (ignoring surrounding whitespace), booleans exactly
+fn cell_matches(actual: Option<&Data>, expected: &serde_json::Value, tolerance: f64) -> bool {
+    let Some(actual) = actual else { return false };
+    match expected {
+        serde_json::Value::Number(n) => {
+            let actual_num = match actual {
+                Data::Float(f) => Some(*f),
+                Data::Int(i) => Some(*i as f64),
+                Data::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            };
+            match (actual_num, n.as_f64()) {
+                (Some(a), Some(e)) => (a - e).abs() <= tolerance.abs(),
+                _ => false,
+            }
+        }
+        serde_json::Value::Bool(b) => matches!(actual, Data::Bool(a) if a == b),
+        serde_json::Value::String(e) => actual.to_string().trim() == e.trim(),
+        _ => false,
+    }
 }
 
 #[derive(Serialize)]
@@ -296,42 +1284,473 @@ pub struct RangeCheckResult {
     pub details: String,
 }
 
+static DEFINED_NAME: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"<definedName\b[^>]*\bname="([^"]+)"[^>]*>([^<]+)</definedName>"#).unwrap()
+});
+
+/// Workbook-defined names (upper-cased, since Excel names are case-insensitive)
+/// mapped to `(sheet, area)` with `$` anchors removed, read from the
+/// `<definedNames>` in `xl/workbook.xml`. Multi-area names are skipped.
+fn defined_names(path: &Path) -> HashMap<String, (String, String)> {
+    let mut names = HashMap::new();
+    let Some(xml) = read_package_part(path, "xl/workbook.xml") else {
+        return names;
+    };
+
+    for caps in DEFINED_NAME.captures_iter(&xml) {
+        let value = unescape_xml(&caps[2]);
+        if value.contains(',') {
+            continue;
+        }
+        let Some((sheet, area)) = value.rsplit_once('!') else { continue };
+        let sheet = match sheet.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+            Some(quoted) => quoted.replace("''", "'"),
+            None => sheet.to_string(),
+        };
+        names
+            .entry(unescape_xml(&caps[1]).to_uppercase())
+            .or_insert((sheet, area.replace('$', "")));
+    }
+    names
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&apos;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Rewrite a check whose range is a defined name into its sheet and A1 area
+fn resolve_named_range(mut check: RangeCheck, names: &HashMap<String, (String, String)>) -> RangeCheck {
+    if let Some((sheet, area)) = names.get(&check.range.trim().to_uppercase()) {
+        check.sheet = Some(sheet.clone());
+        check.range = if check.check_type == "cell_equals" {
+            area.split(':').next().unwrap_or(area).to_string()
+        } else if area.contains(':') {
+            area.clone()
+        } else {
+            format!("{0}:{0}", area)
+        };
+    }
+    check
+}
+
+/// Check every sheet name and range up front so a rubric typo fails loudly
+/// instead of grading the wrong sheet or stopping halfway through
+fn validate_checks(checks: &[RangeCheck], sheet_names: &[String]) -> Result<(), String> {
+    let mut problems = Vec::new();
+    for (i, check) in checks.iter().enumerate() {
+        if matches!(check.check_type.as_str(), "must_have_pivot" | "must_use_functions" | "must_have_chart") {
+            continue;
+        }
+        if let Some(sheet) = &check.sheet {
+            if !sheet_names.contains(sheet) {
+                problems.push(format!("Check {} ({}): sheet '{}' not found", i + 1, check.check_type, sheet));
+            }
+        }
+        let parsed = if check.check_type == "cell_equals" {
+            parse_cell_ref(&check.range).map(|_| ())
+        } else if check.check_type == "has_frozen_panes" || check.check_type == "gridlines_hidden" {
+            // Sheet-wide; the range is ignored
+            Ok(())
+        } else {
+            parse_range(&check.range).map(|_| ())
+        };
+        if let Err(e) = parsed {
+            problems.push(format!("Check {} ({}): {}", i + 1, check.check_type, e));
+        }
+        if check.check_type == "range_number_format" && check.format_kind.is_none() {
+            problems.push(format!("Check {} ({}): format_kind is required", i + 1, check.check_type));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid formula checks:\n{}", problems.join("\n")))
+    }
+}
+
+/// Per-sheet formulas and values, each parsed at most once per workbook
+#[derive(Default)]
+struct SheetCache {
+    formulas: HashMap<String, (Range<String>, bool)>,
+    values: HashMap<String, Range<Data>>,
+}
+
+impl SheetCache {
+    fn load_formulas(&mut self, excel: &mut Workbook, sheet_name: &str) -> Result<(), SheetError> {
+        if !self.formulas.contains_key(sheet_name) {
+            let formulas = sheet_formulas(excel, sheet_name)?;
+            self.formulas.insert(sheet_name.to_string(), formulas);
+        }
+        Ok(())
+    }
+
+    fn load_values(&mut self, excel: &mut Workbook, sheet_name: &str) -> Result<(), SheetError> {
+        if !self.values.contains_key(sheet_name) {
+            let values = sheet_values(excel, sheet_name)?;
+            self.values.insert(sheet_name.to_string(), values);
+        }
+        Ok(())
+    }
+
+    fn values(&mut self, excel: &mut Workbook, sheet_name: &str) -> Result<&Range<Data>, SheetError> {
+        self.load_values(excel, sheet_name)?;
+        Ok(&self.values[sheet_name])
+    }
+}
+
 /// Run rubric-linked formula checks on specified ranges
 #[tauri::command]
 pub async fn run_formula_checks(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     file_path: String,
     checks: Vec<RangeCheck>,
 ) -> Result<Vec<RangeCheckResult>, String> {
+    let pool = db.pool()?;
     let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
         .bind(&submission_id)
-        .fetch_one(&*pool)
+        .fetch_one(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
-    let full_path = Path::new(&folder_path).join(&file_path);
-    
-    if !full_path.exists() {
-        return Err("File not found".to_string());
-    }
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
 
-    let file = File::open(&full_path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let mut excel: Xlsx<BufReader<File>> = Xlsx::new(reader).map_err(|e| e.to_string())?;
+    let mut excel = open_workbook(&full_path)?;
+    
+    let sheet_names = excel.sheet_names().to_vec();
+    
+    // Named ranges are resolved to coordinates; results still report the name
+    let names = defined_names(&full_path);
+    let requested_ranges: Vec<String> = checks.iter().map(|c| c.range.clone()).collect();
+    let checks: Vec<RangeCheck> = checks.into_iter().map(|c| resolve_named_range(c, &names)).collect();
+    validate_checks(&checks, &sheet_names)?;
     
     let mut results = Vec::new();
+    let mut cache = SheetCache::default();
+    let default_sheet = sheet_names.first().cloned().unwrap_or_default();
+    let pivot_count = count_pivot_tables(&full_path);
+    let mut used_functions: Option<(HashSet<String>, Vec<SheetError>)> = None;
+    let mut sheet_xml: Option<HashMap<String, String>> = None;
+    let mut charts: Option<Vec<ChartInfo>> = None;
+    let mut style_formats: Option<Vec<String>> = None;
     
     for check in checks {
-        let sheet_name = check.sheet.clone().unwrap_or_else(|| {
-            excel.sheet_names().first().cloned().unwrap_or_default()
-        });
+        // Workbook-level checks don't look at a range
+        if check.check_type == "must_have_pivot" {
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: pivot_count > 0,
+                details: format!("{} pivot table(s) found", pivot_count),
+            });
+            continue;
+        }
+        
+        if check.check_type == "must_have_chart" {
+            let charts = charts.get_or_insert_with(|| workbook_charts(&full_path));
+            let min_count = check.min_count.unwrap_or(1);
+            let wanted = check.chart_type.as_deref().map(|t| t.trim().to_lowercase());
+            // "bar" is accepted for column charts too, since people use the words interchangeably
+            let matching = charts
+                .iter()
+                .filter(|c| match wanted.as_deref() {
+                    None => true,
+                    Some("bar") => c.types.iter().any(|t| t == "bar" || t == "column"),
+                    Some(w) => c.types.iter().any(|t| t == w),
+                })
+                .count();
+            let found: Vec<String> = charts
+                .iter()
+                .map(|c| if c.types.is_empty() { "unknown".to_string() } else { c.types.join("+") })
+                .collect();
+            let details = format!(
+                "{} matching chart(s), {} required{}",
+                matching,
+                min_count,
+                if found.is_empty() { String::new() } else { format!(" (found: {})", found.join(", ")) }
+            );
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: matching >= min_count,
+                details,
+            });
+            continue;
+        }
+        
+        if check.check_type == "must_use_functions" {
+            if used_functions.is_none() {
+                used_functions = Some(workbook_functions(&mut excel, &mut cache));
+            }
+            let (used, unreadable) = used_functions.as_ref().unwrap();
+            
+            let missing: Vec<String> = check.functions.iter()
+                .map(|f| normalize_function_name(f))
+                .filter(|f| !used.contains(f))
+                .collect();
+            
+            let mut details = if missing.is_empty() {
+                format!("All {} required function(s) used", check.functions.len())
+            } else {
+                format!("Missing function(s): {}", missing.join(", "))
+            };
+            if !unreadable.is_empty() {
+                let skipped: Vec<String> = unreadable.iter().map(|e| e.to_string()).collect();
+                details = format!("{} (not searched: {})", details, skipped.join("; "));
+            }
+            
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: missing.is_empty(),
+                details,
+            });
+            continue;
+        }
+        
+        let sheet_name = check.sheet.clone().unwrap_or_else(|| default_sheet.clone());
+        
+        // View settings come from the sheet XML alone, so they don't need calamine
+        if check.check_type == "has_frozen_panes" || check.check_type == "gridlines_hidden" {
+            let sheet_xml = sheet_xml.get_or_insert_with(|| worksheet_xml(&full_path));
+            let view = sheet_xml.get(&sheet_name).map(|xml| sheet_view(xml));
+            let (passed, details) = match (check.check_type.as_str(), view) {
+                (_, None) => (false, format!("Sheet view for '{}' unavailable for this workbook format", sheet_name)),
+                ("has_frozen_panes", Some(view)) => match view.frozen {
+                    Some((rows, cols)) => (
+                        rows > 0 || cols > 0,
+                        format!(
+                            "Frozen: {} row(s), {} column(s){}",
+                            rows,
+                            cols,
+                            view.top_left_cell.map(|c| format!(", scrolling from {}", c)).unwrap_or_default()
+                        ),
+                    ),
+                    None => (false, "No frozen panes".to_string()),
+                },
+                (_, Some(view)) => (
+                    !view.show_gridlines,
+                    if view.show_gridlines { "Gridlines shown" } else { "Gridlines hidden" }.to_string(),
+                ),
+            };
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed,
+                details,
+            });
+            continue;
+        }
+        
+        // A sheet calamine can't read fails the checks on it, not the whole run
+        let loaded = if check.check_type == "cell_equals" {
+            cache.load_values(&mut excel, &sheet_name)
+        } else {
+            cache.load_formulas(&mut excel, &sheet_name).and_then(|_| {
+                let needs_values = matches!(check.check_type.as_str(), "flag_hardcoded_numbers" | "range_number_format");
+                if needs_values || parse_range(&check.range).is_ok_and(|s| s.is_open()) {
+                    cache.load_values(&mut excel, &sheet_name)
+                } else {
+                    Ok(())
+                }
+            })
+        };
+        if let Err(e) = loaded {
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: false,
+                details: format!("Could not run check: {}", e),
+            });
+            continue;
+        }
         
-        let formulas = excel.worksheet_formula(&sheet_name)
-            .map_err(|e| e.to_string())?;
+        if check.check_type == "cell_equals" {
+            let (col, row) = parse_cell_ref(&check.range)?;
+            // A cell covered by a merge shows its anchor's value
+            let sheet_xml = sheet_xml.get_or_insert_with(|| worksheet_xml(&full_path));
+            let (row, col) = sheet_xml
+                .get(&sheet_name)
+                .and_then(|xml| merged_areas(xml).into_iter().find(|m| m.contains(row, col)))
+                .map_or((row, col), |m| m.start);
+            let values = cache.values(&mut excel, &sheet_name)?;
+            let actual = values.get_value((row, col));
+            let actual_text = actual.map(|v| v.to_string()).filter(|v| !v.is_empty()).unwrap_or_else(|| "(empty)".to_string());
+            
+            let (passed, details) = match &check.expected {
+                Some(expected) => {
+                    let tolerance = check.tolerance.unwrap_or(0.0);
+                    let expected_text = match expected {
+                        serde_json::Value::String(e) => e.clone(),
+                        other => other.to_string(),
+                    };
+                    let tolerance_text = if tolerance > 0.0 { format!(" ± {}", tolerance) } else { String::new() };
+                    (
+                        cell_matches(actual, expected, tolerance),
+                        format!("{}!{}: expected {}{}, actual {}", sheet_name, check.range, expected_text, tolerance_text, actual_text),
+                    )
+                }
+                None => (false, "No expected value configured".to_string()),
+            };
+            
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed,
+                details,
+            });
+            continue;
+        }
+        
+        // Parse range like "D2:D25" (or an open one like "D:D")
+        let spec = parse_range(&check.range)?;
+        
+        if check.check_type == "range_number_format" {
+            let Some(kind) = check.format_kind else {
+                results.push(RangeCheckResult {
+                    range: check.range,
+                    check_type: check.check_type,
+                    passed: false,
+                    details: "No format_kind configured".to_string(),
+                });
+                continue;
+            };
+            let values = &cache.values[&sheet_name];
+            let (start_row, start_col, end_row, end_col) = spec.bounded(values.start().zip(values.end()));
+            let style_formats = style_formats.get_or_insert_with(|| cell_style_formats(&full_path));
+            let sheet_xml = sheet_xml.get_or_insert_with(|| worksheet_xml(&full_path));
+            let styles = sheet_xml.get(&sheet_name).map(|xml| cell_style_indexes(xml)).unwrap_or_default();
+            
+            // Only cells holding a value are judged; blank cells show no format
+            let mut checked = 0;
+            let mut wrong = Vec::new();
+            for row in start_row..=end_row {
+                for col in start_col..=end_col {
+                    if values.get_value((row, col)).is_none_or(|v| matches!(v, Data::Empty)) {
+                        continue;
+                    }
+                    checked += 1;
+                    let code = styles
+                        .get(&(row, col))
+                        .and_then(|&i| style_formats.get(i))
+                        .map_or("General", String::as_str);
+                    let (actual_kind, actual_decimals) = classify_number_format(code);
+                    let conforms = actual_kind == Some(kind) && check.decimals.is_none_or(|d| d == actual_decimals);
+                    if !conforms {
+                        wrong.push(format!("{}{} ({})", col_to_letter(col as usize), row + 1, code));
+                    }
+                }
+            }
+            
+            let wanted = match check.decimals {
+                Some(d) => format!("{:?} with {} decimal(s)", kind, d).to_lowercase(),
+                None => format!("{:?}", kind).to_lowercase(),
+            };
+            let details = if checked == 0 {
+                "No values in range".to_string()
+            } else if wrong.is_empty() {
+                format!("All {} cell(s) formatted as {}", checked, wanted)
+            } else {
+                format!("{}/{} cell(s) not formatted as {}: {}", wrong.len(), checked, wanted, wrong.join(", "))
+            };
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: checked > 0 && wrong.is_empty(),
+                details,
+            });
+            continue;
+        }
+        
+        if check.check_type == "range_has_conditional_formatting" {
+            let used = cache.values.get(&sheet_name).and_then(|v| v.start().zip(v.end()));
+            let (start_row, start_col, end_row, end_col) = spec.bounded(used);
+            let target = CellArea { start: (start_row, start_col), end: (end_row, end_col) };
+            let sheet_xml = sheet_xml.get_or_insert_with(|| worksheet_xml(&full_path));
+            let formats = sheet_xml.get(&sheet_name).map(|xml| conditional_formats(xml)).unwrap_or_default();
+            
+            // Several blocks (and several rules per block) can cover the same cells
+            let overlapping: Vec<&ConditionalFormat> = formats
+                .iter()
+                .filter(|f| f.areas.iter().any(|a| a.overlaps(&target)))
+                .collect();
+            let details = if overlapping.is_empty() {
+                format!("No conditional formatting applies to {}", target.address())
+            } else {
+                let rules: Vec<String> = overlapping
+                    .iter()
+                    .map(|f| {
+                        let applied: Vec<String> = f.areas.iter().map(|a| a.address()).collect();
+                        format!("{} on {}", f.rule_types.join("+"), applied.join(" "))
+                    })
+                    .collect();
+                let rule_count: usize = overlapping.iter().map(|f| f.rule_types.len()).sum();
+                format!("{} rule(s) overlap {}: {}", rule_count, target.address(), rules.join("; "))
+            };
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: !overlapping.is_empty(),
+                details,
+            });
+            continue;
+        }
+        
+        if check.check_type == "range_has_data_validation" {
+            let used = cache.values.get(&sheet_name).and_then(|v| v.start().zip(v.end()));
+            let (start_row, start_col, end_row, end_col) = spec.bounded(used);
+            let sheet_xml = sheet_xml.get_or_insert_with(|| worksheet_xml(&full_path));
+            let validations = sheet_xml.get(&sheet_name).map(|xml| data_validations(xml)).unwrap_or_default();
+            
+            let mut kinds = Vec::new();
+            let mut missing = Vec::new();
+            let mut total_cells = 0;
+            for row in start_row..=end_row {
+                for col in start_col..=end_col {
+                    total_cells += 1;
+                    match validations.iter().find(|v| v.areas.iter().any(|a| a.contains(row, col))) {
+                        Some(v) => {
+                            if !kinds.contains(&v.kind) {
+                                kinds.push(v.kind.clone());
+                            }
+                        }
+                        None => missing.push(format!("{}{}", col_to_letter(col as usize), row + 1)),
+                    }
+                }
+            }
+            
+            let validated = total_cells - missing.len();
+            let details = if missing.is_empty() {
+                format!("{}/{} cells have data validation ({})", validated, total_cells, kinds.join(", "))
+            } else {
+                format!("{}/{} cells have data validation; missing: {}", validated, total_cells, missing.join(", "))
+            };
+            results.push(RangeCheckResult {
+                range: check.range,
+                check_type: check.check_type,
+                passed: total_cells > 0 && missing.is_empty(),
+                details,
+            });
+            continue;
+        }
+        
+        cache.load_formulas(&mut excel, &sheet_name)?;
+        if check.check_type == "flag_hardcoded_numbers" || spec.is_open() {
+            cache.load_values(&mut excel, &sheet_name)?;
+        }
+        let used = cache.values.get(&sheet_name).and_then(|v| v.start().zip(v.end()));
+        let (start_row, start_col, end_row, end_col) = spec.bounded(used);
+        let (formulas, formulas_supported) = &cache.formulas[&sheet_name];
         
-        // Parse range like "D2:D25"
-        let (start_row, start_col, end_row, end_col) = parse_range(&check.range)?;
+        // Addressed absolutely: calamine ranges start at the first used cell,
+        // and non-formula cells come back as empty strings
+        let has_formula = |row: u32, col: u32| {
+            formulas.get_value((row, col)).is_some_and(|f| !f.is_empty())
+        };
         
         let mut formula_count = 0;
         let mut total_cells = 0;
@@ -339,13 +1758,31 @@ pub async fn run_formula_checks(
         for row in start_row..=end_row {
             for col in start_col..=end_col {
                 total_cells += 1;
-                if formulas.get((row as usize, col as usize)).is_some() {
+                if has_formula(row, col) {
                     formula_count += 1;
                 }
             }
         }
         
         let (passed, details) = match check.check_type.as_str() {
+            "flag_hardcoded_numbers" => {
+                // Numeric literals typed (or pasted) where a formula was expected
+                let values = &cache.values[&sheet_name];
+                let mut hardcoded = Vec::new();
+                for row in start_row..=end_row {
+                    for col in start_col..=end_col {
+                        let is_number = matches!(values.get_value((row, col)), Some(Data::Float(_)) | Some(Data::Int(_)));
+                        if is_number && !has_formula(row, col) {
+                            hardcoded.push(format!("{}{}", col_to_letter(col as usize), row + 1));
+                        }
+                    }
+                }
+                if hardcoded.is_empty() {
+                    (true, "No hardcoded numbers found".to_string())
+                } else {
+                    (false, format!("{} hardcoded number(s): {}", hardcoded.len(), hardcoded.join(", ")))
+                }
+            },
             "must_have_formulas" => {
                 let ratio = formula_count as f64 / total_cells as f64;
                 (ratio >= 0.8, format!("{}/{} cells have formulas ({:.0}%)", formula_count, total_cells, ratio * 100.0))
@@ -358,6 +1795,11 @@ pub async fn run_formula_checks(
             },
             _ => (true, "Unknown check type".to_string()),
         };
+        let details = if *formulas_supported {
+            details
+        } else {
+            format!("{} (formulas unavailable for this workbook format)", details)
+        };
         
         results.push(RangeCheckResult {
             range: check.range,
@@ -367,46 +1809,890 @@ pub async fn run_formula_checks(
         });
     }
     
+    for (result, requested) in results.iter_mut().zip(requested_ranges) {
+        result.range = requested;
+    }
+    
     Ok(results)
 }
 
-fn parse_range(range: &str) -> Result<(u32, u32, u32, u32), String> {
-    // Parse "D2:D25" into (row_start, col_start, row_end, col_end)
+/// A parsed range. An endpoint may omit its row (`D:D`, `D2:D`) or its
+/// column (`2:5`); those sides are filled from the sheet's used range.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RangeSpec {
+    start_row: Option<u32>,
+    start_col: Option<u32>,
+    end_row: Option<u32>,
+    end_col: Option<u32>,
+}
+
+impl RangeSpec {
+    fn is_open(&self) -> bool {
+        self.start_row.is_none() || self.start_col.is_none() || self.end_row.is_none() || self.end_col.is_none()
+    }
+
+    /// (row_start, col_start, row_end, col_end), with open sides bounded by
+    /// the used range `used` = ((first_row, first_col), (last_row, last_col))
+    fn bounded(&self, used: Option<((u32, u32), (u32, u32))>) -> (u32, u32, u32, u32) {
+        // An empty sheet leaves an open range with no cells
+        let ((first_row, first_col), (last_row, last_col)) = used.unwrap_or(((1, 1), (0, 0)));
+        (
+            self.start_row.unwrap_or(first_row),
+            self.start_col.unwrap_or(first_col),
+            self.end_row.unwrap_or(last_row),
+            self.end_col.unwrap_or(last_col),
+        )
+    }
+}
+
+/// Last column (XFD) and row of an xlsx sheet, 0-based
+const MAX_COL: u32 = 16_383;
+const MAX_ROW: u32 = 1_048_575;
+
+/// 0-based index of ASCII column letters (A=0, Z=25, AA=26), or None past XFD
+fn column_index(letters: &str) -> Option<u32> {
+    letters
+        .chars()
+        .try_fold(0u32, |acc, c| acc.checked_mul(26)?.checked_add(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1))?
+        .checked_sub(1)
+        .filter(|&col| col <= MAX_COL)
+}
+
+/// Parse one side of a range: `D2`, `$D$2`, `D` (column only) or `2` (row only)
+fn parse_range_endpoint(part: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let cleaned = part.trim().replace('$', "");
+    let split = cleaned.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(cleaned.len());
+    let (letters, digits) = cleaned.split_at(split);
+    
+    if (letters.is_empty() && digits.is_empty()) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid cell reference: {}", part));
+    }
+    
+    let col = match letters {
+        "" => None,
+        l => Some(column_index(l).ok_or_else(|| format!("Column is beyond XFD: {}", part))?),
+    };
+    let row = match digits {
+        "" => None,
+        d => match d.parse::<u32>() {
+            Ok(n) if n > 0 && n - 1 <= MAX_ROW => Some(n - 1),
+            _ => return Err(format!("Invalid row number: {}", part)),
+        },
+    };
+    Ok((col, row))
+}
+
+pub(crate) fn parse_range(range: &str) -> Result<RangeSpec, String> {
+    // Parse "D2:D25", "D:D", "2:5" or "D2:D"
     let parts: Vec<&str> = range.split(':').collect();
     if parts.len() != 2 {
         return Err(format!("Invalid range format: {}", range));
     }
     
-    let (start_col, start_row) = parse_cell_ref(parts[0])?;
-    let (end_col, end_row) = parse_cell_ref(parts[1])?;
+    let (start_col, start_row) = parse_range_endpoint(parts[0])?;
+    let (end_col, end_row) = parse_range_endpoint(parts[1])?;
     
-    Ok((start_row, start_col, end_row, end_col))
+    // A column-only side can't pair with a row-only side (e.g. "D:5")
+    let col_only = |c: Option<u32>, r: Option<u32>| c.is_some() && r.is_none();
+    let row_only = |c: Option<u32>, r: Option<u32>| c.is_none() && r.is_some();
+    if (col_only(start_col, start_row) && row_only(end_col, end_row))
+        || (row_only(start_col, start_row) && col_only(end_col, end_row))
+    {
+        return Err(format!("Invalid range format: {}", range));
+    }
+    
+    Ok(RangeSpec { start_row, start_col, end_row, end_col })
 }
 
-fn parse_cell_ref(cell: &str) -> Result<(u32, u32), String> {
-    let mut col_part = String::new();
-    let mut row_part = String::new();
-    
-    for c in cell.chars() {
-        if c.is_alphabetic() {
-            col_part.push(c.to_ascii_uppercase());
-        } else if c.is_numeric() {
-            row_part.push(c);
+/// Parse a single cell like `B7` or `$B$7` into 0-based (column, row)
+pub(crate) fn parse_cell_ref(cell: &str) -> Result<(u32, u32), String> {
+    match parse_range_endpoint(cell)? {
+        (Some(col), Some(row)) => Ok((col, row)),
+        _ => Err(format!("Invalid cell reference: {}", cell)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{fixture, TestDb};
+    use rust_xlsxwriter::{Chart, ChartType, ConditionalFormatCell, ConditionalFormatCellRule, Formula, Workbook};
+    use tauri::Manager;
+
+    /// A submission whose folder is the fixtures directory
+    async fn fixture_submission(db: &TestDb) -> String {
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        db.submission(&assignment, None, &fixture("")).await
+    }
+
+    fn checks(value: serde_json::Value) -> Vec<RangeCheck> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Inputs lists three amounts, a tax formula for each and their total;
+    /// Summary reads Inputs and Data Sheet through formulas. Formulas carry
+    /// cached results, as they would in a workbook saved by Excel.
+    fn budget_workbook() -> Workbook {
+        let mut workbook = Workbook::new();
+        let inputs = workbook.add_worksheet().set_name("Inputs").unwrap();
+        for (col, header) in ["Item", "Amount", "Tax", "Status"].into_iter().enumerate() {
+            inputs.write(0, col as u16, header).unwrap();
         }
+        for (row, item, amount, status) in [(1, "Rent", 100.0, "Open"), (2, "Food", 250.0, "Closed"), (3, "Fuel", 50.0, "Open")] {
+            inputs.write(row, 0, item).unwrap();
+            inputs.write(row, 1, amount).unwrap();
+            let tax = Formula::new(format!("B{}*B6", row + 1)).set_result((amount / 10.0).to_string());
+            inputs.write_formula(row, 2, tax).unwrap();
+            inputs.write(row, 3, status).unwrap();
+        }
+        inputs.write(4, 0, "Total").unwrap();
+        inputs.write_formula(4, 1, Formula::new("SUM(B2:B4)").set_result("400")).unwrap();
+        // Typed in rather than computed
+        inputs.write(4, 2, 40.0).unwrap();
+        inputs.write(5, 0, "Rate").unwrap();
+        inputs.write(5, 1, 0.1).unwrap();
+
+        let summary = workbook.add_worksheet().set_name("Summary").unwrap();
+        for (row, formula, result) in [
+            (0, "Inputs!B5", "400"),
+            (1, r#"_xlfn.XLOOKUP("Food",Inputs!A2:A4,Inputs!B2:B4)"#, "250"),
+            (2, r#"IF(A1>0,"MAX(",0)"#, "MAX("),
+            (3, "_xlfn._xlws.FILTER(Inputs!B2:B4,Inputs!B2:B4>60)", "250"),
+            (4, "'Data Sheet'!A1", "7"),
+        ] {
+            summary.write_formula(row, 0, Formula::new(formula).set_result(result)).unwrap();
+        }
+
+        workbook.add_worksheet().set_name("Data Sheet").unwrap().write(0, 0, 7.0).unwrap();
+        workbook
     }
-    
-    if col_part.is_empty() || row_part.is_empty() {
-        return Err(format!("Invalid cell reference: {}", cell));
+
+    /// Save `workbook` as work.xlsx in a fresh submission folder, which lives
+    /// as long as the returned directory
+    async fn workbook_submission(db: &TestDb, workbook: &mut Workbook) -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        workbook.save(dir.path().join("work.xlsx")).unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, None, dir.path()).await;
+        (dir, submission)
     }
-    
-    // Convert column letters to index (A=0, B=1, ..., Z=25, AA=26, etc.)
-    let mut col_idx = 0u32;
-    for c in col_part.chars() {
-        col_idx = col_idx * 26 + (c as u32 - 'A' as u32 + 1);
+
+    #[test]
+    fn opens_legacy_xls() {
+        let mut excel = open_workbook(&fixture("legacy.xls")).unwrap();
+        assert_eq!(excel.sheet_names(), ["Data", "Notes"]);
+        let values = sheet_values(&mut excel, "Data").unwrap();
+        assert_eq!(values.get_value((2, 0)), Some(&Data::Float(250.0)));
+    }
+
+    #[tokio::test]
+    async fn formula_map_reads_legacy_xls() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = fixture_submission(&db).await;
+
+        let map = get_formula_map(app.state(), submission, "legacy.xls".into()).await.unwrap();
+        assert_eq!(map.total_formula_count, 2);
+        let data = map.sheets.iter().find(|s| s.sheet_name == "Data").unwrap();
+        let a4 = data.cells.iter().find(|c| c.address == "A4").unwrap();
+        assert_eq!(a4.formula.as_deref(), Some("SUM($A$2:$A$3)"));
+        assert_eq!(a4.value, "350");
+        assert!(data.functions_used.contains(&"SUM".to_string()));
+    }
+
+    #[tokio::test]
+    async fn analysis_counts_pivot_tables() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = fixture_submission(&db).await;
+
+        let analysis = analyze_excel(app.state(), submission.clone(), "pivot.xlsx".into()).await.unwrap();
+        assert!(analysis.has_pivot);
+        assert_eq!(analysis.pivot_count, 1);
+        // The pivot cache definition is a different part and isn't counted
+        assert_eq!(count_pivot_tables(&fixture("pivot.xlsx")), 1);
+
+        let legacy = analyze_excel(app.state(), submission, "legacy.xls".into()).await.unwrap();
+        assert!(!legacy.has_pivot);
+        assert_eq!(legacy.pivot_count, 0);
+    }
+
+    #[tokio::test]
+    async fn must_use_functions_sees_prefixed_names() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "must_use_functions", "description": "", "functions": ["sum", "XLOOKUP", "_xlfn.FILTER"] },
+            // MAX only appears inside a string literal
+            { "check_type": "must_use_functions", "description": "", "functions": ["MAX", "IF"] },
+        ])))
+        .await
+        .unwrap();
+        assert!(results[0].passed, "{}", results[0].details);
+        assert!(!results[1].passed);
+        assert_eq!(results[1].details, "Missing function(s): MAX");
+    }
+
+    #[test]
+    fn stacked_prefixes_are_stripped() {
+        assert_eq!(normalize_function_name("_xlfn._xlws.filter"), "FILTER");
+        assert_eq!(normalize_function_name("_xlws._xlfn.SORT"), "SORT");
+        assert_eq!(normalize_function_name(" xlookup "), "XLOOKUP");
+    }
+
+    #[test]
+    fn functions_are_tokenized() {
+        let found = |formula: &str| {
+            let mut functions = HashSet::new();
+            extract_functions(formula, &mut functions);
+            let mut sorted: Vec<String> = functions.into_iter().collect();
+            sorted.sort();
+            sorted
+        };
+        assert_eq!(found("=IF(SUM(A1:A3)>0,ROUND(A4,2),0)"), ["IF", "ROUND", "SUM"]);
+        assert_eq!(found("=_xlfn.STDEV.S(B2:B9)+log10(C1)"), ["LOG10", "STDEV.S"]);
+        // Text in string literals and quoted sheet names isn't a call
+        assert_eq!(found(r#"=CONCAT("He said ""MAX("" ", 'Q1 (draft)'!A1)"#), ["CONCAT"]);
+        assert_eq!(found("=(A1+B2)*(3)"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn column_letters_past_z() {
+        let cases = [(0, "A"), (25, "Z"), (26, "AA"), (51, "AZ"), (52, "BA"), (701, "ZZ"), (702, "AAA")];
+        for (col, letters) in cases {
+            assert_eq!(col_to_letter(col), letters, "column {}", col);
+            assert_eq!(column_index(letters), Some(col as u32));
+        }
+    }
+
+    #[tokio::test]
+    async fn analysis_detects_macros() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let submission = fixture_submission(&db).await;
+
+        let macros = analyze_excel(app.state(), submission.clone(), "macros.xlsm".into()).await.unwrap();
+        assert!(macros.has_macros);
+        assert_eq!(macros.macro_module_count, Some(3));
+
+        let (_dir, plain) = workbook_submission(&db, &mut budget_workbook()).await;
+        let plain = analyze_excel(app.state(), plain, "work.xlsx".into()).await.unwrap();
+        assert!(!plain.has_macros);
+        assert_eq!(plain.macro_module_count, None);
+    }
+
+    #[tokio::test]
+    async fn analysis_counts_formulas_on_every_sheet() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+        std::fs::copy(fixture("legacy.xls"), dir.path().join("legacy.xls")).unwrap();
+
+        // Four on Inputs, five on Summary, none on Data Sheet
+        let analysis = analyze_excel(app.state(), submission.clone(), "work.xlsx".into()).await.unwrap();
+        assert_eq!(analysis.sheets, ["Inputs", "Summary", "Data Sheet"]);
+        assert_eq!(analysis.formulas_count, 9);
+        let legacy = analyze_excel(app.state(), submission, "legacy.xls".into()).await.unwrap();
+        assert_eq!(legacy.formulas_count, 2);
+    }
+
+    #[test]
+    fn sheet_references_handle_quotes_and_external_links() {
+        assert_eq!(referenced_sheets("'It''s Q1'!A1+[1]Rates!B2+Rates!C3"), ["It's Q1", "Rates"]);
+        assert!(referenced_sheets(r#"IF(A1>0,"See Notes!A1",B2)"#).is_empty());
+    }
+
+    #[tokio::test]
+    async fn formula_map_reports_sheet_dependencies() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        let map = get_formula_map(app.state(), submission, "work.xlsx".into()).await.unwrap();
+        let summary = map.sheets.iter().find(|s| s.sheet_name == "Summary").unwrap();
+        assert_eq!(summary.referenced_sheets, ["Data Sheet", "Inputs"]);
+        let a3 = summary.cells.iter().find(|c| c.address == "A3").unwrap();
+        assert!(!a3.cross_sheet);
+        assert!(summary.cells.iter().find(|c| c.address == "A5").unwrap().cross_sheet);
+
+        let dependencies: Vec<_> = map.dependencies.iter().map(|d| (d.from_sheet.as_str(), d.to_sheet.as_str(), d.formula_count)).collect();
+        assert_eq!(dependencies, [("Summary", "Data Sheet", 1), ("Summary", "Inputs", 3)]);
+    }
+
+    #[tokio::test]
+    async fn cell_equals_compares_cached_values() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "cell_equals", "sheet": "Inputs", "range": "B5", "description": "", "expected": 400 },
+            { "check_type": "cell_equals", "sheet": "Inputs", "range": "$B$5", "description": "", "expected": 400.4, "tolerance": 0.5 },
+            { "check_type": "cell_equals", "sheet": "Inputs", "range": "B5", "description": "", "expected": 401 },
+            { "check_type": "cell_equals", "sheet": "Inputs", "range": "D3", "description": "", "expected": " Closed " },
+            { "check_type": "cell_equals", "sheet": "Inputs", "range": "B5", "description": "" },
+        ])))
+        .await
+        .unwrap();
+        let passed: Vec<bool> = results.iter().map(|r| r.passed).collect();
+        assert_eq!(passed, [true, true, false, true, false]);
+        assert_eq!(results[1].details, "Inputs!$B$5: expected 400.4 ± 0.5, actual 400");
+        assert_eq!(results[4].details, "No expected value configured");
+    }
+
+    #[tokio::test]
+    async fn hardcoded_numbers_are_flagged_by_cell() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "flag_hardcoded_numbers", "sheet": "Inputs", "range": "C2:C5", "description": "" },
+            { "check_type": "flag_hardcoded_numbers", "sheet": "Summary", "range": "A1:A5", "description": "" },
+        ])))
+        .await
+        .unwrap();
+        assert!(!results[0].passed);
+        assert_eq!(results[0].details, "1 hardcoded number(s): C5");
+        assert!(results[1].passed, "{}", results[1].details);
+    }
+
+    #[tokio::test]
+    async fn each_sheet_is_parsed_once_per_run() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+        let check = |check_type: &str, range: &str| serde_json::json!({ "check_type": check_type, "sheet": "Inputs", "range": range, "description": "" });
+
+        // tokio tests run on one thread, so the counter only sees this test
+        let before = SHEET_PARSES.with(|n| n.get());
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            check("must_have_formulas", "C2:C4"),
+            check("all_formulas", "B2:B5"),
+            check("flag_hardcoded_numbers", "C2:C5"),
+            check("cell_equals", "B5"),
+            check("no_formulas", "D2:D4"),
+        ])))
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 5);
+        // Formulas and values of Inputs, once each
+        assert_eq!(SHEET_PARSES.with(|n| n.get()) - before, 2);
+    }
+
+    #[tokio::test]
+    async fn bad_sheets_and_ranges_fail_the_whole_run() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        let outcome = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "must_have_formulas", "sheet": "Inputs", "range": "C2:C4", "description": "" },
+            { "check_type": "must_have_formulas", "sheet": "Input", "range": "C2:C4", "description": "" },
+            { "check_type": "all_formulas", "sheet": "Inputs", "range": "C2-C4", "description": "" },
+            { "check_type": "cell_equals", "range": "B", "description": "", "expected": 1 },
+            { "check_type": "range_number_format", "range": "B2:B4", "description": "" },
+            { "check_type": "must_have_pivot", "sheet": "Nowhere", "description": "" },
+        ])))
+        .await;
+        let Err(error) = outcome else { panic!("checks ran despite invalid entries") };
+        assert_eq!(error, [
+            "Invalid formula checks:",
+            "Check 2 (must_have_formulas): sheet 'Input' not found",
+            "Check 3 (all_formulas): Invalid range format: C2-C4",
+            "Check 4 (cell_equals): Invalid cell reference: B",
+            "Check 5 (range_number_format): format_kind is required",
+        ]
+        .join("\n"));
+    }
+
+    #[tokio::test]
+    async fn named_ranges_resolve_to_their_sheet() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        workbook.define_name("Amounts", "=Inputs!$B$2:$B$4").unwrap();
+        workbook.define_name("GrandTotal", "=Inputs!$B$5").unwrap();
+        let (_dir, submission) = workbook_submission(&db, &mut workbook).await;
+
+        // The names point at Inputs whatever sheet the check itself names
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "no_formulas", "sheet": "Summary", "range": "amounts", "description": "" },
+            { "check_type": "cell_equals", "range": "GrandTotal", "description": "", "expected": 400 },
+            { "check_type": "all_formulas", "range": "GrandTotal", "description": "" },
+        ])))
+        .await
+        .unwrap();
+        assert!(results.iter().all(|r| r.passed), "{:?}", results.iter().map(|r| &r.details).collect::<Vec<_>>());
+        assert_eq!(results[0].range, "amounts");
+        assert_eq!(results[0].details, "0 cells have formulas (expected 0)");
+        assert_eq!(results[2].details, "1/1 cells have formulas");
+    }
+
+    #[tokio::test]
+    async fn open_ranges_stop_at_the_used_range() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+
+        // Inputs uses A1:D6
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "all_formulas", "sheet": "Inputs", "range": "C:C", "description": "" },
+            { "check_type": "all_formulas", "sheet": "Inputs", "range": "C2:C", "description": "" },
+            { "check_type": "all_formulas", "sheet": "Inputs", "range": "5:5", "description": "" },
+            { "check_type": "no_formulas", "sheet": "Data Sheet", "range": "A:XFD", "description": "" },
+        ])))
+        .await
+        .unwrap();
+        let details: Vec<&str> = results.iter().map(|r| r.details.as_str()).collect();
+        assert_eq!(details, [
+            "3/6 cells have formulas",
+            "3/5 cells have formulas",
+            "1/4 cells have formulas",
+            "0 cells have formulas (expected 0)",
+        ]);
+        assert!(parse_range("D:5").is_err());
+        assert!(parse_range("5:D").is_err());
+    }
+
+    #[test]
+    fn formulas_normalize_outside_string_literals() {
+        assert_eq!(normalize_formula("=sum($a$1 : A2, \"Keep $ Me\")"), "SUM(A1:A2,\"Keep $ Me\")");
+    }
+
+    #[tokio::test]
+    async fn reformatted_formulas_still_match() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut submissions = Vec::new();
+        for (student, formulas) in [
+            ("a", &[(1, "=$A$1*2"), (2, "=SUM(A1:A2)"), (3, "=A1+1")][..]),
+            ("b", &[(1, "=a1 * 2"), (2, "=SUM( A1:A2 )"), (4, "=A1+1"), (5, "=A1-1")][..]),
+        ] {
+            let mut workbook = rust_xlsxwriter::Workbook::new();
+            let sheet = workbook.add_worksheet();
+            sheet.write_number(0, 0, 1).unwrap();
+            for (row, formula) in formulas {
+                sheet.write_formula(*row, 0, *formula).unwrap();
+            }
+            std::fs::create_dir_all(dir.path().join(student)).unwrap();
+            workbook.save(dir.path().join(student).join("work.xlsx")).unwrap();
+            submissions.push(db.submission(&assignment, Some(student), &dir.path().join(student)).await);
+        }
+
+        let comparison = compare_workbook_formulas(app.state(), submissions[0].clone(), submissions[1].clone(), "work.xlsx".into())
+            .await
+            .unwrap();
+        let cells: Vec<(&str, &str)> = comparison.matching_cells.iter().map(|c| (c.address.as_str(), c.formula.as_str())).collect();
+        assert_eq!(cells, [("A2", "A1*2"), ("A3", "SUM(A1:A2)")]);
+        assert_eq!((comparison.formula_count_a, comparison.formula_count_b), (3, 4));
+        // Two of five distinct placements agree; three of four formulas appear in both
+        assert_eq!((comparison.similarity, comparison.formula_set_similarity), (0.4, 0.75));
+    }
+
+    #[tokio::test]
+    async fn csv_rosters_handle_quotes_bom_and_semicolons() {
+        let parse = |name: &str| parse_csv_roster(fixture(name).to_string_lossy().to_string());
+        let quoted = parse("roster_quoted.csv").await.unwrap();
+        // The byte-order mark must not end up in the first header
+        assert_eq!(quoted.headers, ["Student ID", "Name", "Email"]);
+        assert_eq!(quoted.data.len(), 2);
+        assert_eq!(quoted.data[0]["Name"], "Lovelace, Ada");
+        assert_eq!(quoted.data[1]["Name"], "Grace \"Amazing\" Hopper");
+        assert_eq!(quoted.data[1]["Email"], "");
+
+        let semicolon = parse("roster_semicolon.csv").await.unwrap();
+        assert_eq!(semicolon.headers, ["SIS ID", "Student Name", "Section"]);
+        let sections: Vec<(&str, &str)> = semicolon.data.iter().map(|r| (r["SIS ID"].as_str(), r["Section"].as_str())).collect();
+        assert_eq!(sections, [("12345678", "A1"), ("87654321", "B2")]);
+
+        assert_eq!(detect_csv_delimiter("id\tname\tnote, with comma"), b'\t');
+        assert_eq!(detect_csv_delimiter("\"a;b\",c"), b',');
+        assert_eq!(parse("missing.csv").await.err().as_deref(), Some("File not found"));
+    }
+
+    /// Rewrite the xlsx at `path` with `part` cut off in the middle of its third row
+    fn break_sheet_xml(path: &Path, part: &str) {
+        use std::io::{Read, Write};
+        let mut source = ZipArchive::new(std::io::Cursor::new(std::fs::read(path).unwrap())).unwrap();
+        let mut broken = zip::ZipWriter::new(File::create(path).unwrap());
+        for i in 0..source.len() {
+            let mut entry = source.by_index(i).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if entry.name() == part {
+                let row = String::from_utf8_lossy(&data).find(r#"<row r="3""#).unwrap();
+                data.truncate(row + 8);
+            }
+            broken.start_file(entry.name(), zip::write::FileOptions::default()).unwrap();
+            broken.write_all(&data).unwrap();
+        }
+        broken.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn one_unreadable_sheet_does_not_hide_the_others() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (dir, submission) = workbook_submission(&db, &mut budget_workbook()).await;
+        break_sheet_xml(&dir.path().join("work.xlsx"), "xl/worksheets/sheet2.xml");
+
+        // Summary's XML breaks off mid-row; Inputs and Data Sheet are intact
+        let map = get_formula_map(app.state(), submission.clone(), "work.xlsx".into()).await.unwrap();
+        let names: Vec<&str> = map.sheets.iter().map(|s| s.sheet_name.as_str()).collect();
+        assert_eq!(names, ["Inputs", "Data Sheet"]);
+        assert_eq!(map.total_formula_count, 4);
+        assert_eq!(map.sheet_errors.len(), 1);
+        let error = &map.sheet_errors[0];
+        assert_eq!((error.sheet_name.as_str(), error.operation.as_str()), ("Summary", "reading formulas"));
+        assert!(error.to_string().starts_with("reading formulas in sheet 'Summary': "), "{}", error);
+
+        let analysis = analyze_excel(app.state(), submission.clone(), "work.xlsx".into()).await.unwrap();
+        assert_eq!(analysis.sheets, ["Inputs", "Summary", "Data Sheet"]);
+        assert_eq!(analysis.formulas_count, 4);
+        let names: Vec<&str> = analysis.sheet_errors.iter().map(|e| e.sheet_name.as_str()).collect();
+        assert_eq!(names, ["Summary"]);
+
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            { "check_type": "must_have_formulas", "sheet": "Summary", "range": "A1:A5", "description": "" },
+            { "check_type": "must_have_formulas", "sheet": "Inputs", "range": "C2:C4", "description": "" },
+            { "check_type": "must_use_functions", "description": "", "functions": ["SUM"] },
+        ])))
+        .await
+        .unwrap();
+        assert!(!results[0].passed);
+        assert!(results[0].details.starts_with("Could not run check: reading formulas in sheet 'Summary': "), "{}", results[0].details);
+        assert!(results[1].passed, "{}", results[1].details);
+        assert!(results[2].passed);
+        assert!(results[2].details.starts_with("All 1 required function(s) used (not searched: reading formulas in sheet 'Summary': "));
+    }
+
+    #[tokio::test]
+    async fn merged_cells_point_at_their_anchor() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let inputs = workbook.worksheet_from_name("Inputs").unwrap();
+        inputs.merge_range(7, 0, 7, 2, "Merged note", &rust_xlsxwriter::Format::new()).unwrap();
+        let (_dir, submission) = workbook_submission(&db, &mut workbook).await;
+
+        // Inputs merges A8:C8; D8 is outside the merge and empty, so it isn't listed
+        let map = get_formula_map(app.state(), submission, "work.xlsx".into()).await.unwrap();
+        let inputs = map.sheets.iter().find(|s| s.sheet_name == "Inputs").unwrap();
+        let row_8: Vec<_> = inputs
+            .cells
+            .iter()
+            .filter(|c| c.address.ends_with('8'))
+            .map(|c| (c.address.as_str(), c.value.as_str(), c.is_merged, c.merge_anchor.as_deref()))
+            .collect();
+        assert_eq!(row_8, [
+            ("A8", "Merged note", true, Some("A8")),
+            ("B8", "", true, Some("A8")),
+            ("C8", "", true, Some("A8")),
+        ]);
+        let b5 = inputs.cells.iter().find(|c| c.address == "B5").unwrap();
+        assert!(!b5.is_merged && b5.merge_anchor.is_none());
+    }
+
+    #[test]
+    fn merge_areas_are_read_from_the_sheet_xml() {
+        let xml = r#"<mergeCells count="2"><mergeCell ref="A1:C1"/><mergeCell ref="$E$10:F12"/></mergeCells>"#;
+        let areas: Vec<_> = merged_areas(xml).iter().map(|a| (a.start, a.end, a.anchor_address())).collect();
+        assert_eq!(areas, [((0, 0), (0, 2), "A1".to_string()), ((9, 4), (11, 5), "E10".to_string())]);
+        assert!(merged_areas("<sheetData/>").is_empty());
+    }
+
+    #[tokio::test]
+    async fn data_validation_is_checked_cell_by_cell() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let statuses = rust_xlsxwriter::DataValidation::new().allow_list_strings(&["Open", "Closed"]).unwrap();
+        workbook.worksheet_from_name("Inputs").unwrap().add_data_validation(1, 3, 3, 3, &statuses).unwrap();
+        let (_dir, submission) = workbook_submission(&db, &mut workbook).await;
+        let check = |range: &str| serde_json::json!({ "check_type": "range_has_data_validation", "sheet": "Inputs", "range": range, "description": "" });
+
+        // Inputs has a list dropdown on D2:D4 only
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            check("D2:D4"),
+            check("C2:D3"),
+            check("A1:A2"),
+        ])))
+        .await
+        .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.passed, r.details.as_str())).collect();
+        assert_eq!(outcome, [
+            (true, "3/3 cells have data validation (list)"),
+            (false, "2/4 cells have data validation; missing: C2, C3"),
+            (false, "0/2 cells have data validation; missing: A1, A2"),
+        ]);
+    }
+
+    #[test]
+    fn data_validations_include_the_x14_extension() {
+        let xml = r#"<dataValidations count="1"><dataValidation type="whole" sqref="A1:A3 C1"/></dataValidations>
+            <extLst><ext><x14:dataValidations count="1"><x14:dataValidation type="list" allowBlank="1">
+            <x14:formula1><xm:f>Lists!$A$1:$A$3</xm:f></x14:formula1><xm:sqref>B2:B9</xm:sqref>
+            </x14:dataValidation></x14:dataValidations></ext></extLst>"#;
+        let found: Vec<_> = data_validations(xml)
+            .iter()
+            .map(|v| (v.kind.clone(), v.areas.iter().map(|a| a.address()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(found, [
+            ("whole".to_string(), vec!["A1:A3".to_string(), "C1".to_string()]),
+            ("list".to_string(), vec!["B2:B9".to_string()]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn charts_are_counted_and_typed() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let mut combo = Chart::new(ChartType::Column);
+        combo.add_series().set_values("Inputs!$B$2:$B$4");
+        let mut tax = Chart::new(ChartType::Line);
+        tax.add_series().set_values("Inputs!$C$2:$C$4");
+        combo.combine(&tax);
+        let mut pie = Chart::new(ChartType::Pie);
+        pie.add_series().set_values("Inputs!$B$2:$B$4");
+        let summary = workbook.worksheet_from_name("Summary").unwrap();
+        summary.insert_chart(1, 3, &combo).unwrap();
+        summary.insert_chart(20, 3, &pie).unwrap();
+        let (dir, submission) = workbook_submission(&db, &mut workbook).await;
+        budget_workbook().save(dir.path().join("plain.xlsx")).unwrap();
+
+        // chart1 is a column + line combo, chart2 a pie
+        let analysis = analyze_excel(app.state(), submission.clone(), "work.xlsx".into()).await.unwrap();
+        assert_eq!(analysis.chart_count, 2);
+        let charts: Vec<_> = analysis.charts.iter().map(|c| (c.part.as_str(), c.types.join("+"))).collect();
+        assert_eq!(charts, [("xl/charts/chart1.xml", "column+line".to_string()), ("xl/charts/chart2.xml", "pie".to_string())]);
+
+        let check = |chart_type: Option<&str>, min_count: usize| {
+            serde_json::json!({ "check_type": "must_have_chart", "description": "", "chart_type": chart_type, "min_count": min_count })
+        };
+        let results = run_formula_checks(app.state(), submission.clone(), "work.xlsx".into(), checks(serde_json::json!([
+            check(None, 2),
+            check(Some("bar"), 1),
+            check(Some("Line"), 2),
+            check(Some("scatter"), 1),
+        ])))
+        .await
+        .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.passed, r.details.as_str())).collect();
+        assert_eq!(outcome, [
+            (true, "2 matching chart(s), 2 required (found: column+line, pie)"),
+            (true, "1 matching chart(s), 1 required (found: column+line, pie)"),
+            (false, "1 matching chart(s), 2 required (found: column+line, pie)"),
+            (false, "0 matching chart(s), 1 required (found: column+line, pie)"),
+        ]);
+
+        let none = run_formula_checks(app.state(), submission, "plain.xlsx".into(), checks(serde_json::json!([check(None, 1)])))
+            .await
+            .unwrap();
+        assert_eq!((none[0].passed, none[0].details.as_str()), (false, "0 matching chart(s), 1 required"));
+    }
+
+    #[tokio::test]
+    async fn conditional_formatting_is_found_where_it_overlaps() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let large = ConditionalFormatCell::new()
+            .set_rule(ConditionalFormatCellRule::GreaterThan(200))
+            .set_format(rust_xlsxwriter::Format::new().set_bold());
+        workbook.worksheet_from_name("Inputs").unwrap().add_conditional_format(1, 1, 3, 1, &large).unwrap();
+        let (_dir, submission) = workbook_submission(&db, &mut workbook).await;
+        let check = |range: &str| serde_json::json!({ "check_type": "range_has_conditional_formatting", "sheet": "Inputs", "range": range, "description": "" });
+
+        // Inputs highlights amounts over 200 in B2:B4
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            check("B3:B4"),
+            check("A1:B2"),
+            check("C2:C4"),
+        ])))
+        .await
+        .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.passed, r.details.as_str())).collect();
+        assert_eq!(outcome, [
+            (true, "1 rule(s) overlap B3:B4: cellIs on B2:B4"),
+            (true, "1 rule(s) overlap A1:B2: cellIs on B2:B4"),
+            (false, "No conditional formatting applies to C2:C4"),
+        ]);
+    }
+
+    #[test]
+    fn conditional_formats_count_linked_x14_rules_once() {
+        let xml = r#"<conditionalFormatting sqref="B2:B4 D2"><cfRule type="cellIs" priority="2"/>
+            <cfRule type="dataBar" priority="1"><extLst><ext><x14:id>{BAR}</x14:id></ext></extLst></cfRule></conditionalFormatting>
+            <conditionalFormatting sqref="B4:C4"><cfRule type="expression" priority="3"/></conditionalFormatting>
+            <extLst><ext><x14:conditionalFormattings>
+            <x14:conditionalFormatting><x14:cfRule type="dataBar" id="{BAR}"/><xm:sqref>B2:B4</xm:sqref></x14:conditionalFormatting>
+            <x14:conditionalFormatting><x14:cfRule type="iconSet" priority="4" id="{ICONS}"/><xm:sqref>E1:E5</xm:sqref></x14:conditionalFormatting>
+            </x14:conditionalFormattings></ext></extLst>"#;
+        let found: Vec<_> = conditional_formats(xml)
+            .iter()
+            .map(|f| (f.areas.iter().map(|a| a.address()).collect::<Vec<_>>().join(" "), f.rule_types.join("+")))
+            .collect();
+        assert_eq!(found, [
+            ("B2:B4 D2".to_string(), "cellIs+dataBar".to_string()),
+            ("B4:C4".to_string(), "expression".to_string()),
+            ("E1:E5".to_string(), "iconSet".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn number_formats_report_the_cells_that_differ() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let inputs = workbook.worksheet_from_name("Inputs").unwrap();
+        let currency = rust_xlsxwriter::Format::new().set_num_format(r##""$"#,##0.00"##);
+        inputs.set_range_format(1, 1, 4, 1, &currency).unwrap();
+        inputs.set_range_format(1, 2, 3, 2, &currency).unwrap();
+        inputs.set_range_format(4, 2, 4, 2, &rust_xlsxwriter::Format::new().set_num_format("0.00")).unwrap();
+        inputs.set_range_format(5, 1, 5, 1, &rust_xlsxwriter::Format::new().set_num_format("0.00%")).unwrap();
+        let (_dir, submission) = workbook_submission(&db, &mut workbook).await;
+        let check = |range: &str, kind: &str, decimals: Option<u32>| {
+            serde_json::json!({ "check_type": "range_number_format", "sheet": "Inputs", "range": range, "description": "", "format_kind": kind, "decimals": decimals })
+        };
+
+        // Amounts and tax are currency, except C5 ("0.00"); the rate in B6 is a percentage
+        let results = run_formula_checks(app.state(), submission, "work.xlsx".into(), checks(serde_json::json!([
+            check("B2:B4", "currency", Some(2)),
+            check("B2:C6", "currency", None),
+            check("B6:C6", "percent", None),
+            check("B2:B3", "currency", Some(0)),
+            check("A1:A2", "integer", None),
+            check("E1:E9", "date", None),
+        ])))
+        .await
+        .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.passed, r.details.as_str())).collect();
+        assert_eq!(outcome, [
+            (true, "All 3 cell(s) formatted as currency with 2 decimal(s)"),
+            (false, "2/9 cell(s) not formatted as currency: C5 (0.00), B6 (0.00%)"),
+            (true, "All 1 cell(s) formatted as percent"),
+            (false, r##"2/2 cell(s) not formatted as currency with 0 decimal(s): B2 ("$"#,##0.00), B3 ("$"#,##0.00)"##),
+            (false, "2/2 cell(s) not formatted as integer: A1 (General), A2 (General)"),
+            (false, "No values in range"),
+        ]);
+    }
+
+    #[test]
+    fn number_format_codes_are_classified() {
+        use NumberFormatKind::*;
+        let cases = [
+            ("[$€-407] #,##0.00", (Some(Currency), 2)),
+            ("#,##0.00 [$-409]", (None, 2)),
+            ("\\$#,##0;[Red]-\\$#,##0", (Some(Currency), 0)),
+            ("0.0%", (Some(Percent), 1)),
+            ("m/d/yyyy", (Some(Date), 0)),
+            ("[h]:mm:ss", (Some(Date), 0)),
+            ("#,##0_);(#,##0)", (Some(Integer), 0)),
+            ("0.00E+00", (None, 2)),
+            ("\"Total: \"0", (Some(Integer), 0)),
+            ("General", (None, 0)),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(classify_number_format(code), expected, "{}", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn frozen_panes_and_gridlines_come_from_the_sheet_view() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let mut workbook = budget_workbook();
+        let inputs = workbook.worksheet_from_name("Inputs").unwrap();
+        inputs.set_freeze_panes(1, 0).unwrap();
+        inputs.set_screen_gridlines(false);
+        let (dir, submission) = workbook_submission(&db, &mut workbook).await;
+        std::fs::copy(fixture("legacy.xls"), dir.path().join("legacy.xls")).unwrap();
+        let check = |check_type: &str, sheet: &str| serde_json::json!({ "check_type": check_type, "sheet": sheet, "description": "" });
+
+        // Inputs freezes its header row and hides gridlines; Summary keeps the defaults
+        let results = run_formula_checks(app.state(), submission.clone(), "work.xlsx".into(), checks(serde_json::json!([
+            check("has_frozen_panes", "Inputs"),
+            check("gridlines_hidden", "Inputs"),
+            check("has_frozen_panes", "Summary"),
+            check("gridlines_hidden", "Summary"),
+        ])))
+        .await
+        .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.passed, r.details.as_str())).collect();
+        assert_eq!(outcome, [
+            (true, "Frozen: 1 row(s), 0 column(s), scrolling from A2"),
+            (true, "Gridlines hidden"),
+            (false, "No frozen panes"),
+            (false, "Gridlines shown"),
+        ]);
+
+        let legacy = run_formula_checks(app.state(), submission, "legacy.xls".into(), checks(serde_json::json!([check("has_frozen_panes", "Data")])))
+            .await
+            .unwrap();
+        assert_eq!((legacy[0].passed, legacy[0].details.as_str()), (false, "Sheet view for 'Data' unavailable for this workbook format"));
+    }
+
+    #[test]
+    fn split_panes_that_scroll_are_not_frozen() {
+        let view = |xml: &str| {
+            let v = sheet_view(xml);
+            (v.frozen, v.top_left_cell, v.show_gridlines)
+        };
+        assert_eq!(
+            view(r#"<sheetView workbookViewId="0"><pane xSplit="2" ySplit="3" topLeftCell="C4" state="frozenSplit"/></sheetView>"#),
+            (Some((3, 2)), Some("C4".to_string()), true)
+        );
+        assert_eq!(
+            view(r#"<sheetView showGridLines="false"><pane xSplit="2400" ySplit="1200" state="split"/></sheetView>"#),
+            (None, None, false)
+        );
+        assert_eq!(view(r#"<sheetViews><sheetView workbookViewId="0"/></sheetViews>"#), (None, None, true));
+    }
+
+    #[tokio::test]
+    async fn workbooks_outside_the_submission_folder_are_refused() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("submission");
+        std::fs::create_dir_all(&folder).unwrap();
+        budget_workbook().save(dir.path().join("outside.xlsx")).unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, None, &folder).await;
+        let denied = Some("Access denied: path is outside the submission folder");
+
+        let analysis = analyze_excel(app.state(), submission.clone(), "../outside.xlsx".into()).await;
+        assert_eq!(analysis.err().as_deref(), denied);
+        let map = get_formula_map(app.state(), submission.clone(), "../outside.xlsx".into()).await;
+        assert_eq!(map.err().as_deref(), denied);
+        let check = checks(serde_json::json!([{ "range": "A1:A2", "check_type": "must_have_formulas", "description": "" }]));
+        let results = run_formula_checks(app.state(), submission.clone(), "../outside.xlsx".into(), check).await;
+        assert_eq!(results.err().as_deref(), denied);
+        let missing = analyze_excel(app.state(), submission, "gone.xlsx".into()).await;
+        assert_eq!(missing.err().as_deref(), Some("File not found"));
+    }
+
+    #[test]
+    fn xml_attributes_are_matched_by_whole_name() {
+        let sheet = r#"<sheet name="Data" sheetId="1" r:id="rId1"/>"#;
+        assert_eq!(xml_attr(sheet, "name").as_deref(), Some("Data"));
+        assert_eq!(xml_attr(sheet, "r:id").as_deref(), Some("rId1"));
+        assert_eq!(xml_attr(sheet, "id"), None);
+        assert_eq!(xml_attr(r#"<c r="E2" s="3">"#, "s").as_deref(), Some("3"));
+        assert_eq!(xml_attr(r#" sqref="A1 B2" type='list'"#, "type").as_deref(), Some("list"));
+        assert_eq!(xml_attr(r##"<numFmt numFmtId="164" formatCode="&quot;$&quot;#,##0"/>"##, "formatCode").as_deref(), Some(r##""$"#,##0"##));
+        // An attribute-like run inside another value isn't an attribute
+        assert_eq!(xml_attr(r#"<x note="a s=&quot;1" s="5"/>"#, "s").as_deref(), Some("5"));
+        assert_eq!(xml_attr("<pane/>", "state"), None);
     }
-    col_idx -= 1; // 0-indexed
-    
-    let row_idx: u32 = row_part.parse::<u32>().map_err(|_| "Invalid row number")? - 1; // 0-indexed
-    
-    Ok((col_idx, row_idx))
 }
@@ -1,9 +1,12 @@
-use tauri::{AppHandle, Manager, State};
-use crate::db::DbPool;
+use tauri::{Manager, State};
+use crate::db::{DbPool, DbState};
 use rust_xlsxwriter::*;
 use std::collections::HashMap;
 use crate::commands::Assignment;
 use sqlx::{FromRow, Error as SqlxError};
+use std::io::Write;
+use serde::Serialize;
+use crate::grading::Lateness;
 
 #[derive(Debug, FromRow)]
 struct ExportGrade {
@@ -18,29 +21,35 @@ struct ExportStudent {
     student_id: String,
     name: String,
     email: Option<String>, // Make email Option as per DB
+    section: Option<String>,
 }
 
-#[tauri::command]
-pub async fn export_gradebook(
-    app: AppHandle,
-    pool: State<'_, DbPool>,
-    assignment_id: String,
-    output_path: String,
-) -> Result<String, String> {
+/// Everything the exporters need for one assignment
+struct GradebookData {
+    assignment: Assignment,
+    questions: Vec<serde_json::Value>,
+    /// Rubric `scale`, if the total is normalized to a fixed number of points
+    scale: Option<f64>,
+    students: Vec<ExportStudent>,
+    grade_map: HashMap<(String, String), (Option<f64>, Option<String>)>,
+}
+
+async fn load_gradebook(pool: &DbPool, assignment_id: &str) -> Result<GradebookData, String> {
     let assignment = sqlx::query_as::<sqlx::Sqlite, Assignment>("SELECT * FROM assignments WHERE id = ?")
-        .bind(&assignment_id)
-        .fetch_one(&*pool)
+        .bind(assignment_id)
+        .fetch_one(pool)
         .await
         .map_err(|e| e.to_string())?;
 
-    let rubric_json = assignment.rubric_json.unwrap_or_else(|| "{}".to_string());
-    let rubric: serde_json::Value = serde_json::from_str(&rubric_json).unwrap_or(serde_json::json!({}));
-    let questions = rubric["questions"].as_array().unwrap_or(&vec![]).clone();
+    let rubric_json = assignment.rubric_json.as_deref().unwrap_or("{}");
+    let rubric: serde_json::Value = serde_json::from_str(rubric_json).unwrap_or(serde_json::json!({}));
+    let questions = rubric["questions"].as_array().cloned().unwrap_or_default();
+    let scale = rubric["scale"].as_f64();
 
     // Use sqlx::query_as instead of query! macro
-    let students = sqlx::query_as::<sqlx::Sqlite, ExportStudent>("SELECT student_id, name, email FROM students WHERE course_id = ? ORDER BY name")
+    let students = sqlx::query_as::<sqlx::Sqlite, ExportStudent>("SELECT student_id, name, email, section FROM students WHERE course_id = ? ORDER BY name")
         .bind(&assignment.course_id)
-        .fetch_all(&*pool)
+        .fetch_all(pool)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -50,51 +59,529 @@ pub async fn export_gradebook(
         FROM grades g
         JOIN submissions sub ON g.submission_id = sub.id
         WHERE sub.assignment_id = ?
+          AND sub.student_id IS NOT NULL
+          AND sub.superseded_by IS NULL
+          AND sub.deleted_at IS NULL
+        ORDER BY sub.version, sub.received_at, sub.id
         "#
     )
-    .bind(&assignment_id)
-    .fetch_all(&*pool)
+    .bind(assignment_id)
+    .fetch_all(pool)
     .await
     .map_err(|e| e.to_string())?;
 
+    // Only current versions are selected; should a student still have two, the
+    // ordering makes the most recent one win rather than whichever came back last
     let mut grade_map: HashMap<(String, String), (Option<f64>, Option<String>)> = HashMap::new();
     for g in raw_grades {
         grade_map.insert((g.student_id, g.question_id), (g.score, g.comment));
     }
 
+    Ok(GradebookData {
+        assignment,
+        questions,
+        scale,
+        students,
+        grade_map,
+    })
+}
+
+/// Write the gradebook xlsx. `low_score_threshold` (a fraction of each question's
+/// max points, e.g. 0.6) highlights question scores below it in red.
+/// `include_letter_grade` adds a Letter column when the course has a grade scale.
+#[tauri::command]
+pub async fn export_gradebook(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_path: String,
+    include_late_penalty: Option<bool>,
+    low_score_threshold: Option<f64>,
+    include_letter_grade: Option<bool>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    if let Some(t) = low_score_threshold {
+        if !(t > 0.0 && t <= 1.0) {
+            return Err("Low score threshold must be a fraction between 0 and 1".to_string());
+        }
+    }
+    let data = load_gradebook(&pool, &assignment_id).await?;
+    let late = if include_late_penalty.unwrap_or(false) {
+        Some(late_penalties(&pool, &assignment_id).await?)
+    } else {
+        None
+    };
+    let letters = if include_letter_grade.unwrap_or(false) {
+        crate::grading::load_grade_scale(&pool, &data.assignment.course_id).await?
+    } else {
+        None
+    };
+    let mut workbook = gradebook_workbook(&data, late.as_ref(), low_score_threshold, letters.as_deref())?;
+    workbook.save(&output_path).map_err(|e| e.to_string())?;
+
+    Ok(output_path)
+}
+
+/// Render the gradebook to a printable PDF by building the xlsx and converting it
+/// with LibreOffice, under the same concurrency limit and timeout as previews.
+#[tauri::command]
+pub async fn export_gradebook_pdf(
+    db: State<'_, DbState>,
+    limiter: State<'_, crate::soffice::SofficeLimiter>,
+    assignment_id: String,
+    output_path: String,
+    include_late_penalty: Option<bool>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let data = load_gradebook(&pool, &assignment_id).await?;
+    let late = if include_late_penalty.unwrap_or(false) {
+        Some(late_penalties(&pool, &assignment_id).await?)
+    } else {
+        None
+    };
+    let mut workbook = gradebook_workbook(&data, late.as_ref(), None, None)?;
+
+    let work_dir = std::env::temp_dir().join(format!("gradebook-pdf-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let result = async {
+        let xlsx_path = work_dir.join("gradebook.xlsx");
+        workbook.save(&xlsx_path).map_err(|e| e.to_string())?;
+        let binary = crate::soffice::configured_binary(&pool).await?;
+        let pdf_path = crate::soffice::convert_to_pdf(&limiter, &binary, &xlsx_path, &work_dir).await?;
+
+        let bytes = std::fs::read(&pdf_path).map_err(|e| e.to_string())?;
+        if !bytes.starts_with(b"%PDF-") {
+            return Err("LibreOffice did not produce a valid PDF".to_string());
+        }
+        // A copy rather than a rename: the temp dir may be on another filesystem
+        std::fs::write(&output_path, &bytes).map_err(|e| e.to_string())
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result?;
+
+    Ok(output_path)
+}
+
+/// Escape text for inclusion in HTML element content or attribute values
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Number without a trailing ".0" for whole values (8 rather than 8.0)
+fn format_points(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded.fract() == 0.0 { format!("{}", rounded as i64) } else { format!("{}", rounded) }
+}
+
+/// Write a standalone HTML feedback page for one submission: each rubric question
+/// with its description, score out of max and comment, then the total.
+/// Ungraded questions are listed as not graded and count as zero.
+#[tauri::command]
+pub async fn generate_feedback_report(
+    db: State<'_, DbState>,
+    submission_id: String,
+    output_path: String,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let html = feedback_html(&pool, &submission_id).await?;
+    std::fs::write(&output_path, html).map_err(|e| e.to_string())?;
+    Ok(output_path)
+}
+
+async fn feedback_html(pool: &DbPool, submission_id: &str) -> Result<String, String> {
+    let (title, rubric_json, student_id, student_name): (String, Option<String>, Option<String>, Option<String>) = sqlx::query_as(
+        r#"
+        SELECT a.title, a.rubric_json, sub.student_id, st.name
+        FROM submissions sub
+        JOIN assignments a ON sub.assignment_id = a.id
+        LEFT JOIN students st ON st.student_id = sub.student_id AND st.course_id = a.course_id
+        WHERE sub.id = ?
+        "#
+    )
+    .bind(submission_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+
+    let rubric: serde_json::Value = rubric_json
+        .as_deref()
+        .and_then(|j| serde_json::from_str(j).ok())
+        .unwrap_or(serde_json::json!({}));
+    let questions = rubric["questions"].as_array().cloned().unwrap_or_default();
+
+    let grades: HashMap<String, (Option<f64>, Option<String>)> = sqlx::query_as::<sqlx::Sqlite, (String, Option<f64>, Option<String>)>(
+        "SELECT question_id, score, comment FROM grades WHERE submission_id = ?"
+    )
+    .bind(submission_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .map(|(q, score, comment)| (q, (score, comment)))
+    .collect();
+
+    let student = match (&student_name, &student_id) {
+        (Some(name), Some(id)) => format!("{} ({})", name, id),
+        (None, Some(id)) => id.clone(),
+        _ => "Unmatched submission".to_string(),
+    };
+
+    let mut rows = String::new();
+    for q in &questions {
+        let q_id = q["question_id"].as_str().unwrap_or("");
+        let (score, comment) = grades.get(q_id).cloned().unwrap_or((None, None));
+        let max_pts = q["max_points"].as_f64().unwrap_or(0.0);
+        let score_cell = match score {
+            Some(v) => format!("{} / {}", format_points(v), format_points(max_pts)),
+            None => format!("<span class=\"ungraded\">Not graded</span> / {}", format_points(max_pts)),
+        };
+        let description = q["description"]
+            .as_str()
+            .filter(|d| !d.trim().is_empty())
+            .map(|d| format!("<div class=\"description\">{}</div>", escape_html(d)))
+            .unwrap_or_default();
+        let comment = comment
+            .filter(|c| !c.trim().is_empty())
+            .map(|c| escape_html(&c).replace('\n', "<br>"))
+            .unwrap_or_default();
+        rows.push_str(&format!(
+            "<tr><td><strong>{}</strong>{}</td><td class=\"score\">{}</td><td>{}</td></tr>\n",
+            escape_html(q["title"].as_str().unwrap_or("Question")),
+            description,
+            score_cell,
+            comment
+        ));
+    }
+
+    let total = crate::grading::final_score(&questions, rubric["scale"].as_f64(), |q_id| {
+        grades.get(q_id).and_then(|(score, _)| *score)
+    });
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} - Feedback</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; color: #222; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border: 1px solid #ccc; padding: 6px 10px; text-align: left; vertical-align: top; }}
+th {{ background: #d9e1f2; }}
+.score {{ white-space: nowrap; }}
+.description {{ color: #555; font-size: 0.9em; margin-top: 4px; }}
+.ungraded {{ color: #999; font-style: italic; }}
+.total {{ font-size: 1.2em; margin-top: 1em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>Feedback for {student}</p>
+<table>
+<tr><th>Question</th><th>Score</th><th>Comments</th></tr>
+{rows}</table>
+<p class="total"><strong>Total: {total} / {out_of}</strong></p>
+</body>
+</html>
+"#,
+        title = escape_html(&title),
+        student = escape_html(&student),
+        rows = rows,
+        total = format_points(total.total),
+        out_of = format_points(total.out_of),
+    );
+    Ok(html)
+}
+
+#[derive(Serialize)]
+pub struct FeedbackFile {
+    pub student_id: String,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct SkippedFeedback {
+    pub student_id: Option<String>,
+    pub submission_id: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct FeedbackBatch {
+    pub files: Vec<FeedbackFile>,
+    pub skipped: Vec<SkippedFeedback>,
+}
+
+/// Write a feedback page (`<student_id>.html`) into `output_dir` for every student
+/// whose current submission has at least one score. Ungraded, unmatched and
+/// missing submissions are listed as skipped.
+#[tauri::command]
+pub async fn generate_all_feedback_reports(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_dir: String,
+) -> Result<FeedbackBatch, String> {
+    let pool = db.pool()?;
+    let rows: Vec<(String, Option<String>, bool)> = sqlx::query_as(
+        r#"
+        SELECT sub.id, sub.student_id,
+            EXISTS (SELECT 1 FROM grades g WHERE g.submission_id = sub.id AND g.score IS NOT NULL)
+        FROM submissions sub
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+        ORDER BY sub.student_id
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let missing: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT st.student_id FROM students st
+        JOIN assignments a ON a.course_id = st.course_id
+        WHERE a.id = ? AND st.student_id NOT IN (
+            SELECT student_id FROM submissions
+            WHERE assignment_id = a.id AND student_id IS NOT NULL AND deleted_at IS NULL
+        )
+        ORDER BY st.student_id
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let out_dir = std::path::Path::new(&output_dir);
+    std::fs::create_dir_all(out_dir).map_err(|e| e.to_string())?;
+    let mut batch = FeedbackBatch { files: Vec::new(), skipped: Vec::new() };
+
+    for (submission_id, student_id, graded) in rows {
+        let Some(student_id) = student_id else {
+            batch.skipped.push(SkippedFeedback { student_id: None, submission_id: Some(submission_id), reason: "Unmatched".to_string() });
+            continue;
+        };
+        if !graded {
+            batch.skipped.push(SkippedFeedback { student_id: Some(student_id), submission_id: Some(submission_id), reason: "Not graded".to_string() });
+            continue;
+        }
+        // Student IDs become file names, so keep them to safe characters
+        let file_stem: String = student_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = out_dir.join(format!("{}.html", file_stem));
+        let html = feedback_html(&pool, &submission_id).await?;
+        std::fs::write(&path, html).map_err(|e| e.to_string())?;
+        batch.files.push(FeedbackFile { student_id, path: path.to_string_lossy().to_string() });
+    }
+    for student_id in missing {
+        batch.skipped.push(SkippedFeedback { student_id: Some(student_id), submission_id: None, reason: "No submission".to_string() });
+    }
+
+    Ok(batch)
+}
+
+#[derive(Serialize)]
+pub struct MailmergeSummary {
+    pub output_path: String,
+    pub written: usize,
+    /// Students left out because the roster has no email for them
+    pub skipped_no_email: usize,
+}
+
+/// Write a mail-merge CSV (`email, name, total_score, max_score, feedback_text`)
+/// with one row per roster student that has an email. `feedback_text` lists each
+/// question's score and comment on its own line; ungraded students get blank scores.
+#[tauri::command]
+pub async fn export_feedback_mailmerge(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_path: String,
+) -> Result<MailmergeSummary, String> {
+    let pool = db.pool()?;
+    let GradebookData { questions, scale, students, grade_map, .. } = load_gradebook(&pool, &assignment_id).await?;
+
+    let mut writer = csv::Writer::from_path(&output_path).map_err(|e| e.to_string())?;
+    writer
+        .write_record(["email", "name", "total_score", "max_score", "feedback_text"])
+        .map_err(|e| e.to_string())?;
+
+    let mut summary = MailmergeSummary { output_path: output_path.clone(), written: 0, skipped_no_email: 0 };
+    for s in &students {
+        let Some(email) = s.email.as_deref().map(str::trim).filter(|e| !e.is_empty()) else {
+            summary.skipped_no_email += 1;
+            continue;
+        };
+        let grade_of = |q_id: &str| grade_map.get(&(s.student_id.clone(), q_id.to_string()));
+        let score = crate::grading::final_score(&questions, scale, |q_id| grade_of(q_id).and_then(|(score, _)| *score));
+        let any_graded = questions.iter().any(|q| grade_of(q["question_id"].as_str().unwrap_or("")).is_some_and(|(score, _)| score.is_some()));
+
+        let feedback: Vec<String> = questions
+            .iter()
+            .filter_map(|q| {
+                let (score, comment) = grade_of(q["question_id"].as_str().unwrap_or(""))?;
+                let title = q["title"].as_str().unwrap_or("Question");
+                let max_pts = format_points(q["max_points"].as_f64().unwrap_or(0.0));
+                let points = score.map_or_else(|| format!("-/{}", max_pts), |v| format!("{}/{}", format_points(v), max_pts));
+                match comment.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+                    Some(c) => Some(format!("{} ({}): {}", title, points, c)),
+                    None => score.map(|_| format!("{} ({})", title, points)),
+                }
+            })
+            .collect();
+
+        writer
+            .write_record([
+                email,
+                s.name.as_str(),
+                &if any_graded { format_points(score.total) } else { String::new() },
+                &format_points(score.out_of),
+                &feedback.join("\n"),
+            ])
+            .map_err(|e| e.to_string())?;
+        summary.written += 1;
+    }
+
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// Lateness of each student's current submission under the assignment's late
+/// policy. Empty when there is no policy or due date.
+async fn late_penalties(pool: &DbPool, assignment_id: &str) -> Result<HashMap<String, Lateness>, String> {
+    let (due_date, policy) = crate::grading::load_late_policy(pool, assignment_id).await?;
+    let (Some(policy), Some(due)) = (policy, due_date.as_deref().and_then(crate::grading::parse_timestamp)) else {
+        return Ok(HashMap::new());
+    };
+
+    let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT student_id, submitted_at FROM submissions WHERE assignment_id = ? AND student_id IS NOT NULL AND deleted_at IS NULL AND superseded_by IS NULL"
+    )
+    .bind(assignment_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(student_id, submitted_at)| {
+            let submitted = crate::grading::parse_timestamp(submitted_at.as_deref()?)?;
+            Some((student_id, crate::grading::compute_lateness(&policy, due, submitted)))
+        })
+        .collect())
+}
+
+/// Build the gradebook sheet: one row per student with ID, name, email, section,
+/// total and percentage, then a score + comment column per question.
+/// With `late`, two trailing columns show the late penalty and the adjusted total.
+/// With `low_score_threshold`, question scores under that fraction of max are shaded red.
+/// With `letters`, a Letter column follows Percentage. Both use the late-adjusted
+/// total when `late` is given, so a row's percentage and letter always agree.
+fn gradebook_workbook(
+    data: &GradebookData,
+    late: Option<&HashMap<String, Lateness>>,
+    low_score_threshold: Option<f64>,
+    letters: Option<&[crate::grading::GradeThreshold]>,
+) -> Result<Workbook, String> {
+    let GradebookData { questions, scale, students, grade_map, .. } = data;
+
     let mut workbook = Workbook::new();
     let worksheet = workbook.add_worksheet();
 
-    worksheet.write_string(0, 0, "Student ID").map_err(|e| e.to_string())?;
-    worksheet.write_string(0, 1, "Name").map_err(|e| e.to_string())?;
-    worksheet.write_string(0, 2, "Total Score").map_err(|e| e.to_string())?;
+    let max_total = crate::grading::final_score(questions, *scale, |_| None).out_of;
+    let (total_col, percent_col, letter_col) = (4, 5, 6);
+    let first_question_col = if letters.is_some() { 7 } else { 6 };
+    let late_col = first_question_col + 2 * questions.len() as u16;
+    let percent_format = Format::new().set_num_format("0.0%");
+    let flag_format = Format::new().set_background_color(Color::RGB(0xFFC7CE));
+    let header_format = Format::new().set_bold().set_background_color(Color::RGB(0xD9E1F2));
+    let low_score_format = Format::new().set_background_color(Color::RGB(0xF8696B));
 
-    let mut col_idx = 3;
-    for q in &questions {
+    // Keep the header and the student ID/name in view while scrolling
+    worksheet.set_freeze_panes(1, 2).map_err(|e| e.to_string())?;
+    // Printed (and PDF) copies: landscape, all columns on one page width, header repeated
+    worksheet.set_landscape();
+    worksheet.set_print_fit_to_pages(1, 0);
+    worksheet.set_repeat_rows(0, 0).map_err(|e| e.to_string())?;
+    for (col, width) in [(0, 14.0), (1, 24.0), (2, 28.0), (3, 10.0), (total_col, 18.0), (percent_col, 12.0)] {
+        worksheet.set_column_width(col, width).map_err(|e| e.to_string())?;
+    }
+
+    worksheet.write_string_with_format(0, 0, "Student ID", &header_format).map_err(|e| e.to_string())?;
+    worksheet.write_string_with_format(0, 1, "Name", &header_format).map_err(|e| e.to_string())?;
+    worksheet.write_string_with_format(0, 2, "Email", &header_format).map_err(|e| e.to_string())?;
+    worksheet.write_string_with_format(0, 3, "Section", &header_format).map_err(|e| e.to_string())?;
+    worksheet.write_string_with_format(0, total_col, &format!("Total Score ({} pts)", max_total), &header_format).map_err(|e| e.to_string())?;
+    // Percentage and Letter both follow the late-adjusted total when there is one
+    let percent_header = if late.is_some() { "Percentage (after late penalty)" } else { "Percentage" };
+    worksheet.write_string_with_format(0, percent_col, percent_header, &header_format).map_err(|e| e.to_string())?;
+    if letters.is_some() {
+        worksheet.write_string_with_format(0, letter_col, "Letter", &header_format).map_err(|e| e.to_string())?;
+    }
+
+    let mut col_idx = first_question_col;
+    for q in questions {
         let title = q["title"].as_str().unwrap_or("Question");
         let max_pts = q["max_points"].as_f64().unwrap_or(0.0);
         
         let q_header = format!("{} ({} pts)", title, max_pts);
-        worksheet.write_string(0, col_idx, &q_header).map_err(|e| e.to_string())?;
-        worksheet.write_string(0, col_idx + 1, "Comments").map_err(|e| e.to_string())?;
+        worksheet.write_string_with_format(0, col_idx, &q_header, &header_format).map_err(|e| e.to_string())?;
+        worksheet.write_string_with_format(0, col_idx + 1, "Comments", &header_format).map_err(|e| e.to_string())?;
+        worksheet.set_column_width(col_idx, 14.0).map_err(|e| e.to_string())?;
+        // Comments run long; give them room
+        worksheet.set_column_width(col_idx + 1, 40.0).map_err(|e| e.to_string())?;
+        if let (Some(threshold), false) = (low_score_threshold, students.is_empty()) {
+            // A plain "less than" rule would also paint empty cells, which Excel reads as 0
+            let first = utility::row_col_to_cell(1, col_idx);
+            let rule = ConditionalFormatFormula::new()
+                .set_rule(format!("=AND(ISNUMBER({first}),{first}<{})", threshold * max_pts).as_str())
+                .set_format(&low_score_format);
+            worksheet
+                .add_conditional_format(1, col_idx, students.len() as u32, col_idx, &rule)
+                .map_err(|e| e.to_string())?;
+        }
         col_idx += 2;
     }
+    if late.is_some() {
+        worksheet.write_string_with_format(0, late_col, "Late Penalty", &header_format).map_err(|e| e.to_string())?;
+        worksheet.write_string_with_format(0, late_col + 1, "Adjusted Total", &header_format).map_err(|e| e.to_string())?;
+        worksheet.set_column_width(late_col, 14.0).map_err(|e| e.to_string())?;
+        worksheet.set_column_width(late_col + 1, 16.0).map_err(|e| e.to_string())?;
+    }
 
+    // Totals of students with at least one graded question, for the Stats sheet
+    let mut graded_totals = Vec::new();
     for (row_idx, s) in students.iter().enumerate() {
         let r = (row_idx + 1) as u32;
         worksheet.write_string(r, 0, &s.student_id).map_err(|e| e.to_string())?;
         worksheet.write_string(r, 1, &s.name).map_err(|e| e.to_string())?;
+        if let Some(email) = &s.email {
+            worksheet.write_string(r, 2, email).map_err(|e| e.to_string())?;
+        }
+        if let Some(section) = &s.section {
+            worksheet.write_string(r, 3, section).map_err(|e| e.to_string())?;
+        }
 
-        let mut total = 0.0;
-        let mut c_idx = 3;
+        let mut c_idx = first_question_col;
         
-        for q in &questions {
+        for q in questions {
             let q_id = q["question_id"].as_str().unwrap_or("");
+            let max_pts = q["max_points"].as_f64().unwrap_or(0.0);
             
             if let Some((score, comment)) = grade_map.get(&(s.student_id.clone(), q_id.to_string())) {
                 if let Some(val) = score {
-                    total += val;
-                    worksheet.write_number(r, c_idx, *val).map_err(|e| e.to_string())?;
+                    // Flag impossible scores with a note so they get fixed before upload
+                    if let Some(problem) = score_range_problem(*val, max_pts) {
+                        worksheet.write_number_with_format(r, c_idx, *val, &flag_format).map_err(|e| e.to_string())?;
+                        let note = Note::new(problem).add_author_prefix(false);
+                        worksheet.insert_note(r, c_idx, &note).map_err(|e| e.to_string())?;
+                    } else {
+                        worksheet.write_number(r, c_idx, *val).map_err(|e| e.to_string())?;
+                    }
                 }
                 if let Some(txt) = comment {
                     worksheet.write_string(r, c_idx + 1, txt).map_err(|e| e.to_string())?;
@@ -102,10 +589,941 @@ pub async fn export_gradebook(
             }
             c_idx += 2;
         }
-        worksheet.write_number(r, 2, total).map_err(|e| e.to_string())?;
+        let score = crate::grading::final_score(questions, *scale, |q_id| {
+            grade_map.get(&(s.student_id.clone(), q_id.to_string())).and_then(|(score, _)| *score)
+        });
+        let total = score.total;
+        worksheet.write_number(r, total_col, total).map_err(|e| e.to_string())?;
+        let any_graded = questions.iter().any(|q| {
+            grade_map
+                .get(&(s.student_id.clone(), q["question_id"].as_str().unwrap_or("").to_string()))
+                .is_some_and(|(score, _)| score.is_some())
+        });
+        if any_graded {
+            graded_totals.push(total);
+        }
+        let mut final_total = total;
+        if let Some(late) = late {
+            let penalty = late.get(&s.student_id).map_or(0.0, |l| l.penalty_points(score));
+            final_total = total - penalty;
+            worksheet.write_number(r, late_col, penalty).map_err(|e| e.to_string())?;
+            worksheet.write_number(r, late_col + 1, final_total).map_err(|e| e.to_string())?;
+        }
+        if max_total > 0.0 {
+            worksheet.write_number_with_format(r, percent_col, final_total / max_total, &percent_format).map_err(|e| e.to_string())?;
+        }
+        if let Some(letter) = letters.filter(|_| any_graded).and_then(|scale| crate::grading::letter_for(scale, final_total, max_total)) {
+            worksheet.write_string(r, letter_col, &letter).map_err(|e| e.to_string())?;
+        }
     }
 
-    workbook.save(&output_path).map_err(|e| e.to_string())?;
+    write_stats_sheet(&mut workbook, data, &graded_totals, max_total)?;
+
+    Ok(workbook)
+}
+
+/// Why a question score can't be right, if it falls outside 0..=max
+fn score_range_problem(score: f64, max_points: f64) -> Option<String> {
+    if score < 0.0 {
+        Some(format!("Score is negative; expected 0 to {}", format_points(max_points)))
+    } else if score > max_points {
+        Some(format!("Score exceeds max {}", format_points(max_points)))
+    } else {
+        None
+    }
+}
+
+/// Descriptive statistics of a set of scores
+struct ScoreSummary {
+    count: usize,
+    mean: f64,
+    median: f64,
+    min: f64,
+    max: f64,
+    /// Population standard deviation
+    std_dev: f64,
+}
+
+fn summarize(values: &[f64]) -> Option<ScoreSummary> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len();
+    let mean = sorted.iter().sum::<f64>() / n as f64;
+    let median = if n % 2 == 0 { (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0 } else { sorted[n / 2] };
+    let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+    Some(ScoreSummary { count: n, mean, median, min: sorted[0], max: sorted[n - 1], std_dev: variance.sqrt() })
+}
+
+/// Number of equal-width bands the totals histogram splits the points range into
+const HISTOGRAM_BINS: usize = 10;
+
+/// Second sheet: per-question and overall statistics over graded scores, plus a
+/// histogram of totals. Values are computed here so they're right without recalculation.
+fn write_stats_sheet(workbook: &mut Workbook, data: &GradebookData, totals: &[f64], max_total: f64) -> Result<(), String> {
+    let GradebookData { questions, students, grade_map, .. } = data;
+    let roster: std::collections::HashSet<&str> = students.iter().map(|s| s.student_id.as_str()).collect();
+    let sheet = workbook.add_worksheet().set_name("Stats").map_err(|e| e.to_string())?;
+    let header_format = Format::new().set_bold().set_background_color(Color::RGB(0xD9E1F2));
+    let number_format = Format::new().set_num_format("0.00");
+
+    let headers = ["Question", "Max Points", "Graded", "Mean", "Median", "Min", "Max", "Std Dev"];
+    for (col, h) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *h, &header_format).map_err(|e| e.to_string())?;
+    }
+    sheet.set_column_width(0, 30.0).map_err(|e| e.to_string())?;
+    sheet.set_freeze_panes(1, 0).map_err(|e| e.to_string())?;
+
+    let mut rows: Vec<(String, f64, Vec<f64>)> = questions
+        .iter()
+        .map(|q| {
+            let q_id = q["question_id"].as_str().unwrap_or("");
+            let scores = grade_map
+                .iter()
+                .filter(|((sid, gq), _)| gq == q_id && roster.contains(sid.as_str()))
+                .filter_map(|(_, (score, _))| *score)
+                .collect();
+            (q["title"].as_str().unwrap_or("Question").to_string(), q["max_points"].as_f64().unwrap_or(0.0), scores)
+        })
+        .collect();
+    rows.push(("Overall".to_string(), max_total, totals.to_vec()));
+
+    for (i, (title, max_pts, scores)) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        sheet.write_string(r, 0, title).map_err(|e| e.to_string())?;
+        sheet.write_number(r, 1, *max_pts).map_err(|e| e.to_string())?;
+        let Some(stats) = summarize(scores) else {
+            sheet.write_number(r, 2, 0.0).map_err(|e| e.to_string())?;
+            continue;
+        };
+        sheet.write_number(r, 2, stats.count as f64).map_err(|e| e.to_string())?;
+        for (col, value) in [(3, stats.mean), (4, stats.median), (5, stats.min), (6, stats.max), (7, stats.std_dev)] {
+            sheet.write_number_with_format(r, col, value, &number_format).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Histogram of totals in equal bands of the possible points; the top band includes the maximum
+    let hist_top = rows.len() as u32 + 2;
+    sheet.write_string_with_format(hist_top, 0, "Total Range", &header_format).map_err(|e| e.to_string())?;
+    sheet.write_string_with_format(hist_top, 1, "Students", &header_format).map_err(|e| e.to_string())?;
+    if max_total <= 0.0 {
+        return Ok(());
+    }
+    let width = max_total / HISTOGRAM_BINS as f64;
+    let mut counts = [0u32; HISTOGRAM_BINS];
+    for total in totals {
+        let bin = ((total / width).floor().max(0.0) as usize).min(HISTOGRAM_BINS - 1);
+        counts[bin] += 1;
+    }
+    for (i, count) in counts.iter().enumerate() {
+        let r = hist_top + 1 + i as u32;
+        let label = format!("{:.1} - {:.1}", width * i as f64, width * (i + 1) as f64);
+        sheet.write_string(r, 0, &label).map_err(|e| e.to_string())?;
+        sheet.write_number(r, 1, *count as f64).map_err(|e| e.to_string())?;
+    }
+
+    let (first, last) = (hist_top + 1, hist_top + HISTOGRAM_BINS as u32);
+    let mut chart = Chart::new(ChartType::Column);
+    chart
+        .add_series()
+        .set_categories(("Stats", first, 0, last, 0))
+        .set_values(("Stats", first, 1, last, 1))
+        .set_name("Students");
+    chart.title().set_name("Distribution of Totals");
+    chart.legend().set_hidden();
+    sheet.insert_chart(hist_top, 3, &chart).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Export the assignment total in the CSV layout Canvas accepts for grade import.
+/// Our `student_id` maps to Canvas' "SIS User ID"; ungraded students are left blank.
+/// With `include_late_penalty`, the total is reduced by the assignment's late policy.
+#[tauri::command]
+pub async fn export_canvas_csv(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_path: String,
+    include_late_penalty: Option<bool>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let GradebookData { assignment, questions, scale, students, grade_map } = load_gradebook(&pool, &assignment_id).await?;
+    let late = if include_late_penalty.unwrap_or(false) {
+        late_penalties(&pool, &assignment_id).await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut writer = csv::Writer::from_path(&output_path).map_err(|e| e.to_string())?;
+    writer
+        .write_record(["Student", "ID", "SIS User ID", "SIS Login ID", "Section", assignment.title.as_str()])
+        .map_err(|e| e.to_string())?;
+
+    for s in &students {
+        let score_of = |q_id: &str| grade_map.get(&(s.student_id.clone(), q_id.to_string())).and_then(|(score, _)| *score);
+        let any_graded = questions.iter().any(|q| score_of(q["question_id"].as_str().unwrap_or("")).is_some());
+        let grade = if any_graded {
+            let score = crate::grading::final_score(&questions, scale, score_of);
+            let penalty = late.get(&s.student_id).map_or(0.0, |l| l.penalty_points(score));
+            (score.total - penalty).to_string()
+        } else {
+            String::new()
+        };
+
+        writer
+            .write_record([
+                s.name.as_str(),
+                "",
+                s.student_id.as_str(),
+                s.email.as_deref().unwrap_or(""),
+                s.section.as_deref().unwrap_or(""),
+                grade.as_str(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+
+    writer.flush().map_err(|e| e.to_string())?;
+
+    Ok(output_path)
+}
+
+/// Export one row per (student, question) for item analysis in R/Python.
+/// Ungraded questions are still emitted with an empty score.
+#[tauri::command]
+pub async fn export_grades_long_csv(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_path: String,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let data = load_gradebook(&pool, &assignment_id).await?;
+    let file = std::fs::File::create(&output_path).map_err(|e| e.to_string())?;
+    write_grades_long(&data, file)?;
+
+    Ok(output_path)
+}
+
+fn write_grades_long<W: Write>(data: &GradebookData, out: W) -> Result<(), String> {
+    let GradebookData { questions, students, grade_map, .. } = data;
+
+    let mut writer = csv::Writer::from_writer(out);
+    writer
+        .write_record(["student_id", "question_id", "score", "max_points", "comment"])
+        .map_err(|e| e.to_string())?;
+
+    for s in students {
+        for q in questions {
+            let q_id = q["question_id"].as_str().unwrap_or("");
+            let max_pts = q["max_points"].as_f64().unwrap_or(0.0);
+            let (score, comment) = grade_map
+                .get(&(s.student_id.clone(), q_id.to_string()))
+                .cloned()
+                .unwrap_or((None, None));
+
+            writer
+                .write_record([
+                    s.student_id.clone(),
+                    q_id.to_string(),
+                    score.map(|v| v.to_string()).unwrap_or_default(),
+                    max_pts.to_string(),
+                    comment.unwrap_or_default(),
+                ])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    writer.flush().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct BundleSubmission {
+    id: String,
+    student_id: Option<String>,
+    folder_path: String,
+}
+
+/// Write everything needed to hand an assignment off into a single ZIP:
+/// gradebook.xlsx, rubric.json, grades.csv, manifest.json and, when
+/// `include_submissions` is set, the extracted folders of the current submissions.
+#[tauri::command]
+pub async fn export_assignment_bundle(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    output_path: String,
+    include_submissions: Option<bool>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    let data = load_gradebook(&pool, &assignment_id).await?;
+    let include_submissions = include_submissions.unwrap_or(false);
+
+    let submissions = if include_submissions {
+        sqlx::query_as::<sqlx::Sqlite, BundleSubmission>(
+            "SELECT id, student_id, folder_path FROM submissions WHERE assignment_id = ? AND deleted_at IS NULL AND superseded_by IS NULL ORDER BY student_id, id"
+        )
+        .bind(&assignment_id)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+
+    let file = std::fs::File::create(&output_path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut entries: Vec<String> = Vec::new();
+
+    let xlsx = gradebook_workbook(&data, None, None, None)?.save_to_buffer().map_err(|e| e.to_string())?;
+    zip.start_file("gradebook.xlsx", options).map_err(|e| e.to_string())?;
+    zip.write_all(&xlsx).map_err(|e| e.to_string())?;
+    entries.push("gradebook.xlsx".to_string());
+
+    let rubric: serde_json::Value = data
+        .assignment
+        .rubric_json
+        .as_deref()
+        .and_then(|r| serde_json::from_str(r).ok())
+        .unwrap_or(serde_json::json!({}));
+    zip.start_file("rubric.json", options).map_err(|e| e.to_string())?;
+    zip.write_all(serde_json::to_string_pretty(&rubric).map_err(|e| e.to_string())?.as_bytes())
+        .map_err(|e| e.to_string())?;
+    entries.push("rubric.json".to_string());
+
+    let mut csv_buf = Vec::new();
+    write_grades_long(&data, &mut csv_buf)?;
+    zip.start_file("grades.csv", options).map_err(|e| e.to_string())?;
+    zip.write_all(&csv_buf).map_err(|e| e.to_string())?;
+    entries.push("grades.csv".to_string());
+
+    let mut submission_file_count = 0usize;
+    let mut missing_folders: Vec<String> = Vec::new();
+    for sub in &submissions {
+        let root = std::path::Path::new(&sub.folder_path);
+        if !root.is_dir() {
+            missing_folders.push(sub.id.clone());
+            continue;
+        }
+        let prefix = format!("submissions/{}_{}", sub.student_id.as_deref().unwrap_or("unmatched"), sub.id);
+        let walker = walkdir::WalkDir::new(root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !crate::commands::is_extraction_junk(&e.file_name().to_string_lossy()));
+        for entry in walker.filter_map(|e| e.ok()) {
+            let rel = match entry.path().strip_prefix(root) {
+                Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            let name = format!("{}/{}", prefix, rel);
+            if entry.file_type().is_dir() {
+                zip.add_directory(name, options).map_err(|e| e.to_string())?;
+            } else if entry.file_type().is_file() {
+                zip.start_file(name, options).map_err(|e| e.to_string())?;
+                let mut f = std::fs::File::open(entry.path()).map_err(|e| e.to_string())?;
+                std::io::copy(&mut f, &mut zip).map_err(|e| e.to_string())?;
+                submission_file_count += 1;
+            }
+        }
+    }
+
+    let graded_count = data.grade_map.values().filter(|(score, _)| score.is_some()).count();
+    let manifest = serde_json::json!({
+        "assignment_id": data.assignment.id,
+        "assignment_title": data.assignment.title,
+        "course_id": data.assignment.course_id,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "entries": entries,
+        "counts": {
+            "students": data.students.len(),
+            "questions": data.questions.len(),
+            "grades": graded_count,
+            "submissions": submissions.len() - missing_folders.len(),
+            "submission_files": submission_file_count,
+        },
+        "includes_submissions": include_submissions,
+        "missing_submission_folders": missing_folders,
+    });
+    zip.start_file("manifest.json", options).map_err(|e| e.to_string())?;
+    zip.write_all(serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    zip.finish().map_err(|e| e.to_string())?;
 
     Ok(output_path)
 }
+
+/// A CSV row that was not applied, with the line number as shown in a spreadsheet
+#[derive(Debug, Serialize)]
+pub struct ImportRowIssue {
+    pub line: u64,
+    pub student_id: String,
+    pub question_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GradeImportSummary {
+    pub applied: usize,
+    /// Rows that were valid but had nothing to apply (unchanged, no submission, claimed elsewhere)
+    pub skipped: Vec<ImportRowIssue>,
+    /// Rows that could not be understood (unknown question/student, bad score)
+    pub errors: Vec<ImportRowIssue>,
+}
+
+/// Bring grades back in from a file written by `export_grades_long_csv`.
+/// Columns are matched by header name; `max_points` and any extra columns are ignored.
+/// Rows land on the student's current submission through the same revisioned
+/// write as `save_grade`, skipping submissions another TA currently has claimed.
+#[tauri::command]
+pub async fn import_grades_csv(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    input_path: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<GradeImportSummary, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let data = load_gradebook(&pool, &assignment_id).await?;
+
+    let mut reader = csv::Reader::from_path(&input_path).map_err(|e| e.to_string())?;
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let (Some(student_col), Some(question_col), Some(score_col)) = (column("student_id"), column("question_id"), column("score")) else {
+        return Err("CSV must have student_id, question_id and score columns".to_string());
+    };
+    let comment_col = column("comment");
+
+    let question_ids: std::collections::HashSet<&str> = data
+        .questions
+        .iter()
+        .filter_map(|q| q["question_id"].as_str())
+        .collect();
+    let roster: std::collections::HashSet<&str> = data.students.iter().map(|s| s.student_id.as_str()).collect();
+
+    let current: Vec<(String, String, Option<String>)> = sqlx::query_as(
+        "SELECT student_id, id, claimed_by_ta_id FROM submissions WHERE assignment_id = ? AND student_id IS NOT NULL AND deleted_at IS NULL AND superseded_by IS NULL"
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let submissions: HashMap<String, (String, Option<String>)> = current
+        .into_iter()
+        .map(|(student_id, id, claimed_by)| (student_id, (id, claimed_by)))
+        .collect();
+
+    let mut summary = GradeImportSummary { applied: 0, skipped: Vec::new(), errors: Vec::new() };
+    let mut touched: Vec<serde_json::Value> = Vec::new();
+
+    for (idx, record) in reader.records().enumerate() {
+        // +2: one for the header row, one because spreadsheets count from 1
+        let line = idx as u64 + 2;
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                summary.errors.push(ImportRowIssue { line, student_id: String::new(), question_id: String::new(), reason: e.to_string() });
+                continue;
+            }
+        };
+        let field = |col: usize| record.get(col).unwrap_or("").trim().to_string();
+        let student_id = field(student_col);
+        let question_id = field(question_col);
+        let issue = |reason: String| ImportRowIssue { line, student_id: student_id.clone(), question_id: question_id.clone(), reason };
+
+        if !question_ids.contains(question_id.as_str()) {
+            summary.errors.push(issue(format!("Unknown question_id '{}'", question_id)));
+            continue;
+        }
+        if !roster.contains(student_id.as_str()) {
+            summary.errors.push(issue(format!("Student '{}' is not on the course roster", student_id)));
+            continue;
+        }
+        let raw_score = field(score_col);
+        let score = if raw_score.is_empty() {
+            None
+        } else {
+            match raw_score.parse::<f64>() {
+                Ok(v) if v.is_finite() => Some(v),
+                _ => {
+                    summary.errors.push(issue(format!("Invalid score '{}'", raw_score)));
+                    continue;
+                }
+            }
+        };
+        let comment = comment_col.map(field).filter(|c| !c.is_empty());
+
+        let Some((submission_id, claimed_by)) = submissions.get(&student_id) else {
+            summary.skipped.push(issue("Student has no submission for this assignment".to_string()));
+            continue;
+        };
+        if claimed_by.as_deref().is_some_and(|owner| owner != ta_id) {
+            summary.skipped.push(issue("Submission is claimed by another TA".to_string()));
+            continue;
+        }
+
+        let existing: Option<(Option<f64>, Option<String>, i64)> = sqlx::query_as(
+            "SELECT score, comment, revision FROM grades WHERE submission_id = ? AND question_id = ?"
+        )
+        .bind(submission_id)
+        .bind(&question_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Some((old_score, old_comment, _)) = &existing {
+            if *old_score == score && *old_comment == comment {
+                summary.skipped.push(issue("Unchanged".to_string()));
+                continue;
+            }
+        }
+        if existing.is_none() && score.is_none() && comment.is_none() {
+            summary.skipped.push(issue("Nothing to import".to_string()));
+            continue;
+        }
+
+        let expected_revision = existing.map(|(_, _, rev)| rev);
+        match crate::commands::write_grade(&pool, submission_id, &question_id, score, comment.as_deref(), &ta_id, expected_revision).await {
+            Ok(_) => {
+                summary.applied += 1;
+                touched.push(serde_json::json!({ "submission_id": submission_id, "question_id": question_id, "score": score }));
+            }
+            Err(e) => summary.errors.push(issue(e)),
+        }
+    }
+
+    let details = serde_json::json!({
+        "input_path": input_path,
+        "applied": summary.applied,
+        "skipped": summary.skipped.len(),
+        "errors": summary.errors.len(),
+        "grades": touched,
+    })
+    .to_string();
+    crate::grading::log_audit_internal(&pool, Some(&ta_id), "import_grades", "assignment", &assignment_id, Some(&details)).await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use tauri::Manager;
+    use calamine::{Data, Reader};
+    use std::path::Path;
+
+    const RUBRIC: &str = r#"{"questions": [
+        {"question_id": "q1", "title": "Journal", "max_points": 10},
+        {"question_id": "q2", "title": "Ledger", "max_points": 10}
+    ]}"#;
+
+    /// Two students, of whom only Ada has been graded (8 + 6 of 20)
+    async fn graded_assignment(db: &TestDb) -> String {
+        let course = db.course().await;
+        db.student(&course, "S1", "Ada Lovelace").await;
+        db.student(&course, "S2", "Grace Hopper").await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let ada = db.submission(&assignment, Some("S1"), Path::new("")).await;
+        db.submission(&assignment, Some("S2"), Path::new("")).await;
+        db.grade(&ada, "q1", Some(8.0), None).await;
+        db.grade(&ada, "q2", Some(6.0), Some("Check the totals")).await;
+        assignment
+    }
+
+    /// One part of a saved xlsx, for checking formatting calamine doesn't read
+    fn xlsx_part(path: &Path, name: &str) -> String {
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        std::io::read_to_string(zip.by_name(name).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn gradebook_has_total_and_percentage() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradebook.xlsx");
+        gradebook_workbook(&data, None, None, None).unwrap().save(&path).unwrap();
+        let mut book = calamine::open_workbook_auto(&path).unwrap();
+        let sheet = book.worksheet_range_at(0).unwrap().unwrap();
+
+        assert_eq!(sheet.get_value((0, 4)), Some(&Data::String("Total Score (20 pts)".into())));
+        assert_eq!(sheet.get_value((0, 5)), Some(&Data::String("Percentage".into())));
+        assert_eq!(sheet.get_value((1, 1)), Some(&Data::String("Ada Lovelace".into())));
+        assert_eq!(sheet.get_value((1, 4)), Some(&Data::Float(14.0)));
+        assert_eq!(sheet.get_value((1, 5)), Some(&Data::Float(0.7)));
+        assert_eq!(sheet.get_value((1, 9)), Some(&Data::String("Check the totals".into())));
+        assert_eq!(sheet.get_value((2, 4)), Some(&Data::Float(0.0)));
+    }
+
+    #[tokio::test]
+    async fn gradebook_uses_the_current_version() {
+        let db = TestDb::new().await;
+        let course = db.course().await;
+        db.student(&course, "S1", "Ada Lovelace").await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+
+        // v1 -> v2 -> v3, each graded with its version number
+        let v1 = db.submission(&assignment, Some("S1"), Path::new("")).await;
+        let v2 = db.submission(&assignment, Some("S1"), Path::new("")).await;
+        let v3 = db.submission(&assignment, Some("S1"), Path::new("")).await;
+        for (id, version, superseded_by) in [(&v1, 1, Some(&v2)), (&v2, 2, Some(&v3)), (&v3, 3, None)] {
+            sqlx::query("UPDATE submissions SET version = ?, superseded_by = ? WHERE id = ?")
+                .bind(version)
+                .bind(superseded_by)
+                .bind(id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            db.grade(id, "q1", Some(version as f64), None).await;
+        }
+
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+        assert_eq!(data.grade_map.len(), 1);
+        assert_eq!(data.grade_map[&("S1".to_string(), "q1".to_string())].0, Some(3.0));
+    }
+
+    #[tokio::test]
+    async fn canvas_csv_leaves_ungraded_blank() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("canvas.csv");
+
+        export_canvas_csv(app.state(), assignment, path.to_string_lossy().into(), None).await.unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines, [
+            "Student,ID,SIS User ID,SIS Login ID,Section,Homework 1",
+            "Ada Lovelace,,S1,,,14",
+            "Grace Hopper,,S2,,,",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn long_csv_has_a_row_per_student_and_question() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+
+        let mut out = Vec::new();
+        write_grades_long(&data, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines, [
+            "student_id,question_id,score,max_points,comment",
+            "S1,q1,8,10,",
+            "S1,q2,6,10,Check the totals",
+            "S2,q1,,10,",
+            "S2,q2,,10,",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn bundle_holds_exports_submissions_and_manifest() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = graded_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("ada");
+        std::fs::create_dir_all(folder.join("work")).unwrap();
+        std::fs::write(folder.join("work/journal.xlsx"), b"x").unwrap();
+        std::fs::write(folder.join(".DS_Store"), b"junk").unwrap();
+        sqlx::query("UPDATE submissions SET folder_path = ? WHERE student_id = 'S1'")
+            .bind(folder.to_string_lossy().to_string())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let ada: String = sqlx::query_scalar("SELECT id FROM submissions WHERE student_id = 'S1'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+
+        let output = dir.path().join("bundle.zip");
+        export_assignment_bundle(app.state(), assignment, output.to_string_lossy().to_string(), Some(true)).await.unwrap();
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+        let mut names: Vec<String> = zip.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, [
+            "gradebook.xlsx".to_string(),
+            "grades.csv".to_string(),
+            "manifest.json".to_string(),
+            "rubric.json".to_string(),
+            format!("submissions/S1_{}/work/", ada),
+            format!("submissions/S1_{}/work/journal.xlsx", ada),
+        ]);
+
+        let manifest: serde_json::Value = serde_json::from_reader(zip.by_name("manifest.json").unwrap()).unwrap();
+        assert_eq!(manifest["counts"], serde_json::json!({
+            "students": 2, "questions": 2, "grades": 2, "submissions": 1, "submission_files": 1,
+        }));
+        assert_eq!(manifest["missing_submission_folders"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn grade_import_applies_changes_and_reports_the_rest() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = graded_assignment(&db).await;
+        let ta = db.ta("Ada").await;
+        let other = db.ta("Grace").await;
+        let grace: String = sqlx::query_scalar("SELECT id FROM submissions WHERE student_id = 'S2'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        db.claim(&grace, &other).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("grades.csv");
+        std::fs::write(&input, "Student_ID,question_id,max_points,score,comment\n\
+            S1,q1,10,8,\n\
+            S1,q2,10,7,Fixed the totals\n\
+            S2,q1,10,9,\n\
+            S3,q1,10,5,\n\
+            S1,q9,10,5,\n\
+            S1,q1,10,eight,\n").unwrap();
+
+        let summary = import_grades_csv(app.state(), assignment, input.to_string_lossy().to_string(), ta, None).await.unwrap();
+        assert_eq!(summary.applied, 1);
+        let reasons = |issues: &[ImportRowIssue]| issues.iter().map(|i| (i.line, i.reason.clone())).collect::<Vec<_>>();
+        assert_eq!(reasons(&summary.skipped), [
+            (2, "Unchanged".to_string()),
+            (4, "Submission is claimed by another TA".to_string()),
+        ]);
+        assert_eq!(reasons(&summary.errors), [
+            (5, "Student 'S3' is not on the course roster".to_string()),
+            (6, "Unknown question_id 'q9'".to_string()),
+            (7, "Invalid score 'eight'".to_string()),
+        ]);
+
+        let (score, comment, revision): (Option<f64>, Option<String>, i64) =
+            sqlx::query_as("SELECT score, comment, revision FROM grades WHERE question_id = 'q2'")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!((score, comment.as_deref(), revision), (Some(7.0), Some("Fixed the totals"), 2));
+    }
+
+    #[tokio::test]
+    async fn gradebook_lists_email_and_section_after_the_name() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        sqlx::query("UPDATE students SET email = 'ada@example.edu', section = 'A1' WHERE student_id = 'S1'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradebook.xlsx");
+        gradebook_workbook(&data, None, None, None).unwrap().save(&path).unwrap();
+        let mut book = calamine::open_workbook_auto(&path).unwrap();
+        let sheet = book.worksheet_range_at(0).unwrap().unwrap();
+        let row = |r: u32| -> Vec<String> { (0..4).map(|c| sheet.get_value((r, c)).map(Data::to_string).unwrap_or_default()).collect() };
+
+        assert_eq!(row(0), ["Student ID", "Name", "Email", "Section"]);
+        assert_eq!(row(1), ["S1", "Ada Lovelace", "ada@example.edu", "A1"]);
+        // Grace has neither, so both cells stay blank
+        assert_eq!(row(2), ["S2", "Grace Hopper", "", ""]);
+        assert_eq!(sheet.get_value((0, 6)), Some(&Data::String("Journal (10 pts)".into())));
+    }
+
+    #[tokio::test]
+    async fn gradebook_freezes_the_header_and_widens_comments() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradebook.xlsx");
+        gradebook_workbook(&data, None, None, None).unwrap().save(&path).unwrap();
+        let sheet = xlsx_part(&path, "xl/worksheets/sheet1.xml");
+        let styles = xlsx_part(&path, "xl/styles.xml");
+
+        assert!(sheet.contains(r#"<pane xSplit="2" ySplit="1" topLeftCell="C2" activePane="bottomRight" state="frozen"/>"#));
+        // Header cells share style 1: the bold font over the blue fill
+        assert!(sheet.contains(r#"<c r="A1" s="1""#) && sheet.contains(r#"<c r="H1" s="1""#));
+        assert!(styles.contains(r#"<xf numFmtId="0" fontId="1" fillId="2" borderId="0" xfId="0" applyFont="1" applyFill="1"/>"#));
+        assert!(styles.contains("<font><b/>") && styles.contains(r#"<fgColor rgb="FFD9E1F2"/>"#));
+        // Question scores are narrow and their comment columns (H, J) wide
+        assert!(sheet.contains(r#"<col min="7" max="7" width="14.7109375" customWidth="1"/>"#));
+        for col in [8, 10] {
+            assert!(sheet.contains(&format!(r#"<col min="{col}" max="{col}" width="40.7109375" customWidth="1"/>"#)));
+        }
+    }
+
+    #[tokio::test]
+    async fn low_scores_are_shaded_only_when_graded() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradebook.xlsx");
+
+        gradebook_workbook(&data, None, Some(0.6), None).unwrap().save(&path).unwrap();
+        let sheet = xlsx_part(&path, "xl/worksheets/sheet1.xml");
+        let rules: Vec<&str> = sheet.match_indices("<conditionalFormatting").map(|(i, _)| &sheet[i..]).collect();
+        assert_eq!(rules.len(), 2);
+        // One rule per question score column, 60% of 10 points, skipping blank cells
+        assert!(rules[0].starts_with(r#"<conditionalFormatting sqref="G2:G3">"#));
+        assert!(rules[0].contains("<formula>AND(ISNUMBER(G2),G2&lt;6)</formula>"));
+        assert!(rules[1].starts_with(r#"<conditionalFormatting sqref="I2:I3">"#));
+        assert!(xlsx_part(&path, "xl/styles.xml").contains(r#"<bgColor rgb="FFF8696B"/>"#));
+
+        gradebook_workbook(&data, None, None, None).unwrap().save(&path).unwrap();
+        assert!(!xlsx_part(&path, "xl/worksheets/sheet1.xml").contains("<conditionalFormatting"));
+    }
+
+    #[tokio::test]
+    async fn stats_sheet_summarizes_graded_scores() {
+        let db = TestDb::new().await;
+        let assignment = graded_assignment(&db).await;
+        let grace: String = sqlx::query_scalar("SELECT id FROM submissions WHERE student_id = 'S2'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        db.grade(&grace, "q1", Some(4.0), None).await;
+        let data = load_gradebook(&db.pool, &assignment).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradebook.xlsx");
+        gradebook_workbook(&data, None, None, None).unwrap().save(&path).unwrap();
+        let mut book = calamine::open_workbook_auto(&path).unwrap();
+        assert_eq!(book.sheet_names(), ["Sheet1", "Stats"]);
+        let stats = book.worksheet_range("Stats").unwrap();
+        let row = |r: u32| -> Vec<String> { (0..8).map(|c| stats.get_value((r, c)).map(Data::to_string).unwrap_or_default()).collect() };
+
+        assert_eq!(row(0), ["Question", "Max Points", "Graded", "Mean", "Median", "Min", "Max", "Std Dev"]);
+        assert_eq!(row(1), ["Journal", "10", "2", "6", "6", "4", "8", "2"]);
+        // Grace has no Ledger score, so only Ada's counts
+        assert_eq!(row(2), ["Ledger", "10", "1", "6", "6", "6", "6", "0"]);
+        assert_eq!(row(3), ["Overall", "20", "2", "9", "9", "4", "14", "5"]);
+
+        // Totals of 4 and 14 fall in the 4-6 and 14-16 bands of 20 points
+        assert_eq!(stats.get_value((5, 0)), Some(&Data::String("Total Range".into())));
+        let bands: Vec<Vec<String>> = (6..16).map(|r| row(r)[..2].to_vec()).filter(|band| band[1] != "0").collect();
+        assert_eq!(bands, [["4.0 - 6.0", "1"], ["14.0 - 16.0", "1"]]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pdf_gradebook_is_converted_from_the_xlsx() {
+        use std::os::unix::fs::PermissionsExt;
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = graded_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        // Stands in for soffice: writes `contents` as the converted workbook
+        let binary = dir.path().join("soffice");
+        let fake_soffice = |contents: &str| {
+            let script = format!("#!/bin/sh\ncase \"$5\" in *.xlsx) printf '%s' '{}' > \"$7/gradebook.pdf\" ;; esac\n", contents);
+            std::fs::write(&binary, script).unwrap();
+            std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        fake_soffice("%PDF-1.4");
+        crate::commands::set_setting(app.state(), crate::soffice::LIBREOFFICE_PATH_SETTING.into(), Some(binary.to_string_lossy().into()))
+            .await
+            .unwrap();
+        let output = dir.path().join("gradebook.pdf");
+        let export = || export_gradebook_pdf(app.state(), app.state(), assignment.clone(), output.to_string_lossy().into(), None);
+
+        assert_eq!(export().await.unwrap(), output.to_string_lossy());
+        assert_eq!(std::fs::read(&output).unwrap(), b"%PDF-1.4");
+
+        std::fs::remove_file(&output).unwrap();
+        fake_soffice("not a pdf");
+        assert_eq!(export().await.err().as_deref(), Some("LibreOffice did not produce a valid PDF"));
+        assert!(!output.exists());
+    }
+
+    #[tokio::test]
+    async fn feedback_report_lists_each_question_and_the_total() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        db.student(&course, "S1", "Ada Lovelace").await;
+        let rubric = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "description": "Record the <b>March</b> entries", "max_points": 10},
+            {"question_id": "q2", "title": "Ledger", "max_points": 5}
+        ]}"#;
+        let assignment = db.assignment(&course, Some(rubric)).await;
+        let ada = db.submission(&assignment, Some("S1"), Path::new("")).await;
+        db.grade(&ada, "q1", Some(7.5), Some("Debits & credits swapped\nin entry 3")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("feedback.html");
+
+        generate_feedback_report(app.state(), ada, output.to_string_lossy().into()).await.unwrap();
+        let html = std::fs::read_to_string(&output).unwrap();
+        assert!(html.contains("<title>Homework 1 - Feedback</title>"));
+        assert!(html.contains("Feedback for Ada Lovelace (S1)"));
+        assert!(html.contains(
+            "<tr><td><strong>Journal</strong><div class=\"description\">Record the &lt;b&gt;March&lt;/b&gt; entries</div></td>\
+             <td class=\"score\">7.5 / 10</td><td>Debits &amp; credits swapped<br>in entry 3</td></tr>"
+        ));
+        // The ungraded question is shown as such and counts as zero
+        assert!(html.contains("<tr><td><strong>Ledger</strong></td><td class=\"score\"><span class=\"ungraded\">Not graded</span> / 5</td><td></td></tr>"));
+        assert!(html.contains("<strong>Total: 7.5 / 15</strong>"));
+
+        let missing = generate_feedback_report(app.state(), "nope".into(), output.to_string_lossy().into()).await;
+        assert_eq!(missing.err().as_deref(), Some("Submission not found"));
+    }
+
+    #[tokio::test]
+    async fn feedback_batch_writes_graded_students_and_lists_the_rest() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = graded_assignment(&db).await;
+        let course: String = sqlx::query_scalar("SELECT course_id FROM assignments").fetch_one(&db.pool).await.unwrap();
+        db.student(&course, "S3/B", "Alan Turing").await;
+        db.student(&course, "S4", "Edsger Dijkstra").await;
+        let alan = db.submission(&assignment, Some("S3/B"), Path::new("")).await;
+        db.grade(&alan, "q1", Some(9.0), None).await;
+        let unmatched = db.submission(&assignment, None, Path::new("")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("feedback");
+
+        let batch = generate_all_feedback_reports(app.state(), assignment, output.to_string_lossy().into()).await.unwrap();
+        let files: Vec<(&str, &Path)> = batch.files.iter().map(|f| (f.student_id.as_str(), Path::new(&f.path))).collect();
+        assert_eq!(files, [("S1", output.join("S1.html").as_path()), ("S3/B", output.join("S3_B.html").as_path())]);
+        assert!(std::fs::read_to_string(output.join("S1.html")).unwrap().contains("Check the totals"));
+        assert_eq!(std::fs::read_dir(&output).unwrap().count(), 2);
+
+        let skipped: Vec<_> = batch
+            .skipped
+            .iter()
+            .map(|s| (s.student_id.as_deref(), s.submission_id.is_some(), s.reason.as_str()))
+            .collect();
+        assert_eq!(skipped, [
+            (None, true, "Unmatched"),
+            (Some("S2"), true, "Not graded"),
+            (Some("S4"), false, "No submission"),
+        ]);
+        assert_eq!(batch.skipped[0].submission_id.as_deref(), Some(unmatched.as_str()));
+    }
+
+    #[tokio::test]
+    async fn mailmerge_skips_students_without_an_email() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = graded_assignment(&db).await;
+        let course: String = sqlx::query_scalar("SELECT course_id FROM assignments").fetch_one(&db.pool).await.unwrap();
+        db.student(&course, "S3", "Alan Turing").await;
+        for (id, email) in [("S1", "ada@example.edu"), ("S2", "  "), ("S3", "alan@example.edu")] {
+            sqlx::query("UPDATE students SET email = ? WHERE student_id = ?").bind(email).bind(id).execute(&db.pool).await.unwrap();
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("mailmerge.csv");
+
+        let summary = export_feedback_mailmerge(app.state(), assignment, output.to_string_lossy().into()).await.unwrap();
+        assert_eq!((summary.written, summary.skipped_no_email), (2, 1));
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(csv, "email,name,total_score,max_score,feedback_text\n\
+            ada@example.edu,Ada Lovelace,14,20,\"Journal (8/10)\nLedger (6/10): Check the totals\"\n\
+            alan@example.edu,Alan Turing,,20,\n");
+    }
+}
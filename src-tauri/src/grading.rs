@@ -1,5 +1,5 @@
-use tauri::State;
-use crate::db::DbPool;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use crate::db::{DbPool, DbState};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
@@ -13,6 +13,10 @@ pub struct SubmissionQueueItem {
     pub status: String,
     pub claimed_by_ta_id: Option<String>,
     pub claimed_by_name: Option<String>,
+    pub assigned_ta_id: Option<String>,
+    pub assigned_to_name: Option<String>,
+    #[serde(skip)]
+    pub anon_seq: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -23,13 +27,37 @@ pub struct SessionBookmark {
 
 // --- Commands ---
 
-/// List all submissions for an assignment with status and claim info
+/// List all submissions for an assignment with status and claim info.
+/// Superseded versions are hidden unless `include_superseded` is set.
+/// Optional filters: `status`, `claimed_by` (a TA ID or "unclaimed") and
+/// `assigned_to` (a TA's pre-assigned queue). `sort` is "name" (default),
+/// "status" or "received_at".
 #[tauri::command]
 pub async fn list_submissions(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     assignment_id: String,
+    include_superseded: Option<bool>,
+    status: Option<String>,
+    claimed_by: Option<String>,
+    assigned_to: Option<String>,
+    sort: Option<String>,
 ) -> Result<Vec<SubmissionQueueItem>, String> {
-    let items = sqlx::query_as::<sqlx::Sqlite, SubmissionQueueItem>(
+    let pool = db.pool()?;
+    let anonymous = is_anonymous(&pool, &assignment_id).await?;
+    // Sort keys map onto fixed clauses so nothing caller-supplied reaches the SQL text.
+    // Anonymous queues order by label instead, since name order would give away who is who.
+    let order_by = match (sort.as_deref().unwrap_or("name"), anonymous) {
+        ("name", false) => "st.name ASC, sub.id ASC",
+        ("name", true) => "sub.anon_seq ASC",
+        ("status", false) => "sub.status ASC, st.name ASC, sub.id ASC",
+        ("status", true) => "sub.status ASC, sub.anon_seq ASC",
+        ("received_at", _) => "sub.received_at ASC, sub.id ASC",
+        (other, _) => return Err(format!("Unknown sort key '{}'", other)),
+    };
+    let unclaimed = claimed_by.as_deref() == Some("unclaimed");
+    let claimed_by = claimed_by.filter(|_| !unclaimed);
+
+    let sql = format!(
         r#"
         SELECT 
             sub.id,
@@ -37,77 +65,600 @@ pub async fn list_submissions(
             st.name as student_name,
             sub.status,
             sub.claimed_by_ta_id,
-            ta.display_name as claimed_by_name
+            ta.display_name as claimed_by_name,
+            sub.assigned_ta_id,
+            assigned.display_name as assigned_to_name,
+            sub.anon_seq
         FROM submissions sub
         LEFT JOIN students st ON sub.student_id = st.student_id 
             AND st.course_id = (SELECT course_id FROM assignments WHERE id = sub.assignment_id)
         LEFT JOIN tas ta ON sub.claimed_by_ta_id = ta.id
+        LEFT JOIN tas assigned ON sub.assigned_ta_id = assigned.id
+        WHERE sub.assignment_id = ?
+          AND sub.deleted_at IS NULL
+          AND (? OR sub.superseded_by IS NULL)
+          AND (? IS NULL OR sub.status = ?)
+          AND (NOT ? OR sub.claimed_by_ta_id IS NULL)
+          AND (? IS NULL OR sub.claimed_by_ta_id = ?)
+          AND (? IS NULL OR sub.assigned_ta_id = ?)
+        ORDER BY {}
+        "#,
+        order_by
+    );
+    let items = sqlx::query_as::<sqlx::Sqlite, SubmissionQueueItem>(&sql)
+    .bind(&assignment_id)
+    .bind(include_superseded.unwrap_or(false))
+    .bind(&status)
+    .bind(&status)
+    .bind(unclaimed)
+    .bind(&claimed_by)
+    .bind(&claimed_by)
+    .bind(&assigned_to)
+    .bind(&assigned_to)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if anonymous {
+        return Ok(anonymize_queue(items));
+    }
+    
+    Ok(items)
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaWorkload {
+    pub ta_id: String,
+    pub submissions: usize,
+}
+
+/// Pre-assign an assignment's current submissions to TAs. `round_robin` deals them
+/// out in name order; `by_section` keeps each section with one TA, giving the next
+/// section to whoever has the least so far. Replaces any earlier distribution.
+#[tauri::command]
+pub async fn distribute_submissions(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    strategy: String,
+    ta_ids: Vec<String>,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<Vec<TaWorkload>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    if ta_ids.is_empty() {
+        return Err("Choose at least one TA".to_string());
+    }
+    let unique: std::collections::HashSet<&String> = ta_ids.iter().collect();
+    if unique.len() != ta_ids.len() {
+        return Err("Each TA can only be listed once".to_string());
+    }
+    for id in &ta_ids {
+        let exists: Option<String> = sqlx::query_scalar("SELECT id FROM tas WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if exists.is_none() {
+            return Err(format!("TA {} not found", id));
+        }
+    }
+
+    let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT sub.id, sub.assigned_ta_id, st.section
+        FROM submissions sub
+        LEFT JOIN students st ON sub.student_id = st.student_id
+            AND st.course_id = (SELECT course_id FROM assignments WHERE id = sub.assignment_id)
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+        ORDER BY st.name IS NULL, st.name ASC, sub.id ASC
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut counts = vec![0usize; ta_ids.len()];
+    let mut plan: Vec<(String, Option<String>, usize)> = Vec::with_capacity(rows.len());
+    match strategy.as_str() {
+        "round_robin" => {
+            for (i, (id, previous, _)) in rows.into_iter().enumerate() {
+                let slot = i % ta_ids.len();
+                counts[slot] += 1;
+                plan.push((id, previous, slot));
+            }
+        }
+        "by_section" => {
+            // Students without a section are grouped together like any other section
+            let mut sections: std::collections::BTreeMap<String, Vec<(String, Option<String>)>> = Default::default();
+            for (id, previous, section) in rows {
+                sections.entry(section.unwrap_or_default()).or_default().push((id, previous));
+            }
+            let mut sections: Vec<_> = sections.into_values().collect();
+            // Largest sections first keeps the greedy split even
+            sections.sort_by_key(|members| std::cmp::Reverse(members.len()));
+            for members in sections {
+                let slot = (0..ta_ids.len()).min_by_key(|&i| counts[i]).unwrap_or(0);
+                counts[slot] += members.len();
+                plan.extend(members.into_iter().map(|(id, previous)| (id, previous, slot)));
+            }
+        }
+        other => return Err(format!("Unknown distribution strategy '{}'", other)),
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    for (submission_id, previous, slot) in &plan {
+        let assigned = &ta_ids[*slot];
+        sqlx::query("UPDATE submissions SET assigned_ta_id = ? WHERE id = ?")
+            .bind(assigned)
+            .bind(submission_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let details = serde_json::json!({
+            "assigned_ta_id": assigned,
+            "previous": previous,
+            "strategy": strategy,
+        }).to_string();
+        sqlx::query(
+            "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, 'assign_ta', 'submission', ?, ?)"
+        )
+        .bind(&ta_id)
+        .bind(submission_id)
+        .bind(&details)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(ta_ids
+        .into_iter()
+        .zip(counts)
+        .map(|(ta_id, submissions)| TaWorkload { ta_id, submissions })
+        .collect())
+}
+
+/// The next submission after `current_submission_id` in the TA's queue that still
+/// needs work: not marked done, or done but with a rubric question left ungraded.
+/// The queue is the name-ordered list minus submissions claimed by or assigned to
+/// other TAs. Wraps to the start unless `wrap` is false; `None` when nothing is left.
+#[tauri::command]
+pub async fn next_submission(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+    current_submission_id: Option<String>,
+    wrap: Option<bool>,
+) -> Result<Option<String>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let rubric_json: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+        .bind(&assignment_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Assignment not found")?;
+    let rubric: serde_json::Value = rubric_json
+        .as_deref()
+        .and_then(|j| serde_json::from_str(j).ok())
+        .unwrap_or(serde_json::json!({}));
+    let question_ids: Vec<String> = rubric["questions"]
+        .as_array()
+        .map(|qs| qs.iter().filter_map(|q| q["question_id"].as_str().map(String::from)).collect())
+        .unwrap_or_default();
+
+    let queue: Vec<(String, bool)> = sqlx::query_as(
+        r#"
+        SELECT sub.id,
+            sub.status = 'done' AND (
+                SELECT COUNT(DISTINCT g.question_id) FROM grades g
+                WHERE g.submission_id = sub.id
+                  AND g.score IS NOT NULL
+                  AND g.question_id IN (SELECT value FROM json_each(?))
+            ) = ? as finished
+        FROM submissions sub
+        LEFT JOIN students st ON sub.student_id = st.student_id
+            AND st.course_id = (SELECT course_id FROM assignments WHERE id = sub.assignment_id)
         WHERE sub.assignment_id = ?
+          AND sub.deleted_at IS NULL
+          AND sub.superseded_by IS NULL
+          AND sub.status != 'error'
+          AND (sub.claimed_by_ta_id IS NULL OR sub.claimed_by_ta_id = ?)
+          AND (sub.assigned_ta_id IS NULL OR sub.assigned_ta_id = ?)
         ORDER BY st.name ASC, sub.id ASC
         "#
     )
+    .bind(serde_json::to_string(&question_ids).map_err(|e| e.to_string())?)
+    .bind(question_ids.len() as i64)
+    .bind(&assignment_id)
+    .bind(&ta_id)
+    .bind(&ta_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Start just after the current submission; an unknown current starts from the top
+    let start = current_submission_id
+        .as_ref()
+        .and_then(|cur| queue.iter().position(|(id, _)| id == cur))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let (before, after) = queue.split_at(start.min(queue.len()));
+    let wrapped = if wrap.unwrap_or(true) { before } else { &[] };
+    Ok(after
+        .iter()
+        .chain(wrapped)
+        .find(|(id, finished)| !finished && Some(id) != current_submission_id.as_ref())
+        .map(|(id, _)| id.clone()))
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SubmissionVersion {
+    pub id: String,
+    pub version: i64,
+    pub submitted_at: Option<String>,
+    pub file_hash: Option<String>,
+    pub superseded_by: Option<String>,
+}
+
+/// Get a student's submission history for an assignment, oldest first
+#[tauri::command]
+pub async fn get_submission_versions(
+    db: State<'_, DbState>,
+    student_id: String,
+    assignment_id: String,
+) -> Result<Vec<SubmissionVersion>, String> {
+    let pool = db.pool()?;
+    let versions = sqlx::query_as::<sqlx::Sqlite, SubmissionVersion>(
+        r#"
+        SELECT id, version, submitted_at, file_hash, superseded_by
+        FROM submissions
+        WHERE assignment_id = ? AND student_id = ?
+        ORDER BY version ASC
+        "#
+    )
     .bind(&assignment_id)
-    .fetch_all(&*pool)
+    .bind(&student_id)
+    .fetch_all(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
-    Ok(items)
+    Ok(versions)
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TaClaimCount {
+    pub ta_id: String,
+    pub display_name: Option<String>,
+    pub claimed: i64,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct AssignmentProgress {
+    pub total: i64,
+    pub unstarted: i64,
+    pub in_progress: i64,
+    pub done: i64,
+    pub flagged: i64,
+    pub error: i64,
+    /// Submissions with a score for every rubric question
+    pub fully_graded: i64,
+    pub unmatched: i64,
+    pub claims: Vec<TaClaimCount>,
+}
+
+/// Grading progress for an assignment's current (non-superseded) submissions
+#[tauri::command]
+pub async fn get_assignment_progress(
+    db: State<'_, DbState>,
+    assignment_id: String,
+) -> Result<AssignmentProgress, String> {
+    let pool = db.pool()?;
+    let rubric_json: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+        .bind(&assignment_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Assignment not found")?;
+
+    let rubric: serde_json::Value = rubric_json
+        .as_deref()
+        .and_then(|j| serde_json::from_str(j).ok())
+        .unwrap_or(serde_json::json!({}));
+    let question_ids: Vec<String> = rubric["questions"]
+        .as_array()
+        .map(|qs| qs.iter().filter_map(|q| q["question_id"].as_str().map(String::from)).collect())
+        .unwrap_or_default();
+
+    let mut progress = AssignmentProgress::default();
+
+    let by_status: Vec<(String, i64)> = sqlx::query_as(
+        r#"
+        SELECT status, COUNT(*) FROM submissions
+        WHERE assignment_id = ? AND deleted_at IS NULL AND superseded_by IS NULL
+        GROUP BY status
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for (status, count) in by_status {
+        progress.total += count;
+        match status.as_str() {
+            "unstarted" => progress.unstarted = count,
+            "in_progress" => progress.in_progress = count,
+            "done" => progress.done = count,
+            "flagged" => progress.flagged = count,
+            "error" => progress.error = count,
+            _ => {}
+        }
+    }
+
+    progress.unmatched = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM submissions
+        WHERE assignment_id = ? AND deleted_at IS NULL AND superseded_by IS NULL AND student_id IS NULL
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if !question_ids.is_empty() {
+        progress.fully_graded = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM submissions sub
+            WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+              AND (
+                SELECT COUNT(DISTINCT g.question_id) FROM grades g
+                WHERE g.submission_id = sub.id
+                  AND g.score IS NOT NULL
+                  AND g.question_id IN (SELECT value FROM json_each(?))
+              ) = ?
+            "#
+        )
+        .bind(&assignment_id)
+        .bind(serde_json::to_string(&question_ids).map_err(|e| e.to_string())?)
+        .bind(question_ids.len() as i64)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    progress.claims = sqlx::query_as::<sqlx::Sqlite, TaClaimCount>(
+        r#"
+        SELECT sub.claimed_by_ta_id as ta_id, ta.display_name, COUNT(*) as claimed
+        FROM submissions sub
+        LEFT JOIN tas ta ON ta.id = sub.claimed_by_ta_id
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+          AND sub.claimed_by_ta_id IS NOT NULL
+        GROUP BY sub.claimed_by_ta_id
+        ORDER BY claimed DESC
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(progress)
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuestionAverage {
+    pub question_id: String,
+    pub count: usize,
+    pub average_score: f64,
+    /// Average as a fraction of the question's max points; None if it's worth 0
+    pub average_percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaGraderStats {
+    pub ta_id: String,
+    pub display_name: Option<String>,
+    pub submissions_graded: usize,
+    pub scores_count: usize,
+    /// Mean of each score as a fraction of its question's max points
+    pub average_percent: Option<f64>,
+    /// Population variance of those fractions
+    pub variance_percent: Option<f64>,
+    pub questions: Vec<QuestionAverage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraderStats {
+    /// Per-question averages across all graders, for comparison
+    pub overall: Vec<QuestionAverage>,
+    pub tas: Vec<TaGraderStats>,
+}
+
+/// Per-TA score averages for spotting grader drift. A score is attributed to the
+/// TA who last saved it, or the submission's claimant for older rows without one.
+/// Scores are normalized by max points so questions of different sizes compare.
+#[tauri::command]
+pub async fn get_grader_stats(
+    db: State<'_, DbState>,
+    assignment_id: String,
+) -> Result<GraderStats, String> {
+    let pool = db.pool()?;
+    let rubric_json: Option<String> = sqlx::query_scalar("SELECT rubric_json FROM assignments WHERE id = ?")
+        .bind(&assignment_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Assignment not found")?;
+    let rubric: serde_json::Value = rubric_json
+        .as_deref()
+        .and_then(|j| serde_json::from_str(j).ok())
+        .unwrap_or(serde_json::json!({}));
+    // Rubric order, so every TA's questions line up
+    let questions: Vec<(String, f64)> = rubric["questions"]
+        .as_array()
+        .map(|qs| {
+            qs.iter()
+                .filter_map(|q| Some((q["question_id"].as_str()?.to_string(), q["max_points"].as_f64().unwrap_or(0.0))))
+                .collect()
+        })
+        .unwrap_or_default();
+    let max_points: std::collections::HashMap<&str, f64> = questions.iter().map(|(id, max)| (id.as_str(), *max)).collect();
+
+    let rows: Vec<(String, Option<String>, String, String, f64)> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(g.updated_by_ta_id, sub.claimed_by_ta_id) as ta_id, ta.display_name,
+               g.submission_id, g.question_id, g.score
+        FROM grades g
+        JOIN submissions sub ON g.submission_id = sub.id
+        LEFT JOIN tas ta ON ta.id = COALESCE(g.updated_by_ta_id, sub.claimed_by_ta_id)
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+          AND g.score IS NOT NULL
+          AND COALESCE(g.updated_by_ta_id, sub.claimed_by_ta_id) IS NOT NULL
+        ORDER BY ta.display_name, ta_id
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let question_averages = |scores: &[(&str, f64)]| -> Vec<QuestionAverage> {
+        questions
+            .iter()
+            .filter_map(|(q_id, max)| {
+                let vals: Vec<f64> = scores.iter().filter(|(q, _)| q == q_id).map(|(_, v)| *v).collect();
+                if vals.is_empty() {
+                    return None;
+                }
+                let average_score = vals.iter().sum::<f64>() / vals.len() as f64;
+                Some(QuestionAverage {
+                    question_id: q_id.clone(),
+                    count: vals.len(),
+                    average_score,
+                    average_percent: (*max > 0.0).then(|| average_score / max),
+                })
+            })
+            .collect()
+    };
+
+    let all_scores: Vec<(&str, f64)> = rows.iter().map(|(_, _, _, q, v)| (q.as_str(), *v)).collect();
+    let overall = question_averages(&all_scores);
+
+    let mut tas: Vec<TaGraderStats> = Vec::new();
+    let mut ta_ids: Vec<&str> = Vec::new();
+    for (ta_id, _, _, _, _) in &rows {
+        if !ta_ids.contains(&ta_id.as_str()) {
+            ta_ids.push(ta_id);
+        }
+    }
+    for ta_id in ta_ids {
+        let mine: Vec<&(String, Option<String>, String, String, f64)> = rows.iter().filter(|r| r.0 == ta_id).collect();
+        let scores: Vec<(&str, f64)> = mine.iter().map(|r| (r.3.as_str(), r.4)).collect();
+        let submissions: std::collections::HashSet<&str> = mine.iter().map(|r| r.2.as_str()).collect();
+
+        let fractions: Vec<f64> = scores
+            .iter()
+            .filter_map(|(q, v)| max_points.get(q).filter(|m| **m > 0.0).map(|m| v / m))
+            .collect();
+        let (average_percent, variance_percent) = if fractions.is_empty() {
+            (None, None)
+        } else {
+            let n = fractions.len() as f64;
+            let mean = fractions.iter().sum::<f64>() / n;
+            let variance = fractions.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / n;
+            (Some(mean), Some(variance))
+        };
+
+        tas.push(TaGraderStats {
+            ta_id: ta_id.to_string(),
+            display_name: mine[0].1.clone(),
+            submissions_graded: submissions.len(),
+            scores_count: scores.len(),
+            average_percent,
+            variance_percent,
+            questions: question_averages(&scores),
+        });
+    }
+
+    Ok(GraderStats { overall, tas })
 }
 
 /// Claim a submission for grading (TA lock)
 #[tauri::command]
 pub async fn claim_submission(
-    pool: State<'_, DbPool>,
+    app: AppHandle,
+    db: State<'_, DbState>,
     submission_id: String,
     ta_id: String,
+    session_token: Option<String>,
 ) -> Result<bool, String> {
-    // Check if already claimed by another TA
-    let current_claim: Option<String> = sqlx::query_scalar(
-        "SELECT claimed_by_ta_id FROM submissions WHERE id = ?"
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    if take_claim(&pool, &submission_id, &ta_id).await? {
+        log_audit_internal(&pool, Some(&ta_id), "claim", "submission", &submission_id, None).await?;
+        note_activity(&app, &pool, &submission_id, &ta_id, "claimed").await?;
+    }
+    Ok(true)
+}
+
+/// Give `ta_id` the claim on a submission. Returns false if they already held
+/// it, and an error naming the owner if another TA does.
+async fn take_claim(pool: &DbPool, submission_id: &str, ta_id: &str) -> Result<bool, String> {
+    // Claim only if unclaimed, in one statement, so two TAs can't both win
+    let result = sqlx::query(
+        "UPDATE submissions SET claimed_by_ta_id = ?, claimed_at = CURRENT_TIMESTAMP, status = 'in_progress' WHERE id = ? AND claimed_by_ta_id IS NULL"
     )
-    .bind(&submission_id)
-    .fetch_optional(&*pool)
+    .bind(ta_id)
+    .bind(submission_id)
+    .execute(pool)
     .await
-    .map_err(|e| e.to_string())?
-    .flatten();
-    
-    if let Some(existing) = current_claim {
-        if existing != ta_id {
-            return Err(format!("Submission already claimed by another TA"));
-        }
-        // Already claimed by this TA
+    .map_err(|e| e.to_string())?;
+    if result.rows_affected() > 0 {
         return Ok(true);
     }
-    
-    // Claim it
-    sqlx::query(
-        "UPDATE submissions SET claimed_by_ta_id = ?, claimed_at = CURRENT_TIMESTAMP, status = 'in_progress' WHERE id = ?"
+
+    // Someone holds it (possibly us) - find out who
+    let owner: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT sub.claimed_by_ta_id, ta.display_name FROM submissions sub LEFT JOIN tas ta ON ta.id = sub.claimed_by_ta_id WHERE sub.id = ?"
     )
-    .bind(&ta_id)
-    .bind(&submission_id)
-    .execute(&*pool)
+    .bind(submission_id)
+    .fetch_optional(pool)
     .await
     .map_err(|e| e.to_string())?;
-    
-    // Log audit
-    log_audit_internal(&pool, Some(&ta_id), "claim", "submission", &submission_id, None).await?;
-    
-    Ok(true)
+
+    match owner {
+        None => Err("Submission not found".to_string()),
+        // Already claimed by this TA
+        Some((Some(existing), _)) if existing == ta_id => Ok(false),
+        Some((Some(existing), name)) => Err(format!(
+            "Submission already claimed by {}",
+            name.unwrap_or(existing)
+        )),
+        Some((None, _)) => Err("Submission was released while claiming; try again".to_string()),
+    }
 }
 
 /// Release a submission claim
 #[tauri::command]
 pub async fn release_submission(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     ta_id: String,
+    session_token: Option<String>,
 ) -> Result<bool, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
     // Verify ownership
     let current_claim: Option<String> = sqlx::query_scalar(
         "SELECT claimed_by_ta_id FROM submissions WHERE id = ?"
     )
     .bind(&submission_id)
-    .fetch_optional(&*pool)
+    .fetch_optional(&pool)
     .await
     .map_err(|e| e.to_string())?
     .flatten();
@@ -122,7 +673,7 @@ pub async fn release_submission(
         "UPDATE submissions SET claimed_by_ta_id = NULL, claimed_at = NULL WHERE id = ?"
     )
     .bind(&submission_id)
-    .execute(&*pool)
+    .execute(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
@@ -131,32 +682,85 @@ pub async fn release_submission(
     Ok(true)
 }
 
+/// Release claims left behind by TAs who stopped grading (e.g. closed the app):
+/// in-progress submissions claimed more than `older_than_minutes` ago.
+/// Returns the IDs of the released submissions.
+#[tauri::command]
+pub async fn release_stale_claims(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    older_than_minutes: i64,
+) -> Result<Vec<String>, String> {
+    let pool = db.pool()?;
+    let cutoff = format!("-{} minutes", older_than_minutes.max(0));
+    let stale: Vec<(String, String, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT id, claimed_by_ta_id, claimed_at FROM submissions
+        WHERE assignment_id = ?
+          AND status = 'in_progress'
+          AND claimed_by_ta_id IS NOT NULL
+          AND claimed_at < datetime('now', ?)
+        "#
+    )
+    .bind(&assignment_id)
+    .bind(&cutoff)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut released = Vec::new();
+    for (submission_id, ta_id, claimed_at) in stale {
+        // Only release if nobody re-claimed it since the select
+        let result = sqlx::query(
+            "UPDATE submissions SET claimed_by_ta_id = NULL, claimed_at = NULL WHERE id = ? AND claimed_by_ta_id = ? AND claimed_at IS ?"
+        )
+        .bind(&submission_id)
+        .bind(&ta_id)
+        .bind(&claimed_at)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if result.rows_affected() == 0 {
+            continue;
+        }
+
+        let details = serde_json::json!({
+            "previous_ta_id": ta_id,
+            "claimed_at": claimed_at,
+            "older_than_minutes": older_than_minutes,
+        })
+        .to_string();
+        log_audit_internal(&pool, None, "release_stale_claim", "submission", &submission_id, Some(&details)).await?;
+        released.push(submission_id);
+    }
+
+    Ok(released)
+}
+
 /// Force takeover of a submission (admin action, logged)
 #[tauri::command]
 pub async fn force_claim_submission(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     ta_id: String,
+    session_token: Option<String>,
 ) -> Result<bool, String> {
-    let prev_claim: Option<String> = sqlx::query_scalar(
-        "SELECT claimed_by_ta_id FROM submissions WHERE id = ?"
-    )
-    .bind(&submission_id)
-    .fetch_optional(&*pool)
-    .await
-    .map_err(|e| e.to_string())?
-    .flatten();
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let previous = submission_state(&pool, &submission_id).await?;
+    let prev_claim = previous["claimed_by_ta_id"].clone();
     
     sqlx::query(
         "UPDATE submissions SET claimed_by_ta_id = ?, claimed_at = CURRENT_TIMESTAMP WHERE id = ?"
     )
     .bind(&ta_id)
     .bind(&submission_id)
-    .execute(&*pool)
+    .execute(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
-    let details = serde_json::json!({ "previous_ta": prev_claim }).to_string();
+    let after = submission_state(&pool, &submission_id).await?;
+    let details = serde_json::json!({ "previous_ta": prev_claim, "previous": previous, "after": after }).to_string();
     log_audit_internal(&pool, Some(&ta_id), "force_claim", "submission", &submission_id, Some(&details)).await?;
     
     Ok(true)
@@ -165,37 +769,128 @@ pub async fn force_claim_submission(
 /// Update submission status
 #[tauri::command]
 pub async fn update_submission_status(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     status: String,
     ta_id: Option<String>,
+    session_token: Option<String>,
 ) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
     // Validate status
     let valid = ["unstarted", "in_progress", "done", "flagged", "error"];
     if !valid.contains(&status.as_str()) {
         return Err(format!("Invalid status: {}", status));
     }
     
+    let previous = submission_state(&pool, &submission_id).await?;
+    
     sqlx::query("UPDATE submissions SET status = ? WHERE id = ?")
         .bind(&status)
         .bind(&submission_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
-    let details = serde_json::json!({ "new_status": status }).to_string();
+    let after = submission_state(&pool, &submission_id).await?;
+    let details = serde_json::json!({ "new_status": status, "previous": previous, "after": after }).to_string();
     log_audit_internal(&pool, ta_id.as_deref(), "status_change", "submission", &submission_id, Some(&details)).await?;
     
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+pub struct UngradedQuestion {
+    pub question_id: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompletionResult {
+    pub completed: bool,
+    /// Rubric questions still without a score; empty when `completed`
+    pub missing: Vec<UngradedQuestion>,
+}
+
+/// Mark a submission `done`, but only once every rubric question has a score.
+/// Otherwise nothing changes and the ungraded questions are returned.
+#[tauri::command]
+pub async fn complete_submission(
+    db: State<'_, DbState>,
+    submission_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<CompletionResult, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let (claimed_by, rubric_json): (Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT s.claimed_by_ta_id, a.rubric_json FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+    if claimed_by.as_deref().is_some_and(|owner| owner != ta_id) {
+        return Err("Submission is claimed by another TA".to_string());
+    }
+
+    let graded: std::collections::HashSet<String> = sqlx::query_scalar(
+        "SELECT question_id FROM grades WHERE submission_id = ? AND score IS NOT NULL"
+    )
+    .bind(&submission_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .collect();
+
+    let rubric: serde_json::Value = rubric_json
+        .as_deref()
+        .and_then(|j| serde_json::from_str(j).ok())
+        .unwrap_or(serde_json::json!({}));
+    let missing: Vec<UngradedQuestion> = rubric["questions"]
+        .as_array()
+        .map(|qs| qs.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|q| {
+            let question_id = q["question_id"].as_str()?;
+            (!graded.contains(question_id)).then(|| UngradedQuestion {
+                question_id: question_id.to_string(),
+                title: q["title"].as_str().map(String::from),
+            })
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Ok(CompletionResult { completed: false, missing });
+    }
+
+    let previous = submission_state(&pool, &submission_id).await?;
+    sqlx::query("UPDATE submissions SET status = 'done' WHERE id = ?")
+        .bind(&submission_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Logged as a status change so undo_last_action can reopen it
+    let after = submission_state(&pool, &submission_id).await?;
+    let details = serde_json::json!({ "new_status": "done", "previous": previous, "after": after, "verified_complete": true }).to_string();
+    log_audit_internal(&pool, Some(&ta_id), "status_change", "submission", &submission_id, Some(&details)).await?;
+
+    Ok(CompletionResult { completed: true, missing })
+}
+
 /// Get session bookmark for resuming
 #[tauri::command]
 pub async fn get_session_bookmark(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     ta_id: String,
+    session_token: Option<String>,
     assignment_id: String,
 ) -> Result<SessionBookmark, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
     // Find the last submission this TA was working on
     let last_sub: Option<String> = sqlx::query_scalar(
         r#"
@@ -209,7 +904,7 @@ pub async fn get_session_bookmark(
     )
     .bind(&assignment_id)
     .bind(&ta_id)
-    .fetch_optional(&*pool)
+    .fetch_optional(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
@@ -219,74 +914,301 @@ pub async fn get_session_bookmark(
     })
 }
 
-/// Mark submission as last opened (for session resume)
+/// Mark submission as last opened (for session resume). With a `ta_id`,
+/// also tells other open clients that this TA is looking at it.
 #[tauri::command]
 pub async fn touch_submission(
-    pool: State<'_, DbPool>,
+    app: AppHandle,
+    db: State<'_, DbState>,
     submission_id: String,
+    ta_id: Option<String>,
+    session_token: Option<String>,
 ) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
     sqlx::query("UPDATE submissions SET last_opened_at = CURRENT_TIMESTAMP WHERE id = ?")
         .bind(&submission_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
+    if let Some(ta_id) = ta_id {
+        note_activity(&app, &pool, &submission_id, &ta_id, "viewing").await?;
+    }
     Ok(())
 }
 
-// --- Audit Logging ---
+// --- Concurrent Viewers ---
 
-async fn log_audit_internal(
-    pool: &DbPool,
-    ta_id: Option<&str>,
-    action: &str,
-    entity_type: &str,
-    entity_id: &str,
-    details: Option<&str>,
-) -> Result<(), String> {
+/// Emitted when a TA opens or claims a submission
+pub const SUBMISSION_ACTIVITY_EVENT: &str = "submission-activity";
+
+/// A TA counts as still viewing a submission for this long after opening it
+const ACTIVE_VIEWER_MINUTES: i64 = 10;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SubmissionActivity {
+    pub submission_id: String,
+    pub ta_id: String,
+    pub ta_name: Option<String>,
+    /// "viewing" or "claimed"
+    pub action: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ActiveViewer {
+    pub ta_id: String,
+    pub display_name: Option<String>,
+    pub last_seen_at: String,
+}
+
+async fn note_activity<R: Runtime>(app: &AppHandle<R>, pool: &DbPool, submission_id: &str, ta_id: &str, action: &str) -> Result<(), String> {
     sqlx::query(
-        "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, ?, ?, ?, ?)"
+        r#"
+        INSERT INTO submission_viewers (submission_id, ta_id, last_seen_at) VALUES (?, ?, CURRENT_TIMESTAMP)
+        ON CONFLICT(submission_id, ta_id) DO UPDATE SET last_seen_at = excluded.last_seen_at
+        "#
     )
+    .bind(submission_id)
     .bind(ta_id)
-    .bind(action)
-    .bind(entity_type)
-    .bind(entity_id)
-    .bind(details)
     .execute(pool)
     .await
     .map_err(|e| e.to_string())?;
+
+    let ta_name: Option<String> = sqlx::query_scalar("SELECT display_name FROM tas WHERE id = ?")
+        .bind(ta_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = app.emit(SUBMISSION_ACTIVITY_EVENT, SubmissionActivity {
+        submission_id: submission_id.to_string(),
+        ta_id: ta_id.to_string(),
+        ta_name,
+        action: action.to_string(),
+    });
     Ok(())
 }
 
+/// TAs who opened or claimed the submission recently, most recent first
 #[tauri::command]
-pub async fn log_audit(
-    pool: State<'_, DbPool>,
-    ta_id: Option<String>,
-    action: String,
-    entity_type: String,
+pub async fn get_active_viewers(
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<Vec<ActiveViewer>, String> {
+    let pool = db.pool()?;
+    sqlx::query_as::<sqlx::Sqlite, ActiveViewer>(
+        r#"
+        SELECT v.ta_id, ta.display_name, v.last_seen_at
+        FROM submission_viewers v
+        LEFT JOIN tas ta ON ta.id = v.ta_id
+        WHERE v.submission_id = ? AND v.last_seen_at >= datetime('now', ?)
+        ORDER BY v.last_seen_at DESC
+        "#
+    )
+    .bind(&submission_id)
+    .bind(format!("-{} minutes", ACTIVE_VIEWER_MINUTES))
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// --- Audit Logging ---
+
+pub(crate) async fn log_audit_internal(
+    pool: &DbPool,
+    ta_id: Option<&str>,
+    action: &str,
+    entity_type: &str,
+    entity_id: &str,
+    details: Option<&str>,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(ta_id)
+    .bind(action)
+    .bind(entity_type)
+    .bind(entity_id)
+    .bind(details)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Audit actions that `undo_last_action` can revert. Each stores the
+/// submission's prior state under `previous` and the state it left behind
+/// under `after` in its details.
+const REVERSIBLE_ACTIONS: [&str; 3] = ["status_change", "quarantine", "force_claim"];
+
+/// Snapshot of the submission fields reversible actions change
+async fn submission_state(pool: &DbPool, submission_id: &str) -> Result<serde_json::Value, String> {
+    let (status, notes, claimed_by, claimed_at): (String, Option<String>, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT status, notes, claimed_by_ta_id, claimed_at FROM submissions WHERE id = ?"
+    )
+    .bind(submission_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+    
+    Ok(serde_json::json!({
+        "status": status,
+        "notes": notes,
+        "claimed_by_ta_id": claimed_by,
+        "claimed_at": claimed_at,
+    }))
+}
+
+/// Revert the most recent reversible action on a submission that hasn't
+/// already been undone. Refused if the submission has changed since that
+/// action. Returns the action that was undone.
+#[tauri::command]
+pub async fn undo_last_action(
+    db: State<'_, DbState>,
+    submission_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let last: Option<(i64, String, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT id, action, details_json FROM audit_log
+        WHERE entity_type = 'submission' AND entity_id = ?
+          AND action IN (SELECT value FROM json_each(?))
+          AND id NOT IN (
+            SELECT json_extract(details_json, '$.undone_audit_id') FROM audit_log
+            WHERE action = 'undo' AND entity_id = ? AND json_valid(details_json)
+              AND json_extract(details_json, '$.undone_audit_id') IS NOT NULL
+          )
+        ORDER BY id DESC
+        LIMIT 1
+        "#
+    )
+    .bind(&submission_id)
+    .bind(serde_json::to_string(&REVERSIBLE_ACTIONS).map_err(|e| e.to_string())?)
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    let (audit_id, action, details_json) = last.ok_or("Nothing to undo")?;
+    let details: serde_json::Value = details_json
+        .as_deref()
+        .and_then(|d| serde_json::from_str(d).ok())
+        .unwrap_or_default();
+    let previous = &details["previous"];
+    let after = &details["after"];
+    let status = previous["status"]
+        .as_str()
+        .ok_or("Cannot undo: this change was recorded without its previous state")?;
+    if after["status"].as_str().is_none() {
+        return Err("Cannot undo: this change was recorded without its resulting state".to_string());
+    }
+    
+    // Only restore if the submission is still exactly as the action left it, so
+    // undoing never wipes out another TA's later claim, notes or status change
+    let restored = sqlx::query(
+        r#"
+        UPDATE submissions SET status = ?, notes = ?, claimed_by_ta_id = ?, claimed_at = ?
+        WHERE id = ? AND status = ? AND notes IS ? AND claimed_by_ta_id IS ? AND claimed_at IS ?
+        "#
+    )
+    .bind(status)
+    .bind(previous["notes"].as_str())
+    .bind(previous["claimed_by_ta_id"].as_str())
+    .bind(previous["claimed_at"].as_str())
+    .bind(&submission_id)
+    .bind(after["status"].as_str())
+    .bind(after["notes"].as_str())
+    .bind(after["claimed_by_ta_id"].as_str())
+    .bind(after["claimed_at"].as_str())
+    .execute(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if restored.rows_affected() == 0 {
+        return Err(format!("Cannot undo {}: the submission has changed since", action));
+    }
+    
+    let undo_details = serde_json::json!({ "undone_audit_id": audit_id, "undone_action": action }).to_string();
+    log_audit_internal(&pool, Some(&ta_id), "undo", "submission", &submission_id, Some(&undo_details)).await?;
+    
+    Ok(action)
+}
+
+#[tauri::command]
+pub async fn log_audit(
+    db: State<'_, DbState>,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+    action: String,
+    entity_type: String,
     entity_id: String,
     details: Option<String>,
 ) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
     log_audit_internal(&pool, ta_id.as_deref(), &action, &entity_type, &entity_id, details.as_deref()).await
 }
 
-/// Get audit log entries
+#[derive(Debug, Serialize)]
+pub struct AuditLogPage {
+    /// Matching entries across all pages
+    pub total_count: i64,
+    pub entries: Vec<serde_json::Value>,
+}
+
+/// Get audit log entries, newest first. All filters are optional;
+/// `since`/`until` are inclusive timestamps (`YYYY-MM-DD HH:MM:SS`).
 #[tauri::command]
 pub async fn get_audit_log(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     limit: i32,
-) -> Result<Vec<serde_json::Value>, String> {
-    let rows = sqlx::query(
-        "SELECT id, ts, ta_id, action, entity_type, entity_id, details_json FROM audit_log ORDER BY ts DESC LIMIT ?"
-    )
+    offset: Option<i32>,
+    ta_id: Option<String>,
+    entity_type: Option<String>,
+    entity_id: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<AuditLogPage, String> {
+    let pool = db.pool()?;
+    const FILTER: &str = r#"
+        WHERE (?1 IS NULL OR ta_id = ?1)
+          AND (?2 IS NULL OR entity_type = ?2)
+          AND (?3 IS NULL OR entity_id = ?3)
+          AND (?4 IS NULL OR ts >= ?4)
+          AND (?5 IS NULL OR ts <= ?5)
+    "#;
+    
+    let total_count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM audit_log {}", FILTER))
+        .bind(&ta_id)
+        .bind(&entity_type)
+        .bind(&entity_id)
+        .bind(&since)
+        .bind(&until)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    let rows = sqlx::query(&format!(
+        "SELECT id, ts, ta_id, action, entity_type, entity_id, details_json FROM audit_log {} ORDER BY ts DESC, id DESC LIMIT ?6 OFFSET ?7",
+        FILTER
+    ))
+    .bind(&ta_id)
+    .bind(&entity_type)
+    .bind(&entity_id)
+    .bind(&since)
+    .bind(&until)
     .bind(limit)
-    .fetch_all(&*pool)
+    .bind(offset.unwrap_or(0))
+    .fetch_all(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
-    let mut result = Vec::new();
+    let mut entries = Vec::new();
     for row in rows {
         use sqlx::Row;
-        result.push(serde_json::json!({
+        entries.push(serde_json::json!({
             "id": row.get::<i64, _>("id"),
             "ts": row.get::<String, _>("ts"),
             "ta_id": row.get::<Option<String>, _>("ta_id"),
@@ -297,7 +1219,105 @@ pub async fn get_audit_log(
         }));
     }
     
-    Ok(result)
+    Ok(AuditLogPage { total_count, entries })
+}
+
+// --- Search ---
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct GradeSearchHit {
+    pub submission_id: String,
+    pub student_id: Option<String>,
+    pub student_name: Option<String>,
+    /// The question whose comment matched, or None for a student name/ID match
+    pub question_id: Option<String>,
+    pub snippet: String,
+    #[serde(skip)]
+    pub anon_seq: i64,
+}
+
+/// Characters of context kept on each side of a match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Cut a short window of `text` around the first case-insensitive match of `needle`
+fn snippet_around(text: &str, needle: &str) -> String {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let chars: Vec<char> = text.chars().collect();
+    let hay: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+    let pat: Vec<char> = needle.chars().map(fold).collect();
+    
+    let pos = if pat.is_empty() { None } else { hay.windows(pat.len()).position(|w| w == pat.as_slice()) };
+    let Some(pos) = pos else {
+        return chars.iter().take(SNIPPET_CONTEXT * 2).collect();
+    };
+    let start = pos.saturating_sub(SNIPPET_CONTEXT);
+    let end = (pos + pat.len() + SNIPPET_CONTEXT).min(chars.len());
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    out.extend(&chars[start..end]);
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// Find submissions in an assignment by grade comment, student name, or student ID
+/// (case-insensitive substring match). Anonymous assignments only search comments
+/// and show labels instead of students.
+#[tauri::command]
+pub async fn search_grades(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    query: String,
+) -> Result<Vec<GradeSearchHit>, String> {
+    let pool = db.pool()?;
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let pattern = format!("%{}%", escaped);
+    let anonymous = is_anonymous(&pool, &assignment_id).await?;
+    
+    let mut hits = sqlx::query_as::<sqlx::Sqlite, GradeSearchHit>(
+        r#"
+        SELECT sub.id as submission_id, sub.student_id, st.name as student_name,
+            g.question_id, g.comment as snippet, sub.anon_seq
+        FROM grades g
+        JOIN submissions sub ON sub.id = g.submission_id
+        JOIN assignments a ON a.id = sub.assignment_id
+        LEFT JOIN students st ON st.student_id = sub.student_id AND st.course_id = a.course_id
+        WHERE sub.assignment_id = ?1 AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+          AND g.comment LIKE ?2 ESCAPE '\'
+        UNION ALL
+        SELECT sub.id, sub.student_id, st.name, NULL, COALESCE(st.name, '') || ' (' || sub.student_id || ')', sub.anon_seq
+        FROM submissions sub
+        JOIN assignments a ON a.id = sub.assignment_id
+        LEFT JOIN students st ON st.student_id = sub.student_id AND st.course_id = a.course_id
+        WHERE sub.assignment_id = ?1 AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+          AND NOT ?3 AND (st.name LIKE ?2 ESCAPE '\' OR sub.student_id LIKE ?2 ESCAPE '\')
+        "#
+    )
+    .bind(&assignment_id)
+    .bind(&pattern)
+    .bind(anonymous)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    for hit in &mut hits {
+        if hit.question_id.is_some() {
+            hit.snippet = snippet_around(&hit.snippet, query);
+        }
+        if anonymous {
+            hit.student_id = None;
+            hit.student_name = Some(pseudonym(hit.anon_seq));
+        }
+    }
+    
+    Ok(hits)
 }
 
 // --- Session Bookmarks (Enhanced) ---
@@ -313,34 +1333,37 @@ pub struct EnhancedSessionBookmark {
 /// Save session bookmark with question index
 #[tauri::command]
 pub async fn save_session_bookmark(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     ta_id: String,
+    session_token: Option<String>,
     assignment_id: String,
     submission_id: String,
     question_index: i32,
 ) -> Result<(), String> {
-    // Upsert into a session_bookmarks table (or use key-value approach)
-    // For simplicity, we'll use the audit log with a special action type
-    // OR we can create a lightweight table. Let's use a simple approach:
-    // Store in submissions.notes as JSON for the TA's last position
-    
-    // Actually, let's just update last_opened_at and store question_index in a simple table
-    // For now, we'll use the existing touch + store question_index in local storage (frontend)
-    // OR we add a new column. Let's add to existing submissions table a simple approach.
-    
-    // Simplest: Store in audit_log with action = "session_bookmark"
-    let details = serde_json::json!({
-        "assignment_id": assignment_id,
-        "submission_id": submission_id,
-        "question_index": question_index
-    }).to_string();
-    
-    log_audit_internal(&pool, Some(&ta_id), "session_bookmark", "session", &assignment_id, Some(&details)).await?;
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO session_bookmarks (ta_id, assignment_id, submission_id, question_index, updated_at)
+        VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)
+        ON CONFLICT(ta_id, assignment_id) DO UPDATE SET
+            submission_id = excluded.submission_id,
+            question_index = excluded.question_index,
+            updated_at = excluded.updated_at
+        "#
+    )
+    .bind(&ta_id)
+    .bind(&assignment_id)
+    .bind(&submission_id)
+    .bind(question_index)
+    .execute(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
     
     // Also touch the submission
     sqlx::query("UPDATE submissions SET last_opened_at = CURRENT_TIMESTAMP WHERE id = ?")
         .bind(&submission_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
@@ -350,33 +1373,33 @@ pub async fn save_session_bookmark(
 /// Get last session bookmark for a TA on an assignment
 #[tauri::command]
 pub async fn get_last_session_bookmark(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     ta_id: String,
+    session_token: Option<String>,
     assignment_id: String,
 ) -> Result<EnhancedSessionBookmark, String> {
-    // Find the most recent session_bookmark audit entry
-    let row: Option<(String, String)> = sqlx::query_as(
-        "SELECT entity_id, details_json FROM audit_log WHERE ta_id = ? AND action = 'session_bookmark' AND entity_id = ? ORDER BY ts DESC LIMIT 1"
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let row: Option<(Option<String>, i32, Option<String>)> = sqlx::query_as(
+        "SELECT submission_id, question_index, updated_at FROM session_bookmarks WHERE ta_id = ? AND assignment_id = ?"
     )
     .bind(&ta_id)
     .bind(&assignment_id)
-    .fetch_optional(&*pool)
+    .fetch_optional(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
-    if let Some((_entity, details_json)) = row {
-        if let Ok(details) = serde_json::from_str::<serde_json::Value>(&details_json) {
-            return Ok(EnhancedSessionBookmark {
-                assignment_id,
-                submission_id: details["submission_id"].as_str().map(|s| s.to_string()),
-                question_index: details["question_index"].as_i64().unwrap_or(0) as i32,
-                last_saved_at: Some(details_json),
-            });
-        }
+    if let Some((submission_id, question_index, updated_at)) = row {
+        return Ok(EnhancedSessionBookmark {
+            assignment_id,
+            submission_id,
+            question_index,
+            last_saved_at: updated_at,
+        });
     }
     
     // Fallback to basic bookmark
-    let basic = get_session_bookmark(pool.clone(), ta_id, assignment_id.clone()).await?;
+    let basic = get_session_bookmark(db.clone(), ta_id, session_token, assignment_id.clone()).await?;
     Ok(EnhancedSessionBookmark {
         assignment_id,
         submission_id: basic.submission_id,
@@ -394,44 +1417,205 @@ pub struct UnmatchedSubmission {
     pub folder_path: String,
     pub received_at: String,
     pub suggested_student_id: Option<String>,
+    pub suggested_confidence: Option<f64>,
 }
 
-/// Get all unmatched submissions for an assignment
+/// Get all unmatched submissions for an assignment, each with a best-guess
+/// student from the same heuristics used at import (when one clearly wins)
 #[tauri::command]
 pub async fn get_unmatched_submissions(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     assignment_id: String,
 ) -> Result<Vec<UnmatchedSubmission>, String> {
-    let items = sqlx::query_as::<sqlx::Sqlite, UnmatchedSubmission>(
+    let pool = db.pool()?;
+    let mut items = sqlx::query_as::<sqlx::Sqlite, UnmatchedSubmission>(
         r#"
-        SELECT id, source_zip_path, folder_path, received_at, NULL as suggested_student_id
+        SELECT id, source_zip_path, folder_path, received_at,
+            NULL as suggested_student_id, NULL as suggested_confidence
         FROM submissions 
-        WHERE assignment_id = ? AND student_id IS NULL
+        WHERE assignment_id = ? AND student_id IS NULL AND deleted_at IS NULL
         ORDER BY received_at ASC
         "#
     )
     .bind(&assignment_id)
-    .fetch_all(&*pool)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    if items.is_empty() {
+        return Ok(items);
+    }
+    
+    let (course_id, id_pattern, lms_convention): (String, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT c.id, c.id_pattern, c.lms_convention FROM courses c JOIN assignments a ON a.course_id = c.id WHERE a.id = ?"
+    )
+    .bind(&assignment_id)
+    .fetch_one(&pool)
     .await
     .map_err(|e| e.to_string())?;
+    let id_regex = crate::submissions::compile_id_pattern(id_pattern.as_deref())?;
+    let lms_naming = crate::submissions::compile_lms_naming(lms_convention.as_deref())?;
+    let roster = crate::submissions::load_roster(&pool, &course_id).await?;
+    
+    for item in &mut items {
+        let filename = std::path::Path::new(&item.source_zip_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let m = crate::submissions::match_student(&filename, std::path::Path::new(&item.folder_path), &id_regex, &roster, lms_naming.as_ref());
+        if m.student_id.is_some() {
+            item.suggested_student_id = m.student_id;
+            item.suggested_confidence = Some(m.confidence);
+        }
+    }
     
     Ok(items)
 }
 
+#[derive(Debug, Serialize)]
+pub struct RematchedSubmission {
+    pub submission_id: String,
+    pub student_id: String,
+    pub method: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RematchConflict {
+    pub submission_id: String,
+    pub student_id: String,
+    /// The student's current submission, which this one would otherwise collide with
+    pub existing_submission_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RematchSummary {
+    pub checked: usize,
+    pub matched: Vec<RematchedSubmission>,
+    /// Matched a student who already has a current submission; left for a TA to sort out
+    pub conflicts: Vec<RematchConflict>,
+    pub still_unmatched: usize,
+}
+
+/// Re-run the import matching against the current roster for every unmatched
+/// submission, e.g. after a roster fix. Confident matches are applied; a match
+/// onto a student who already has a submission is reported instead. Quarantined
+/// submissions are left alone until a TA releases them.
+#[tauri::command]
+pub async fn rematch_unmatched(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    ta_id: Option<String>,
+    session_token: Option<String>,
+) -> Result<RematchSummary, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_acting_ta(&pool, ta_id.as_deref(), session_token.as_deref()).await?;
+    let (course_id, id_pattern, lms_convention): (String, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT c.id, c.id_pattern, c.lms_convention FROM courses c JOIN assignments a ON a.course_id = c.id WHERE a.id = ?"
+    )
+    .bind(&assignment_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Assignment not found")?;
+    let id_regex = crate::submissions::compile_id_pattern(id_pattern.as_deref())?;
+    let lms_naming = crate::submissions::compile_lms_naming(lms_convention.as_deref())?;
+    let roster = crate::submissions::load_roster(&pool, &course_id).await?;
+
+    let unmatched: Vec<(String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT id, source_zip_path, folder_path
+        FROM submissions
+        WHERE assignment_id = ? AND student_id IS NULL AND deleted_at IS NULL AND superseded_by IS NULL
+          AND status != 'error'
+        ORDER BY received_at ASC
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut summary = RematchSummary { checked: unmatched.len(), matched: Vec::new(), conflicts: Vec::new(), still_unmatched: 0 };
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    for (submission_id, source_zip_path, folder_path) in unmatched {
+        let filename = std::path::Path::new(&source_zip_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let m = crate::submissions::match_student(&filename, std::path::Path::new(&folder_path), &id_regex, &roster, lms_naming.as_ref());
+        let Some(student_id) = m.student_id else {
+            summary.still_unmatched += 1;
+            continue;
+        };
+
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM submissions WHERE assignment_id = ? AND student_id = ? AND deleted_at IS NULL AND superseded_by IS NULL"
+        )
+        .bind(&assignment_id)
+        .bind(&student_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Some(existing_submission_id) = existing {
+            summary.conflicts.push(RematchConflict { submission_id, student_id, existing_submission_id });
+            continue;
+        }
+
+        sqlx::query("UPDATE submissions SET student_id = ?, match_method = ?, match_confidence = ? WHERE id = ?")
+            .bind(&student_id)
+            .bind(m.method)
+            .bind(m.confidence)
+            .bind(&submission_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let details = serde_json::json!({
+            "student_id": student_id,
+            "method": m.method,
+            "confidence": m.confidence,
+        })
+        .to_string();
+        sqlx::query(
+            "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, 'auto_match', 'submission', ?, ?)"
+        )
+        .bind(&ta_id)
+        .bind(&submission_id)
+        .bind(&details)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        summary.matched.push(RematchedSubmission {
+            submission_id,
+            student_id,
+            method: m.method.to_string(),
+            confidence: m.confidence,
+        });
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
 /// Manually match a submission to a student
 #[tauri::command]
 pub async fn manual_match_submission(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     student_id: String,
     ta_id: String,
+    session_token: Option<String>,
 ) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
     // Verify student exists
     let course_id: Option<String> = sqlx::query_scalar(
         "SELECT a.course_id FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
     )
     .bind(&submission_id)
-    .fetch_optional(&*pool)
+    .fetch_optional(&pool)
     .await
     .map_err(|e| e.to_string())?;
     
@@ -442,7 +1626,7 @@ pub async fn manual_match_submission(
     )
     .bind(&cid)
     .bind(&student_id)
-    .fetch_optional(&*pool)
+    .fetch_optional(&pool)
     .await
     .map_err(|e| e.to_string())?
     .is_some();
@@ -455,7 +1639,7 @@ pub async fn manual_match_submission(
     sqlx::query("UPDATE submissions SET student_id = ?, match_method = 'manual', match_confidence = 1.0 WHERE id = ?")
         .bind(&student_id)
         .bind(&submission_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
@@ -466,22 +1650,145 @@ pub async fn manual_match_submission(
     Ok(())
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MatchRequest {
+    pub submission_id: String,
+    pub student_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchOutcome {
+    pub submission_id: String,
+    pub student_id: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Match many submissions at once. Every student ID is checked against its
+/// course roster first, then the valid matches are written in one transaction.
+/// With `atomic`, any invalid item means nothing is applied.
+#[tauri::command]
+pub async fn bulk_match_submissions(
+    db: State<'_, DbState>,
+    matches: Vec<MatchRequest>,
+    ta_id: String,
+    session_token: Option<String>,
+    atomic: Option<bool>,
+) -> Result<Vec<MatchOutcome>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let atomic = atomic.unwrap_or(false);
+    let mut rosters: std::collections::HashMap<String, crate::submissions::Roster> = std::collections::HashMap::new();
+    let mut checks: Vec<Result<(), String>> = Vec::with_capacity(matches.len());
+    
+    for m in &matches {
+        let course_id: Option<String> = sqlx::query_scalar(
+            "SELECT a.course_id FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
+        )
+        .bind(&m.submission_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        
+        let Some(cid) = course_id else {
+            checks.push(Err("Submission not found".to_string()));
+            continue;
+        };
+        if !rosters.contains_key(&cid) {
+            let roster = crate::submissions::load_roster(&pool, &cid).await?;
+            rosters.insert(cid.clone(), roster);
+        }
+        if rosters[&cid].contains_key(&m.student_id) {
+            checks.push(Ok(()));
+        } else {
+            checks.push(Err(format!("Student {} not found in roster", m.student_id)));
+        }
+    }
+    
+    let any_invalid = checks.iter().any(|c| c.is_err());
+    if atomic && any_invalid {
+        return Ok(matches
+            .into_iter()
+            .zip(checks)
+            .map(|(m, check)| MatchOutcome {
+                submission_id: m.submission_id,
+                student_id: m.student_id,
+                success: false,
+                error: Some(check.err().unwrap_or_else(|| "Not applied: other matches in the batch are invalid".to_string())),
+            })
+            .collect());
+    }
+    
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let mut outcomes = Vec::with_capacity(matches.len());
+    
+    for (m, check) in matches.into_iter().zip(checks) {
+        let applied = match check {
+            Err(e) => Err(e),
+            Ok(()) => {
+                let details = serde_json::json!({ "student_id": m.student_id, "bulk": true }).to_string();
+                let res = sqlx::query("UPDATE submissions SET student_id = ?, match_method = 'manual', match_confidence = 1.0 WHERE id = ?")
+                    .bind(&m.student_id)
+                    .bind(&m.submission_id)
+                    .execute(&mut *tx)
+                    .await;
+                match res {
+                    Ok(_) => sqlx::query(
+                        "INSERT INTO audit_log (ta_id, action, entity_type, entity_id, details_json) VALUES (?, 'manual_match', 'submission', ?, ?)"
+                    )
+                    .bind(&ta_id)
+                    .bind(&m.submission_id)
+                    .bind(&details)
+                    .execute(&mut *tx)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        };
+        
+        if atomic {
+            if let Err(e) = &applied {
+                // Dropping the transaction rolls back everything applied so far
+                return Err(format!("Bulk match rolled back: {}: {}", m.submission_id, e));
+            }
+        }
+        
+        outcomes.push(MatchOutcome {
+            submission_id: m.submission_id,
+            student_id: m.student_id,
+            success: applied.is_ok(),
+            error: applied.err(),
+        });
+    }
+    
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(outcomes)
+}
+
 /// Skip/quarantine a submission that cannot be matched
 #[tauri::command]
 pub async fn quarantine_submission(
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     submission_id: String,
     reason: String,
     ta_id: String,
+    session_token: Option<String>,
 ) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let previous = submission_state(&pool, &submission_id).await?;
+    
     sqlx::query("UPDATE submissions SET status = 'error', notes = ? WHERE id = ?")
         .bind(&reason)
         .bind(&submission_id)
-        .execute(&*pool)
+        .execute(&pool)
         .await
         .map_err(|e| e.to_string())?;
     
-    let details = serde_json::json!({ "reason": reason }).to_string();
+    let after = submission_state(&pool, &submission_id).await?;
+    let details = serde_json::json!({ "reason": reason, "previous": previous, "after": after }).to_string();
     log_audit_internal(&pool, Some(&ta_id), "quarantine", "submission", &submission_id, Some(&details)).await?;
     
     Ok(())
@@ -566,3 +1873,1492 @@ pub async fn validate_zip(
     })
 }
 
+
+/// An assignment total and the points it is out of
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct FinalScore {
+    pub total: f64,
+    pub out_of: f64,
+}
+
+/// Combine per-question scores into the assignment total. Questions without a
+/// `weight` count their raw points, weighted ones `score / max_points * weight`;
+/// with a rubric `scale` the result is then normalized to that many points.
+/// An unweighted, unscaled rubric is a plain sum of scores.
+pub(crate) fn final_score(
+    questions: &[serde_json::Value],
+    scale: Option<f64>,
+    score_of: impl Fn(&str) -> Option<f64>,
+) -> FinalScore {
+    let mut total = 0.0;
+    let mut out_of = 0.0;
+    for q in questions {
+        let max_pts = q["max_points"].as_f64().unwrap_or(0.0);
+        let score = score_of(q["question_id"].as_str().unwrap_or(""));
+        match q["weight"].as_f64() {
+            Some(weight) => {
+                if let Some(val) = score.filter(|_| max_pts > 0.0) {
+                    total += val / max_pts * weight;
+                }
+                out_of += weight;
+            }
+            None => {
+                total += score.unwrap_or(0.0);
+                out_of += max_pts;
+            }
+        }
+    }
+    match scale {
+        Some(scale) if out_of > 0.0 => FinalScore { total: total / out_of * scale, out_of: scale },
+        _ => FinalScore { total, out_of },
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubmissionFinalScore {
+    pub submission_id: String,
+    pub total: f64,
+    pub out_of: f64,
+    /// `total / out_of`, None when the rubric is worth nothing
+    pub percentage: Option<f64>,
+    pub graded_questions: usize,
+    pub question_count: usize,
+}
+
+/// Weighted, scaled total for one submission as it will appear in the gradebook
+#[tauri::command]
+pub async fn compute_final_score(
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<SubmissionFinalScore, String> {
+    let pool = db.pool()?;
+    let rubric_json: Option<String> = sqlx::query_scalar(
+        "SELECT a.rubric_json FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+    let rubric: serde_json::Value = serde_json::from_str(rubric_json.as_deref().unwrap_or("{}")).unwrap_or(serde_json::json!({}));
+    let questions = rubric["questions"].as_array().cloned().unwrap_or_default();
+
+    let scores: Vec<(String, Option<f64>)> = sqlx::query_as("SELECT question_id, score FROM grades WHERE submission_id = ?")
+        .bind(&submission_id)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let scores: std::collections::HashMap<String, f64> = scores
+        .into_iter()
+        .filter_map(|(q, score)| score.map(|v| (q, v)))
+        .collect();
+
+    let FinalScore { total, out_of } = final_score(&questions, rubric["scale"].as_f64(), |q| scores.get(q).copied());
+    let graded_questions = questions
+        .iter()
+        .filter(|q| scores.contains_key(q["question_id"].as_str().unwrap_or("")))
+        .count();
+
+    Ok(SubmissionFinalScore {
+        submission_id,
+        total,
+        out_of,
+        percentage: (out_of > 0.0).then(|| total / out_of),
+        graded_questions,
+        question_count: questions.len(),
+    })
+}
+
+/// How an assignment penalizes late work. Penalties are a percentage of the
+/// points possible, charged per started day after the due date.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone, Copy)]
+pub struct LatePolicy {
+    pub percent_per_day: f64,
+    /// Submissions this many minutes past the due date are still on time
+    pub grace_minutes: i64,
+    /// Cap on the total penalty, in percent; None = uncapped (up to 100%)
+    pub max_penalty_percent: Option<f64>,
+}
+
+/// Set or clear (with None) an assignment's late policy
+#[tauri::command]
+pub async fn set_late_policy(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    policy: Option<LatePolicy>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    if let Some(p) = &policy {
+        if !p.percent_per_day.is_finite() || p.percent_per_day < 0.0 {
+            return Err("Percent per day must be a non-negative number".to_string());
+        }
+        if p.grace_minutes < 0 {
+            return Err("Grace period cannot be negative".to_string());
+        }
+        if p.max_penalty_percent.is_some_and(|m| !m.is_finite() || !(0.0..=100.0).contains(&m)) {
+            return Err("Max penalty must be between 0 and 100 percent".to_string());
+        }
+    }
+
+    let result = sqlx::query(
+        "UPDATE assignments SET late_percent_per_day = ?, late_grace_minutes = ?, late_max_penalty_percent = ? WHERE id = ?"
+    )
+    .bind(policy.map(|p| p.percent_per_day))
+    .bind(policy.map_or(0, |p| p.grace_minutes))
+    .bind(policy.and_then(|p| p.max_penalty_percent))
+    .bind(&assignment_id)
+    .execute(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err("Assignment not found".to_string());
+    }
+    Ok(())
+}
+
+/// The assignment's due date and late policy, if it has one
+pub(crate) async fn load_late_policy(pool: &DbPool, assignment_id: &str) -> Result<(Option<String>, Option<LatePolicy>), String> {
+    let (due_date, percent_per_day, grace_minutes, max_penalty_percent): (Option<String>, Option<f64>, i64, Option<f64>) = sqlx::query_as(
+        "SELECT due_date, late_percent_per_day, late_grace_minutes, late_max_penalty_percent FROM assignments WHERE id = ?"
+    )
+    .bind(assignment_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Assignment not found")?;
+
+    let policy = percent_per_day.map(|percent_per_day| LatePolicy { percent_per_day, grace_minutes, max_penalty_percent });
+    Ok((due_date, policy))
+}
+
+/// Parse a stored timestamp. RFC 3339 values carry their own offset; bare
+/// date-times (as entered in the UI) are local time, and a bare date means
+/// the end of that day.
+pub(crate) fn parse_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+
+    let value = value.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&chrono::Utc));
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(23, 59, 59))
+        })?;
+    chrono::Local.from_local_datetime(&naive).earliest().map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+/// Lateness of one submission under a policy
+#[derive(Debug, Serialize, Clone, Copy, Default)]
+pub struct Lateness {
+    /// Minutes past the due date (0 if on time)
+    pub minutes_late: i64,
+    /// Days the penalty was charged for; 0 when on time or within grace
+    pub days_charged: i64,
+    pub penalty_percent: f64,
+}
+
+pub(crate) fn compute_lateness(
+    policy: &LatePolicy,
+    due: chrono::DateTime<chrono::Utc>,
+    submitted: chrono::DateTime<chrono::Utc>,
+) -> Lateness {
+    let minutes_late = (submitted - due).num_minutes().max(0);
+    if submitted <= due || minutes_late <= policy.grace_minutes {
+        return Lateness { minutes_late, ..Default::default() };
+    }
+    // Any part of a day counts as a full day, measured from the due date
+    let seconds_late = (submitted - due).num_seconds();
+    let days_charged = (seconds_late + 86_399) / 86_400;
+    let cap = policy.max_penalty_percent.unwrap_or(100.0).min(100.0);
+    let penalty_percent = (days_charged as f64 * policy.percent_per_day).min(cap);
+    Lateness { minutes_late, days_charged, penalty_percent }
+}
+
+impl Lateness {
+    /// Points removed from `score`: `penalty_percent` of the points possible,
+    /// never taking the score below zero
+    pub fn penalty_points(&self, score: FinalScore) -> f64 {
+        (score.out_of * self.penalty_percent / 100.0).min(score.total.max(0.0))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatePenaltyResult {
+    pub submission_id: String,
+    pub due_date: Option<String>,
+    pub submitted_at: Option<String>,
+    pub policy: Option<LatePolicy>,
+    pub lateness: Lateness,
+    pub raw_score: f64,
+    pub out_of: f64,
+    /// Points removed: `penalty_percent` of `out_of`, never more than `raw_score`
+    pub penalty_points: f64,
+    pub adjusted_score: f64,
+}
+
+/// Work out the late penalty for a submission from the due date and its
+/// `submitted_at`. Nothing is stored; missing dates or policy mean no penalty.
+#[tauri::command]
+pub async fn apply_late_penalty(
+    db: State<'_, DbState>,
+    submission_id: String,
+) -> Result<LatePenaltyResult, String> {
+    let pool = db.pool()?;
+    let (assignment_id, submitted_at): (String, Option<String>) =
+        sqlx::query_as("SELECT assignment_id, submitted_at FROM submissions WHERE id = ?")
+            .bind(&submission_id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Submission not found")?;
+    let (due_date, policy) = load_late_policy(&pool, &assignment_id).await?;
+    let score = compute_final_score(db.clone(), submission_id.clone()).await?;
+
+    let lateness = match (&policy, due_date.as_deref().and_then(parse_timestamp), submitted_at.as_deref().and_then(parse_timestamp)) {
+        (Some(policy), Some(due), Some(submitted)) => compute_lateness(policy, due, submitted),
+        _ => Lateness::default(),
+    };
+    let penalty_points = lateness.penalty_points(FinalScore { total: score.total, out_of: score.out_of });
+
+    Ok(LatePenaltyResult {
+        submission_id,
+        due_date,
+        submitted_at,
+        policy,
+        lateness,
+        raw_score: score.total,
+        out_of: score.out_of,
+        penalty_points,
+        adjusted_score: score.total - penalty_points,
+    })
+}
+
+// --- Regrade Requests ---
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RegradeRequest {
+    pub id: String,
+    pub submission_id: String,
+    pub student_id: Option<String>,
+    pub student_name: Option<String>,
+    pub question_id: String,
+    pub reason: String,
+    pub status: String,
+    pub requested_by_ta_id: Option<String>,
+    pub requested_at: String,
+    pub resolution: Option<String>,
+    pub resolved_by_ta_id: Option<String>,
+    pub resolved_at: Option<String>,
+    #[serde(skip)]
+    pub anon_seq: i64,
+}
+
+/// Flag one question of a submission for regrade. The submission moves to
+/// `flagged` until every open request on it is resolved. Returns the request ID.
+#[tauri::command]
+pub async fn request_regrade(
+    db: State<'_, DbState>,
+    submission_id: String,
+    question_id: String,
+    reason: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<String, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err("A reason is required".to_string());
+    }
+
+    let (status, rubric_json): (String, Option<String>) = sqlx::query_as(
+        "SELECT s.status, a.rubric_json FROM submissions s JOIN assignments a ON s.assignment_id = a.id WHERE s.id = ?"
+    )
+    .bind(&submission_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Submission not found")?;
+
+    let rubric: serde_json::Value = serde_json::from_str(rubric_json.as_deref().unwrap_or("{}")).unwrap_or(serde_json::json!({}));
+    let known = rubric["questions"]
+        .as_array()
+        .is_some_and(|qs| qs.iter().any(|q| q["question_id"].as_str() == Some(question_id.as_str())));
+    if !known {
+        return Err(format!("Unknown question_id '{}'", question_id));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query(
+        "INSERT INTO regrade_requests (id, submission_id, question_id, reason, previous_status, requested_by_ta_id) VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(&submission_id)
+    .bind(&question_id)
+    .bind(&reason)
+    .bind(&status)
+    .bind(&ta_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE submissions SET status = 'flagged' WHERE id = ?")
+        .bind(&submission_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let details = serde_json::json!({
+        "request_id": id,
+        "question_id": question_id,
+        "reason": reason,
+        "previous_status": status,
+    })
+    .to_string();
+    log_audit_internal(&pool, Some(&ta_id), "request_regrade", "submission", &submission_id, Some(&details)).await?;
+
+    Ok(id)
+}
+
+/// Regrade requests for an assignment, open ones first. Students are shown by
+/// label on anonymous assignments.
+#[tauri::command]
+pub async fn list_regrade_requests(
+    db: State<'_, DbState>,
+    assignment_id: String,
+) -> Result<Vec<RegradeRequest>, String> {
+    let pool = db.pool()?;
+    let mut requests = sqlx::query_as::<sqlx::Sqlite, RegradeRequest>(
+        r#"
+        SELECT r.id, r.submission_id, sub.student_id, st.name as student_name, r.question_id, r.reason, r.status,
+               r.requested_by_ta_id, r.requested_at, r.resolution, r.resolved_by_ta_id, r.resolved_at, sub.anon_seq
+        FROM regrade_requests r
+        JOIN submissions sub ON r.submission_id = sub.id
+        LEFT JOIN students st ON sub.student_id = st.student_id
+            AND st.course_id = (SELECT course_id FROM assignments WHERE id = sub.assignment_id)
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL
+        ORDER BY r.status = 'resolved', r.requested_at ASC
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if is_anonymous(&pool, &assignment_id).await? {
+        for request in &mut requests {
+            request.student_id = None;
+            request.student_name = Some(pseudonym(request.anon_seq));
+        }
+    }
+    Ok(requests)
+}
+
+/// Close a regrade request with a note on how it was handled. When it was the
+/// last open request, a still-flagged submission goes back to the status it
+/// had before the first of them was opened.
+#[tauri::command]
+pub async fn resolve_regrade(
+    db: State<'_, DbState>,
+    request_id: String,
+    resolution: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let resolution = resolution.trim().to_string();
+    if resolution.is_empty() {
+        return Err("A resolution is required".to_string());
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let submission_id: String = sqlx::query_scalar(
+        r#"
+        UPDATE regrade_requests
+        SET status = 'resolved', resolution = ?, resolved_by_ta_id = ?, resolved_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND status = 'open'
+        RETURNING submission_id
+        "#
+    )
+    .bind(&resolution)
+    .bind(&ta_id)
+    .bind(&request_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Regrade request not found or already resolved")?;
+
+    let still_open: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM regrade_requests WHERE submission_id = ? AND status = 'open'")
+        .bind(&submission_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut restored_status: Option<String> = None;
+    if still_open == 0 {
+        // The latest request opened while the submission wasn't flagged holds the pre-flag status
+        let previous: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT previous_status FROM regrade_requests
+            WHERE submission_id = ? AND previous_status IS NOT NULL AND previous_status != 'flagged'
+            ORDER BY requested_at DESC, rowid DESC
+            LIMIT 1
+            "#
+        )
+        .bind(&submission_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let previous = previous.unwrap_or_else(|| "in_progress".to_string());
+
+        let result = sqlx::query("UPDATE submissions SET status = ? WHERE id = ? AND status = 'flagged'")
+            .bind(&previous)
+            .bind(&submission_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if result.rows_affected() > 0 {
+            restored_status = Some(previous);
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let details = serde_json::json!({
+        "request_id": request_id,
+        "resolution": resolution,
+        "restored_status": restored_status,
+    })
+    .to_string();
+    log_audit_internal(&pool, Some(&ta_id), "resolve_regrade", "submission", &submission_id, Some(&details)).await?;
+
+    Ok(())
+}
+
+// --- Letter Grades ---
+
+/// One step of a course's grade scale: totals at or above `min_percent` get `letter`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GradeThreshold {
+    pub min_percent: f64,
+    pub letter: String,
+}
+
+/// Set or clear (with None or an empty list) a course's letter grade scale.
+/// Thresholds must run from highest to lowest percent with no repeats.
+#[tauri::command]
+pub async fn set_course_grade_scale(
+    db: State<'_, DbState>,
+    course_id: String,
+    scale: Option<Vec<GradeThreshold>>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    let scale = scale.filter(|s| !s.is_empty());
+    if let Some(steps) = &scale {
+        for step in steps {
+            if !step.min_percent.is_finite() || !(0.0..=100.0).contains(&step.min_percent) {
+                return Err(format!("Threshold for '{}' must be between 0 and 100 percent", step.letter));
+            }
+            if step.letter.trim().is_empty() {
+                return Err("Every threshold needs a letter".to_string());
+            }
+        }
+        if let Some(pair) = steps.windows(2).find(|w| w[1].min_percent >= w[0].min_percent) {
+            return Err(format!(
+                "Thresholds must decrease: '{}' ({}%) follows '{}' ({}%)",
+                pair[1].letter, pair[1].min_percent, pair[0].letter, pair[0].min_percent
+            ));
+        }
+    }
+
+    let json = scale.map(|s| serde_json::to_string(&s)).transpose().map_err(|e| e.to_string())?;
+    let result = sqlx::query("UPDATE courses SET grade_scale_json = ? WHERE id = ?")
+        .bind(&json)
+        .bind(&course_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err("Course not found".to_string());
+    }
+    Ok(())
+}
+
+/// The course's grade scale, if it has one
+pub(crate) async fn load_grade_scale(pool: &DbPool, course_id: &str) -> Result<Option<Vec<GradeThreshold>>, String> {
+    let json: Option<String> = sqlx::query_scalar("SELECT grade_scale_json FROM courses WHERE id = ?")
+        .bind(course_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Course not found")?;
+    json.map(|j| serde_json::from_str(&j).map_err(|e| format!("The course's stored grade scale is malformed: {}", e)))
+        .transpose()
+}
+
+/// Letter for `total` out of `max` under `scale`. A total exactly on a threshold
+/// earns that letter; below every threshold there is no letter.
+pub(crate) fn letter_for(scale: &[GradeThreshold], total: f64, max: f64) -> Option<String> {
+    if max <= 0.0 {
+        return None;
+    }
+    // Rounded so 89.99999999 from float division still lands on a 90% threshold
+    let percent = (total / max * 100.0 * 1e6).round() / 1e6;
+    scale.iter().find(|t| percent >= t.min_percent).map(|t| t.letter.clone())
+}
+
+/// Letter grade for a total under the course's scale; None when the course has no scale
+#[tauri::command]
+pub async fn compute_letter_grade(
+    db: State<'_, DbState>,
+    total: f64,
+    max: f64,
+    course_id: String,
+) -> Result<Option<String>, String> {
+    let pool = db.pool()?;
+    Ok(load_grade_scale(&pool, &course_id)
+        .await?
+        .and_then(|scale| letter_for(&scale, total, max)))
+}
+
+// --- Anonymous Grading ---
+
+/// Stand-in for a student's identity, e.g. "Submission #0042". Built from the
+/// submission's `anon_seq`, which is unique within the assignment and never changes.
+pub fn pseudonym(anon_seq: i64) -> String {
+    format!("Submission #{:04}", anon_seq)
+}
+
+pub(crate) async fn is_anonymous(pool: &DbPool, assignment_id: &str) -> Result<bool, String> {
+    let anonymous: Option<bool> = sqlx::query_scalar("SELECT anonymous FROM assignments WHERE id = ?")
+        .bind(assignment_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(anonymous.unwrap_or(false))
+}
+
+/// Swap names for pseudonyms
+fn anonymize_queue(items: Vec<SubmissionQueueItem>) -> Vec<SubmissionQueueItem> {
+    items
+        .into_iter()
+        .map(|item| SubmissionQueueItem {
+            student_id: None,
+            student_name: Some(pseudonym(item.anon_seq)),
+            ..item
+        })
+        .collect()
+}
+
+async fn require_course_admin(pool: &DbPool, assignment_id: &str, ta_id: &str) -> Result<(), String> {
+    let is_admin: Option<i32> = sqlx::query_scalar(
+        r#"
+        SELECT 1 FROM assignments a
+        JOIN course_tas ct ON ct.course_id = a.course_id
+        WHERE a.id = ? AND ct.ta_id = ? AND ct.role = 'admin'
+        "#
+    )
+    .bind(assignment_id)
+    .bind(ta_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if is_admin.is_none() {
+        return Err("Only a course admin can change anonymous grading".to_string());
+    }
+    Ok(())
+}
+
+/// Turn anonymous grading on or off for an assignment. Admin only.
+/// Exports always use real student ids either way.
+#[tauri::command]
+pub async fn set_assignment_anonymous(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    anonymous: bool,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<(), String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    require_course_admin(&pool, &assignment_id, &ta_id).await?;
+    sqlx::query("UPDATE assignments SET anonymous = ? WHERE id = ?")
+        .bind(anonymous)
+        .bind(&assignment_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let details = serde_json::json!({ "anonymous": anonymous }).to_string();
+    log_audit_internal(&pool, Some(&ta_id), "set_anonymous", "assignment", &assignment_id, Some(&details)).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevealedIdentity {
+    pub submission_id: String,
+    pub label: String,
+    pub student_id: Option<String>,
+    pub student_name: Option<String>,
+}
+
+/// Map an anonymous assignment's pseudonyms back to students. Admin only,
+/// and every reveal is written to the audit log.
+#[tauri::command]
+pub async fn reveal_anonymous_identities(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<Vec<RevealedIdentity>, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    require_course_admin(&pool, &assignment_id, &ta_id).await?;
+    let rows: Vec<(String, i64, Option<String>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT s.id, s.anon_seq, s.student_id, st.name
+        FROM submissions s
+        JOIN assignments a ON a.id = s.assignment_id
+        LEFT JOIN students st ON st.student_id = s.student_id AND st.course_id = a.course_id
+        WHERE s.assignment_id = ? AND s.deleted_at IS NULL AND s.superseded_by IS NULL
+        ORDER BY s.anon_seq
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let revealed: Vec<RevealedIdentity> = rows
+        .into_iter()
+        .map(|(submission_id, anon_seq, student_id, student_name)| RevealedIdentity {
+            label: pseudonym(anon_seq),
+            submission_id,
+            student_id,
+            student_name,
+        })
+        .collect();
+
+    let details = serde_json::json!({ "count": revealed.len() }).to_string();
+    log_audit_internal(&pool, Some(&ta_id), "reveal_identities", "assignment", &assignment_id, Some(&details)).await?;
+    Ok(revealed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use std::path::Path;
+    use tauri::Manager;
+
+    const RUBRIC: &str = r#"{"questions": [
+        {"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []},
+        {"question_id": "q2", "title": "Ledger", "max_points": 10, "comment_presets": []}
+    ]}"#;
+
+    async fn set_status(db: &TestDb, submission_id: &str, status: &str) {
+        sqlx::query("UPDATE submissions SET status = ? WHERE id = ?")
+            .bind(status)
+            .bind(submission_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    /// An unmatched submission uploaded as `zip_name`, received after the ones before it
+    async fn unmatched(db: &TestDb, assignment_id: &str, zip_name: &str) -> String {
+        let id = db.submission(assignment_id, None, Path::new("/nonexistent")).await;
+        sqlx::query("UPDATE submissions SET source_zip_path = ?, received_at = datetime('now', '+' || anon_seq || ' seconds') WHERE id = ?")
+            .bind(format!("/uploads/{}", zip_name))
+            .bind(&id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn progress_counts_current_submissions_only() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let folder = Path::new("/nonexistent");
+        let done = db.submission(&assignment, Some("1"), folder).await;
+        let partial = db.submission(&assignment, Some("2"), folder).await;
+        let unmatched = db.submission(&assignment, None, folder).await;
+        let old = db.submission(&assignment, Some("2"), folder).await;
+        sqlx::query("UPDATE submissions SET superseded_by = ? WHERE id = ?")
+            .bind(&partial)
+            .bind(&old)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        db.grade(&done, "q1", Some(8.0), None).await;
+        db.grade(&done, "q2", Some(0.0), None).await;
+        db.grade(&partial, "q1", Some(5.0), None).await;
+        db.grade(&partial, "q2", None, Some("Missing")).await;
+        db.grade(&old, "q1", Some(9.0), None).await;
+        db.grade(&old, "q2", Some(9.0), None).await;
+        set_status(&db, &done, "done").await;
+        set_status(&db, &partial, "in_progress").await;
+        set_status(&db, &unmatched, "flagged").await;
+        let ada = db.ta("Ada").await;
+        db.claim(&partial, &ada).await;
+        db.claim(&unmatched, &ada).await;
+
+        let progress = get_assignment_progress(app.state(), assignment).await.unwrap();
+        assert_eq!(progress.total, 3);
+        assert_eq!((progress.unstarted, progress.in_progress, progress.done, progress.flagged), (0, 1, 1, 1));
+        assert_eq!(progress.fully_graded, 1);
+        assert_eq!(progress.unmatched, 1);
+        assert_eq!(progress.claims.len(), 1);
+        assert_eq!((progress.claims[0].display_name.as_deref(), progress.claims[0].claimed), (Some("Ada"), 2));
+    }
+
+    #[tokio::test]
+    async fn only_old_in_progress_claims_are_released() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let folder = Path::new("/nonexistent");
+        let ada = db.ta("Ada").await;
+        let abandoned = db.submission(&assignment, Some("1"), folder).await;
+        let active = db.submission(&assignment, Some("2"), folder).await;
+        let finished = db.submission(&assignment, Some("3"), folder).await;
+        for (submission, status) in [(&abandoned, "in_progress"), (&active, "in_progress"), (&finished, "done")] {
+            db.claim(submission, &ada).await;
+            set_status(&db, submission, status).await;
+        }
+        sqlx::query("UPDATE submissions SET claimed_at = datetime('now', '-2 hours') WHERE id IN (?, ?)")
+            .bind(&abandoned)
+            .bind(&finished)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let released = release_stale_claims(app.state(), assignment.clone(), 60).await.unwrap();
+        assert_eq!(released, [abandoned.as_str()]);
+        let claimed: Vec<String> = sqlx::query_scalar("SELECT id FROM submissions WHERE claimed_by_ta_id IS NOT NULL ORDER BY anon_seq")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(claimed, [active, finished]);
+        let audited: Vec<String> = sqlx::query_scalar("SELECT entity_id FROM audit_log WHERE action = 'release_stale_claim'")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(audited, [abandoned]);
+        assert!(release_stale_claims(app.state(), assignment, 60).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn concurrent_claims_have_one_winner() {
+        let db = TestDb::new().await;
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        let ada = db.ta("Ada").await;
+        let grace = db.ta("Grace").await;
+
+        let mut claims = tokio::task::JoinSet::new();
+        for ta in [&ada, &grace, &ada, &grace] {
+            let (pool, submission, ta) = (db.pool.clone(), submission.clone(), ta.clone());
+            claims.spawn(async move { take_claim(&pool, &submission, &ta).await });
+        }
+        let mut outcomes = Vec::new();
+        while let Some(outcome) = claims.join_next().await {
+            outcomes.push(outcome.unwrap());
+        }
+
+        let owner: String = sqlx::query_scalar("SELECT claimed_by_ta_id FROM submissions WHERE id = ?")
+            .bind(&submission)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let winner = if owner == ada { "Ada" } else { "Grace" };
+        assert_eq!(outcomes.iter().filter(|o| matches!(o, Ok(true))).count(), 1);
+        assert_eq!(outcomes.iter().filter(|o| matches!(o, Ok(false))).count(), 1);
+        let refused = format!("Submission already claimed by {}", winner);
+        assert!(outcomes.iter().filter(|o| o.is_err()).all(|o| o.as_ref().unwrap_err() == &refused), "{:?}", outcomes);
+    }
+
+    #[tokio::test]
+    async fn bookmarks_keep_the_latest_position_per_assignment() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let folder = Path::new("/nonexistent");
+        let first = db.submission(&assignment, Some("1"), folder).await;
+        let second = db.submission(&assignment, Some("2"), folder).await;
+        let ada = db.ta("Ada").await;
+        let bookmark = || get_last_session_bookmark(app.state(), ada.clone(), None, assignment.clone());
+
+        // Without a saved bookmark, fall back to the submission being graded
+        db.claim(&first, &ada).await;
+        set_status(&db, &first, "in_progress").await;
+        let fallback = bookmark().await.unwrap();
+        assert_eq!((fallback.submission_id.as_deref(), fallback.question_index), (Some(first.as_str()), 0));
+        assert!(fallback.last_saved_at.is_none());
+
+        save_session_bookmark(app.state(), ada.clone(), None, assignment.clone(), first.clone(), 2).await.unwrap();
+        save_session_bookmark(app.state(), ada.clone(), None, assignment.clone(), second.clone(), 4).await.unwrap();
+        let saved = bookmark().await.unwrap();
+        assert_eq!((saved.submission_id.as_deref(), saved.question_index), (Some(second.as_str()), 4));
+        assert!(saved.last_saved_at.is_some());
+
+        let counts: (i64, i64) = sqlx::query_as("SELECT (SELECT COUNT(*) FROM session_bookmarks), (SELECT COUNT(*) FROM audit_log)")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(counts, (1, 0));
+    }
+
+    #[tokio::test]
+    async fn unmatched_submissions_come_with_a_suggestion() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        db.student(&course, "12345678", "Ada Lovelace").await;
+        let by_id = unmatched(&db, &assignment, "12345678_hw1.zip").await;
+        let by_name = unmatched(&db, &assignment, "lovelace_ada.zip").await;
+        let unknown = unmatched(&db, &assignment, "final_v2.zip").await;
+
+        let suggestions: Vec<_> = get_unmatched_submissions(app.state(), assignment)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.id, s.suggested_student_id, s.suggested_confidence.is_some()))
+            .collect();
+        let ada = Some("12345678".to_string());
+        assert_eq!(suggestions, [(by_id, ada.clone(), true), (by_name, ada, true), (unknown, None, false)]);
+    }
+
+    #[tokio::test]
+    async fn bulk_match_checks_the_roster_before_writing() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        db.student(&course, "12345678", "Ada Lovelace").await;
+        db.student(&course, "87654321", "Grace Hopper").await;
+        let ta = db.ta("Alan").await;
+        let first = unmatched(&db, &assignment, "a.zip").await;
+        let second = unmatched(&db, &assignment, "b.zip").await;
+        let requests = |student: &str| vec![
+            MatchRequest { submission_id: first.clone(), student_id: "12345678".into() },
+            MatchRequest { submission_id: second.clone(), student_id: student.into() },
+        ];
+        let matched = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM submissions WHERE student_id IS NOT NULL")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap()
+        };
+
+        let outcomes = bulk_match_submissions(app.state(), requests("99999999"), ta.clone(), None, Some(true)).await.unwrap();
+        assert!(outcomes.iter().all(|o| !o.success));
+        assert_eq!(outcomes[0].error.as_deref(), Some("Not applied: other matches in the batch are invalid"));
+        assert_eq!(outcomes[1].error.as_deref(), Some("Student 99999999 not found in roster"));
+        assert_eq!(matched().await, 0);
+
+        let outcomes = bulk_match_submissions(app.state(), requests("99999999"), ta.clone(), None, None).await.unwrap();
+        assert_eq!(outcomes.iter().map(|o| o.success).collect::<Vec<_>>(), [true, false]);
+        assert_eq!(matched().await, 1);
+
+        let outcomes = bulk_match_submissions(app.state(), requests("87654321"), ta, None, Some(true)).await.unwrap();
+        assert!(outcomes.iter().all(|o| o.success));
+        assert_eq!(matched().await, 2);
+    }
+
+    #[tokio::test]
+    async fn undo_walks_back_one_action_at_a_time() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        let ada = db.ta("Ada").await;
+        let grace = db.ta("Grace").await;
+        let undo = || undo_last_action(app.state(), submission.clone(), ada.clone(), None);
+        let state = || async {
+            sqlx::query_as::<_, (String, Option<String>, Option<String>)>("SELECT status, notes, claimed_by_ta_id FROM submissions WHERE id = ?")
+                .bind(&submission)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap()
+        };
+
+        update_submission_status(app.state(), submission.clone(), "flagged".into(), Some(ada.clone()), None).await.unwrap();
+        quarantine_submission(app.state(), submission.clone(), "Corrupt ZIP".into(), ada.clone(), None).await.unwrap();
+        assert_eq!(state().await, ("error".to_string(), Some("Corrupt ZIP".to_string()), None));
+
+        assert_eq!(undo().await.unwrap(), "quarantine");
+        assert_eq!(state().await, ("flagged".to_string(), None, None));
+        assert_eq!(undo().await.unwrap(), "status_change");
+        assert_eq!(state().await, ("unstarted".to_string(), None, None));
+        assert_eq!(undo().await.unwrap_err(), "Nothing to undo");
+
+        // A later change by someone else blocks the undo instead of being overwritten
+        force_claim_submission(app.state(), submission.clone(), ada.clone(), None).await.unwrap();
+        db.claim(&submission, &grace).await;
+        assert_eq!(undo().await.unwrap_err(), "Cannot undo force_claim: the submission has changed since");
+        assert_eq!(state().await.2, Some(grace));
+    }
+
+    #[tokio::test]
+    async fn audit_log_pages_carry_the_filtered_total() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        for (i, ta) in ["ada", "grace", "ada", "ada", "grace"].into_iter().enumerate() {
+            log_audit_internal(&db.pool, Some(ta), "claim", "submission", &format!("s{}", i), None).await.unwrap();
+        }
+        let page = |limit: i32, offset: Option<i32>, ta: Option<&str>| {
+            get_audit_log(app.state(), limit, offset, ta.map(String::from), None, None, None, None)
+        };
+        let entity_ids = |page: &AuditLogPage| page.entries.iter().map(|e| e["entity_id"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+        let first = page(2, None, None).await.unwrap();
+        assert_eq!((first.total_count, entity_ids(&first)), (5, vec!["s4".to_string(), "s3".to_string()]));
+        let last = page(2, Some(4), None).await.unwrap();
+        assert_eq!(entity_ids(&last), ["s0"]);
+        let ada = page(10, None, Some("ada")).await.unwrap();
+        assert_eq!((ada.total_count, entity_ids(&ada)), (3, vec!["s3".to_string(), "s2".to_string(), "s0".to_string()]));
+
+        let none = get_audit_log(app.state(), 10, None, None, None, None, Some("2000-01-01 00:00:00".into()), Some("2000-12-31 23:59:59".into()))
+            .await
+            .unwrap();
+        assert_eq!((none.total_count, none.entries.len()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn search_matches_comments_and_students() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        db.student(&course, "12345678", "Ada Lovelace").await;
+        let folder = Path::new("/nonexistent");
+        let old = db.submission(&assignment, Some("12345678"), folder).await;
+        let current = db.submission(&assignment, Some("12345678"), folder).await;
+        sqlx::query("UPDATE submissions SET superseded_by = ? WHERE id = ?")
+            .bind(&current)
+            .bind(&old)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.grade(&old, "q1", Some(5.0), Some("Tax rate is 100% wrong")).await;
+        db.grade(&current, "q1", Some(8.0), Some("Tax rate fixed; 10% is right")).await;
+        let search = |query: &str| search_grades(app.state(), assignment.clone(), query.to_string());
+
+        let hits = search("10%").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].submission_id.as_str(), hits[0].question_id.as_deref()), (current.as_str(), Some("q1")));
+        assert!(search("100%").await.unwrap().is_empty());
+
+        let hits = search("lovelace").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].question_id.as_deref(), hits[0].snippet.as_str()), (None, "Ada Lovelace (12345678)"));
+        assert!(search("  ").await.unwrap().is_empty());
+
+        // Anonymous assignments only match comments, and hide who wrote them
+        sqlx::query("UPDATE assignments SET anonymous = 1 WHERE id = ?")
+            .bind(&assignment)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(search("1234").await.unwrap().is_empty());
+        let hits = search("tax").await.unwrap();
+        assert_eq!((hits[0].student_id.as_deref(), hits[0].student_name.as_deref()), (None, Some("Submission #0002")));
+    }
+
+    #[tokio::test]
+    async fn final_score_applies_weights_then_scale() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let rubric = r#"{"questions": [
+            {"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []},
+            {"question_id": "q2", "title": "Ledger", "max_points": 4, "weight": 20, "comment_presets": []},
+            {"question_id": "q3", "title": "Memo", "max_points": 5, "comment_presets": []}
+        ], "scale": 50}"#;
+        let assignment = db.assignment(&course, Some(rubric)).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("")).await;
+        db.grade(&submission, "q1", Some(8.0), None).await;
+        db.grade(&submission, "q2", Some(2.0), None).await;
+
+        // (8 + 2/4 * 20 + 0) of (10 + 20 + 5), scaled to 50
+        let score = compute_final_score(app.state(), submission).await.unwrap();
+        assert!((score.total - 18.0 / 35.0 * 50.0).abs() < 1e-9, "{}", score.total);
+        assert_eq!(score.out_of, 50.0);
+        assert_eq!((score.graded_questions, score.question_count), (2, 3));
+
+        let plain: Vec<serde_json::Value> = serde_json::from_str(r#"[{"question_id": "q1", "max_points": 10}, {"question_id": "q2", "max_points": 5}]"#).unwrap();
+        let FinalScore { total, out_of } = final_score(&plain, None, |q| (q == "q1").then_some(7.0));
+        assert_eq!((total, out_of), (7.0, 15.0));
+    }
+
+    #[tokio::test]
+    async fn late_penalty_charges_started_days_after_grace() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let rubric = r#"{"questions": [{"question_id": "q1", "title": "Journal", "max_points": 10, "comment_presets": []}]}"#;
+        let assignment = db.assignment(&course, Some(rubric)).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("")).await;
+        db.grade(&submission, "q1", Some(8.0), None).await;
+        sqlx::query("UPDATE assignments SET due_date = '2026-03-01T23:59:00Z' WHERE id = ?")
+            .bind(&assignment)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let submitted_at = |at: &'static str| {
+            sqlx::query("UPDATE submissions SET submitted_at = ? WHERE id = ?").bind(at).bind(submission.clone()).execute(&db.pool)
+        };
+
+        let invalid = LatePolicy { percent_per_day: 10.0, grace_minutes: 15, max_penalty_percent: Some(120.0) };
+        assert_eq!(set_late_policy(app.state(), assignment.clone(), Some(invalid)).await.unwrap_err(), "Max penalty must be between 0 and 100 percent");
+        let policy = LatePolicy { percent_per_day: 10.0, grace_minutes: 15, max_penalty_percent: Some(25.0) };
+        set_late_policy(app.state(), assignment.clone(), Some(policy)).await.unwrap();
+
+        let mut charged = Vec::new();
+        for at in ["2026-03-02T00:09:00Z", "2026-03-02T00:59:00Z", "2026-03-05T12:00:00Z"] {
+            submitted_at(at).await.unwrap();
+            let result = apply_late_penalty(app.state(), submission.clone()).await.unwrap();
+            charged.push((result.lateness.days_charged, result.lateness.penalty_percent, result.adjusted_score));
+        }
+        // Within grace, one started day, then four days capped at 25%
+        assert_eq!(charged, [(0, 0.0, 8.0), (1, 10.0, 7.0), (4, 25.0, 5.5)]);
+
+        set_late_policy(app.state(), assignment, None).await.unwrap();
+        let result = apply_late_penalty(app.state(), submission).await.unwrap();
+        assert_eq!((result.penalty_points, result.adjusted_score), (0.0, 8.0));
+    }
+
+    #[tokio::test]
+    async fn regrade_flags_until_every_request_is_resolved() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let submission = db.submission(&assignment, Some("1"), Path::new("/nonexistent")).await;
+        set_status(&db, &submission, "done").await;
+        let ada = db.ta("Ada").await;
+        let request = |question: &str, reason: &str| {
+            request_regrade(app.state(), submission.clone(), question.to_string(), reason.to_string(), ada.clone(), None)
+        };
+        let status = || sqlx::query_scalar::<_, String>("SELECT status FROM submissions WHERE id = ?").bind(&submission).fetch_one(&db.pool);
+
+        assert_eq!(request("q1", "  ").await.unwrap_err(), "A reason is required");
+        assert_eq!(request("q9", "Wrong total").await.unwrap_err(), "Unknown question_id 'q9'");
+        let first = request("q1", "Wrong total").await.unwrap();
+        let second = request("q2", "Missed the note").await.unwrap();
+        assert_eq!(status().await.unwrap(), "flagged");
+
+        resolve_regrade(app.state(), first.clone(), "Added 2 points".into(), ada.clone(), None).await.unwrap();
+        assert_eq!(status().await.unwrap(), "flagged");
+        let listed = list_regrade_requests(app.state(), assignment.clone()).await.unwrap();
+        let order: Vec<(&str, &str)> = listed.iter().map(|r| (r.id.as_str(), r.status.as_str())).collect();
+        assert_eq!(order, [(second.as_str(), "open"), (first.as_str(), "resolved")]);
+
+        // The submission goes back to the status it had before the first request
+        resolve_regrade(app.state(), second, "No change".into(), ada.clone(), None).await.unwrap();
+        assert_eq!(status().await.unwrap(), "done");
+        let again = resolve_regrade(app.state(), first, "Twice".into(), ada, None).await;
+        assert_eq!(again.unwrap_err(), "Regrade request not found or already resolved");
+    }
+
+    #[tokio::test]
+    async fn grader_stats_attribute_scores_to_the_saving_ta() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let folder = Path::new("/nonexistent");
+        let (ada, bo) = (db.ta("Ada").await, db.ta("Bo").await);
+        let first = db.submission(&assignment, Some("1"), folder).await;
+        let second = db.submission(&assignment, Some("2"), folder).await;
+        let third = db.submission(&assignment, Some("3"), folder).await;
+        db.claim(&first, &ada).await;
+        db.claim(&second, &ada).await;
+        for (submission, q1, q2) in [(&first, 8.0, 6.0), (&second, 4.0, 2.0)] {
+            db.grade(submission, "q1", Some(q1), None).await;
+            db.grade(submission, "q2", Some(q2), None).await;
+        }
+        // Saved by Bo without a claim; the blank q2 is not a score
+        db.grade(&third, "q1", Some(10.0), None).await;
+        db.grade(&third, "q2", None, Some("Pending")).await;
+        sqlx::query("UPDATE grades SET updated_by_ta_id = ? WHERE submission_id = ?")
+            .bind(&bo)
+            .bind(&third)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let stats = get_grader_stats(app.state(), assignment).await.unwrap();
+        let close = |a: Option<f64>, b: f64| a.is_some_and(|a| (a - b).abs() < 1e-9);
+        let overall: Vec<(&str, usize, f64)> = stats.overall.iter().map(|q| (q.question_id.as_str(), q.count, q.average_score)).collect();
+        assert_eq!(overall, [("q1", 3, 22.0 / 3.0), ("q2", 2, 4.0)]);
+
+        let names: Vec<Option<&str>> = stats.tas.iter().map(|t| t.display_name.as_deref()).collect();
+        assert_eq!(names, [Some("Ada"), Some("Bo")]);
+        let (ada_stats, bo_stats) = (&stats.tas[0], &stats.tas[1]);
+        assert_eq!((ada_stats.submissions_graded, ada_stats.scores_count), (2, 4));
+        assert!(close(ada_stats.average_percent, 0.5) && close(ada_stats.variance_percent, 0.05));
+        assert!(close(ada_stats.questions[1].average_percent, 0.4));
+        assert_eq!((bo_stats.submissions_graded, bo_stats.scores_count, bo_stats.questions.len()), (1, 1, 1));
+        assert!(close(bo_stats.average_percent, 1.0) && close(bo_stats.variance_percent, 0.0));
+    }
+
+    /// An assignment with one submission per student "1".."count", named "Student 1" onwards
+    async fn roster_submissions(db: &TestDb, count: usize) -> (String, String, Vec<String>) {
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        let mut submissions = Vec::new();
+        for i in 1..=count {
+            let student = i.to_string();
+            db.student(&course, &student, &format!("Student {}", i)).await;
+            submissions.push(db.submission(&assignment, Some(&student), Path::new("/nonexistent")).await);
+        }
+        (course, assignment, submissions)
+    }
+
+    #[tokio::test]
+    async fn round_robin_deals_submissions_out_in_name_order() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_, assignment, submissions) = roster_submissions(&db, 7).await;
+        let tas = vec![db.ta("Ada").await, db.ta("Bo").await, db.ta("Cy").await];
+        let distribute = |strategy: &str, ta_ids: Vec<String>| {
+            distribute_submissions(app.state(), assignment.clone(), strategy.to_string(), ta_ids, tas[0].clone(), None)
+        };
+
+        assert_eq!(distribute("alphabetical", tas.clone()).await.unwrap_err(), "Unknown distribution strategy 'alphabetical'");
+        let twice = vec![tas[0].clone(), tas[0].clone()];
+        assert_eq!(distribute("round_robin", twice).await.unwrap_err(), "Each TA can only be listed once");
+
+        let workload = distribute("round_robin", tas.clone()).await.unwrap();
+        let counts: Vec<(&str, usize)> = workload.iter().map(|w| (w.ta_id.as_str(), w.submissions)).collect();
+        assert_eq!(counts, [(tas[0].as_str(), 3), (tas[1].as_str(), 2), (tas[2].as_str(), 2)]);
+        let queue = list_submissions(app.state(), assignment.clone(), None, None, None, Some(tas[1].clone()), None).await.unwrap();
+        let names: Vec<Option<&str>> = queue.iter().map(|s| s.student_name.as_deref()).collect();
+        assert_eq!(names, [Some("Student 2"), Some("Student 5")]);
+        assert!(queue.iter().all(|s| s.assigned_to_name.as_deref() == Some("Bo") && s.claimed_by_ta_id.is_none()));
+
+        let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE action = 'assign_ta'").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(audited, submissions.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn sections_stay_with_one_ta() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (course, assignment, _) = roster_submissions(&db, 7).await;
+        for (student, section) in [("1", "A"), ("2", "A"), ("3", "A"), ("4", "B"), ("5", "B"), ("6", "C"), ("7", "C")] {
+            sqlx::query("UPDATE students SET section = ? WHERE course_id = ? AND student_id = ?")
+                .bind(section)
+                .bind(&course)
+                .bind(student)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let tas = vec![db.ta("Ada").await, db.ta("Bo").await];
+
+        let workload = distribute_submissions(app.state(), assignment.clone(), "by_section".into(), tas.clone(), tas[0].clone(), None)
+            .await
+            .unwrap();
+        // A goes first as the largest; B and C then both go to whoever has least
+        assert_eq!(workload.iter().map(|w| w.submissions).collect::<Vec<_>>(), [3, 4]);
+        let sections: Vec<(String, String)> = sqlx::query_as(
+            "SELECT DISTINCT st.section, sub.assigned_ta_id FROM submissions sub JOIN students st ON st.student_id = sub.student_id ORDER BY st.section"
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        let expected = [("A", &tas[0]), ("B", &tas[1]), ("C", &tas[1])].map(|(s, t)| (s.to_string(), t.clone()));
+        assert_eq!(sections, expected);
+    }
+
+    #[tokio::test]
+    async fn queue_filters_by_status_and_claim() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_, assignment, submissions) = roster_submissions(&db, 4).await;
+        let ada = db.ta("Ada").await;
+        set_status(&db, &submissions[0], "done").await;
+        set_status(&db, &submissions[2], "done").await;
+        db.claim(&submissions[1], &ada).await;
+        sqlx::query("UPDATE submissions SET received_at = datetime('now', '-' || anon_seq || ' minutes')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let list = |status: Option<&str>, claimed_by: Option<&str>, sort: Option<&str>| {
+            let (status, claimed_by, sort) = (status.map(String::from), claimed_by.map(String::from), sort.map(String::from));
+            list_submissions(app.state(), assignment.clone(), None, status, claimed_by, None, sort)
+        };
+        let names = |items: Vec<SubmissionQueueItem>| -> Vec<String> { items.into_iter().filter_map(|s| s.student_name).collect() };
+
+        assert_eq!(names(list(None, None, None).await.unwrap()), ["Student 1", "Student 2", "Student 3", "Student 4"]);
+        assert_eq!(names(list(Some("done"), None, None).await.unwrap()), ["Student 1", "Student 3"]);
+        assert_eq!(names(list(None, Some("unclaimed"), None).await.unwrap()), ["Student 1", "Student 3", "Student 4"]);
+        assert_eq!(names(list(Some("unstarted"), Some(&ada), None).await.unwrap()), ["Student 2"]);
+        assert_eq!(names(list(None, None, Some("status")).await.unwrap()), ["Student 1", "Student 3", "Student 2", "Student 4"]);
+        assert_eq!(names(list(None, None, Some("received_at")).await.unwrap()), ["Student 4", "Student 3", "Student 2", "Student 1"]);
+        assert_eq!(list(None, None, Some("grade")).await.unwrap_err(), "Unknown sort key 'grade'");
+    }
+
+    #[tokio::test]
+    async fn next_skips_finished_and_others_work_then_wraps() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_, assignment, subs) = roster_submissions(&db, 4).await;
+        let (ada, bo) = (db.ta("Ada").await, db.ta("Bo").await);
+        for q in ["q1", "q2"] {
+            db.grade(&subs[0], q, Some(5.0), None).await;
+        }
+        set_status(&db, &subs[0], "done").await;
+        // Marked done with a question still blank, so it still needs work
+        db.grade(&subs[1], "q1", Some(5.0), None).await;
+        set_status(&db, &subs[1], "done").await;
+        db.claim(&subs[2], &bo).await;
+        let next = |current: Option<&String>, wrap: Option<bool>| {
+            next_submission(app.state(), assignment.clone(), ada.clone(), None, current.cloned(), wrap)
+        };
+
+        assert_eq!(next(None, None).await.unwrap().as_ref(), Some(&subs[1]));
+        assert_eq!(next(Some(&subs[1]), None).await.unwrap().as_ref(), Some(&subs[3]));
+        assert_eq!(next(Some(&subs[3]), None).await.unwrap().as_ref(), Some(&subs[1]));
+        assert_eq!(next(Some(&subs[3]), Some(false)).await.unwrap(), None);
+
+        db.grade(&subs[1], "q2", Some(5.0), None).await;
+        for q in ["q1", "q2"] {
+            db.grade(&subs[3], q, Some(5.0), None).await;
+        }
+        set_status(&db, &subs[3], "done").await;
+        assert_eq!(next(Some(&subs[0]), None).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn completion_requires_a_score_for_every_question() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_, _, subs) = roster_submissions(&db, 1).await;
+        let (ada, bo) = (db.ta("Ada").await, db.ta("Bo").await);
+        db.claim(&subs[0], &ada).await;
+        db.grade(&subs[0], "q1", Some(7.0), None).await;
+        db.grade(&subs[0], "q2", None, Some("Come back to this")).await;
+        let status = || sqlx::query_scalar::<_, String>("SELECT status FROM submissions WHERE id = ?").bind(&subs[0]).fetch_one(&db.pool);
+
+        let other = complete_submission(app.state(), subs[0].clone(), bo, None).await;
+        assert_eq!(other.err().as_deref(), Some("Submission is claimed by another TA"));
+        let partial = complete_submission(app.state(), subs[0].clone(), ada.clone(), None).await.unwrap();
+        assert!(!partial.completed);
+        let missing: Vec<(&str, Option<&str>)> = partial.missing.iter().map(|q| (q.question_id.as_str(), q.title.as_deref())).collect();
+        assert_eq!(missing, [("q2", Some("Ledger"))]);
+        assert_eq!(status().await.unwrap(), "unstarted");
+
+        sqlx::query("UPDATE grades SET score = 4 WHERE question_id = 'q2'").execute(&db.pool).await.unwrap();
+        let done = complete_submission(app.state(), subs[0].clone(), ada, None).await.unwrap();
+        assert!(done.completed && done.missing.is_empty());
+        assert_eq!(status().await.unwrap(), "done");
+    }
+
+    #[tokio::test]
+    async fn letter_grades_follow_the_course_scale() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let step = |min_percent: f64, letter: &str| GradeThreshold { min_percent, letter: letter.to_string() };
+        let set = |scale: Option<Vec<GradeThreshold>>| set_course_grade_scale(app.state(), course.clone(), scale);
+        let letter = |total: f64, max: f64| compute_letter_grade(app.state(), total, max, course.clone());
+
+        // Unconfigured courses have no letters
+        assert_eq!(letter(30.0, 30.0).await.unwrap(), None);
+
+        set(Some(vec![step(90.0, "A"), step(80.0, "B"), step(70.0, "C")])).await.unwrap();
+        // 8.1/9 is 89.999...% in floating point but still earns the A
+        assert_eq!(letter(8.1, 9.0).await.unwrap().as_deref(), Some("A"));
+        assert_eq!(letter(26.99, 30.0).await.unwrap().as_deref(), Some("B"));
+        assert_eq!(letter(24.0, 30.0).await.unwrap().as_deref(), Some("B"));
+        assert_eq!(letter(21.0, 30.0).await.unwrap().as_deref(), Some("C"));
+        assert_eq!(letter(20.0, 30.0).await.unwrap(), None);
+
+        assert_eq!(
+            set(Some(vec![step(80.0, "B"), step(90.0, "A")])).await.err().as_deref(),
+            Some("Thresholds must decrease: 'A' (90%) follows 'B' (80%)")
+        );
+        assert_eq!(
+            set(Some(vec![step(90.0, "A"), step(90.0, "A-")])).await.err().as_deref(),
+            Some("Thresholds must decrease: 'A-' (90%) follows 'A' (90%)")
+        );
+        assert_eq!(
+            set(Some(vec![step(120.0, "A+")])).await.err().as_deref(),
+            Some("Threshold for 'A+' must be between 0 and 100 percent")
+        );
+        assert_eq!(set(Some(vec![step(50.0, " ")])).await.err().as_deref(), Some("Every threshold needs a letter"));
+        // A rejected scale leaves the saved one alone
+        assert_eq!(letter(27.0, 30.0).await.unwrap().as_deref(), Some("A"));
+
+        set(Some(vec![])).await.unwrap();
+        assert_eq!(letter(30.0, 30.0).await.unwrap(), None);
+        let missing = set_course_grade_scale(app.state(), "nope".into(), None).await;
+        assert_eq!(missing.err().as_deref(), Some("Course not found"));
+    }
+
+    #[tokio::test]
+    async fn anonymous_mode_hides_students_from_graders_but_not_exports() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (course, assignment, subs) = roster_submissions(&db, 2).await;
+        // Name order (Ada, Zed) is the reverse of upload order
+        for (id, name) in [("1", "Zed Zimmer"), ("2", "Ada Lovelace")] {
+            sqlx::query("UPDATE students SET name = ? WHERE student_id = ?").bind(name).bind(id).execute(&db.pool).await.unwrap();
+        }
+        let admin = db.ta("Admin").await;
+        let grader = db.ta("Grader").await;
+        for (ta, role) in [(&admin, "admin"), (&grader, "ta")] {
+            sqlx::query("INSERT INTO course_tas (course_id, ta_id, role) VALUES (?, ?, ?)")
+                .bind(&course)
+                .bind(ta)
+                .bind(role)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        db.grade(&subs[0], "q1", Some(7.0), None).await;
+        let listed = || async {
+            list_submissions(app.state(), assignment.clone(), None, None, None, None, None)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.id, s.student_id, s.student_name))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            set_assignment_anonymous(app.state(), assignment.clone(), true, grader.clone(), None).await.unwrap_err(),
+            "Only a course admin can change anonymous grading"
+        );
+        set_assignment_anonymous(app.state(), assignment.clone(), true, admin.clone(), None).await.unwrap();
+
+        let label = |seq: i64| Some(pseudonym(seq));
+        assert_eq!(label(1).as_deref(), Some("Submission #0001"));
+        assert_eq!(listed().await, [(subs[0].clone(), None, label(1)), (subs[1].clone(), None, label(2))]);
+        let detail = serde_json::to_value(crate::commands::get_submission_detail(app.state(), subs[1].clone()).await.unwrap()).unwrap();
+        assert_eq!((&detail["student_id"], &detail["student_name"]), (&serde_json::Value::Null, &serde_json::json!("Submission #0002")));
+
+        // Exports still carry the real students
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("canvas.csv");
+        crate::export::export_canvas_csv(app.state(), assignment.clone(), csv.to_string_lossy().into(), None).await.unwrap();
+        let exported = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(exported.lines().skip(1).collect::<Vec<_>>(), ["Ada Lovelace,,2,,,", "Zed Zimmer,,1,,,7"]);
+
+        let revealed: Vec<_> = reveal_anonymous_identities(app.state(), assignment.clone(), admin.clone(), None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.label, r.student_id, r.student_name))
+            .collect();
+        assert_eq!(revealed, [
+            ("Submission #0001".to_string(), Some("1".to_string()), Some("Zed Zimmer".to_string())),
+            ("Submission #0002".to_string(), Some("2".to_string()), Some("Ada Lovelace".to_string())),
+        ]);
+        let audited: Vec<String> = sqlx::query_scalar("SELECT action FROM audit_log WHERE entity_id = ? ORDER BY id")
+            .bind(&assignment)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(audited, ["set_anonymous", "reveal_identities"]);
+
+        set_assignment_anonymous(app.state(), assignment.clone(), false, admin, None).await.unwrap();
+        assert_eq!(listed().await[0], (subs[1].clone(), Some("2".to_string()), Some("Ada Lovelace".to_string())));
+    }
+
+    #[tokio::test]
+    async fn recent_viewers_are_active_and_announced() {
+        use tauri::Listener;
+        let db = TestDb::new().await;
+        let app = db.app();
+        let (_, _, subs) = roster_submissions(&db, 1).await;
+        let (ada, bo) = (db.ta("Ada").await, db.ta("Bo").await);
+        let announced = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = announced.clone();
+        app.listen(SUBMISSION_ACTIVITY_EVENT, move |event| sink.lock().unwrap().push(event.payload().to_string()));
+
+        note_activity(app.handle(), &db.pool, &subs[0], &ada, "viewing").await.unwrap();
+        note_activity(app.handle(), &db.pool, &subs[0], &bo, "claimed").await.unwrap();
+        let viewers = || async {
+            let mut names: Vec<_> = get_active_viewers(app.state(), subs[0].clone())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|v| v.display_name.unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(viewers().await, ["Ada", "Bo"]);
+
+        let payloads: Vec<serde_json::Value> = announced.lock().unwrap().iter().map(|p| serde_json::from_str(p).unwrap()).collect();
+        assert_eq!(payloads[1], serde_json::json!({
+            "submission_id": subs[0], "ta_id": bo, "ta_name": "Bo", "action": "claimed",
+        }));
+        assert_eq!(payloads.len(), 2);
+
+        // Outside the window a viewer drops off, and a fresh look brings them back
+        sqlx::query("UPDATE submission_viewers SET last_seen_at = datetime('now', ?) WHERE ta_id = ?")
+            .bind(format!("-{} minutes", ACTIVE_VIEWER_MINUTES + 1))
+            .bind(&ada)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(viewers().await, ["Bo"]);
+        note_activity(app.handle(), &db.pool, &subs[0], &ada, "viewing").await.unwrap();
+        assert_eq!(viewers().await, ["Ada", "Bo"]);
+    }
+
+    #[tokio::test]
+    async fn rematch_picks_up_students_added_to_the_roster() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, Some(RUBRIC)).await;
+        db.student(&course, "87654321", "Bo Chen").await;
+        let bo = db.submission(&assignment, Some("87654321"), Path::new("/nonexistent")).await;
+        let ada = unmatched(&db, &assignment, "12345678_hw1.zip").await;
+        let duplicate = unmatched(&db, &assignment, "87654321_resubmit.zip").await;
+        let cy = unmatched(&db, &assignment, "S1234_hw1.zip").await;
+        unmatched(&db, &assignment, "notes.zip").await;
+        let broken = unmatched(&db, &assignment, "12345678_copy.zip").await;
+        set_status(&db, &broken, "error").await;
+        let rematch = || rematch_unmatched(app.state(), assignment.clone(), None, None);
+
+        // Ada isn't on the roster yet, so only Bo's second upload matches, onto a student who already has one
+        let summary = rematch().await.unwrap();
+        assert_eq!((summary.checked, summary.matched.len(), summary.still_unmatched), (4, 0, 3));
+        let conflicts: Vec<_> = summary.conflicts.iter().map(|c| (&c.submission_id, c.student_id.as_str(), &c.existing_submission_id)).collect();
+        assert_eq!(conflicts, [(&duplicate, "87654321", &bo)]);
+
+        db.student(&course, "12345678", "Ada Lovelace").await;
+        db.student(&course, "S1234", "Cy Diaz").await;
+        sqlx::query("UPDATE courses SET id_pattern = ? WHERE id = ?").bind(r"(S\d{4}|\d{8})").bind(&course).execute(&db.pool).await.unwrap();
+        let summary = rematch().await.unwrap();
+        let matched: Vec<_> = summary.matched.iter().map(|m| (&m.submission_id, m.student_id.as_str(), m.method.as_str(), m.confidence)).collect();
+        assert_eq!(matched, [(&ada, "12345678", "filename", 1.0), (&cy, "S1234", "filename", 1.0)]);
+        assert_eq!((summary.checked, summary.conflicts.len(), summary.still_unmatched), (4, 1, 1));
+
+        let matched_to: Option<String> = sqlx::query_scalar("SELECT student_id FROM submissions WHERE id = ?").bind(&ada).fetch_one(&db.pool).await.unwrap();
+        assert_eq!(matched_to.as_deref(), Some("12345678"));
+        let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE action = 'auto_match'").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(audited, 2);
+    }
+}
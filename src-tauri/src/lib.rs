@@ -1,10 +1,13 @@
 mod db;
+mod auth;
 mod commands;
 mod submissions;
 mod excel;
 mod export;
 mod grading;
 mod docx;
+mod soffice;
+mod pdf;
 
 use tauri::Manager;
 
@@ -12,65 +15,131 @@ use tauri::Manager;
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(soffice::SofficeLimiter::default())
+        .manage(db::DbState::default())
         .setup(|app| {
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                match db::init_db(&handle).await {
-                    Ok(pool) => {
-                        handle.manage(pool);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to initialize database: {}", e);
-                    }
+                db::start(&handle).await;
+                if let Ok(pool) = handle.state::<db::DbState>().pool() {
+                    soffice::apply_saved_limits(&pool, &handle.state::<soffice::SofficeLimiter>()).await;
                 }
             });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            db::get_startup_status,
             // Course & TA
             commands::create_course,
             commands::list_courses,
+            commands::set_course_id_pattern,
+            commands::set_course_lms_convention,
+            commands::delete_course,
+            commands::delete_assignment,
+            commands::restore_assignment,
+            commands::get_db_info,
+            commands::get_setting,
+            commands::set_setting,
             commands::create_ta,
             commands::list_tas,
+            auth::set_ta_pin,
+            auth::start_ta_session,
+            auth::end_ta_session,
             commands::save_roster,
+            commands::preview_roster_import,
+            commands::save_roster_mapped,
             commands::list_students,
             // Assignments
             commands::create_assignment,
             commands::list_assignments,
             commands::get_assignment,
             commands::update_rubric,
+            commands::get_rubric_change_impact,
+            commands::diff_rubrics,
+            commands::clone_rubric,
+            commands::save_rubric_template,
+            commands::list_rubric_templates,
+            commands::apply_rubric_template,
             // Submissions
             submissions::process_submissions,
+            submissions::find_duplicate_submissions,
+            submissions::get_cache_usage,
+            submissions::clear_assignment_cache,
+            submissions::clear_orphan_cache,
+            submissions::reextract_submission,
             commands::get_submission_detail,
             commands::read_submission_file,
+            commands::reveal_submission_folder,
             // Grading
             commands::save_grade,
+            commands::bulk_save_grade,
+            commands::save_grade_draft,
+            commands::get_grade_draft,
+            commands::apply_comment_preset,
             commands::get_grades,
+            commands::assign_file_to_question,
             grading::list_submissions,
+            grading::distribute_submissions,
+            grading::next_submission,
+            grading::get_submission_versions,
+            grading::get_assignment_progress,
+            grading::compute_final_score,
+            grading::get_grader_stats,
+            grading::set_late_policy,
+            grading::apply_late_penalty,
+            grading::set_course_grade_scale,
+            grading::compute_letter_grade,
+            grading::set_assignment_anonymous,
+            grading::reveal_anonymous_identities,
             grading::claim_submission,
             grading::release_submission,
+            grading::release_stale_claims,
             grading::force_claim_submission,
             grading::update_submission_status,
+            grading::complete_submission,
             grading::get_session_bookmark,
             grading::touch_submission,
+            grading::get_active_viewers,
+            grading::undo_last_action,
             grading::log_audit,
             grading::get_audit_log,
+            grading::search_grades,
             grading::save_session_bookmark,
             grading::get_last_session_bookmark,
             grading::get_unmatched_submissions,
+            grading::rematch_unmatched,
             grading::manual_match_submission,
+            grading::bulk_match_submissions,
             grading::quarantine_submission,
+            grading::request_regrade,
+            grading::list_regrade_requests,
+            grading::resolve_regrade,
             grading::validate_zip,
             // Excel
             excel::analyze_excel,
             excel::generate_excel_pdf,
             excel::parse_excel_roster,
+            excel::parse_csv_roster,
             excel::get_formula_map,
             excel::run_formula_checks,
+            excel::compare_workbook_formulas,
             // DOCX
             docx::convert_docx_pdf,
+            soffice::detect_libreoffice,
+            soffice::get_conversion_limits,
+            soffice::set_conversion_limits,
+            soffice::batch_convert_to_pdf,
+            pdf::extract_pdf_text,
             // Export
-            export::export_gradebook
+            export::export_gradebook,
+            export::export_gradebook_pdf,
+            export::generate_feedback_report,
+            export::generate_all_feedback_reports,
+            export::export_feedback_mailmerge,
+            export::export_canvas_csv,
+            export::export_grades_long_csv,
+            export::export_assignment_bundle,
+            export::import_grades_csv
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,167 @@
+//! Page counts and text search in converted documents. Parsing is left to
+//! lopdf and pdf-extract; this module only bounds what they are handed.
+
+use tauri::State;
+use crate::db::DbState;
+use crate::soffice::{self, SofficeLimiter};
+use serde::Serialize;
+use std::path::Path;
+
+/// Larger PDFs are refused rather than parsed. Submissions are coursework, so
+/// anything past this is almost certainly not a document worth searching.
+const MAX_PDF_BYTES: u64 = 64 * 1024 * 1024;
+
+fn read_pdf(path: &Path) -> Result<Vec<u8>, String> {
+    let len = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if len > MAX_PDF_BYTES {
+        return Err(format!("PDF is larger than {} MB", MAX_PDF_BYTES / (1024 * 1024)));
+    }
+    std::fs::read(path).map_err(|e| e.to_string())
+}
+
+/// Number of pages in a PDF, or None if its page tree can't be read
+pub fn page_count(path: &Path) -> Option<u32> {
+    let bytes = read_pdf(path).ok()?;
+    let doc = lopdf::Document::load_mem(&bytes).ok()?;
+    let pages = doc.get_pages().len() as u32;
+    (pages > 0).then_some(pages)
+}
+
+/// Text of each page, in order. pdf-extract panics on some malformed files, so
+/// it runs on a blocking thread where a panic comes back as an error instead
+/// of taking the command down with it.
+async fn page_texts(path: &Path) -> Result<Vec<String>, String> {
+    let bytes = read_pdf(path)?;
+    let pages = tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem_by_pages(&bytes))
+        .await
+        .map_err(|_| "PDF could not be read".to_string())?
+        .map_err(|e| format!("PDF could not be read: {}", e))?;
+    Ok(pages
+        .into_iter()
+        .map(|text| text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_string())
+        .collect())
+}
+
+#[derive(Serialize, Debug)]
+pub struct PdfPageText {
+    /// 1-based page number
+    pub page: usize,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PdfTextResult {
+    /// Absolute path of the PDF the text came from
+    pub pdf_path: String,
+    pub pages: Vec<PdfPageText>,
+}
+
+/// Plain text of a submission PDF, page by page, for searching without the
+/// viewer. Word and Excel files are converted first (reusing a cached PDF).
+#[tauri::command]
+pub async fn extract_pdf_text(
+    db: State<'_, DbState>,
+    limiter: State<'_, SofficeLimiter>,
+    submission_id: String,
+    file_path: String,
+) -> Result<PdfTextResult, String> {
+    let pool = db.pool()?;
+    let folder_path: String = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE id = ?")
+        .bind(&submission_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Submission not found")?;
+    let full_path = crate::commands::resolve_in_folder(&folder_path, &file_path)?;
+
+    let ext = full_path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let pdf_path = if ext == "pdf" {
+        full_path
+    } else if soffice::CONVERTIBLE_EXTENSIONS.contains(&ext.as_str()) {
+        let output_dir = full_path.parent().ok_or("File has no parent folder")?;
+        match soffice::cached_pdf(&full_path, output_dir) {
+            Some(cached) => cached,
+            None => {
+                let binary = soffice::configured_binary(&pool).await?;
+                soffice::convert_to_pdf(&limiter, &binary, &full_path, output_dir).await?
+            }
+        }
+    } else {
+        return Err(format!("Cannot extract text from .{} files", ext));
+    };
+
+    let pages = page_texts(&pdf_path)
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| PdfPageText { page: i + 1, text })
+        .collect();
+
+    Ok(PdfTextResult {
+        pdf_path: pdf_path.to_string_lossy().to_string(),
+        pages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{fixture, TestDb};
+    use tauri::Manager;
+
+    #[test]
+    fn counts_pages() {
+        assert_eq!(page_count(&fixture("two_pages.pdf")), Some(2));
+    }
+
+    #[tokio::test]
+    async fn extracts_text_per_page() {
+        let pages = page_texts(&fixture("two_pages.pdf")).await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains("Total revenue was 4200 dollars"), "{:?}", pages[0]);
+        assert!(pages[1].contains("Depreciation uses straight line"), "{:?}", pages[1]);
+        assert!(!pages[0].contains("Depreciation"));
+    }
+
+    #[tokio::test]
+    async fn malformed_pdf_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let full = std::fs::read(fixture("two_pages.pdf")).unwrap();
+        let truncated = dir.path().join("truncated.pdf");
+        std::fs::write(&truncated, &full[..full.len() / 2]).unwrap();
+        let garbage = dir.path().join("garbage.pdf");
+        std::fs::write(&garbage, b"%PDF-1.4\n1 0 obj << /Kids [[[[[[[[ >>").unwrap();
+
+        for path in [&truncated, &garbage] {
+            assert!(page_texts(path).await.is_err());
+        }
+        assert_eq!(page_count(&garbage), None);
+    }
+
+    #[tokio::test]
+    async fn submission_text_is_numbered_by_page() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("submission");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::copy(fixture("two_pages.pdf"), folder.join("report.pdf")).unwrap();
+        std::fs::write(folder.join("notes.txt"), b"Plain text").unwrap();
+        std::fs::copy(fixture("two_pages.pdf"), dir.path().join("outside.pdf")).unwrap();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let submission = db.submission(&assignment, Some("1"), &folder).await;
+        let extract = |file: &str| extract_pdf_text(app.state(), app.state(), submission.clone(), file.to_string());
+
+        let result = extract("report.pdf").await.unwrap();
+        assert_eq!(Path::new(&result.pdf_path), folder.join("report.pdf").canonicalize().unwrap());
+        assert_eq!(result.pages.iter().map(|p| p.page).collect::<Vec<_>>(), [1, 2]);
+        assert!(result.pages[1].text.contains("Depreciation uses straight line"));
+
+        assert_eq!(extract("notes.txt").await.err().as_deref(), Some("Cannot extract text from .txt files"));
+        assert_eq!(
+            extract("../outside.pdf").await.err().as_deref(),
+            Some("Access denied: path is outside the submission folder")
+        );
+    }
+}
@@ -0,0 +1,794 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use crate::db::{DbPool, DbState};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use tokio::process::Command;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// How many LibreOffice conversions may run at once unless configured otherwise
+pub const DEFAULT_MAX_CONVERSIONS: usize = 1;
+/// How long a single conversion may run before it is killed
+pub const DEFAULT_CONVERSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Bounds concurrent `soffice` invocations and their run time. Managed in
+/// Tauri state so every conversion command shares the same permits.
+#[derive(Clone)]
+pub struct SofficeLimiter {
+    permits: Arc<Semaphore>,
+    /// Permits to retire as running conversions finish, after the limit was
+    /// lowered while they held them
+    shrink_debt: Arc<AtomicUsize>,
+    config: Arc<RwLock<LimiterConfig>>,
+}
+
+struct LimiterConfig {
+    max_conversions: usize,
+    timeout: Duration,
+}
+
+/// A held conversion slot. Dropping it frees the slot, unless the limit was
+/// lowered meanwhile, in which case the slot is retired instead.
+struct ConversionSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    shrink_debt: Arc<AtomicUsize>,
+}
+
+impl Drop for ConversionSlot {
+    fn drop(&mut self) {
+        let retire = self.shrink_debt.fetch_update(Ordering::AcqRel, Ordering::Acquire, |d| d.checked_sub(1)).is_ok();
+        if let (true, Some(permit)) = (retire, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+impl SofficeLimiter {
+    pub fn new(max_conversions: usize, timeout: Duration) -> Self {
+        let max_conversions = max_conversions.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max_conversions)),
+            shrink_debt: Arc::new(AtomicUsize::new(0)),
+            config: Arc::new(RwLock::new(LimiterConfig { max_conversions, timeout })),
+        }
+    }
+
+    /// Change the limits. The permit count is resized in place, so conversions
+    /// already running keep counting against the new limit; lowering it only
+    /// lets new ones start once enough of them have finished.
+    pub fn configure(&self, max_conversions: usize, timeout: Duration) {
+        let max_conversions = max_conversions.max(1);
+        let mut config = self.config.write().unwrap_or_else(|e| e.into_inner());
+        if max_conversions > config.max_conversions {
+            let grow = max_conversions - config.max_conversions;
+            // Permits not yet retired from an earlier decrease count towards the increase
+            let mut settled = 0;
+            let _ = self.shrink_debt.fetch_update(Ordering::AcqRel, Ordering::Acquire, |d| {
+                settled = d.min(grow);
+                Some(d - settled)
+            });
+            self.permits.add_permits(grow - settled);
+        } else if max_conversions < config.max_conversions {
+            let shrink = config.max_conversions - max_conversions;
+            let forgotten = self.permits.forget_permits(shrink);
+            self.shrink_debt.fetch_add(shrink - forgotten, Ordering::AcqRel);
+        }
+        config.max_conversions = max_conversions;
+        config.timeout = timeout;
+    }
+
+    pub fn limits(&self) -> ConversionLimits {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        ConversionLimits { max_conversions: config.max_conversions, timeout_secs: config.timeout.as_secs() }
+    }
+
+    /// Wait for a free slot; returns it with the timeout to run under
+    async fn acquire(&self) -> Result<(ConversionSlot, Duration), String> {
+        let permit = self.permits.clone().acquire_owned().await.map_err(|e| e.to_string())?;
+        let timeout = self.config.read().unwrap_or_else(|e| e.into_inner()).timeout;
+        Ok((ConversionSlot { permit: Some(permit), shrink_debt: self.shrink_debt.clone() }, timeout))
+    }
+}
+
+impl Default for SofficeLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONVERSIONS, DEFAULT_CONVERSION_TIMEOUT)
+    }
+}
+
+/// Settings keys for the conversion limits
+pub const MAX_CONVERSIONS_SETTING: &str = "soffice_max_conversions";
+pub const CONVERSION_TIMEOUT_SETTING: &str = "soffice_timeout_secs";
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConversionLimits {
+    pub max_conversions: usize,
+    pub timeout_secs: u64,
+}
+
+/// Apply the limits saved in settings, if any. Called once the database is open;
+/// unreadable values are reported and the defaults kept.
+pub async fn apply_saved_limits(pool: &DbPool, limiter: &SofficeLimiter) {
+    let setting = |key: &'static str| async move {
+        sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(pool)
+            .await
+            .ok()
+            .flatten()
+    };
+    let parse = |key: &str, value: Option<String>, default: u64| match value.map(|v| v.trim().parse::<u64>()) {
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            eprintln!("Ignoring invalid {} setting; using {}", key, default);
+            default
+        }
+        None => default,
+    };
+    let max = parse(MAX_CONVERSIONS_SETTING, setting(MAX_CONVERSIONS_SETTING).await, DEFAULT_MAX_CONVERSIONS as u64);
+    let timeout = parse(CONVERSION_TIMEOUT_SETTING, setting(CONVERSION_TIMEOUT_SETTING).await, DEFAULT_CONVERSION_TIMEOUT.as_secs());
+    limiter.configure(max as usize, Duration::from_secs(timeout));
+}
+
+/// Current conversion concurrency limit and timeout
+#[tauri::command]
+pub fn get_conversion_limits(limiter: State<'_, SofficeLimiter>) -> ConversionLimits {
+    limiter.limits()
+}
+
+/// Set how many LibreOffice conversions may run at once and how many seconds
+/// each may take. Saved to settings so they apply again on the next start.
+#[tauri::command]
+pub async fn set_conversion_limits(
+    db: State<'_, DbState>,
+    limiter: State<'_, SofficeLimiter>,
+    max_conversions: usize,
+    timeout_secs: u64,
+) -> Result<ConversionLimits, String> {
+    let pool = db.pool()?;
+    if max_conversions == 0 {
+        return Err("At least one conversion must be allowed at a time".to_string());
+    }
+    if timeout_secs == 0 {
+        return Err("Conversion timeout must be at least one second".to_string());
+    }
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    for (key, value) in [(MAX_CONVERSIONS_SETTING, max_conversions as u64), (CONVERSION_TIMEOUT_SETTING, timeout_secs)] {
+        sqlx::query(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, CURRENT_TIMESTAMP)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )
+            .bind(key)
+            .bind(value.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    limiter.configure(max_conversions, Duration::from_secs(timeout_secs));
+    Ok(limiter.limits())
+}
+
+/// Settings key holding an explicit path to the soffice binary
+pub const LIBREOFFICE_PATH_SETTING: &str = "libreoffice_path";
+
+#[cfg(windows)]
+const SOFFICE_BIN: &str = "soffice.exe";
+#[cfg(not(windows))]
+const SOFFICE_BIN: &str = "soffice";
+
+/// Common install locations for the current OS
+fn install_candidates() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    #[cfg(target_os = "macos")]
+    {
+        paths.push(PathBuf::from("/Applications/LibreOffice.app/Contents/MacOS/soffice"));
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(PathBuf::from(home).join("Applications/LibreOffice.app/Contents/MacOS/soffice"));
+        }
+    }
+    #[cfg(windows)]
+    {
+        for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+            if let Some(dir) = std::env::var_os(var) {
+                paths.push(PathBuf::from(dir).join("LibreOffice").join("program").join(SOFFICE_BIN));
+            }
+        }
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        for p in [
+            "/usr/bin/soffice",
+            "/usr/local/bin/soffice",
+            "/usr/lib/libreoffice/program/soffice",
+            "/opt/libreoffice/program/soffice",
+            "/snap/bin/libreoffice",
+        ] {
+            paths.push(PathBuf::from(p));
+        }
+    }
+    paths
+}
+
+/// Look for soffice on PATH first, then in the usual install locations
+fn find_libreoffice() -> Option<PathBuf> {
+    let on_path = std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).map(|dir| dir.join(SOFFICE_BIN)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    on_path.into_iter().chain(install_candidates()).find(|p| p.is_file())
+}
+
+/// The path saved in settings, if one has been set
+async fn saved_binary(pool: &DbPool) -> Result<Option<PathBuf>, String> {
+    let configured: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(LIBREOFFICE_PATH_SETTING)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(configured.filter(|p| !p.trim().is_empty()).map(PathBuf::from))
+}
+
+/// The soffice binary to run: the configured path if set, else one found on PATH
+/// or in a standard install location. Falls back to the bare name so the spawn
+/// error explains that LibreOffice is missing.
+pub async fn configured_binary(pool: &DbPool) -> Result<PathBuf, String> {
+    Ok(saved_binary(pool)
+        .await?
+        .or_else(find_libreoffice)
+        .unwrap_or_else(|| PathBuf::from(SOFFICE_BIN)))
+}
+
+/// Resolve the LibreOffice binary, preferring the configured path.
+/// Returns the path so the UI can offer to save it.
+#[tauri::command]
+pub async fn detect_libreoffice(db: State<'_, DbState>) -> Result<String, String> {
+    let pool = db.pool()?;
+    if let Some(configured) = saved_binary(&pool).await?.filter(|p| p.is_file()) {
+        return Ok(configured.to_string_lossy().to_string());
+    }
+    find_libreoffice()
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| {
+            "LibreOffice was not found. Install it from https://www.libreoffice.org or set its location in Settings.".to_string()
+        })
+}
+
+/// Kill a hung conversion along with the helper processes soffice spawns
+/// (the child is started in its own process group on unix)
+fn kill_process_tree(pid: u32) {
+    #[cfg(unix)]
+    // Without `--`, procps kill reads `-<pid>` as another option and the group survives
+    let _ = std::process::Command::new("kill")
+        .args(["-KILL", "--"])
+        .arg(format!("-{}", pid))
+        .status();
+    #[cfg(windows)]
+    let _ = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .status();
+}
+
+/// `file://` URL for a local path, as expected by `-env:UserInstallation`
+fn file_url(path: &Path) -> String {
+    let p = path.to_string_lossy().replace('\\', "/");
+    if p.starts_with('/') {
+        format!("file://{}", p)
+    } else {
+        format!("file:///{}", p)
+    }
+}
+
+/// Where the PDF for `input` is kept: the full file name plus `.pdf`, so
+/// `report.docx` and `report.xlsx` in one folder don't share `report.pdf`
+fn pdf_path_for(input: &Path, output_dir: &Path) -> Result<PathBuf, String> {
+    let file_name = input.file_name().ok_or("Invalid file name")?.to_string_lossy();
+    Ok(output_dir.join(format!("{}.pdf", file_name)))
+}
+
+/// A previously converted PDF that is still usable: non-empty and at least
+/// as new as the source. Zero-byte PDFs from failed runs count as stale.
+pub fn cached_pdf(input: &Path, output_dir: &Path) -> Option<PathBuf> {
+    let pdf_path = pdf_path_for(input, output_dir).ok()?;
+    let pdf_meta = std::fs::metadata(&pdf_path).ok()?;
+    if pdf_meta.len() == 0 {
+        return None;
+    }
+    let source_modified = std::fs::metadata(input).and_then(|m| m.modified()).ok()?;
+    let pdf_modified = pdf_meta.modified().ok()?;
+    (pdf_modified >= source_modified).then_some(pdf_path)
+}
+
+/// Convert a document to PDF in `output_dir` using LibreOffice headless.
+/// Waits for a free conversion slot, and gives each run its own throwaway
+/// user profile so parallel instances don't corrupt each other.
+/// Returns the path of the produced PDF.
+pub async fn convert_to_pdf(
+    limiter: &SofficeLimiter,
+    binary: &Path,
+    input: &Path,
+    output_dir: &Path,
+) -> Result<PathBuf, String> {
+    let (_slot, timeout) = limiter.acquire().await?;
+
+    let run_id = Uuid::new_v4();
+    let profile_dir = std::env::temp_dir().join(format!("soffice-profile-{}", run_id));
+    // LibreOffice always names its output `<stem>.pdf`, so it writes into a private
+    // staging folder and the result is moved to its extension-qualified name
+    let staging_dir = std::env::temp_dir().join(format!("soffice-out-{}", run_id));
+    std::fs::create_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+
+    let pdf_path = pdf_path_for(input, output_dir)?;
+
+    let mut cmd = Command::new(binary);
+    cmd.arg(format!("-env:UserInstallation={}", file_url(&profile_dir)))
+        .arg("--headless")
+        .arg("--convert-to")
+        .arg("pdf")
+        .arg(input)
+        .arg("--outdir")
+        .arg(&staging_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let result = async {
+        let child = cmd.spawn().map_err(|e| {
+            format!(
+                "Could not start LibreOffice at '{}': {}. Set its location in Settings or run detection.",
+                binary.display(),
+                e
+            )
+        })?;
+        let pid = child.id();
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(res) => res.map_err(|e| format!("Failed to run LibreOffice: {}", e))?,
+            Err(_) => {
+                if let Some(pid) = pid {
+                    kill_process_tree(pid);
+                }
+                return Err("conversion timed out".to_string());
+            }
+        };
+
+        if !output.status.success() {
+            return Err(format!(
+                "LibreOffice conversion failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stem = input.file_stem().ok_or("Invalid file name")?.to_string_lossy();
+        let produced = staging_dir.join(format!("{}.pdf", stem));
+        if !produced.is_file() {
+            return Err("LibreOffice finished without producing a PDF".to_string());
+        }
+        // The temp dir may be on another filesystem, where a rename fails
+        std::fs::rename(&produced, &pdf_path)
+            .or_else(|_| std::fs::copy(&produced, &pdf_path).map(|_| ()))
+            .map_err(|e| format!("Failed to save PDF: {}", e))?;
+        Ok(pdf_path)
+    }
+    .await;
+
+    // A timed-out run's partial PDF goes with the staging folder, so it is
+    // never mistaken for a result
+    let _ = std::fs::remove_dir_all(&profile_dir);
+    let _ = std::fs::remove_dir_all(&staging_dir);
+    result
+}
+
+/// Extensions LibreOffice converts for previews
+pub const CONVERTIBLE_EXTENSIONS: [&str; 5] = ["docx", "doc", "xlsx", "xlsm", "xls"];
+
+/// Event emitted after each file of a batch conversion finishes
+pub const BATCH_PROGRESS_EVENT: &str = "pdf-batch-progress";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchConversionItem {
+    pub submission_id: String,
+    /// Relative to the submission folder
+    pub file_path: String,
+    /// "converted", "cached" or "failed"
+    pub status: String,
+    pub pdf_name: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchProgress {
+    pub assignment_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub item: BatchConversionItem,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct BatchConversionReport {
+    pub total: usize,
+    pub converted: usize,
+    pub cached: usize,
+    pub failed: usize,
+    pub items: Vec<BatchConversionItem>,
+}
+
+/// Pre-convert every DOCX/XLSX in an assignment's current submissions so
+/// previews open instantly later. Files with an up-to-date PDF are skipped;
+/// the rest share the limiter's conversion slots. Emits `pdf-batch-progress`
+/// after each file.
+#[tauri::command]
+pub async fn batch_convert_to_pdf(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    limiter: State<'_, SofficeLimiter>,
+    assignment_id: String,
+    ta_id: String,
+    session_token: Option<String>,
+) -> Result<BatchConversionReport, String> {
+    let pool = db.pool()?;
+    crate::auth::verify_ta(&pool, &ta_id, session_token.as_deref()).await?;
+    let report = convert_assignment(&pool, &limiter, &assignment_id, |progress| {
+        let _ = app.emit(BATCH_PROGRESS_EVENT, progress);
+    })
+    .await?;
+
+    let details = serde_json::json!({
+        "total": report.total,
+        "converted": report.converted,
+        "cached": report.cached,
+        "failed": report.failed,
+    })
+    .to_string();
+    crate::grading::log_audit_internal(&pool, Some(&ta_id), "batch_convert_pdf", "assignment", &assignment_id, Some(&details)).await?;
+
+    Ok(report)
+}
+
+/// The work of `batch_convert_to_pdf`. `on_progress` is called as each file
+/// finishes, cached ones first.
+pub(crate) async fn convert_assignment(
+    pool: &DbPool,
+    limiter: &SofficeLimiter,
+    assignment_id: &str,
+    mut on_progress: impl FnMut(BatchProgress),
+) -> Result<BatchConversionReport, String> {
+    let folders: Vec<(String, String)> = sqlx::query_as(
+        "SELECT id, folder_path FROM submissions WHERE assignment_id = ? AND deleted_at IS NULL AND superseded_by IS NULL"
+    )
+    .bind(assignment_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut files: Vec<(String, PathBuf, String)> = Vec::new();
+    for (submission_id, folder_path) in &folders {
+        let root = Path::new(folder_path);
+        let walker = walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| !crate::commands::is_extraction_junk(&e.file_name().to_string_lossy()));
+        for entry in walker.filter_map(|e| e.ok()) {
+            let ext = entry.path().extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            // Office lock files (~$name.docx) aren't real documents
+            let lock_file = entry.file_name().to_string_lossy().starts_with("~$");
+            if entry.file_type().is_file() && !lock_file && CONVERTIBLE_EXTENSIONS.contains(&ext.as_str()) {
+                let rel = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().replace('\\', "/");
+                files.push((submission_id.clone(), entry.path().to_path_buf(), rel));
+            }
+        }
+    }
+
+    let mut report = BatchConversionReport { total: files.len(), ..Default::default() };
+    let mut pending = tokio::task::JoinSet::new();
+    let mut binary: Option<PathBuf> = None;
+
+    for (submission_id, full_path, rel) in files {
+        let output_dir = full_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if let Some(cached) = cached_pdf(&full_path, &output_dir) {
+            let item = BatchConversionItem {
+                submission_id,
+                file_path: rel,
+                status: "cached".to_string(),
+                pdf_name: cached.file_name().map(|n| n.to_string_lossy().to_string()),
+                error: None,
+            };
+            record_batch_item(assignment_id, &mut report, item, &mut on_progress);
+            continue;
+        }
+
+        if binary.is_none() {
+            binary = Some(configured_binary(pool).await?);
+        }
+        let binary = binary.clone().unwrap_or_default();
+        let limiter = limiter.clone();
+        pending.spawn(async move {
+            let result = convert_to_pdf(&limiter, &binary, &full_path, &output_dir).await;
+            let (status, pdf_name, error) = match result {
+                Ok(pdf) => ("converted", pdf.file_name().map(|n| n.to_string_lossy().to_string()), None),
+                Err(e) => ("failed", None, Some(e)),
+            };
+            BatchConversionItem { submission_id, file_path: rel, status: status.to_string(), pdf_name, error }
+        });
+    }
+
+    while let Some(joined) = pending.join_next().await {
+        let item = joined.map_err(|e| e.to_string())?;
+        record_batch_item(assignment_id, &mut report, item, &mut on_progress);
+    }
+    Ok(report)
+}
+
+fn record_batch_item(
+    assignment_id: &str,
+    report: &mut BatchConversionReport,
+    item: BatchConversionItem,
+    on_progress: &mut impl FnMut(BatchProgress),
+) {
+    match item.status.as_str() {
+        "converted" => report.converted += 1,
+        "cached" => report.cached += 1,
+        _ => report.failed += 1,
+    }
+    let progress = BatchProgress {
+        assignment_id: assignment_id.to_string(),
+        completed: report.items.len() + 1,
+        total: report.total,
+        item: item.clone(),
+    };
+    on_progress(progress);
+    report.items.push(item);
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use std::os::unix::fs::PermissionsExt;
+    use tauri::Manager;
+
+    /// A stand-in for soffice: a shell script that runs `body` with `$input`
+    /// and `$outdir` taken from the arguments convert_to_pdf passes
+    fn fake_soffice(dir: &Path, body: &str) -> PathBuf {
+        let path = dir.join("soffice");
+        let script = format!("#!/bin/sh\ninput=\"$5\"\noutdir=\"$7\"\nstem=$(basename \"$input\")\nstem=\"${{stem%.*}}\"\n{}\n", body);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Records how many conversions are running as each one starts, then waits
+    /// until `barrier` conversions have started and recorded theirs before
+    /// writing its PDF. A conversion that can't reach the barrier fails after
+    /// a few seconds.
+    fn barrier_soffice(dir: &Path, state: &Path, barrier: usize) -> PathBuf {
+        fake_soffice(
+            dir,
+            &format!(
+                concat!(
+                    "state=\"{state}\"\n",
+                    "touch \"$state/active.$stem\"\n",
+                    "seen=$(ls \"$state\" | grep -c '^active\\.')\n",
+                    "echo \"$seen\" > \"$state/seen.$stem\"\n",
+                    "tries=0\n",
+                    "# Nobody finishes until the whole group has counted, so the last to start sees all of it\n",
+                    "while [ \"$(ls \"$state\" | grep -c '^seen\\.')\" -lt {barrier} ]; do\n",
+                    "  tries=$((tries + 1)); [ $tries -gt 200 ] && exit 1\n",
+                    "  sleep 0.02\n",
+                    "done\n",
+                    "rm \"$state/active.$stem\"\n",
+                    "echo '%PDF-1.4' > \"$outdir/$stem.pdf\"",
+                ),
+                state = state.display(),
+                barrier = barrier,
+            ),
+        )
+    }
+
+    /// Convert `count` documents at once, each waiting until `barrier` of them
+    /// have started. Returns the most that were ever running together.
+    async fn convert_together(limiter: &SofficeLimiter, count: usize, barrier: usize) -> usize {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("state");
+        std::fs::create_dir(&state).unwrap();
+        let binary = barrier_soffice(dir.path(), &state, barrier);
+        let inputs: Vec<PathBuf> = (0..count).map(|i| dir.path().join(format!("essay{}.docx", i))).collect();
+        for input in &inputs {
+            std::fs::write(input, b"not really a docx").unwrap();
+        }
+
+        let mut running = tokio::task::JoinSet::new();
+        for input in inputs {
+            let (limiter, binary, output_dir) = (limiter.clone(), binary.clone(), dir.path().to_path_buf());
+            running.spawn(async move {
+                let pdf = convert_to_pdf(&limiter, &binary, &input, &output_dir).await.unwrap();
+                assert_eq!(pdf, pdf_path_for(&input, &output_dir).unwrap());
+            });
+        }
+        while let Some(done) = running.join_next().await {
+            done.unwrap();
+        }
+        (0..count)
+            .map(|i| std::fs::read_to_string(state.join(format!("seen.essay{}", i))).unwrap().trim().parse().unwrap())
+            .max()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn conversions_wait_for_a_free_slot() {
+        assert_eq!(convert_together(&SofficeLimiter::new(1, Duration::from_secs(10)), 3, 1).await, 1);
+        // Each pair has to run side by side to get past the barrier, but never a third
+        assert_eq!(convert_together(&SofficeLimiter::new(2, Duration::from_secs(10)), 4, 2).await, 2);
+        assert_eq!(convert_together(&SofficeLimiter::new(3, Duration::from_secs(10)), 3, 3).await, 3);
+    }
+
+    #[tokio::test]
+    async fn lowering_the_limit_counts_conversions_already_running() {
+        let limiter = SofficeLimiter::new(3, Duration::from_secs(10));
+        let mut held = Vec::new();
+        for _ in 0..3 {
+            held.push(limiter.acquire().await.unwrap().0);
+        }
+        limiter.configure(1, Duration::from_secs(10));
+        assert_eq!(limiter.limits().max_conversions, 1);
+
+        // Two of the three running conversions have to finish before the one slot frees up
+        held.pop();
+        assert_eq!(limiter.permits.available_permits(), 0);
+        held.pop();
+        assert_eq!(limiter.permits.available_permits(), 0);
+        held.pop();
+        assert_eq!(limiter.permits.available_permits(), 1);
+
+        // Raising the limit first cancels slots still waiting to be retired
+        limiter.configure(2, Duration::from_secs(10));
+        let running = [limiter.acquire().await.unwrap().0, limiter.acquire().await.unwrap().0];
+        limiter.configure(1, Duration::from_secs(10));
+        limiter.configure(3, Duration::from_secs(10));
+        assert_eq!(limiter.permits.available_permits(), 1);
+        drop(running);
+        assert_eq!(limiter.permits.available_permits(), 3);
+    }
+
+    #[tokio::test]
+    async fn hung_conversion_is_killed_with_its_children() {
+        let dir = tempfile::tempdir().unwrap();
+        // A helper process that would leave a marker if it outlived the timeout
+        let binary = fake_soffice(
+            dir.path(),
+            &format!("(sleep 2; touch \"{}/survived\") &\nwait", dir.path().display()),
+        );
+        let input = dir.path().join("essay.docx");
+        std::fs::write(&input, b"not really a docx").unwrap();
+
+        let limiter = SofficeLimiter::new(1, Duration::from_secs(1));
+        let started = std::time::Instant::now();
+        let result = convert_to_pdf(&limiter, &binary, &input, dir.path()).await;
+        assert_eq!(result.unwrap_err(), "conversion timed out");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(!pdf_path_for(&input, dir.path()).unwrap().exists());
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert!(!dir.path().join("survived").exists());
+        // The slot was given back
+        assert_eq!(limiter.permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn configured_path_wins_over_detection() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        let binary = fake_soffice(dir.path(), "echo '%PDF-1.4' > \"$outdir/$stem.pdf\"");
+        let set_path = |path: &Path| {
+            crate::commands::set_setting(app.state(), LIBREOFFICE_PATH_SETTING.into(), Some(path.to_string_lossy().into()))
+        };
+
+        set_path(&binary).await.unwrap();
+        assert_eq!(configured_binary(&db.pool).await.unwrap(), binary);
+        assert_eq!(detect_libreoffice(app.state()).await.unwrap(), binary.to_string_lossy());
+
+        // A stale setting is still what conversions try, so the error names it;
+        // detection looks elsewhere instead of offering it again
+        let missing = dir.path().join("gone/soffice");
+        set_path(&missing).await.unwrap();
+        assert_eq!(configured_binary(&db.pool).await.unwrap(), missing);
+        let detected = detect_libreoffice(app.state()).await;
+        assert_eq!(detected.ok(), find_libreoffice().map(|p| p.to_string_lossy().to_string()));
+
+        let input = dir.path().join("essay.docx");
+        std::fs::write(&input, b"not really a docx").unwrap();
+        let err = convert_to_pdf(&SofficeLimiter::default(), &missing, &input, dir.path()).await.unwrap_err();
+        assert!(err.starts_with(&format!("Could not start LibreOffice at '{}'", missing.display())), "{}", err);
+    }
+
+    #[test]
+    fn cached_pdf_must_be_non_empty_and_current() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("report.docx");
+        std::fs::write(&input, b"not really a docx").unwrap();
+        let pdf = dir.path().join("report.docx.pdf");
+        let set_modified = |path: &Path, secs_ago: u64| {
+            let when = std::time::SystemTime::now() - Duration::from_secs(secs_ago);
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(when).unwrap();
+        };
+
+        assert_eq!(cached_pdf(&input, dir.path()), None);
+        std::fs::write(&pdf, b"").unwrap();
+        assert_eq!(cached_pdf(&input, dir.path()), None);
+
+        std::fs::write(&pdf, b"%PDF-1.4").unwrap();
+        set_modified(&input, 60);
+        set_modified(&pdf, 30);
+        assert_eq!(cached_pdf(&input, dir.path()), Some(pdf.clone()));
+        // Editing the source makes the PDF stale
+        set_modified(&input, 0);
+        assert_eq!(cached_pdf(&input, dir.path()), None);
+        // A same-stem file of another type has its own PDF
+        assert_eq!(cached_pdf(&dir.path().join("report.xlsx"), dir.path()), None);
+    }
+
+    #[tokio::test]
+    async fn batch_converts_current_submissions_and_reuses_cached_pdfs() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let dir = tempfile::tempdir().unwrap();
+        let binary = fake_soffice(
+            dir.path(),
+            "[ \"$stem\" = broken ] && { echo 'source file could not be loaded' >&2; exit 1; }\necho '%PDF-1.4' > \"$outdir/$stem.pdf\"",
+        );
+        crate::commands::set_setting(app.state(), LIBREOFFICE_PATH_SETTING.into(), Some(binary.to_string_lossy().into()))
+            .await
+            .unwrap();
+
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let (first, second, old) = (dir.path().join("first"), dir.path().join("second"), dir.path().join("old"));
+        for (folder, files) in [
+            (&first, &["essay.docx", "~$essay.docx", "notes.txt", "work/ledger.xlsx"][..]),
+            (&second, &["broken.docx", "budget.xls"][..]),
+            (&old, &["draft.docx"][..]),
+        ] {
+            for file in files {
+                std::fs::create_dir_all(folder.join(file).parent().unwrap()).unwrap();
+                std::fs::write(folder.join(file), b"not really a document").unwrap();
+            }
+        }
+        // Converted earlier and still current
+        std::fs::write(second.join("budget.xls.pdf"), b"%PDF-1.4").unwrap();
+        db.submission(&assignment, Some("1"), &first).await;
+        let current = db.submission(&assignment, Some("2"), &second).await;
+        let superseded = db.submission(&assignment, Some("2"), &old).await;
+        sqlx::query("UPDATE submissions SET superseded_by = ? WHERE id = ?")
+            .bind(&current)
+            .bind(&superseded)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let mut progress = Vec::new();
+        let limiter = SofficeLimiter::new(2, Duration::from_secs(10));
+        let report = convert_assignment(&db.pool, &limiter, &assignment, |p| progress.push((p.completed, p.total))).await.unwrap();
+        assert_eq!((report.total, report.converted, report.cached, report.failed), (4, 2, 1, 1));
+        assert_eq!(progress, [(1, 4), (2, 4), (3, 4), (4, 4)]);
+
+        let mut items: Vec<(&str, &str, Option<&str>)> =
+            report.items.iter().map(|i| (i.file_path.as_str(), i.status.as_str(), i.pdf_name.as_deref())).collect();
+        items.sort();
+        assert_eq!(items, [
+            ("broken.docx", "failed", None),
+            ("budget.xls", "cached", Some("budget.xls.pdf")),
+            ("essay.docx", "converted", Some("essay.docx.pdf")),
+            ("work/ledger.xlsx", "converted", Some("ledger.xlsx.pdf")),
+        ]);
+        let broken = report.items.iter().find(|i| i.status == "failed").unwrap();
+        assert!(broken.error.as_deref().unwrap().ends_with("source file could not be loaded"), "{:?}", broken.error);
+        assert!(first.join("work/ledger.xlsx.pdf").is_file());
+        assert!(!old.join("draft.docx.pdf").exists());
+    }
+}
@@ -1,10 +1,11 @@
-use crate::db::DbPool;
+use crate::db::{DbPool, DbState};
 use serde::{Deserialize, Serialize};
 use sqlx::sqlite::SqlitePool;
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Manager, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 use sha2::{Sha256, Digest};
 use zip::ZipArchive;
 use regex::Regex;
@@ -17,142 +18,889 @@ pub struct ProcessResult {
     message: Option<String>,
 }
 
+/// Emitted after each ZIP in `process_submissions` is handled
+pub const SUBMISSION_PROGRESS_EVENT: &str = "submission-progress";
+
+#[derive(Serialize, Clone)]
+pub struct SubmissionProgress {
+    assignment_id: String,
+    /// 1-based position of this file in the batch
+    index: usize,
+    total: usize,
+    filename: String,
+    status: String,
+}
+
+fn error_result(filename: &str, student_id: Option<String>, message: String) -> ProcessResult {
+    ProcessResult {
+        filename: filename.to_string(),
+        status: "Error".to_string(),
+        student_id,
+        message: Some(message),
+    }
+}
+
+/// A course's ID pattern, LMS naming and roster, loaded once per batch
+struct CourseMatcher {
+    id_regex: Regex,
+    lms_naming: Option<LmsNaming>,
+    roster: Roster,
+}
+
+/// A ZIP that has been hashed, extracted and matched, waiting to be inserted
+struct PreparedSubmission {
+    path_str: String,
+    filename: String,
+    hash: String,
+    extraction_dir: PathBuf,
+    student: StudentMatch,
+    original_filename: String,
+    submitted_at: Option<String>,
+    nested_note: Option<String>,
+}
+
 #[tauri::command]
 pub async fn process_submissions(
     app: AppHandle,
-    pool: State<'_, DbPool>,
+    db: State<'_, DbState>,
     assignment_id: String,
     file_paths: Vec<String>,
 ) -> Result<Vec<ProcessResult>, String> {
-    let mut results = Vec::new();
+    let pool = db.pool()?;
     let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
     let cache_dir = app_data_dir.join("cache").join(&assignment_id);
+    ingest_submissions(&pool, &cache_dir, &assignment_id, file_paths, |progress| {
+        let _ = app.emit(SUBMISSION_PROGRESS_EVENT, progress);
+    })
+    .await
+}
 
+/// Hash, extract and match each ZIP, then insert the batch. All file work happens
+/// before the transaction is opened, so SQLite's write lock is only held for the
+/// inserts and other TAs' saves aren't blocked behind a long import.
+/// `on_progress` is called once per file, in order, after its file work is done.
+pub(crate) async fn ingest_submissions(
+    pool: &DbPool,
+    cache_dir: &Path,
+    assignment_id: &str,
+    file_paths: Vec<String>,
+    mut on_progress: impl FnMut(SubmissionProgress),
+) -> Result<Vec<ProcessResult>, String> {
     if !cache_dir.exists() {
-        fs::create_dir_all(&cache_dir).map_err(|e| e.to_string())?;
+        fs::create_dir_all(cache_dir).map_err(|e| e.to_string())?;
     }
 
-    // Pre-fetch roster for matching
-    // For now, we'll query DB inside loop or just cache it? 
-    // Querying inside loop is fine for 150 students. optimized later if needed.
+    // Course settings and roster are loaded once so matching is a pure memory operation
+    let (course_id, id_pattern, lms_convention): (String, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT c.id, c.id_pattern, c.lms_convention FROM courses c JOIN assignments a ON a.course_id = c.id WHERE a.id = ?"
+    )
+        .bind(assignment_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Assignment not found")?;
     
-    // Regex for student ID detection (Simple patterns for now)
-    // Matches 8-digit IDs, or common patterns.
-    let id_regex = Regex::new(r"(\d{8})").unwrap(); 
+    // Regex for student ID detection, configurable per course.
+    // Compiled up front so a bad pattern fails before any file is touched.
+    let matcher = CourseMatcher {
+        id_regex: compile_id_pattern(id_pattern.as_deref())?,
+        lms_naming: compile_lms_naming(lms_convention.as_deref())?,
+        roster: load_roster(pool, &course_id).await?,
+    };
 
+    // 1. File work: each file either fails here or is prepared for insertion
+    let total = file_paths.len();
+    let mut prepared: Vec<Result<PreparedSubmission, ProcessResult>> = Vec::with_capacity(total);
+    let mut batch_hashes = std::collections::HashSet::new();
     for path_str in file_paths {
-        let path = Path::new(&path_str);
-        let filename = path.file_name().unwrap().to_string_lossy().to_string();
-        
-        // 1. Hash File
-        let hash = match compute_sha256(&path) {
-            Ok(h) => h,
-            Err(e) => {
-                results.push(ProcessResult {
-                    filename: filename.clone(),
-                    status: "Error".to_string(),
-                    student_id: None,
-                    message: Some(format!("Failed to hash: {}", e)),
-                });
+        let step = prepare_submission(pool, cache_dir, assignment_id, path_str, &matcher, &mut batch_hashes).await;
+        let (filename, status) = match &step {
+            Ok(p) => (p.filename.clone(), if p.student.student_id.is_some() { "Matched" } else { "Unmatched" }.to_string()),
+            Err(r) => (r.filename.clone(), r.status.clone()),
+        };
+        prepared.push(step);
+        on_progress(SubmissionProgress {
+            assignment_id: assignment_id.to_string(),
+            index: prepared.len(),
+            total,
+            filename,
+            status,
+        });
+    }
+
+    // 2. Inserts for the batch go through one short transaction
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let mut results = Vec::with_capacity(total);
+    for step in prepared {
+        let submission = match step {
+            Ok(s) => s,
+            Err(result) => {
+                results.push(result);
                 continue;
             }
         };
+        results.push(insert_submission(&mut tx, assignment_id, submission).await);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(results)
+}
+
+/// Hash, dedupe, extract and match one ZIP. `batch_hashes` catches the same ZIP
+/// appearing twice in one batch, which the database can't see yet.
+async fn prepare_submission(
+    pool: &DbPool,
+    cache_dir: &Path,
+    assignment_id: &str,
+    path_str: String,
+    matcher: &CourseMatcher,
+    batch_hashes: &mut std::collections::HashSet<String>,
+) -> Result<PreparedSubmission, ProcessResult> {
+    let path = Path::new(&path_str);
+    let filename = path.file_name().unwrap().to_string_lossy().to_string();
+
+    let hash = compute_sha256(path).map_err(|e| error_result(&filename, None, format!("Failed to hash: {}", e)))?;
+
+    // Identical ZIP already imported for this assignment -> skip
+    let duplicate_of: Option<String> = if batch_hashes.contains(&hash) {
+        Some("in this batch".to_string())
+    } else {
+        sqlx::query_scalar("SELECT id FROM submissions WHERE assignment_id = ? AND file_hash = ?")
+            .bind(assignment_id)
+            .bind(&hash)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| error_result(&filename, None, format!("DB Error: {}", e)))?
+            .map(|id: String| format!("submission {}", id))
+    };
+    if let Some(existing) = duplicate_of {
+        return Err(ProcessResult {
+            filename,
+            status: "Duplicate".to_string(),
+            student_id: None,
+            message: Some(format!("Identical to existing {}", existing)),
+        });
+    }
+    batch_hashes.insert(hash.clone());
+
+    let extraction_dir = cache_dir.join(&hash);
+    if !extraction_dir.exists() {
+        extract_zip(path, &extraction_dir).map_err(|e| error_result(&filename, None, format!("Extraction failed: {}", e)))?;
+    }
+    let leftover_zips = unwrap_nested_zips(&extraction_dir).await.map_err(|e| error_result(&filename, None, e))?;
+    // Several inner ZIPs can't be told apart automatically; a TA has to pick
+    let nested_note = (leftover_zips > 0)
+        .then(|| format!("Contains {} inner ZIPs that need manual extraction", leftover_zips));
+
+    let student = match_student(&filename, &extraction_dir, &matcher.id_regex, &matcher.roster, matcher.lms_naming.as_ref());
+    let original_filename = matcher.lms_naming.as_ref()
+        .and_then(|n| parse_lms_filename(n, &filename))
+        .and_then(|l| l.original)
+        .unwrap_or_else(|| filename.clone());
+    // The ZIP's modification time stands in for when the student handed it in;
+    // received_at is always the moment it was ingested here
+    let submitted_at = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+
+    Ok(PreparedSubmission {
+        path_str,
+        filename,
+        hash,
+        extraction_dir,
+        student,
+        original_filename,
+        submitted_at,
+        nested_note,
+    })
+}
+
+/// Insert one prepared submission, superseding the student's current version if any
+async fn insert_submission(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    assignment_id: &str,
+    submission: PreparedSubmission,
+) -> ProcessResult {
+    let PreparedSubmission { path_str, filename, hash, extraction_dir, student, original_filename, submitted_at, nested_note } = submission;
+    let StudentMatch { student_id: matched_student_id, method: match_method, confidence: match_confidence } = student;
+    let submission_id = uuid::Uuid::new_v4().to_string();
+
+    // A different upload from an already-submitted student replaces their current submission
+    let previous: Option<(String, i64)> = match &matched_student_id {
+        Some(sid) => match sqlx::query_as(
+            "SELECT id, version FROM submissions WHERE assignment_id = ? AND student_id = ? AND superseded_by IS NULL"
+        )
+            .bind(assignment_id)
+            .bind(sid)
+            .fetch_optional(&mut **tx)
+            .await
+        {
+            Ok(previous) => previous,
+            Err(e) => return error_result(&filename, matched_student_id, format!("DB Error: {}", e)),
+        },
+        None => None,
+    };
+    let version = previous.as_ref().map(|(_, v)| v + 1).unwrap_or(1);
+    let previous_id = previous.map(|(id, _)| id);
+
+    let insert_res = sqlx::query("INSERT INTO submissions (id, assignment_id, student_id, source_zip_path, zip_hash, received_at, submitted_at, status, folder_path, file_hash, version, match_method, match_confidence, notes, original_filename, anon_seq) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(anon_seq), 0) + 1 FROM submissions WHERE assignment_id = ?))")
+        .bind(&submission_id)
+        .bind(assignment_id)
+        .bind(&matched_student_id)
+        .bind(&path_str)
+        .bind(&hash)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(&submitted_at)
+        .bind(if nested_note.is_some() { "flagged" } else { "unstarted" })
+        .bind(extraction_dir.to_string_lossy().to_string())
+        .bind(&hash)
+        .bind(version)
+        .bind(match_method)
+        .bind(match_confidence)
+        .bind(&nested_note)
+        .bind(&original_filename)
+        .bind(assignment_id)
+        .execute(&mut **tx)
+        .await;
+
+    let insert_res = match (insert_res, &previous_id) {
+        (Ok(_), Some(prev)) => {
+            // The resubmission stays in whichever TA's queue the original was in
+            let carried = sqlx::query("UPDATE submissions SET assigned_ta_id = (SELECT assigned_ta_id FROM submissions WHERE id = ?) WHERE id = ?")
+                .bind(prev)
+                .bind(&submission_id)
+                .execute(&mut **tx)
+                .await;
+            match carried {
+                Ok(_) => sqlx::query("UPDATE submissions SET superseded_by = ? WHERE id = ?")
+                    .bind(&submission_id)
+                    .bind(prev)
+                    .execute(&mut **tx)
+                    .await,
+                err => err,
+            }
+        }
+        (res, _) => res,
+    };
+
+    match insert_res {
+        Err(e) => error_result(&filename, matched_student_id, format!("DB Error: {}", e)),
+        Ok(_) => ProcessResult {
+            filename,
+            status: if matched_student_id.is_some() { "Matched" } else { "Unmatched" }.to_string(),
+            student_id: matched_student_id,
+            message: [previous_id.map(|prev| format!("Supersedes submission {}", prev)), nested_note]
+                .into_iter()
+                .flatten()
+                .reduce(|a, b| format!("{}; {}", a, b)),
+        },
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateMember {
+    pub submission_id: String,
+    /// None on anonymous assignments, where `student_name` holds the label
+    pub student_id: Option<String>,
+    pub student_name: Option<String>,
+    /// Set for file-level clusters: the shared file, relative to the submission folder
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCluster {
+    pub file_hash: String,
+    pub members: Vec<DuplicateMember>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    /// Whole uploads that are byte-identical across students
+    pub submissions: Vec<DuplicateCluster>,
+    /// Individual files shared across students (only with `include_files`)
+    pub files: Vec<DuplicateCluster>,
+}
+
+/// Find byte-identical work handed in by two or more different students.
+/// Compares the uploaded ZIPs' hashes, and with `include_files` also hashes every
+/// extracted file to catch partial copies. Empty files are ignored. Members of
+/// anonymous assignments are shown by label.
+#[tauri::command]
+pub async fn find_duplicate_submissions(
+    db: State<'_, DbState>,
+    assignment_id: String,
+    include_files: Option<bool>,
+) -> Result<DuplicateReport, String> {
+    let pool = db.pool()?;
+    let rows: Vec<(String, String, String, Option<String>, String)> = sqlx::query_as(
+        r#"
+        SELECT sub.file_hash, sub.id, sub.student_id, st.name, sub.folder_path
+        FROM submissions sub
+        LEFT JOIN students st ON sub.student_id = st.student_id
+            AND st.course_id = (SELECT course_id FROM assignments WHERE id = sub.assignment_id)
+        WHERE sub.assignment_id = ? AND sub.deleted_at IS NULL AND sub.superseded_by IS NULL
+          AND sub.student_id IS NOT NULL AND sub.file_hash IS NOT NULL
+        ORDER BY sub.student_id
+        "#
+    )
+    .bind(&assignment_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut by_hash: HashMap<String, Vec<DuplicateMember>> = HashMap::new();
+    for (hash, submission_id, student_id, student_name, _) in &rows {
+        by_hash.entry(hash.clone()).or_default().push(DuplicateMember {
+            submission_id: submission_id.clone(),
+            student_id: Some(student_id.clone()),
+            student_name: student_name.clone(),
+            file_path: None,
+        });
+    }
+
+    // Hashing every extracted file is slow disk work; keep it off the async runtime
+    let files = if include_files.unwrap_or(false) {
+        tokio::task::spawn_blocking(move || hash_submission_files(&rows))
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        HashMap::new()
+    };
+
+    let mut report = DuplicateReport {
+        submissions: shared_clusters(by_hash),
+        files: shared_clusters(files),
+    };
+    if crate::grading::is_anonymous(&pool, &assignment_id).await? {
+        let labels: HashMap<String, i64> = sqlx::query_as("SELECT id, anon_seq FROM submissions WHERE assignment_id = ?")
+            .bind(&assignment_id)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+        let members = report.submissions.iter_mut().chain(report.files.iter_mut()).flat_map(|c| c.members.iter_mut());
+        for member in members {
+            member.student_id = None;
+            member.student_name = labels.get(&member.submission_id).map(|seq| crate::grading::pseudonym(*seq));
+        }
+    }
+    Ok(report)
+}
 
-        // 2. Extract
-        let extraction_dir = cache_dir.join(&hash);
-        if !extraction_dir.exists() {
-            if let Err(e) = extract_zip(&path, &extraction_dir) {
-                 results.push(ProcessResult {
-                    filename: filename.clone(),
-                    status: "Error".to_string(),
-                    student_id: None,
-                    message: Some(format!("Extraction failed: {}", e)),
-                });
+/// Hash every non-empty file in each submission folder, grouped by hash
+fn hash_submission_files(rows: &[(String, String, String, Option<String>, String)]) -> HashMap<String, Vec<DuplicateMember>> {
+    let mut files: HashMap<String, Vec<DuplicateMember>> = HashMap::new();
+    for (_, submission_id, student_id, student_name, folder_path) in rows {
+        let root = Path::new(folder_path);
+        let walker = walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| !crate::commands::is_extraction_junk(&e.file_name().to_string_lossy()));
+        for entry in walker.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() || entry.metadata().map(|m| m.len() == 0).unwrap_or(true) {
                 continue;
             }
+            let Ok(hash) = compute_sha256(entry.path()) else { continue };
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().replace('\\', "/");
+            files.entry(hash).or_default().push(DuplicateMember {
+                submission_id: submission_id.clone(),
+                student_id: Some(student_id.clone()),
+                student_name: student_name.clone(),
+                file_path: Some(rel),
+            });
+        }
+    }
+    files
+}
+
+/// Keep only hashes shared by at least two different students, largest clusters first
+fn shared_clusters(groups: HashMap<String, Vec<DuplicateMember>>) -> Vec<DuplicateCluster> {
+    let mut clusters: Vec<DuplicateCluster> = groups
+        .into_iter()
+        .filter(|(_, members)| {
+            let students: std::collections::HashSet<Option<&str>> = members.iter().map(|m| m.student_id.as_deref()).collect();
+            students.len() >= 2
+        })
+        .map(|(file_hash, members)| DuplicateCluster { file_hash, members })
+        .collect();
+    clusters.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then_with(|| a.file_hash.cmp(&b.file_hash)));
+    clusters
+}
+
+/// Root of the extraction cache: `<app data>/cache/<assignment_id>/<zip hash>`
+fn cache_root(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("cache"))
+}
+
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Immediate subdirectories of `path`
+fn subdirs(path: &Path) -> Vec<PathBuf> {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Extraction folders still referenced by a submission that hasn't been deleted
+async fn live_folders(pool: &DbPool) -> Result<std::collections::HashSet<PathBuf>, String> {
+    let folders: Vec<String> = sqlx::query_scalar("SELECT folder_path FROM submissions WHERE deleted_at IS NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(folders.into_iter().map(PathBuf::from).collect())
+}
+
+#[derive(Serialize, Debug)]
+pub struct AssignmentCacheUsage {
+    pub assignment_id: String,
+    /// None when the assignment no longer exists
+    pub title: Option<String>,
+    pub bytes: u64,
+    pub folder_count: usize,
+    /// Part of `bytes` in folders no live submission points at
+    pub orphan_bytes: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheUsage {
+    pub total_bytes: u64,
+    pub assignments: Vec<AssignmentCacheUsage>,
+}
+
+/// Disk used by extracted submissions, per assignment, largest first
+#[tauri::command]
+pub async fn get_cache_usage(app: AppHandle, db: State<'_, DbState>) -> Result<CacheUsage, String> {
+    let pool = db.pool()?;
+    cache_usage(&pool, &cache_root(&app)?).await
+}
+
+/// `get_cache_usage` for the cache at `root`
+async fn cache_usage(pool: &DbPool, root: &Path) -> Result<CacheUsage, String> {
+    let live = live_folders(pool).await?;
+    let titles: HashMap<String, String> = sqlx::query_as::<_, (String, String)>("SELECT id, title FROM assignments")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+
+    let mut assignments = Vec::new();
+    for assignment_dir in subdirs(root) {
+        let assignment_id = assignment_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let folders = subdirs(&assignment_dir);
+        let bytes = dir_size(&assignment_dir);
+        let mut in_folders = 0;
+        let mut orphan_bytes = 0;
+        for folder in &folders {
+            let size = dir_size(folder);
+            in_folders += size;
+            if !live.contains(folder) {
+                orphan_bytes += size;
+            }
         }
+        // Stray files directly under the assignment dir are orphans too
+        orphan_bytes += bytes.saturating_sub(in_folders);
+
+        assignments.push(AssignmentCacheUsage {
+            title: titles.get(&assignment_id).cloned(),
+            assignment_id,
+            bytes,
+            folder_count: folders.len(),
+            orphan_bytes,
+        });
+    }
+    assignments.sort_by_key(|a| std::cmp::Reverse(a.bytes));
+
+    Ok(CacheUsage {
+        total_bytes: assignments.iter().map(|a| a.bytes).sum(),
+        assignments,
+    })
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct CacheCleanup {
+    pub removed_folders: usize,
+    pub freed_bytes: u64,
+}
 
-        // 3. Match Student
-        // Strategy A: Filename
-        let mut matched_student_id = None;
-        if let Some(caps) = id_regex.captures(&filename) {
-            matched_student_id = Some(caps.get(1).unwrap().as_str().to_string());
+/// Remove extraction folders under `assignment_dir` that no live submission uses.
+/// Only files on disk are touched; submission rows and grades stay in the database.
+fn remove_orphans(assignment_dir: &Path, live: &std::collections::HashSet<PathBuf>, cleanup: &mut CacheCleanup) -> Result<(), String> {
+    for folder in subdirs(assignment_dir) {
+        if live.contains(&folder) {
+            continue;
         }
+        let size = dir_size(&folder);
+        fs::remove_dir_all(&folder).map_err(|e| format!("Failed to remove {}: {}", folder.display(), e))?;
+        cleanup.removed_folders += 1;
+        cleanup.freed_bytes += size;
+    }
+    // Drop the assignment dir itself once nothing is left in it
+    if fs::read_dir(assignment_dir).map(|mut d| d.next().is_none()).unwrap_or(false) {
+        let _ = fs::remove_dir(assignment_dir);
+    }
+    Ok(())
+}
 
-        // Strategy B: Metadata file inside zip (optional, but requested)
-        if matched_student_id.is_none() {
-            let metadata_path = extraction_dir.join("student_id.txt");
-            if metadata_path.exists() {
-                if let Ok(content) = fs::read_to_string(metadata_path) {
-                    let trimmed = content.trim();
-                    if id_regex.is_match(trimmed) {
-                         matched_student_id = Some(trimmed.to_string());
-                    }
-                }
+/// Delete one assignment's cached extractions that no live submission references
+#[tauri::command]
+pub async fn clear_assignment_cache(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    assignment_id: String,
+) -> Result<CacheCleanup, String> {
+    let pool = db.pool()?;
+    // The ID becomes a path component, so it must not be able to escape the cache
+    if assignment_id.is_empty() || assignment_id.contains(['/', '\\']) || assignment_id.contains("..") {
+        return Err("Invalid assignment ID".to_string());
+    }
+    let assignment_dir = cache_root(&app)?.join(&assignment_id);
+    let live = live_folders(&pool).await?;
+    let mut cleanup = CacheCleanup::default();
+    if assignment_dir.is_dir() {
+        remove_orphans(&assignment_dir, &live, &mut cleanup)?;
+    }
+    Ok(cleanup)
+}
+
+/// Delete every cached extraction that no live submission references
+#[tauri::command]
+pub async fn clear_orphan_cache(app: AppHandle, db: State<'_, DbState>) -> Result<CacheCleanup, String> {
+    let pool = db.pool()?;
+    let live = live_folders(&pool).await?;
+    let mut cleanup = CacheCleanup::default();
+    for assignment_dir in subdirs(&cache_root(&app)?) {
+        remove_orphans(&assignment_dir, &live, &mut cleanup)?;
+    }
+    Ok(cleanup)
+}
+
+/// Rebuild a submission's extracted folder from its stored source ZIP
+#[tauri::command]
+pub async fn reextract_submission(db: State<'_, DbState>, submission_id: String) -> Result<String, String> {
+    let pool = db.pool()?;
+    let (source_zip, folder_path, file_hash): (String, String, Option<String>) = sqlx::query_as(
+        "SELECT source_zip_path, folder_path, file_hash FROM submissions WHERE id = ? AND deleted_at IS NULL"
+    )
+        .bind(&submission_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Submission not found")?;
+    if folder_path.is_empty() {
+        return Err("Submission has no extraction folder recorded".to_string());
+    }
+    if !Path::new(&source_zip).is_file() {
+        return Err("Source ZIP is no longer available; re-import the submission".to_string());
+    }
+
+    let validation = crate::grading::validate_zip(source_zip.clone()).await?;
+    if !validation.is_valid {
+        return Err(validation.error_message.unwrap_or_else(|| "Source ZIP is invalid".to_string()));
+    }
+    // A replaced file at the same path would silently change what was graded
+    if let Some(expected) = file_hash {
+        let actual = compute_sha256(Path::new(&source_zip)).map_err(|e| e.to_string())?;
+        if actual != expected {
+            return Err("Source ZIP has changed since it was imported".to_string());
+        }
+    }
+
+    // Extract beside the target and swap in, so a failure never leaves a half-written folder
+    let target = PathBuf::from(&folder_path);
+    let staging = target.with_extension("reextract");
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| e.to_string())?;
+    }
+    if let Err(e) = extract_zip(Path::new(&source_zip), &staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Extraction failed: {}", e));
+    }
+    if let Err(e) = unwrap_nested_zips(&staging).await {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    if target.exists() {
+        fs::remove_dir_all(&target).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&staging, &target).map_err(|e| e.to_string())?;
+
+    Ok(folder_path)
+}
+
+/// Course roster keyed by student ID, with names for fuzzy matching
+pub(crate) type Roster = HashMap<String, String>;
+
+#[cfg(test)]
+thread_local! {
+    /// Calls to `load_roster` on this thread, so tests can check it isn't run per file
+    static ROSTER_LOADS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+pub(crate) async fn load_roster(pool: &DbPool, course_id: &str) -> Result<Roster, String> {
+    #[cfg(test)]
+    ROSTER_LOADS.with(|n| n.set(n.get() + 1));
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT student_id, name FROM students WHERE course_id = ?")
+        .bind(course_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Outcome of matching a submission to a roster student
+pub(crate) struct StudentMatch {
+    pub student_id: Option<String>,
+    pub method: &'static str,
+    pub confidence: f64,
+}
+
+/// Run the matching strategies against the roster, in order of confidence
+pub(crate) fn match_student(
+    filename: &str,
+    extraction_dir: &Path,
+    id_regex: &Regex,
+    roster: &Roster,
+    naming: Option<&LmsNaming>,
+) -> StudentMatch {
+    // An LMS prefix holds internal numbers the ID pattern could mistake for a student ID,
+    // so only the username and the student's own file name are searched
+    let lms = naming.and_then(|n| {
+        parse_lms_filename(n, filename)
+            .or_else(|| top_level_folder(extraction_dir).and_then(|f| parse_lms_filename(n, &f)))
+    });
+    let id_text = match &lms {
+        Some(l) => [l.username.as_deref(), l.original.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(" "),
+        None => filename.to_string(),
+    };
+
+    // Strategy A: Filename
+    let mut candidate = None;
+    let mut method = "none";
+    if let Some(caps) = id_regex.captures(&id_text) {
+        // Use the first capture group if the pattern has one, else the whole match
+        let id = caps.get(1).or_else(|| caps.get(0)).unwrap();
+        candidate = Some(id.as_str().to_string());
+        method = "filename";
+    }
+
+    // Strategy B: Metadata file inside zip (optional, but requested)
+    if candidate.is_none() {
+        if let Ok(content) = fs::read_to_string(extraction_dir.join("student_id.txt")) {
+            let trimmed = content.trim();
+            if id_regex.is_match(trimmed) {
+                candidate = Some(trimmed.to_string());
+                method = "metadata";
             }
         }
-        
-        // Strategy C: Check if this ID exists in Roster for this Course
-        // We need course_id from assignment... 
-        // Let's look up course_id first.
-        let course_id_res: Option<String> = sqlx::query_scalar("SELECT course_id FROM assignments WHERE id = ?")
-            .bind(&assignment_id)
-            .fetch_optional(&*pool)
-            .await
-            .unwrap_or(None);
-            
-        let mut valid_match = false;
-        if let Some(cid) = &course_id_res {
-             if let Some(sid) = &matched_student_id {
-                 // Verify student exists in course
-                 let exists: bool = sqlx::query_scalar::<sqlx::Sqlite, i32>("SELECT 1 FROM students WHERE course_id = ? AND student_id = ?")
-                    .bind(cid)
-                    .bind(sid)
-                    .fetch_optional(&*pool)
-                    .await
-                    .unwrap_or(None)
-                    .is_some();
-                 if exists {
-                     valid_match = true;
-                 } else {
-                     matched_student_id = None; // ID found but not in roster -> Unmatched
-                 }
-             }
-        }
-
-        // 4. DB Insert
-        let status = if valid_match { "Matched" } else { "Unmatched" };
-        let submission_id = uuid::Uuid::new_v4().to_string();
-        
-        // TODO: Handle duplicates/updates. For now, simple insert.
-        let insert_res = sqlx::query("INSERT INTO submissions (id, assignment_id, student_id, submitted_at, status, folder_path) VALUES (?, ?, ?, ?, ?, ?)")
-            .bind(&submission_id)
-            .bind(&assignment_id)
-            .bind(&matched_student_id)
-            .bind(chrono::Utc::now().to_rfc3339())
-            .bind(status)
-            .bind(extraction_dir.to_string_lossy().to_string())
-            .execute(&*pool)
-            .await;
-            
-        if let Err(e) = insert_res {
-             results.push(ProcessResult {
-                filename,
-                status: "Error".to_string(),
-                student_id: matched_student_id,
-                message: Some(format!("DB Error: {}", e)),
-            });
-        } else {
-            results.push(ProcessResult {
-                filename,
-                status: status.to_string(),
-                student_id: matched_student_id,
-                message: None,
-            });
+    }
+
+    // Strategy C: The ID only counts if it exists in the course roster
+    if let Some(sid) = candidate {
+        if roster.contains_key(&sid) {
+            return StudentMatch { student_id: Some(sid), method, confidence: 1.0 };
         }
     }
 
-    Ok(results)
+    // Strategy C2: The LMS-written name, e.g. Canvas "smithjohn", spelled out in full
+    if let Some(name) = lms.as_ref().and_then(|l| l.student_name.as_deref()) {
+        let keys = squashed_name_keys(name);
+        let mut hits = roster.iter().filter(|(_, roster_name)| {
+            let roster_keys = squashed_name_keys(roster_name);
+            keys.iter().any(|k| !k.is_empty() && roster_keys.contains(k))
+        });
+        if let (Some((sid, _)), None) = (hits.next(), hits.next()) {
+            return StudentMatch { student_id: Some(sid.clone()), method: "filename", confidence: 0.95 };
+        }
+    }
+
+    // Strategy D: Fuzzy match the filename / top-level folder against roster names
+    let haystack = match lms.as_ref().and_then(|l| l.student_name.as_deref()) {
+        Some(name) => name.to_string(),
+        None => format!("{} {}", filename, top_level_folder(extraction_dir).unwrap_or_default()),
+    };
+    if let Some((sid, confidence)) = fuzzy_match_name(&haystack, roster) {
+        return StudentMatch { student_id: Some(sid), method: "fuzzy_name", confidence };
+    }
+
+    StudentMatch { student_id: None, method: "none", confidence: 0.0 }
+}
+
+/// Student ID pattern used when a course doesn't configure its own: 8-digit IDs
+pub(crate) const DEFAULT_ID_PATTERN: &str = r"(\d{8})";
+
+/// Compile a course's student ID pattern, falling back to the default when unset
+pub(crate) fn compile_id_pattern(pattern: Option<&str>) -> Result<Regex, String> {
+    let pattern = pattern.filter(|p| !p.trim().is_empty()).unwrap_or(DEFAULT_ID_PATTERN);
+    Regex::new(pattern).map_err(|e| format!("Invalid student ID pattern '{}': {}", pattern, e))
+}
+
+/// Filename conventions of LMS bulk downloads, selectable per course
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LmsConvention {
+    /// `smithjohn[_LATE]_<user id>_<submission id>_<original name>`
+    Canvas,
+    /// `John Smith_<participant id>_assignsubmission_file_<original name>`
+    Moodle,
+    /// `<assignment>_<username>_attempt_<yyyy-mm-dd-hh-mm-ss>[_<original name>]`
+    Blackboard,
+}
+
+impl LmsConvention {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "canvas" => Ok(Self::Canvas),
+            "moodle" => Ok(Self::Moodle),
+            "blackboard" => Ok(Self::Blackboard),
+            other => Err(format!("Unknown LMS convention '{}'", other)),
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::Canvas => r"(?i)^(?P<name>[a-z]+)(?:_late)?_(?P<id>\d+)_\d+_(?P<original>.+)$",
+            Self::Moodle => r"^(?P<name>.+?)_(?P<id>\d+)_assignsubmission_[a-z]+_(?P<original>.*)$",
+            Self::Blackboard => r"^.+?_(?P<user>[^_]+)_attempt_\d{4}(?:-\d{2}){5}(?:_(?P<original>.+))?(?:\.[A-Za-z0-9]+)?$",
+        }
+    }
+}
+
+/// A course's LMS convention with its filename pattern compiled once per batch
+pub(crate) struct LmsNaming {
+    convention: LmsConvention,
+    regex: Regex,
+}
+
+/// Compile a course's LMS convention; unset means filenames are taken as-is
+pub(crate) fn compile_lms_naming(convention: Option<&str>) -> Result<Option<LmsNaming>, String> {
+    let Some(convention) = convention.filter(|c| !c.trim().is_empty()) else { return Ok(None) };
+    let convention = LmsConvention::parse(convention)?;
+    let regex = Regex::new(convention.pattern()).map_err(|e| e.to_string())?;
+    Ok(Some(LmsNaming { convention, regex }))
+}
+
+/// What an LMS-decorated filename says about its student
+#[derive(Debug, PartialEq)]
+pub(crate) struct LmsFilename {
+    /// Student name as the LMS wrote it ("smithjohn" on Canvas, "John Smith" on Moodle)
+    pub student_name: Option<String>,
+    /// Login name, which institutions often set to the student ID (Blackboard)
+    pub username: Option<String>,
+    /// LMS-internal user/participant number; not a roster ID
+    pub lms_id: Option<String>,
+    /// File name the student uploaded, without the LMS prefix
+    pub original: Option<String>,
+}
+
+/// Split an LMS prefix off a filename or wrapper folder name
+pub(crate) fn parse_lms_filename(naming: &LmsNaming, filename: &str) -> Option<LmsFilename> {
+    let caps = naming.regex.captures(filename)?;
+    let field = |name: &str| caps.name(name).map(|m| m.as_str().trim().to_string()).filter(|v| !v.is_empty());
+    Some(LmsFilename {
+        student_name: field("name"),
+        username: if naming.convention == LmsConvention::Blackboard { field("user") } else { None },
+        lms_id: field("id"),
+        original: field("original"),
+    })
+}
+
+/// Letters of a name, lowercased, in both "lastfirst" and "firstlast" order
+fn squashed_name_keys(name: &str) -> [String; 2] {
+    let tokens = name_tokens(name);
+    let forward = tokens.concat();
+    let reversed = tokens.iter().rev().cloned().collect::<Vec<_>>().concat();
+    [forward, reversed]
+}
+
+/// Minimum similarity for a fuzzy name match to be accepted
+const FUZZY_MATCH_THRESHOLD: f64 = 0.85;
+/// The best candidate must lead the runner-up by this much, otherwise it's ambiguous
+const FUZZY_MATCH_MARGIN: f64 = 0.1;
+
+/// Name of the single top-level folder inside an extracted submission, if there is one
+fn top_level_folder(dir: &Path) -> Option<String> {
+    let mut entries = fs::read_dir(dir).ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() != "__MACOSX");
+    let first = entries.next()?;
+    if entries.next().is_some() || !first.path().is_dir() {
+        return None;
+    }
+    Some(first.file_name().to_string_lossy().to_string())
+}
+
+/// Lowercase alphabetic tokens of a name or filename ("Smith_John_HW3" -> smith, john, hw)
+fn name_tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|t| t.len() > 1)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Similarity in [0, 1] between a roster name and free text: every name token is
+/// compared to its closest text token and the per-token similarities are averaged
+fn name_similarity(name: &str, text_tokens: &[String]) -> f64 {
+    let tokens = name_tokens(name);
+    if tokens.is_empty() || text_tokens.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = tokens.iter().map(|nt| {
+        text_tokens.iter().map(|tt| {
+            let max_len = nt.chars().count().max(tt.chars().count()) as f64;
+            1.0 - levenshtein(nt, tt) as f64 / max_len
+        }).fold(0.0, f64::max)
+    }).sum();
+    total / tokens.len() as f64
+}
+
+/// Pick the roster student whose name best matches `text`. Returns nothing when the
+/// best score is below the threshold or a second candidate is too close behind.
+fn fuzzy_match_name(text: &str, roster: &Roster) -> Option<(String, f64)> {
+    let text_tokens = name_tokens(text);
+    let mut scored: Vec<(&String, f64)> = roster.iter()
+        .map(|(sid, name)| (sid, name_similarity(name, &text_tokens)))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    
+    let (best_id, best_score) = *scored.first()?;
+    if best_score < FUZZY_MATCH_THRESHOLD {
+        return None;
+    }
+    if let Some((_, runner_up)) = scored.get(1) {
+        if best_score - runner_up < FUZZY_MATCH_MARGIN {
+            return None;
+        }
+    }
+    Some((best_id.clone(), best_score))
 }
 
 fn compute_sha256(path: &Path) -> io::Result<String> {
@@ -167,6 +915,48 @@ fn compute_sha256(path: &Path) -> io::Result<String> {
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+/// How many levels of ZIP-inside-ZIP wrapping are unpacked before giving up
+const MAX_NESTED_ZIP_DEPTH: usize = 3;
+
+/// ZIP files anywhere inside an extraction, ignoring archive clutter
+fn inner_zips(dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| !crate::commands::is_extraction_junk(&e.file_name().to_string_lossy()))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Unpack a lone ZIP wrapped inside an extraction (as some LMS exports do), level by
+/// level up to `MAX_NESTED_ZIP_DEPTH`. Each inner ZIP is replaced by a folder of the
+/// same name. Returns how many inner ZIPs were left for manual handling.
+async fn unwrap_nested_zips(dir: &Path) -> Result<usize, String> {
+    for depth in 0..=MAX_NESTED_ZIP_DEPTH {
+        let inner = inner_zips(dir);
+        if inner.is_empty() {
+            return Ok(0);
+        }
+        if inner.len() > 1 || depth == MAX_NESTED_ZIP_DEPTH {
+            return Ok(inner.len());
+        }
+        let zip_path = &inner[0];
+        let validation = crate::grading::validate_zip(zip_path.to_string_lossy().to_string()).await?;
+        if !validation.is_valid {
+            return Err(format!(
+                "Inner ZIP rejected: {}",
+                validation.error_message.unwrap_or_else(|| "invalid archive".to_string())
+            ));
+        }
+        let out_dir = zip_path.with_extension("");
+        extract_zip(zip_path, &out_dir).map_err(|e| format!("Inner ZIP extraction failed: {}", e))?;
+        fs::remove_file(zip_path).map_err(|e| e.to_string())?;
+    }
+    Ok(0)
+}
+
 fn extract_zip(zip_path: &Path, out_dir: &Path) -> io::Result<()> {
     let file = File::open(zip_path)?;
     let mut archive = ZipArchive::new(file)?;
@@ -192,3 +982,441 @@ fn extract_zip(zip_path: &Path, out_dir: &Path) -> io::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+
+    /// Write a ZIP holding `entries` (name, contents) at `path`
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, contents) in entries {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// An assignment in a course whose roster holds Ada (12345678)
+    async fn roster_assignment(db: &TestDb) -> String {
+        let course = db.course().await;
+        db.student(&course, "12345678", "Ada Lovelace").await;
+        db.assignment(&course, None).await
+    }
+
+    fn paths(dir: &Path, names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| dir.join(n).to_string_lossy().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn identical_zips_are_reported_as_duplicates() {
+        let db = TestDb::new().await;
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        for name in ["12345678_hw.zip", "copy.zip", "later_copy.zip"] {
+            write_zip(&dir.path().join(name), &[("answers.txt", b"Debit cash 100")]);
+        }
+
+        let results = ingest_submissions(&db.pool, &cache, &assignment, paths(dir.path(), &["12345678_hw.zip", "copy.zip"]), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(results[0].status, "Matched");
+        assert_eq!(results[1].status, "Duplicate");
+        assert_eq!(results[1].message.as_deref(), Some("Identical to existing in this batch"));
+
+        // A later batch is checked against what was already imported
+        let results = ingest_submissions(&db.pool, &cache, &assignment, paths(dir.path(), &["later_copy.zip"]), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(results[0].status, "Duplicate");
+        assert!(results[0].message.as_deref().unwrap().starts_with("Identical to existing submission "));
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn resubmissions_form_a_version_chain() {
+        let db = TestDb::new().await;
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let names = ["12345678_v1.zip", "12345678_v2.zip", "12345678_v3.zip"];
+        for (i, name) in names.iter().enumerate() {
+            write_zip(&dir.path().join(name), &[("answers.txt", format!("Draft {}", i + 1).as_bytes())]);
+            let results = ingest_submissions(&db.pool, &cache, &assignment, paths(dir.path(), &[name]), |_| {})
+                .await
+                .unwrap();
+            assert_eq!(results[0].status, "Matched");
+            assert_eq!(results[0].message.is_some(), i > 0, "{:?}", results[0].message);
+        }
+
+        let app = db.app();
+        let versions = crate::grading::get_submission_versions(app.state(), "12345678".into(), assignment.clone())
+            .await
+            .unwrap();
+        assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(versions[0].superseded_by.as_deref(), Some(versions[1].id.as_str()));
+        assert_eq!(versions[1].superseded_by.as_deref(), Some(versions[2].id.as_str()));
+        assert_eq!(versions[2].superseded_by, None);
+
+        let queue = crate::grading::list_submissions(app.state(), assignment.clone(), None, None, None, None, None).await.unwrap();
+        assert_eq!(queue.len(), 1);
+        let history = crate::grading::list_submissions(app.state(), assignment, Some(true), None, None, None, None).await.unwrap();
+        assert_eq!(history.len(), 3);
+    }
+
+    fn roster(entries: &[(&str, &str)]) -> Roster {
+        entries.iter().map(|(id, name)| (id.to_string(), name.to_string())).collect()
+    }
+
+    #[test]
+    fn fuzzy_match_accepts_a_clear_winner_only() {
+        let students = roster(&[("11111111", "Ada Lovelace"), ("22222222", "Grace Hopper")]);
+        // One typo still matches
+        let (id, confidence) = fuzzy_match_name("Lovelase_Ada_HW3.zip", &students).unwrap();
+        assert_eq!(id, "11111111");
+        assert!((FUZZY_MATCH_THRESHOLD..1.0).contains(&confidence));
+        assert_eq!(fuzzy_match_name("homework3.zip", &students), None);
+
+        // A typo that is as close to one student as to another is too close to call
+        let twins = roster(&[("11111111", "Jon Smith"), ("22222222", "Jon Smyth")]);
+        assert_eq!(fuzzy_match_name("Jon_Smoth.zip", &twins), None);
+    }
+
+    #[test]
+    fn unmatched_id_falls_back_to_the_name() {
+        let students = roster(&[("11111111", "Ada Lovelace")]);
+        let id_regex = compile_id_pattern(None).unwrap();
+        let found = match_student("Ada_Lovelace_hw.zip", Path::new("/nonexistent"), &id_regex, &students, None);
+        assert_eq!(found.student_id.as_deref(), Some("11111111"));
+        assert_eq!(found.method, "fuzzy_name");
+    }
+
+    #[tokio::test]
+    async fn course_id_pattern_is_used_for_matching() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        db.student(&course, "A1234567", "Ada Lovelace").await;
+        let assignment = db.assignment(&course, None).await;
+
+        let bad = crate::commands::set_course_id_pattern(app.state(), course.clone(), Some(r"(A\d{7}".into())).await;
+        assert!(bad.unwrap_err().starts_with("Invalid student ID pattern"));
+        crate::commands::set_course_id_pattern(app.state(), course, Some(r"id-(A\d{7})".into())).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        write_zip(&dir.path().join("hw_id-A1234567.zip"), &[("answers.txt", b"Credit revenue")]);
+        let results = ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &["hw_id-A1234567.zip"]), |_| {})
+            .await
+            .unwrap();
+        // The capture group, not the whole match, is the student ID
+        assert_eq!(results[0].status, "Matched");
+        assert_eq!(results[0].student_id.as_deref(), Some("A1234567"));
+    }
+
+    #[tokio::test]
+    async fn roster_is_loaded_once_per_batch() {
+        let db = TestDb::new().await;
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut names = Vec::new();
+        for i in 0..5 {
+            let id = format!("1000000{}", i);
+            db.student(&course, &id, &format!("Student {}", i)).await;
+            let name = format!("{}_hw.zip", id);
+            write_zip(&dir.path().join(&name), &[("answers.txt", id.as_bytes())]);
+            names.push(name);
+        }
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        // tokio tests run on one thread, so the counter only sees this test
+        let before = ROSTER_LOADS.with(|n| n.get());
+        let results = ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &names), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(ROSTER_LOADS.with(|n| n.get()) - before, 1);
+        assert!(results.iter().all(|r| r.status == "Matched"));
+    }
+
+    #[tokio::test]
+    async fn duplicates_need_two_students_and_skip_empty_files() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let dir = tempfile::tempdir().unwrap();
+        for (student, name, zip_hash, files) in [
+            ("1", "Ada Lovelace", "h1", &[("report.xlsx", "shared"), ("empty.txt", "")][..]),
+            ("2", "Grace Hopper", "h1", &[("notes.txt", "Grace's own")][..]),
+            ("3", "Alan Turing", "h2", &[("copy/report.xlsx", "shared"), ("empty.txt", "")][..]),
+        ] {
+            db.student(&course, student, name).await;
+            let folder = dir.path().join(student);
+            for (file, contents) in files {
+                let path = folder.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            let id = db.submission(&assignment, Some(student), &folder).await;
+            sqlx::query("UPDATE submissions SET file_hash = ? WHERE id = ?")
+                .bind(zip_hash)
+                .bind(&id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let members = |cluster: &DuplicateCluster| -> Vec<(Option<String>, Option<String>)> {
+            cluster.members.iter().map(|m| (m.student_name.clone(), m.file_path.clone())).collect()
+        };
+
+        let report = find_duplicate_submissions(app.state(), assignment.clone(), None).await.unwrap();
+        assert_eq!(report.submissions.len(), 1);
+        assert_eq!(report.submissions[0].file_hash, "h1");
+        assert_eq!(members(&report.submissions[0]), [(Some("Ada Lovelace".into()), None), (Some("Grace Hopper".into()), None)]);
+        assert!(report.files.is_empty());
+
+        let report = find_duplicate_submissions(app.state(), assignment.clone(), Some(true)).await.unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(
+            members(&report.files[0]),
+            [(Some("Ada Lovelace".into()), Some("report.xlsx".into())), (Some("Alan Turing".into()), Some("copy/report.xlsx".into()))]
+        );
+
+        sqlx::query("UPDATE assignments SET anonymous = 1 WHERE id = ?").bind(&assignment).execute(&db.pool).await.unwrap();
+        let report = find_duplicate_submissions(app.state(), assignment, None).await.unwrap();
+        let labels: Vec<(Option<&str>, Option<&str>)> =
+            report.submissions[0].members.iter().map(|m| (m.student_id.as_deref(), m.student_name.as_deref())).collect();
+        assert_eq!(labels, [(None, Some("Submission #0001")), (None, Some("Submission #0002"))]);
+    }
+
+    #[tokio::test]
+    async fn cache_usage_separates_live_and_orphaned_folders() {
+        let db = TestDb::new().await;
+        let course = db.course().await;
+        let assignment = db.assignment(&course, None).await;
+        let root = tempfile::tempdir().unwrap();
+        let write = |path: PathBuf, bytes: usize| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; bytes]).unwrap();
+        };
+        let assignment_dir = root.path().join(&assignment);
+        let (live, orphan) = (assignment_dir.join("live"), assignment_dir.join("orphan"));
+        write(live.join("answers/ledger.xlsx"), 100);
+        write(orphan.join("ledger.xlsx"), 50);
+        write(assignment_dir.join("stray.txt"), 5);
+        write(root.path().join("deleted-assignment/old/essay.docx"), 10);
+        db.submission(&assignment, Some("1"), &live).await;
+        // A deleted submission no longer keeps its folder alive
+        let deleted = db.submission(&assignment, Some("2"), &orphan).await;
+        sqlx::query("UPDATE submissions SET deleted_at = datetime('now') WHERE id = ?").bind(&deleted).execute(&db.pool).await.unwrap();
+
+        let usage = cache_usage(&db.pool, root.path()).await.unwrap();
+        assert_eq!(usage.total_bytes, 165);
+        let rows: Vec<(Option<&str>, u64, usize, u64)> =
+            usage.assignments.iter().map(|a| (a.title.as_deref(), a.bytes, a.folder_count, a.orphan_bytes)).collect();
+        assert_eq!(rows, [(Some("Homework 1"), 155, 2, 55), (None, 10, 1, 10)]);
+
+        let live_set = live_folders(&db.pool).await.unwrap();
+        let mut cleanup = CacheCleanup::default();
+        for dir in subdirs(root.path()) {
+            remove_orphans(&dir, &live_set, &mut cleanup).unwrap();
+        }
+        assert_eq!((cleanup.removed_folders, cleanup.freed_bytes), (2, 60));
+        assert!(live.join("answers/ledger.xlsx").is_file() && !orphan.exists());
+        assert!(!root.path().join("deleted-assignment").exists());
+    }
+
+    #[tokio::test]
+    async fn reextraction_rebuilds_the_folder_from_the_same_zip() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("12345678_hw.zip");
+        write_zip(&zip_path, &[("answers.txt", b"Debit cash 100"), ("work/ledger.csv", b"cash,100")]);
+        ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &["12345678_hw.zip"]), |_| {})
+            .await
+            .unwrap();
+        let (submission, folder): (String, String) = sqlx::query_as("SELECT id, folder_path FROM submissions")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let folder = PathBuf::from(folder);
+
+        // A TA tidied up by hand and lost a file
+        fs::remove_dir_all(folder.join("work")).unwrap();
+        fs::write(folder.join("answers.txt"), b"edited").unwrap();
+        assert_eq!(reextract_submission(app.state(), submission.clone()).await.unwrap(), folder.to_string_lossy());
+        assert_eq!(fs::read(folder.join("answers.txt")).unwrap(), b"Debit cash 100");
+        assert_eq!(fs::read(folder.join("work/ledger.csv")).unwrap(), b"cash,100");
+
+        write_zip(&zip_path, &[("answers.txt", b"Something else")]);
+        let changed = reextract_submission(app.state(), submission.clone()).await;
+        assert_eq!(changed.unwrap_err(), "Source ZIP has changed since it was imported");
+        fs::remove_file(&zip_path).unwrap();
+        let gone = reextract_submission(app.state(), submission).await;
+        assert_eq!(gone.unwrap_err(), "Source ZIP is no longer available; re-import the submission");
+        // Failed attempts leave the last good extraction alone
+        assert_eq!(fs::read(folder.join("answers.txt")).unwrap(), b"Debit cash 100");
+    }
+
+    #[tokio::test]
+    async fn ingest_records_the_source_zip_and_when_it_arrived() {
+        let db = TestDb::new().await;
+        let app = db.app();
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("homework.zip");
+        write_zip(&zip_path, &[("answers.txt", b"No name anywhere")]);
+        let handed_in = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 24 * 3600);
+        File::options().write(true).open(&zip_path).unwrap().set_modified(handed_in).unwrap();
+
+        let results = ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &["homework.zip"]), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(results[0].status, "Unmatched");
+
+        let unmatched = crate::grading::get_unmatched_submissions(app.state(), assignment).await.unwrap();
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].source_zip_path, zip_path.to_string_lossy());
+        let received = chrono::DateTime::parse_from_rfc3339(&unmatched[0].received_at).unwrap();
+        assert!((chrono::Utc::now() - received.with_timezone(&chrono::Utc)).num_seconds() < 60);
+
+        // The file's own timestamp is when it was handed in, kept apart from ingest time
+        let submitted_at: String = sqlx::query_scalar("SELECT submitted_at FROM submissions WHERE id = ?")
+            .bind(&unmatched[0].id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(submitted_at, chrono::DateTime::<chrono::Utc>::from(handed_in).to_rfc3339());
+    }
+
+    #[tokio::test]
+    async fn progress_is_reported_once_per_file_in_order() {
+        let db = TestDb::new().await;
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        write_zip(&dir.path().join("12345678_hw.zip"), &[("answers.txt", b"Debit cash 100")]);
+        write_zip(&dir.path().join("homework.zip"), &[("answers.txt", b"Credit revenue")]);
+        write_zip(&dir.path().join("copy.zip"), &[("answers.txt", b"Debit cash 100")]);
+
+        let mut progress = Vec::new();
+        let names = ["12345678_hw.zip", "homework.zip", "copy.zip"];
+        ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &names), |p| progress.push(p))
+            .await
+            .unwrap();
+        let seen: Vec<(usize, usize, &str, &str)> =
+            progress.iter().map(|p| (p.index, p.total, p.filename.as_str(), p.status.as_str())).collect();
+        assert_eq!(seen, [
+            (1, 3, "12345678_hw.zip", "Matched"),
+            (2, 3, "homework.zip", "Unmatched"),
+            (3, 3, "copy.zip", "Duplicate"),
+        ]);
+        assert!(progress.iter().all(|p| p.assignment_id == assignment));
+    }
+
+    /// Bytes of a ZIP holding `entries`
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inner.zip");
+        write_zip(&path, entries);
+        fs::read(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn nested_zips_are_unpacked_unless_ambiguous() {
+        let db = TestDb::new().await;
+        let assignment = roster_assignment(&db).await;
+        let dir = tempfile::tempdir().unwrap();
+        let deep = zip_bytes(&[("answers.txt", b"Debit cash 100")]);
+        let middle = zip_bytes(&[("deep.zip", &deep)]);
+        write_zip(&dir.path().join("12345678_hw.zip"), &[("submission.zip", &middle), ("__MACOSX/._submission.zip", b"junk")]);
+        let (first, second) = (zip_bytes(&[("a.txt", b"one")]), zip_bytes(&[("b.txt", b"two")]));
+        write_zip(&dir.path().join("two_inside.zip"), &[("part1.zip", &first), ("part2.zip", &second)]);
+        write_zip(&dir.path().join("corrupt_inside.zip"), &[("broken.zip", b"PK not really")]);
+
+        let names = ["12345678_hw.zip", "two_inside.zip", "corrupt_inside.zip"];
+        let results = ingest_submissions(&db.pool, &dir.path().join("cache"), &assignment, paths(dir.path(), &names), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(results[0].status, "Matched");
+        assert_eq!(results[1].message.as_deref(), Some("Contains 2 inner ZIPs that need manual extraction"));
+        assert_eq!(results[2].status, "Error");
+        assert!(results[2].message.as_deref().unwrap().starts_with("Inner ZIP rejected"), "{:?}", results[2].message);
+
+        let rows: Vec<(Option<String>, String, String)> =
+            sqlx::query_as("SELECT student_id, status, folder_path FROM submissions ORDER BY anon_seq").fetch_all(&db.pool).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].0.as_deref(), rows[0].1.as_str()), (Some("12345678"), "unstarted"));
+        let unwrapped = Path::new(&rows[0].2).join("submission/deep/answers.txt");
+        assert_eq!(fs::read(unwrapped).unwrap(), b"Debit cash 100");
+        assert!(!Path::new(&rows[0].2).join("submission.zip").exists());
+        // Ambiguous inner ZIPs are left in place for a TA
+        assert_eq!(rows[1].1, "flagged");
+        assert!(Path::new(&rows[1].2).join("part1.zip").is_file());
+    }
+
+    #[tokio::test]
+    async fn nesting_deeper_than_the_limit_is_left_for_a_ta() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut zip = zip_bytes(&[("answers.txt", b"Debit cash 100")]);
+        for level in 0..MAX_NESTED_ZIP_DEPTH {
+            zip = zip_bytes(&[(&format!("level{}.zip", level), &zip)]);
+        }
+        fs::write(dir.path().join("outer.zip"), zip).unwrap();
+        assert_eq!(unwrap_nested_zips(dir.path()).await.unwrap(), 1);
+    }
+
+    fn lms(convention: &str, filename: &str) -> Option<LmsFilename> {
+        parse_lms_filename(&compile_lms_naming(Some(convention)).unwrap().unwrap(), filename)
+    }
+
+    #[test]
+    fn lms_prefixes_are_split_off() {
+        let field = |v: &str| Some(v.to_string());
+        assert_eq!(lms("canvas", "lovelaceada_LATE_48213_1190352_Homework 3.zip"), Some(LmsFilename {
+            student_name: field("lovelaceada"),
+            username: None,
+            lms_id: field("48213"),
+            original: field("Homework 3.zip"),
+        }));
+        assert_eq!(lms("moodle", "Ada Lovelace_1042_assignsubmission_file_ledger.xlsx"), Some(LmsFilename {
+            student_name: field("Ada Lovelace"),
+            username: None,
+            lms_id: field("1042"),
+            original: field("ledger.xlsx"),
+        }));
+        let blackboard = lms("blackboard", "HW3_12345678_attempt_2026-03-01-10-15-00_ledger.xlsx").unwrap();
+        assert_eq!((blackboard.username, blackboard.original), (field("12345678"), field("ledger.xlsx")));
+        let bare = lms("blackboard", "HW3_12345678_attempt_2026-03-01-10-15-00.zip").unwrap();
+        assert_eq!((bare.username, bare.original), (field("12345678"), None));
+
+        assert_eq!(lms("canvas", "homework.zip"), None);
+        assert!(compile_lms_naming(None).unwrap().is_none());
+        assert_eq!(compile_lms_naming(Some("D2L")).err().as_deref(), Some("Unknown LMS convention 'd2l'"));
+    }
+
+    #[test]
+    fn lms_numbers_are_not_taken_for_student_ids() {
+        let students = roster(&[("12345678", "Ada Lovelace"), ("87654321", "Grace Hopper")]);
+        let id_regex = compile_id_pattern(None).unwrap();
+        let canvas = compile_lms_naming(Some("canvas")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        // Canvas's user number happens to look like Grace's ID; the name decides
+        let found = match_student("lovelaceada_87654321_1190352_hw.zip", dir.path(), &id_regex, &students, canvas.as_ref());
+        assert_eq!((found.student_id.as_deref(), found.method), (Some("12345678"), "filename"));
+        // Without the convention the same file goes to Grace
+        let plain = match_student("lovelaceada_87654321_1190352_hw.zip", dir.path(), &id_regex, &students, None);
+        assert_eq!(plain.student_id.as_deref(), Some("87654321"));
+
+        // Moodle wraps each upload in a folder carrying the prefix
+        let moodle = compile_lms_naming(Some("moodle")).unwrap();
+        fs::create_dir(dir.path().join("Grace Hopper_1042_assignsubmission_file_")).unwrap();
+        let wrapped = match_student("download.zip", dir.path(), &id_regex, &students, moodle.as_ref());
+        assert_eq!(wrapped.student_id.as_deref(), Some("87654321"));
+    }
+}
@@ -6,20 +6,23 @@ import CourseDetail from "./components/CourseDetail";
 import AssignmentWizard from "./components/AssignmentWizard"; // Anticipating next step
 import ImportSubmissions from "./components/ImportSubmissions";
 import GraderWorkspace from "./components/GraderWorkspace";
+import StartupGate from "./components/StartupGate";
 
 function App() {
   return (
-    <BrowserRouter>
-      <Routes>
-        <Route path="/" element={<Dashboard />} />
-        <Route path="/create-course" element={<CourseSetup />} />
-        <Route path="/course/:courseId" element={<CourseDetail />} />
-        <Route path="/create-assignment/:courseId" element={<AssignmentWizard />} />
-        <Route path="/import-submissions/:courseId/:assignmentId" element={<ImportSubmissions />} />
-        <Route path="/grader/:courseId/:assignmentId" element={<GraderWorkspace />} />
-        <Route path="/import-roster/:courseId" element={<RosterUpload />} />
-      </Routes>
-    </BrowserRouter>
+    <StartupGate>
+      <BrowserRouter>
+        <Routes>
+          <Route path="/" element={<Dashboard />} />
+          <Route path="/create-course" element={<CourseSetup />} />
+          <Route path="/course/:courseId" element={<CourseDetail />} />
+          <Route path="/create-assignment/:courseId" element={<AssignmentWizard />} />
+          <Route path="/import-submissions/:courseId/:assignmentId" element={<ImportSubmissions />} />
+          <Route path="/grader/:courseId/:assignmentId" element={<GraderWorkspace />} />
+          <Route path="/import-roster/:courseId" element={<RosterUpload />} />
+        </Routes>
+      </BrowserRouter>
+    </StartupGate>
   );
 }
 
@@ -31,7 +31,10 @@ export default function AssignmentWizard() {
             navigate(`/course/${courseId}`);
         } catch (e) {
             console.error(e);
-            alert("Error creating assignment: " + e);
+            const message = Array.isArray(e)
+                ? e.map((err: { path: string; message: string }) => err.path ? `${err.path}: ${err.message}` : err.message).join("\n")
+                : String(e);
+            alert("Error creating assignment: " + message);
         }
     }
 
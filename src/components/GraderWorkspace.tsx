@@ -1,8 +1,9 @@
-import { useState, useEffect, useCallback } from "react";
+import { useState, useEffect, useCallback, useRef } from "react";
 import { useParams, Link } from "react-router-dom";
 // @ts-ignore
 import { Panel, Group as PanelGroup, Separator as PanelResizeHandle } from "react-resizable-panels";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import StudentQueue, { StudentQueueItem } from "./grader/StudentQueue";
 import FileViewer, { FileInfo } from "./grader/FileViewer";
 import ScoringPanel, { GradeRecord, Rubric } from "./grader/ScoringPanel";
@@ -23,6 +24,19 @@ interface SubmissionFromBackend {
     claimed_by_name: string | null;
 }
 
+interface SubmissionActivity {
+    submission_id: string;
+    ta_id: string;
+    ta_name: string | null;
+    action: string;
+}
+
+interface ActiveViewer {
+    ta_id: string;
+    display_name: string | null;
+    last_seen_at: string;
+}
+
 export default function GraderWorkspace() {
     const { courseId, assignmentId } = useParams();
     const [students, setStudents] = useState<StudentQueueItem[]>([]);
@@ -41,6 +55,12 @@ export default function GraderWorkspace() {
     // Current TA (would come from app state/context in real app)
     const [currentTaId] = useState<string>("ta-1"); // Placeholder
 
+    // Token from start_ta_session, once this TA has signed in with their PIN
+    const sessionToken = useRef<string | null>(null);
+
+    // Other TAs who have the current submission open
+    const [otherViewers, setOtherViewers] = useState<string[]>([]);
+
     // Search
     const [searchTerm, setSearchTerm] = useState("");
 
@@ -57,6 +77,50 @@ export default function GraderWorkspace() {
         }
     }, [currentSubId]);
 
+    useEffect(() => {
+        setOtherViewers([]);
+        if (!currentSubId) return;
+        let cancelled = false;
+        invoke<ActiveViewer[]>("get_active_viewers", { submissionId: currentSubId })
+            .then(viewers => {
+                if (cancelled) return;
+                const others = viewers.filter(v => v.ta_id !== currentTaId);
+                setOtherViewers(others.map(v => v.display_name || v.ta_id));
+            })
+            .catch(console.error);
+        const unlisten = listen<SubmissionActivity>("submission-activity", e => {
+            const a = e.payload;
+            if (a.submission_id !== currentSubId || a.ta_id === currentTaId) return;
+            const name = a.ta_name || a.ta_id;
+            setOtherViewers(prev => prev.includes(name) ? prev : [...prev, name]);
+        });
+        return () => {
+            cancelled = true;
+            unlisten.then(f => f());
+        };
+    }, [currentSubId, currentTaId]);
+
+    // End the session when leaving the workspace
+    useEffect(() => () => {
+        if (sessionToken.current) {
+            invoke("end_ta_session", { sessionToken: sessionToken.current }).catch(console.error);
+        }
+    }, []);
+
+    // Invoke a command as the current TA. A TA with a PIN is asked for it the
+    // first time (and whenever the session expires), then the call is retried.
+    async function invokeAsTa<T>(command: string, args: Record<string, unknown>): Promise<T> {
+        try {
+            return await invoke<T>(command, { ...args, taId: currentTaId, sessionToken: sessionToken.current });
+        } catch (e) {
+            if (e !== "session_required") throw e;
+            const pin = window.prompt("Enter your grading PIN");
+            if (!pin) throw "Sign in with your PIN to continue";
+            sessionToken.current = await invoke<string>("start_ta_session", { taId: currentTaId, pin });
+            return await invoke<T>(command, { ...args, taId: currentTaId, sessionToken: sessionToken.current });
+        }
+    }
+
     // Keyboard shortcuts
     useEffect(() => {
         const handleKeyDown = (e: KeyboardEvent) => {
@@ -128,13 +192,10 @@ export default function GraderWorkspace() {
     async function claimAndLoad(subId: string) {
         try {
             // Claim submission
-            await invoke("claim_submission", {
-                submissionId: subId,
-                taId: currentTaId
-            });
+            await invokeAsTa("claim_submission", { submissionId: subId });
 
             // Touch for session resume
-            await invoke("touch_submission", { submissionId: subId });
+            await invokeAsTa("touch_submission", { submissionId: subId });
 
             // Load details
             await loadSubmissionDetail(subId);
@@ -168,15 +229,23 @@ export default function GraderWorkspace() {
 
     const handleSaveGrade = async (qId: string, score: number | null, comment: string | null) => {
         if (!currentSubId) return;
+        const existing = grades.find(g => g.question_id === qId);
         try {
-            await invoke("save_grade", {
+            await invokeAsTa("save_grade", {
                 submissionId: currentSubId,
                 questionId: qId,
                 score,
-                comment
+                comment,
+                expectedRevision: existing ? existing.revision : null
             });
             loadGrades(currentSubId);
         } catch (e) {
+            if (e === "stale_grade") {
+                // Someone else saved this question since we loaded it
+                await loadGrades(currentSubId);
+                alert("This grade was changed elsewhere. The latest version has been loaded; please re-apply your change.");
+                return;
+            }
             alert("Failed to save: " + e);
         }
     };
@@ -218,10 +287,9 @@ export default function GraderWorkspace() {
     const flagCurrentSubmission = async () => {
         if (!currentSubId) return;
         try {
-            await invoke("update_submission_status", {
+            await invokeAsTa("update_submission_status", {
                 submissionId: currentSubId,
-                status: "flagged",
-                taId: currentTaId
+                status: "flagged"
             });
             loadSubmissions();
         } catch (e) {
@@ -232,10 +300,9 @@ export default function GraderWorkspace() {
     const markDone = async () => {
         if (!currentSubId) return;
         try {
-            await invoke("update_submission_status", {
+            await invokeAsTa("update_submission_status", {
                 submissionId: currentSubId,
-                status: "done",
-                taId: currentTaId
+                status: "done"
             });
             loadSubmissions();
             nextStudent();
@@ -291,6 +358,12 @@ export default function GraderWorkspace() {
                 </div>
             </div>
 
+            {otherViewers.length > 0 && (
+                <div style={{ padding: '6px 15px', background: '#5a4a00', color: '#ffe08a', fontSize: '0.9em' }}>
+                    {otherViewers.join(", ")} {otherViewers.length === 1 ? "is" : "are"} also viewing this submission
+                </div>
+            )}
+
             {/* Main workspace */}
             <div style={{ flex: 1, position: 'relative' }}>
                 <PanelGroup orientation="horizontal">
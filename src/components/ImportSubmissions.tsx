@@ -1,5 +1,6 @@
 import { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { useParams, useNavigate } from "react-router-dom";
 
 interface ProcessResult {
@@ -9,12 +10,21 @@ interface ProcessResult {
     message: string | null;
 }
 
+interface SubmissionProgress {
+    assignment_id: string;
+    index: number;
+    total: number;
+    filename: string;
+    status: string;
+}
+
 export default function ImportSubmissions() {
     const { courseId, assignmentId } = useParams(); // Need assignmentId
     // @ts-ignore
     const navigate = useNavigate();
     const [submitting, setSubmitting] = useState(false);
     const [results, setResults] = useState<ProcessResult[]>([]);
+    const [progress, setProgress] = useState<SubmissionProgress | null>(null);
 
     if (!courseId) return null; // Avoid unused variable check
 
@@ -35,6 +45,10 @@ export default function ImportSubmissions() {
 
         if (paths.length > 0) {
             setSubmitting(true);
+            setProgress(null);
+            const unlisten = await listen<SubmissionProgress>("submission-progress", e => {
+                if (e.payload.assignment_id === assignmentId) setProgress(e.payload);
+            });
             try {
                 const res = await invoke<ProcessResult[]>("process_submissions", {
                     assignmentId,
@@ -44,6 +58,7 @@ export default function ImportSubmissions() {
             } catch (err: any) {
                 alert("Error: " + err);
             }
+            unlisten();
             setSubmitting(false);
         } else {
             alert("No ZIP files found or unable to resolve paths. (Try dropping files directly)");
@@ -71,7 +86,14 @@ export default function ImportSubmissions() {
                 <p>(Bulk upload supported)</p>
             </div>
 
-            {submitting && <p>Processing... (This may take a while)</p>}
+            {submitting && (
+                progress ? (
+                    <div>
+                        <progress value={progress.index} max={progress.total} style={{ width: '100%' }} />
+                        <p>Processing {progress.index} of {progress.total}: {progress.filename} ({progress.status})</p>
+                    </div>
+                ) : <p>Processing... (This may take a while)</p>
+            )}
 
             {results.length > 0 && (
                 <div style={{ marginTop: 30 }}>
@@ -30,13 +30,10 @@ export default function RosterUpload() {
 
                 // setFile(null); // Unused
 
-                if (path.endsWith(".xlsx")) {
-                    const res = await invoke<{ headers: string[], data: any[] }>("parse_excel_roster", { filePath: path });
-                    setHeaders(res.headers);
-                    setParsedData(res.data);
-                } else {
-                    alert("For CSV, please use the standard file input below (if available) or convert to Excel.");
-                }
+                const command = path.toLowerCase().endsWith(".csv") ? "parse_csv_roster" : "parse_excel_roster";
+                const res = await invoke<{ headers: string[], data: any[] }>(command, { filePath: path });
+                setHeaders(res.headers);
+                setParsedData(res.data);
             }
         } catch (e) {
             console.error(e);
@@ -49,17 +46,16 @@ export default function RosterUpload() {
     const finalUpload = async () => {
         if (parsedData.length === 0) return;
 
-        const students = parsedData.map((row: any) => ({
-            student_id: row[mapping.student_id] || "",
-            name: row[mapping.name] || "",
-            email: row[mapping.email] || null,
-            section: row[mapping.section] || null
-        })).filter((s: any) => s.student_id && s.student_id.trim() !== "");
-
         try {
-            const count = await invoke("save_roster", {
-                courseId: courseId,
-                students
+            const count = await invoke("save_roster_mapped", {
+                courseId,
+                rows: parsedData,
+                mapping: {
+                    student_id: mapping.student_id,
+                    name: mapping.name,
+                    email: mapping.email || null,
+                    section: mapping.section || null
+                }
             });
             alert(`Success! Imported ${count} students.`);
             navigate("/");
@@ -73,9 +69,9 @@ export default function RosterUpload() {
         <div className="container">
             <h1>Upload Roster</h1>
             <div style={{ marginBottom: 20 }}>
-                <button onClick={handleSelectFile}>Select Roster File (XLSX)</button>
+                <button onClick={handleSelectFile}>Select Roster File (XLSX or CSV)</button>
                 <div style={{ marginTop: 10, fontSize: '0.8em', color: '#666' }}>
-                    Note: Only .xlsx files supported via this button. For CSV, use the underlying code logic or convert to Excel.
+                    CSV files may be comma, semicolon or tab separated.
                 </div>
             </div>
 
@@ -99,7 +95,20 @@ export default function RosterUpload() {
                             {headers.map(h => <option key={h} value={h}>{h}</option>)}
                         </select>
                     </div>
-                    {/* Add email/section mapping similarly */}
+                    <div className="row">
+                        <label>Email (optional):</label>
+                        <select onChange={(e) => setMapping({ ...mapping, email: e.target.value })}>
+                            <option value="">-- None --</option>
+                            {headers.map(h => <option key={h} value={h}>{h}</option>)}
+                        </select>
+                    </div>
+                    <div className="row">
+                        <label>Section (optional):</label>
+                        <select onChange={(e) => setMapping({ ...mapping, section: e.target.value })}>
+                            <option value="">-- None --</option>
+                            {headers.map(h => <option key={h} value={h}>{h}</option>)}
+                        </select>
+                    </div>
 
                     <button onClick={finalUpload} style={{ marginTop: 20 }}>Import Roster</button>
                 </div>
@@ -2,12 +2,14 @@ import { v4 as uuidv4 } from 'uuid';
 
 export interface RubricStructure {
     questions: Question[];
+    scale?: number; // Normalize the total to this many points
 }
 
 export interface Question {
     question_id: string;
     title: string;
     max_points: number;
+    weight?: number; // Unset = counts raw points
     description?: string;
     comment_presets: CommentPreset[];
     // excel_checks omitted for simplicity in V1 UI, but structuring for it
@@ -66,6 +68,13 @@ export default function RubricBuilder({ value, onChange }: Props) {
                             onChange={(e) => updateQuestion(idx, { ...q, max_points: parseFloat(e.target.value) })}
                             width={50}
                         />
+                        <input
+                            type="number"
+                            value={q.weight ?? ""}
+                            onChange={(e) => updateQuestion(idx, { ...q, weight: e.target.value === "" ? undefined : parseFloat(e.target.value) })}
+                            placeholder="Weight"
+                            width={50}
+                        />
                         <button onClick={() => removeQuestion(idx)} style={{ background: 'red' }}>X</button>
                     </div>
                     <textarea
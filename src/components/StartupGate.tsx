@@ -0,0 +1,42 @@
+import { ReactNode, useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+interface StartupStatus {
+    state: "initializing" | "ready" | "failed";
+    error: string | null;
+}
+
+// Holds the app back until the database is open, and shows why if it never opens
+export default function StartupGate({ children }: { children: ReactNode }) {
+    const [status, setStatus] = useState<StartupStatus>({ state: "initializing", error: null });
+
+    useEffect(() => {
+        const unlisten = listen<StartupStatus>("startup-status", (event) => setStatus(event.payload));
+        // The event may have fired before we subscribed
+        invoke<StartupStatus>("get_startup_status").then(setStatus).catch(console.error);
+        return () => {
+            unlisten.then((f) => f());
+        };
+    }, []);
+
+    if (status.state === "ready") {
+        return <>{children}</>;
+    }
+
+    if (status.state === "failed") {
+        return (
+            <div className="container">
+                <h1>Database failed to open</h1>
+                <p>The grading database could not be opened or upgraded, so nothing can be loaded.</p>
+                <pre style={{ whiteSpace: "pre-wrap", background: "#fee", padding: 10 }}>{status.error}</pre>
+            </div>
+        );
+    }
+
+    return (
+        <div className="container">
+            <p>Opening database...</p>
+        </div>
+    );
+}
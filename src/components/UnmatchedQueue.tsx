@@ -7,6 +7,7 @@ interface UnmatchedSubmission {
     folder_path: string;
     received_at: string;
     suggested_student_id: string | null;
+    suggested_confidence: number | null;
 }
 
 interface Student {
@@ -14,14 +15,22 @@ interface Student {
     name: string;
 }
 
+interface RematchSummary {
+    checked: number;
+    matched: { submission_id: string; student_id: string }[];
+    conflicts: { submission_id: string; student_id: string }[];
+    still_unmatched: number;
+}
+
 interface Props {
     assignmentId: string;
     courseId: string;
     taId: string;
+    sessionToken?: string | null;
     onComplete: () => void;
 }
 
-export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplete }: Props) {
+export default function UnmatchedQueue({ assignmentId, courseId, taId, sessionToken, onComplete }: Props) {
     const [unmatched, setUnmatched] = useState<UnmatchedSubmission[]>([]);
     const [students, setStudents] = useState<Student[]>([]);
     const [currentIndex, setCurrentIndex] = useState(0);
@@ -51,6 +60,11 @@ export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplet
     }
 
     const currentSubmission = unmatched[currentIndex];
+
+    // Preselect the suggested student, if any
+    useEffect(() => {
+        setSelectedStudentId(currentSubmission?.suggested_student_id ?? "");
+    }, [currentSubmission?.id]);
     const filteredStudents = students.filter(s =>
         !searchTerm ||
         s.name.toLowerCase().includes(searchTerm.toLowerCase()) ||
@@ -64,7 +78,8 @@ export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplet
             await invoke("manual_match_submission", {
                 submissionId: currentSubmission.id,
                 studentId: selectedStudentId,
-                taId
+                taId,
+                sessionToken
             });
             // Move to next
             if (currentIndex < unmatched.length - 1) {
@@ -83,6 +98,24 @@ export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplet
         }
     }
 
+    async function handleRematch() {
+        setProcessing(true);
+        try {
+            const summary = await invoke<RematchSummary>("rematch_unmatched", { assignmentId, taId, sessionToken });
+            let message = `Matched ${summary.matched.length} of ${summary.checked} submissions.`;
+            if (summary.conflicts.length > 0) {
+                message += `\n${summary.conflicts.length} matched a student who already has a submission and need manual review.`;
+            }
+            alert(message);
+            setCurrentIndex(0);
+            loadData();
+        } catch (e) {
+            alert("Re-run matching failed: " + e);
+        } finally {
+            setProcessing(false);
+        }
+    }
+
     async function handleSkip() {
         if (!currentSubmission) return;
         setProcessing(true);
@@ -90,7 +123,8 @@ export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplet
             await invoke("quarantine_submission", {
                 submissionId: currentSubmission.id,
                 reason: "Skipped during manual resolution",
-                taId
+                taId,
+                sessionToken
             });
             if (currentIndex < unmatched.length - 1) {
                 setCurrentIndex(i => i + 1);
@@ -154,6 +188,14 @@ export default function UnmatchedQueue({ assignmentId, courseId, taId, onComplet
             <h2 style={{ margin: '0 0 10px 0' }}>Resolve Unmatched Submissions</h2>
             <p style={{ color: '#888', marginBottom: 20 }}>
                 {currentIndex + 1} of {unmatched.length} remaining
+                <button
+                    onClick={handleRematch}
+                    disabled={processing}
+                    title="Match again against the current roster"
+                    style={{ marginLeft: 15, padding: '4px 10px', fontSize: '0.85em' }}
+                >
+                    Re-run matching
+                </button>
             </p>
 
             {/* Progress bar */}
@@ -10,6 +10,11 @@ interface Props {
 interface Analysis {
     sheets: string[];
     formulas_count: number;
+    has_macros: boolean;
+    macro_module_count: number | null;
+    chart_count: number;
+    charts: { part: string; types: string[] }[];
+    sheet_errors: { sheet_name: string; operation: string; message: string }[];
 }
 
 export default function ExcelInspector({ submissionId, filePath }: Props) {
@@ -56,11 +61,27 @@ export default function ExcelInspector({ submissionId, filePath }: Props) {
 
             {analysis && (
                 <div>
+                    {analysis.has_macros && (
+                        <div style={{ color: '#e67e22', marginBottom: 10 }}>
+                            ⚠ This workbook contains VBA macros
+                            {analysis.macro_module_count !== null && ` (${analysis.macro_module_count} modules)`}.
+                            Results may be computed by code rather than formulas.
+                        </div>
+                    )}
                     <h4>Sheets ({analysis.sheets.length})</h4>
                     <ul>
                         {analysis.sheets.map(s => <li key={s}>{s}</li>)}
                     </ul>
-                    <p>Formulas detected: {analysis.formulas_count} (Analysis limited in V1)</p>
+                    <p>Formulas detected: {analysis.formulas_count}</p>
+                    {analysis.sheet_errors.map(e => (
+                        <div key={e.sheet_name} style={{ color: '#c0392b' }}>
+                            Could not read sheet '{e.sheet_name}' ({e.operation}): {e.message}
+                        </div>
+                    ))}
+                    <p>
+                        Charts detected: {analysis.chart_count}
+                        {analysis.chart_count > 0 && ` (${analysis.charts.map(c => c.types.join("+") || "unknown").join(", ")})`}
+                    </p>
                 </div>
             )}
 
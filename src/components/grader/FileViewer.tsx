@@ -8,6 +8,8 @@ export interface FileInfo {
     path: string;
     name: string;
     is_dir: boolean;
+    size_bytes: number;
+    parent: string | null;
 }
 
 interface Props {
@@ -18,7 +20,7 @@ interface Props {
 // File type detection
 function getFileType(name: string): 'excel' | 'code' | 'pdf' | 'image' | 'docx' | 'other' {
     const ext = name.split('.').pop()?.toLowerCase() || '';
-    if (ext === 'xlsx' || ext === 'xls') return 'excel';
+    if (ext === 'xlsx' || ext === 'xlsm' || ext === 'xls') return 'excel';
     if (['py', 'r', 'rmd', 'js', 'ts', 'java', 'cpp', 'c', 'h', 'txt', 'md', 'json', 'csv', 'sql', 'html', 'css'].includes(ext)) return 'code';
     if (ext === 'pdf') return 'pdf';
     if (['png', 'jpg', 'jpeg', 'gif', 'webp', 'svg'].includes(ext)) return 'image';
@@ -49,7 +51,9 @@ function getLanguage(name: string): string {
     return langMap[ext] || 'plaintext';
 }
 
-export default function FileViewer({ files, submissionId }: Props) {
+export default function FileViewer({ files: entries, submissionId }: Props) {
+    // Folders are reported for tree views; the tab strip only shows files
+    const files = entries.filter(f => !f.is_dir);
     const [selectedPath, setSelectedPath] = useState<string | null>(null);
     const [fileContent, setFileContent] = useState<string>("");
     const [loadingContent, setLoadingContent] = useState(false);
@@ -68,11 +72,18 @@ export default function FileViewer({ files, submissionId }: Props) {
     async function loadFileContent(path: string) {
         setLoadingContent(true);
         try {
-            const content = await invoke<string>("read_submission_file", {
+            const preview = await invoke<{ content: string | null; is_binary: boolean; truncated: boolean; total_size: number }>("read_submission_file", {
                 submissionId,
                 filePath: path
             });
-            setFileContent(content);
+            if (preview.is_binary) {
+                setFileContent("Binary file, preview unavailable.");
+            } else {
+                const note = preview.truncated
+                    ? `\n\n[Preview truncated: file is ${(preview.total_size / (1024 * 1024)).toFixed(1)} MB]`
+                    : "";
+                setFileContent((preview.content ?? "") + note);
+            }
         } catch (e) {
             setFileContent(`Error loading file: ${e}`);
         } finally {
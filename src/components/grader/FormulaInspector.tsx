@@ -5,6 +5,8 @@ interface CellInfo {
     address: string;
     value: string;
     formula: string | null;
+    is_merged: boolean;
+    merge_anchor: string | null;
 }
 
 interface SheetFormulaMap {
@@ -14,11 +16,18 @@ interface SheetFormulaMap {
     functions_used: string[];
 }
 
+interface SheetError {
+    sheet_name: string;
+    operation: string;
+    message: string;
+}
+
 interface FormulaMapResult {
     sheets: SheetFormulaMap[];
     total_formula_count: number;
     has_pivot: boolean;
     hidden_sheets: string[];
+    sheet_errors: SheetError[];
 }
 
 interface RangeCheck {
@@ -26,6 +35,9 @@ interface RangeCheck {
     sheet?: string;
     check_type: string;
     description: string;
+    functions?: string[];
+    expected?: number | string | boolean;
+    tolerance?: number;
 }
 
 interface RangeCheckResult {
@@ -145,6 +157,16 @@ export default function FormulaInspector({ submissionId, filePath, rubricChecks
                 </span>
             </div>
 
+            {formulaMap.sheet_errors.length > 0 && (
+                <div style={{ padding: '6px 15px', background: '#4a2020', color: '#f5b7b1', fontSize: '0.85em' }}>
+                    {formulaMap.sheet_errors.map(e => (
+                        <div key={e.sheet_name}>
+                            Could not read sheet '{e.sheet_name}' ({e.operation}): {e.message}
+                        </div>
+                    ))}
+                </div>
+            )}
+
             {/* Sheet Tabs */}
             <div style={{
                 display: 'flex',
@@ -301,6 +323,11 @@ export default function FormulaInspector({ submissionId, filePath, rubricChecks
                         <span style={{ color: '#888' }}>Value: </span>
                         {selectedCell.value || '(empty)'}
                     </div>
+                    {selectedCell.is_merged && selectedCell.merge_anchor !== selectedCell.address && (
+                        <div style={{ marginBottom: 5, color: '#888' }}>
+                            Merged into {selectedCell.merge_anchor}
+                        </div>
+                    )}
                     {selectedCell.formula ? (
                         <div style={{
                             background: '#1a3a5a',
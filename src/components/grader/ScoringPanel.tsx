@@ -23,6 +23,7 @@ export interface GradeRecord {
     question_id: string;
     score: number | null;
     comment: string | null;
+    revision: number;
 }
 
 interface Props {